@@ -0,0 +1,111 @@
+//! Benchmarks of the paths every message takes: encryption on the client, ingestion into the
+//! server's store, serving fetches and snapshots and trial decryption on the client. The server's
+//! store is a module of the server binary, so it's included from there.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use nym_chat::{EncryptedMessage, Key, Message, Payload};
+
+#[allow(dead_code)]
+#[path = "../src/bin/server/store.rs"]
+mod store;
+
+use store::{Location, Store};
+
+const PROVIDER: &str = "provider";
+
+/// A message of `len` bytes of text encrypted to `room`
+fn message(room: &Key, len: usize) -> EncryptedMessage {
+    Message::new("bench".into(), "x".repeat(len)).encrypt(room)
+}
+
+fn filled_store(room: &Key, count: usize) -> Store {
+    let mut store = Store::in_memory();
+    for arrived in 0..count as u64 {
+        store
+            .push(PROVIDER, message(room, 200), arrived, None)
+            .unwrap();
+    }
+    store
+}
+
+fn client(c: &mut Criterion) {
+    let room = Key::generate();
+    let other_room = Key::generate();
+
+    c.bench_function("encrypt", |b| {
+        let msg = Message::new("bench".into(), "x".repeat(200));
+        b.iter(|| msg.encrypt(&room))
+    });
+    // Most messages on a server belong to other rooms, so failing fast matters most
+    c.bench_function("trial_decrypt_other_room", |b| {
+        b.iter_batched(
+            || message(&other_room, 200),
+            |msg| Payload::decrypt(msg, &room),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("trial_decrypt_own_room", |b| {
+        b.iter_batched(
+            || message(&room, 200),
+            |msg| Payload::decrypt(msg, &room),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn ingestion(c: &mut Criterion) {
+    let room = Key::generate();
+    let mut group = c.benchmark_group("store_push");
+    group.throughput(Throughput::Elements(1));
+
+    group.bench_function("in_memory", |b| {
+        let mut store = Store::in_memory();
+        b.iter_batched(
+            || message(&room, 200),
+            |msg| store.push(PROVIDER, msg, 0, None).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    let dir = std::env::temp_dir().join(format!("nym-chat-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, group_commit) in [
+        ("file_group_commit", true),
+        ("file_sync_every_message", false),
+    ] {
+        let location = Location::Log(dir.join(name));
+        let mut store = Store::open(&location, Some(Key::generate()), group_commit).unwrap();
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || message(&room, 200),
+                |msg| store.push(PROVIDER, msg, 0, None).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn serving(c: &mut Criterion) {
+    let room = Key::generate();
+    let store = filled_store(&room, 10_000);
+
+    let mut group = c.benchmark_group("serve_10k_messages");
+    group.throughput(Throughput::Elements(10_000));
+    group.bench_function("fetch_json", |b| {
+        b.iter(|| serde_json::to_string(store.since(PROVIDER, 0)).unwrap())
+    });
+    let serialized = bincode::serialize(store.since(PROVIDER, 0)).unwrap();
+    group.bench_function("snapshot_compress", |b| {
+        b.iter(|| nym_chat::snapshot::compress(&serialized))
+    });
+    let snapshot = nym_chat::snapshot::compress(&serialized);
+    group.bench_function("snapshot_decode", |b| {
+        b.iter(|| nym_chat::snapshot::decode(&snapshot).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, client, ingestion, serving);
+criterion_main!(benches);
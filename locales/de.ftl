@@ -0,0 +1,287 @@
+# German strings of the client
+
+## Terminal UI
+
+input-title = Nachricht senden
+input-bytes-left = Nachricht senden (noch { $bytes } Bytes)
+input-packets = Nachricht senden ({ $packets } Pakete, etwa { $seconds } s bis zum Server)
+input-paste = Nachricht senden (Einfügung in { $parts } Teilen: Enter sendet sie, Escape verwirft sie)
+fragment-warning = Diese Nachricht benötigt { $packets } Mixnet-Pakete (etwa { $seconds } s bis zum Server), dadurch ist sie langsamer und geht eher verloren. Drücke erneut Enter, um sie trotzdem zu senden.
+paste-split = Der eingefügte Text hat mehrere Zeilen oder ist zu groß für eine Nachricht. Drücke Enter, um ihn als { $parts } nummerierte Nachrichten zu senden, oder Escape, um ihn zu verwerfen.
+paste-dropped = Eingefügter Text verworfen
+chat-log-title = Nachrichten
+date-format = %-d.%-m.%Y
+date-separator = — { $date } —
+time-just-now = jetzt
+time-minutes-ago = vor { $minutes } Min.
+time-hours-ago = vor { $hours } Std.
+time-days-ago = vor { $days } T.
+status-help = Esc: beenden  Bild↑/Bild↓: blättern  Strg+O: Links
+status-newer-messages = [{ $count } neuere Nachrichten unten]
+status-log-rewritten = ⚠ Der Server hat sein Protokoll umgeschrieben, Nachrichten könnten entfernt oder ersetzt worden sein
+status-tree-head-invalid = ⚠ Der Tree Head des Servers hat eine ungültige Signatur
+status-catching-up = [hole auf, noch { $count }]
+status-undecryptable = [{ $count } nicht entschlüsselbare Nachrichten: falscher Raumschlüssel oder ein neuerer Client? /debug zeigt Details]
+links-title = Links (Enter: öffnen, c: kopieren, Esc: schließen)
+links-confirm = { $url } öffnen? [y/n]
+links-none = Keine Links in den letzten Nachrichten
+link-opened = { $url } geöffnet
+link-open-failed = Konnte { $url } nicht öffnen: { $error }
+link-copied = { $url } kopiert
+link-copy-failed = Konnte { $url } nicht kopieren: { $error }
+
+## Commands
+
+unknown-command = Unbekannter Befehl { $command }
+presence-joined = beigetreten
+presence-active = aktiv
+presence-away = abwesend
+daemon-error = Fehler des Daemons: { $error }
+announce-sent = { $url } als Spiegelserver angekündigt
+announce-invalid = Konnte den Spiegelserver nicht ankündigen: { $error }
+notify-rules = Benachrichtigungen: { $level }, Stichwörter: { $keywords }, Ruhezeit: { $quiet }
+notify-level-all = alle Nachrichten
+notify-level-mentions = nur Erwähnungen und Stichwörter
+notify-level-muted = stumm
+notify-none = keine
+notify-invalid-level = Unbekannte Benachrichtigungsstufe { $level }, erlaubt sind all, mentions oder muted
+notify-invalid-quiet-hours = Ungültige Ruhezeit { $hours }, z.B. 22:00-07:00 oder off
+notify-not-watched = { $keyword } steht nicht auf der Liste der Stichwörter
+notify-save-failed = Konnte die Benachrichtigungseinstellungen nicht speichern: { $error }
+retention = Nachrichten speichern: { $persist }, aufbewahren: { $keep }, exportieren: { $export } (gilt ab dem nächsten Start)
+retention-on = ja
+retention-off = nein
+retention-days = { $days ->
+    [one] einen Tag
+   *[other] { $days } Tage
+}
+retention-forever = unbegrenzt
+retention-invalid = Verwendung: /retention persist on|off, /retention keep <Tage>|forever oder /retention export on|off
+queue = In der Warteschlange: { $queued }
+queue-none = Keine Nachrichten in der Warteschlange, alles hat die Server erreicht
+queue-added = Konnte die Nachricht nicht an jeden Server übergeben, { $count } Nachrichten in der Warteschlange werden alle 30 Sekunden erneut gesendet (/queue listet sie, /unqueue <Nummer> bricht eine ab)
+debug-empty = Noch nichts abgerufen
+debug-fetched = { $server }: { $count } neue Nachrichten in { $latency } ms abgerufen
+debug-nothing-new = { $server }: nichts Neues ({ $count } Abrufe)
+debug-fetch-failed = { $server }: Abruf fehlgeschlagen: { $error }
+debug-decrypted = { $server }: Nachricht { $digest } entschlüsselt
+debug-other-key = { $server }: { $count } Nachrichten für andere Räume (versuchte Schlüssel { $candidates })
+debug-unknown-payload = { $server }: Nachricht { $digest } entschlüsselt, aber ihr Inhalt ist unbekannt, vielleicht stammt sie von einem neueren Client
+plugin-unknown = Unbekanntes Plugin { $name }, verfügbar sind: { $known }
+plugin-config-invalid = Ungültige Konfiguration für Plugin { $name }: { $error }
+plugins-none = Keine Plugins aktiviert, siehe --plugin
+padding-off = Nachrichten werden nicht aufgefüllt, siehe --pad-messages
+padding-buckets = Auffüllen { $policy }: Nachrichten werden auf { $buckets } Bytes aufgefüllt, das hätte die { $samples } Nachrichten im Raum um { $overhead }% vergrößert
+translate-active = Eingehende Nachrichten werden nach { $language } übersetzt, /translate off pausiert
+translate-paused = Übersetzen ist pausiert, /translate on setzt fort
+translate-usage = Verwendung: /translate [on|off]
+translate-failed = Übersetzen fehlgeschlagen: { $error }. Läuft der Übersetzungsdienst?
+queue-sent = { $count } Nachrichten aus der Warteschlange gesendet
+queue-cancelled = { $text } wird nicht mehr an die Server gesendet, die es noch nicht erreicht hat
+queue-unknown = Es gibt keine Nachricht { $number } in der Warteschlange, /queue listet sie
+queue-save-failed = Konnte die Warteschlange nicht speichern: { $error }
+schedule = Geplant: { $pending }
+schedule-none = Keine geplanten Nachrichten, nutze /schedule <HH:MM> <Text>
+schedule-usage = Verwendung: /schedule <HH:MM> <Text> sendet den Text zur nächsten solchen Uhrzeit, /unschedule <Nummer> bricht ab
+scheduled = Die Nachricht wird um { $time } gesendet, falls der Client dann läuft, sonst direkt nach dem nächsten Start
+schedule-cancelled = { $text } wird nicht gesendet
+schedule-unknown = Es gibt keine geplante Nachricht { $number }, /schedule listet sie auf
+schedule-save-failed = Die geplanten Nachrichten konnten nicht gespeichert werden: { $error }
+away-default = Ich bin gerade nicht da und antworte später
+away-set = Du bist abwesend, Erwähnungen in diesem Raum werden automatisch beantwortet bis du /back tippst
+away-set-no-auto-reply = Du bist abwesend. Dieser Raum sendet keine automatischen Antworten, /autoreply on schaltet sie ein
+away-cleared = Willkommen zurück, keine automatischen Antworten mehr
+away-not-away = Du warst nicht abwesend
+away-save-failed = Der Abwesenheitsstatus konnte nicht gespeichert werden: { $error }
+incognito-unsupported = Im Inkognito-Modus nicht verfügbar, dafür müsste etwas auf der Festplatte gespeichert werden
+autoreply-active = Du bist seit { $since } abwesend, Erwähnungen in diesem Raum werden mit { $message } beantwortet
+autoreply-on = Erwähnungen in diesem Raum werden automatisch beantwortet, während du abwesend bist (/away)
+autoreply-off = Dieser Raum sendet keine automatischen Antworten
+autoreply-usage = Verwendung: /autoreply on|off
+
+
+priority = Priorität: { $priority }, Abruf alle { $interval }s
+priority-usage = Verwendung: /priority high|normal|low, Räume mit hoher Priorität werden viermal so oft abgerufen, solche mit niedriger alle 5 Minuten
+priority-save-failed = Konnte die Priorität nicht speichern: { $error }
+retention-save-failed = Konnte die Aufbewahrungseinstellungen nicht speichern: { $error }
+broadcast-read-only = Nur Besitzer des Signaturschlüssels können in diesen Raum schreiben
+broadcast-rejected = Nachricht von { $sender } ohne gültige Signatur verworfen
+poll = Umfrage { $id } von { $sender }: { $question } { $options }
+polls-none = Noch keine Umfragen
+poll-sent = Umfrage gesendet, sie erscheint sobald der Server sie hat
+poll-invalid = Verwendung: /poll <Frage> | <Option> | <Option>...
+vote-sent = Für { $option } abgestimmt
+vote-unknown-poll = Unbekannte Umfrage, verwende die Kennung aus /polls
+vote-invalid-option = Verwendung: /vote <Umfrage-Kennung> <Nummer der Option>
+card = Kontaktkarte von { $name }, Fingerabdruck: { $fingerprint }, Räume: { $rooms }
+card-none = keine
+author-invalid = Nachricht angeblich von { $sender } mit ungültiger Autorensignatur verworfen
+sender-unverified = { $sender } [unbestätigt]
+contacts-none = Noch keine Kontakte, jeder der eine signierte Nachricht sendet wird hinzugefügt
+contact-entry = { $name } ({ $fingerprint }, { $rooms } Räume) { $notes }
+contact-verified-entry = { $name } ({ $fingerprint }, verifiziert, { $rooms } Räume) { $notes }
+contacts-usage = Verwendung: /contacts, /contacts export <Pfad> oder /contacts import <Pfad>
+contacts-exported = { $count } Kontakte nach { $path } exportiert
+contacts-imported = { $count } Kontakte aus { $path } importiert
+contacts-file-failed = Die Kontaktdatei konnte nicht gelesen oder geschrieben werden: { $error }
+contacts-save-failed = Das Adressbuch konnte nicht gespeichert werden: { $error }
+contact-unknown = Kein Kontakt mit Fingerabdruck { $fingerprint }
+contact-ambiguous = Mehrere Fingerabdrücke beginnen mit { $fingerprint }, gib mehr davon an
+contact-forgotten = { $fingerprint } vergessen
+contact-verified = { $name } ({ $fingerprint }) als verifiziert markiert
+contact-unverified = { $name } ({ $fingerprint }) ist nicht mehr verifiziert
+contact-noted = Notizen zu { $name } ({ $fingerprint }) aktualisiert
+petname-set = { $fingerprint } wird jetzt als { $name } angezeigt
+petname-removed = Eigenen Namen von { $fingerprint } entfernt
+
+card-sent = Kontaktkarte gesendet
+burn-sent = Selbstzerstörende Nachricht gesendet, sie wird nicht in deinem Verlauf gespeichert
+burn-received = Selbstzerstörende Nachricht von { $sender }, drücke Strg+B um sie zu lesen
+burn-read = Selbstzerstörende Nachricht von { $sender } (gelesen und gelöscht)
+burn-none = Keine ungelesenen selbstzerstörenden Nachrichten
+burn-confirm-title = Selbstzerstörende Nachricht von { $sender }
+burn-confirm = Sie wird gelöscht, sobald du sie wieder schließt. Drücke y, um sie anzuzeigen, eine andere Taste, um sie später zu lesen.
+burn-title = { $sender } (jede Taste löscht die Nachricht)
+
+receipts = { $received } Empfangsbestätigungen für unsere Nachrichten in dieser Sitzung erhalten, { $awaiting } fehlen noch. Alle Bestätigungen liegen in { $path }
+receipts-in-memory = { $received } Empfangsbestätigungen für unsere Nachrichten in dieser Sitzung erhalten, { $awaiting } fehlen noch. Inkognito-Sitzungen behalten sie nur im Speicher
+receipts-disabled = Empfangsbestätigungen werden nicht geprüft, starte den Client mit --server-key um sie aufzubewahren
+receipts-invalid = Der Server hat { $count } Nachrichten ohne gültige Empfangsbestätigung gesendet
+messages-dropped = { $server } hat { $count } Nachrichten von { $sender } nicht ausgeliefert, der Server oder das Netzwerk hat sie verworfen
+messages-missed = { $count } Nachrichten verpasst, { $url } hat sie gelöscht, bevor wir sie abgerufen haben
+receipt-save-failed = Konnte eine Empfangsbestätigung nicht speichern: { $error }
+tree-head-save-failed = Konnte den Tree Head des Servers nicht speichern: { $error }
+room-created = Raum { $name } ({ $id }) auf diesem Server erstellt, andere können mit { $invite } beitreten
+room-created-read-only = Mitglieder, die nur lesen dürfen, können mit { $invite } beitreten
+invite-usage = Verwendung: /invite [read] [no-history], read lässt die Schreibberechtigung weg, no-history lässt neue Mitglieder erst ab jetzt mitlesen
+invite-no-history-unsupported = Mitglieder dieses Raums können den ganzen Verlauf lesen, nur in Räumen, die mit /create-protected erstellt wurden, bleibt er neuen Mitgliedern verborgen
+
+room-archived = Raum archiviert: der Verlauf bleibt lesbar, aber bis /unarchive wird nichts abgerufen oder gesendet
+room-archived-notice = Dieser Raum ist archiviert, bis /unarchive wird nichts abgerufen oder gesendet
+room-archived-read-only = Der Raum ist archiviert, hole ihn mit /unarchive zurück, um wieder Nachrichten zu senden
+room-unarchived = Der Raum ist nicht mehr archiviert, es wird wieder abgerufen
+archive-save-failed = Konnte nicht ändern, ob der Raum archiviert ist: { $error }
+room-left = Raum verlassen, sein Schlüssel und alles dazu Gespeicherte sind gelöscht
+room-left-kept = Raum verlassen, er ist nicht mehr im Profil, aber sein Verlauf bleibt im Datenverzeichnis
+room-wipe-failed = Raum verlassen, aber konnte nicht alles dazu Gespeicherte löschen: { $error }
+room-created-no-profile = Der Raum wird nirgends gespeichert, starte den Client ohne Befehl, um erstellte Räume in deinem Profil zu behalten
+http-config-invalid = Konnte die HTTP-Konfiguration nicht verwenden: { $error }
+url-without-room-key = --url braucht den Raum, dem beigetreten werden soll, gib seinen --room-key oder die --passphrase an
+room-key-missing = Gib den Raumschlüssel oder die --passphrase an, aus der er abgeleitet wird
+snippets-invalid = Konnte die Textbausteine nicht laden: { $error }
+snippets-none = Keine Textbausteine, starte den Client mit --snippets <Datei>, um welche festzulegen
+snippets-list = Textbausteine: { $names }, sende einen mit /snip <Name> <Wert> | <Wert>
+snippet-unknown = Es gibt keinen Textbaustein { $name }, /snip listet sie auf
+snippet-missing-value = Der Textbaustein braucht einen Wert für {"{"}{ $number }{"}"}, trenne die Werte mit |
+snippet-invalid-placeholder = Der Textbaustein hat einen ungültigen Platzhalter { $placeholder }, nutze {"{{"} und {"}}"} für Klammern
+snippet-not-sent = Zu viel los, der Textbaustein wurde nicht gesendet, versuche es noch einmal
+profile-save-failed = Konnte das Profil nicht speichern: { $error }
+stats =
+    { $sent } Nachrichten in dieser Sitzung gesendet, { $delivered } zurückgekommen, { $pending } noch unterwegs
+    Umlaufzeit: { $min }s min, { $median }s Median, { $p90 }s für 90%, { $max }s max (Abruf alle { $interval }s)
+stats-health = Mixnet: { $nym_errors } Fehler des Nym-Clients, { $reconnects } Neuverbindungen, { $send_failures } fehlgeschlagene Sendungen, { $fetch_failures } fehlgeschlagene Abrufe
+stats-empty = { $sent } Nachrichten in dieser Sitzung gesendet, noch keine zurückgekommen, { $pending } noch unterwegs (Abruf alle { $interval }s)
+keygen-result =
+    Signaturschlüssel (geheim halten, mit --signing-key angeben um zu schreiben): { $signing }
+    Prüfschlüssel (weitergeben, mit --broadcast-key angeben um zu lesen): { $verifying }
+moderator-keygen-result =
+    Moderationsschlüssel (geheim halten, mit --moderator-key angeben um Meldungen zu lesen): { $moderator }
+    Meldeschlüssel (weitergeben, mit --report-key angeben oder Einladungen damit erstellen): { $report }
+report-usage = Verwendung: /report <Absender> <Grund>
+report-no-moderator = Dieser Raum hat keine Moderation, an die gemeldet werden kann. Einladungen moderierter Räume enthalten den Meldeschlüssel (oder gib --report-key an)
+report-not-found = Keine neuere Nachricht von { $sender } zum Melden
+report-failed = Konnte den Verlauf nicht lesen: { $error }
+report-sent = Die neueste Nachricht von { $sender } wurde der Moderation gemeldet
+report-received = Meldung von { $reporter } über { $sender }: „{ $text }“ ({ $reason })
+reports-not-moderator = Nur die Moderation kann Meldungen lesen, starte den Client mit --moderator-key
+reports-none = Bisher keine Meldungen
+reports-message = { $sender }: „{ $text }“ { $count }-mal gemeldet ({ $reasons })
+reports-save-failed = Konnte die Meldungen nicht speichern: { $error }
+mirror-announced = Spiegelserver angekündigt: { $url }
+archive-exported = { $count } Nachrichten exportiert
+archive-export-failed = Konnte das Archiv nicht exportieren: { $error }
+sharekey-exported = Die Einladung in den Raum wurde für den öffentlichen Schlüssel verschlüsselt nach { $path } geschrieben, schicke sie an die empfangende Person. Lesen lässt sie sich mit `nym-chat import-key <Datei>`.
+sharekey-export-failed = Konnte den Raumschlüssel nicht exportieren: { $error }
+sharekey-usage = Verwendung: /sharekey <Datei mit öffentlichem Schlüssel> [<Ausgabedatei>], die Datei muss einen age- oder ASCII-kodierten OpenPGP-Schlüssel enthalten
+sharekey-imported = Die Datei enthält diese Einladung, beitreten mit `nym-chat join <Einladung> <Name>`:
+    { $invite }
+sharekey-import-failed = Konnte den Raumschlüssel nicht importieren: { $error }
+archive-import-at-startup = Archive können nur beim Start importiert werden, starte den Client mit --import-archive <Datei>
+archive-import-failed = Konnte das Archiv nicht importieren: { $error }
+backup-usage = Verwendung: /backup <Datei>, schreibt das Profil mit allen Räumen verschlüsselt nach <Datei>
+backup-written = Die Sicherung wurde nach { $path } geschrieben. Schreibe diesen Satz auf, nur mit ihm lässt sich die Sicherung mit `nym-chat restore <Datei> "<Satz>"` wiederherstellen:
+    { $phrase }
+backup-failed = Konnte die Sicherung nicht schreiben: { $error }
+transcript-write-failed = Konnte nicht ins Protokoll schreiben: { $error }
+
+## Startup
+
+unknown-locale = Unbekannte Sprache { $locale }, verfügbar sind: { $available }
+attach-failed = Konnte nicht mit dem Daemon verbinden: { $error }
+transcript-open-failed = Konnte das Protokoll nicht öffnen: { $error }
+transcript-valid = Das Protokoll ist unverändert, { $entries } Einträge geprüft
+transcript-invalid = Das Protokoll ist ungültig: { $error }
+doctor-nym-ok = Der Nym-Client unter { $websocket } läuft, seine Adresse ist { $address }
+doctor-nym-unreachable = Der Nym-Client unter { $websocket } ist nicht erreichbar: { $error }. Starte nym-client oder gib ihn mit --websocket an.
+doctor-nym-silent = Der Nym-Client unter { $websocket } hat die Verbindung angenommen, aber nicht geantwortet, starte ihn neu
+doctor-server-unreachable = Der Server unter { $url } ist nicht erreichbar: { $error }. Prüfe die URL in der Einladung.
+doctor-version-unknown = Der Server nennt seine Version nicht, er ist älter als dieser Client ({ $ours }). Bitte den Betreiber, ihn zu aktualisieren.
+doctor-version-same = Der Server läuft mit derselben Version wie dieser Client ({ $ours })
+doctor-version-different = Der Server läuft mit Version { $theirs }, dieser Client mit { $ours }. Neuere Funktionen fehlen womöglich auf einer Seite, aktualisiere die ältere.
+doctor-fetch-failed = Abrufen der Nachrichten von { $url } fehlgeschlagen: { $error }
+doctor-no-messages = Der Server hat noch keine Nachrichten, der Raumschlüssel lässt sich erst prüfen, wenn jemand schreibt
+doctor-decrypted = { $count } der { $total } neuesten Nachrichten des Servers ließen sich mit dem Raumschlüssel entschlüsseln, der Schlüssel stimmt
+doctor-wrong-key = Keine der { $count } neuesten Nachrichten des Servers ließ sich mit dem Raumschlüssel { $room } entschlüsseln. Vergleiche die Schlüssel-ID mit jemandem im Raum: entweder ist der Schlüssel falsch oder der Raum liegt auf einem anderen Server als { $url }. Ist der Raum ruhig und teilt sich den Server mit belebten Räumen, sind seine Nachrichten vielleicht nur älter.
+doctor-unknown-payloads = { $count } Nachrichten ließen sich mit dem Raumschlüssel entschlüsseln, aber nicht lesen, sie stammen von einem neueren Client. Aktualisiere nym-chat.
+doctor-problems = { $count ->
+    [one] Ein Problem gefunden
+   *[other] { $count } Probleme gefunden
+}
+onboarding-welcome = Willkommen bei nym-chat! Zuerst richten wir dein Profil ein, es wird verschlüsselt im Datenverzeichnis gespeichert.
+onboarding-name = Dein Name in Chats:
+onboarding-new-passphrase = Passphrase für das Profil (darf leer sein, die Eingabe ist sichtbar):
+onboarding-passphrase = Passphrase deines Profils:
+onboarding-created = Dein Identitätsschlüssel wurde erzeugt, sein Fingerabdruck ist { $fingerprint }
+onboarding-incognito = Inkognito: Nichts wird auf der Festplatte gespeichert, deine Wegwerf-Identität { $fingerprint } ist nach dem Beenden weg
+onboarding-room = Gib die Nummer eines Raums ein, füge einen Einladungslink ein oder gib einen Server als <url> oder <url>=<Nym-Adresse> an, um einen neuen Raum zu erstellen:
+onboarding-room-name = Name des neuen Raums (optional):
+onboarding-room-created = Raum erstellt, andere können mit { $invite } beitreten
+onboarding-invalid = Das ist weder eine Raumnummer, ein Einladungslink mit Schlüssel noch ein Server
+onboarding-provider-failed = Konnte den Server nicht nach seiner Nym-Adresse fragen, gib den Server stattdessen als <url>=<Nym-Adresse> an: { $error }
+onboarding-failed = Konnte das Profil nicht einrichten: { $error }
+invite-without-key = Die Einladung enthält keinen Raumschlüssel, bitte gib ihn mit --room an
+invite-without-key-hint = Die Einladung enthält keinen Raumschlüssel, bitte gib ihn mit --room an (Hinweis: { $hint })
+provider-address-failed = Konnte den Server nicht nach seiner Nym-Adresse fragen, bitte gib sie mit --service-provider an: { $error }
+invalid-device-link = Kein gültiger Geräte-Link, erzeuge einen mit /link
+device-sync-waiting = Warte darauf, dass die Daten des anderen Geräts durch das Mixnet ankommen...
+device-sync-missing = Die Gerätedaten sind nicht innerhalb von { $minutes } Minuten angekommen, versuche es mit einem neuen Link
+device-sync-unreachable = Konnte die Gerätedaten nicht vom Server abrufen: { $error }
+directory-unreachable = Konnte den Verzeichnisraum nicht lesen, es geht ohne seine Spiegelserver weiter: { $error }
+
+## Screen reader mode
+
+screen-reader-help = Screenreader-Modus. Gib eine Nachricht ein und drücke Enter, um sie zu senden, /quit beendet das Programm.
+screen-reader-notice = Hinweis: { $text }
+screen-reader-burn-received = Selbstzerstörende Nachricht von { $sender }, tippe /reveal um sie einmal zu lesen
+screen-reader-burn = Selbstzerstörende Nachricht, jetzt gelöscht: { $message }
+
+screen-reader-warning = Warnung: { $text }
+screen-reader-undecryptable = { $count } Nachrichten konnten nicht entschlüsselt werden, vielleicht ist der Raumschlüssel falsch oder sie stammen von einem neueren Client. /debug zeigt Details.
+screen-reader-message = Nachricht { $index } von { $sender }: { $text }
+screen-reader-annotation = Unter Nachricht { $index }: { $text }
+
+## Platform support
+
+daemon-unsupported = Der Daemon-Modus benötigt Unix-Sockets, die es auf dieser Plattform nicht gibt
+history-open-failed = Konnte den Nachrichtenverlauf des Raums nicht öffnen
+history-write-failed = Konnte den Nachrichtenverlauf des Raums nicht schreiben
+address-book-open-failed = Konnte das Adressbuch nicht öffnen
+petnames-move-failed = Konnte die eigenen Namen aus dem Profil nicht ins Adressbuch übernehmen
+linked-room-invalid = Das verknüpfte Gerät hat einen ungültigen Raumschlüssel gesendet
+linked-profile-failed = Konnte das Profil des verknüpften Geräts nicht übernehmen
+restore-incognito = Inkognito-Sitzungen haben kein Profil, in das eine Sicherung wiederhergestellt werden könnte
+backup-restored = Das Profil wurde mit { $rooms } Räumen wiederhergestellt, starte den Client ohne Befehl, um es zu verwenden
+backup-restore-failed = Konnte die Sicherung nicht wiederherstellen: { $error }
+nym-unreachable = Konnte keine Verbindung zum Nym-Client unter { $url } herstellen, läuft er?
+statsd-unreachable = Konnte den statsd-Server nicht erreichen
+daemon-bind-failed = Konnte den Steuer-Socket { $path } nicht öffnen
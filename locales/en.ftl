@@ -0,0 +1,288 @@
+# English strings of the client, also the fallback for strings missing in other locales
+
+## Terminal UI
+
+input-title = Send Message
+input-bytes-left = Send Message ({ $bytes } bytes left)
+input-packets = Send Message ({ $packets } packets, about { $seconds } s to the server)
+input-paste = Send Message (paste in { $parts } parts: enter sends them, escape drops them)
+fragment-warning = This message needs { $packets } mixnet packets (about { $seconds } s to the server), which makes it slower and more likely to get lost. Press enter again to send it anyway.
+paste-split = The paste has several lines or is too big for one message. Press enter to send it as { $parts } numbered messages or escape to drop it.
+paste-dropped = Dropped the paste
+chat-log-title = Messages
+# chrono format string, see https://docs.rs/chrono/latest/chrono/format/strftime
+date-format = %B %-d, %Y
+date-separator = — { $date } —
+time-just-now = now
+time-minutes-ago = { $minutes }m ago
+time-hours-ago = { $hours }h ago
+time-days-ago = { $days }d ago
+status-help = Esc: quit  PgUp/PgDn: scroll  Ctrl+O: links
+status-newer-messages = [{ $count } newer messages below]
+status-log-rewritten = ⚠ The server rewrote its log, messages may have been removed or replaced
+status-tree-head-invalid = ⚠ The server's tree head has an invalid signature
+status-catching-up = [catching up, { $count } left]
+status-undecryptable = [{ $count } undecryptable messages: wrong room key or a newer client? /debug shows details]
+links-title = Links (Enter: open, c: copy, Esc: close)
+links-confirm = Open { $url } ? [y/n]
+links-none = No links in recent messages
+link-opened = Opened { $url }
+link-open-failed = Couldn't open { $url }: { $error }
+link-copied = Copied { $url }
+link-copy-failed = Couldn't copy { $url }: { $error }
+
+## Commands
+
+unknown-command = Unknown command { $command }
+presence-joined = joined
+presence-active = active
+presence-away = away
+daemon-error = Daemon error: { $error }
+announce-sent = Announced { $url } as mirror
+announce-invalid = Couldn't announce mirror: { $error }
+notify-rules = Notifications: { $level }, keywords: { $keywords }, quiet hours: { $quiet }
+notify-level-all = all messages
+notify-level-mentions = mentions and keywords only
+notify-level-muted = muted
+notify-none = none
+notify-invalid-level = Unknown notification level { $level }, use all, mentions or muted
+notify-invalid-quiet-hours = Invalid quiet hours { $hours }, use e.g. 22:00-07:00 or off
+notify-not-watched = { $keyword } isn't on the watch list
+notify-save-failed = Couldn't save the notification settings: { $error }
+retention = Store messages: { $persist }, keep: { $keep }, include in exports: { $export } (takes effect on the next start)
+retention-on = yes
+retention-off = no
+retention-days = { $days ->
+    [one] one day
+   *[other] { $days } days
+}
+retention-forever = forever
+retention-invalid = Usage: /retention persist on|off, /retention keep <days>|forever or /retention export on|off
+queue = Queued: { $queued }
+queue-none = No queued messages, everything reached the servers
+queue-added = Couldn't hand the message to every server, { $count } queued messages are retried every 30 seconds (/queue lists them, /unqueue <number> cancels one)
+debug-empty = Nothing fetched yet
+debug-fetched = { $server }: fetched { $count } new messages in { $latency } ms
+debug-nothing-new = { $server }: nothing new ({ $count } fetches)
+debug-fetch-failed = { $server }: fetch failed: { $error }
+debug-decrypted = { $server }: decrypted message { $digest }
+debug-other-key = { $server }: { $count } messages for other rooms (tried keys { $candidates })
+debug-unknown-payload = { $server }: message { $digest } decrypted but its content is unknown, maybe it's from a newer client
+plugin-unknown = Unknown plugin { $name }, available are: { $known }
+plugin-config-invalid = Invalid configuration for plugin { $name }: { $error }
+plugins-none = No plugins enabled, see --plugin
+padding-off = Messages aren't padded, see --pad-messages
+padding-buckets = Padding { $policy }: messages are padded to { $buckets } bytes, which would have added { $overhead }% to the { $samples } messages seen in the room
+translate-active = Translating incoming messages to { $language }, /translate off pauses
+translate-paused = Translating is paused, /translate on resumes
+translate-usage = Usage: /translate [on|off]
+translate-failed = Translating failed: { $error }. Is the translation service running?
+queue-sent = Sent { $count } queued messages
+queue-cancelled = Won't send { $text } to the servers it didn't reach yet
+queue-unknown = There is no queued message { $number }, /queue lists them
+queue-save-failed = Couldn't save the queued messages: { $error }
+schedule = Scheduled: { $pending }
+schedule-none = No scheduled messages, use /schedule <HH:MM> <text>
+schedule-usage = Usage: /schedule <HH:MM> <text> sends the text at the next such time, /unschedule <number> cancels it
+scheduled = The message goes out at { $time } if the client is running then, otherwise right after its next start
+schedule-cancelled = Won't send { $text }
+schedule-unknown = There is no scheduled message { $number }, /schedule lists them
+schedule-save-failed = Couldn't save the scheduled messages: { $error }
+away-default = I'm away right now and will answer later
+away-set = You're away, mentions in this room get an auto-reply until you type /back
+away-set-no-auto-reply = You're away. This room doesn't send auto-replies, /autoreply on opts in
+away-cleared = Welcome back, no more auto-replies
+away-not-away = You weren't away
+away-save-failed = Couldn't save the away status: { $error }
+incognito-unsupported = Not available in incognito mode, it would have to be stored on disk
+autoreply-active = You're away since { $since }, mentions in this room get the auto-reply { $message }
+autoreply-on = Mentions in this room get an auto-reply while you're away (/away)
+autoreply-off = This room doesn't send auto-replies
+autoreply-usage = Usage: /autoreply on|off
+
+
+priority = Priority: { $priority }, fetching every { $interval }s
+priority-usage = Usage: /priority high|normal|low, high priority rooms are fetched four times as often, low priority ones every 5 minutes
+priority-save-failed = Couldn't save the priority: { $error }
+retention-save-failed = Couldn't save the retention settings: { $error }
+broadcast-read-only = Only holders of the signing key can post to this room
+broadcast-rejected = Dropped a message from { $sender } without a valid signature
+poll = Poll { $id } by { $sender }: { $question } { $options }
+polls-none = No polls yet
+poll-sent = Poll sent, it shows up once the server has it
+poll-invalid = Usage: /poll <question> | <option> | <option>...
+vote-sent = Voted for { $option }
+vote-unknown-poll = Unknown poll, use the id shown by /polls
+vote-invalid-option = Usage: /vote <poll id> <option number>
+card = Contact card of { $name }, fingerprint: { $fingerprint }, rooms: { $rooms }
+card-none = none
+author-invalid = Dropped a message claiming to be from { $sender } with an invalid author signature
+sender-unverified = { $sender } [unverified]
+contacts-none = No contacts yet, everyone who sends a signed message is added
+contact-entry = { $name } ({ $fingerprint }, { $rooms } rooms) { $notes }
+contact-verified-entry = { $name } ({ $fingerprint }, verified, { $rooms } rooms) { $notes }
+contacts-usage = Usage: /contacts, /contacts export <path> or /contacts import <path>
+contacts-exported = Exported { $count } contacts to { $path }
+contacts-imported = Imported { $count } contacts from { $path }
+contacts-file-failed = Couldn't read or write the contacts file: { $error }
+contacts-save-failed = Couldn't save the address book: { $error }
+contact-unknown = No contact with fingerprint { $fingerprint }
+contact-ambiguous = Several contacts' fingerprints start with { $fingerprint }, give more of it
+contact-forgotten = Forgot { $fingerprint }
+contact-verified = Marked { $name } ({ $fingerprint }) as verified
+contact-unverified = { $name } ({ $fingerprint }) is no longer verified
+contact-noted = Updated the notes on { $name } ({ $fingerprint })
+petname-set = { $fingerprint } is now shown as { $name }
+petname-removed = Removed the petname of { $fingerprint }
+
+card-sent = Contact card sent
+burn-sent = Burn-after-reading message sent, it's not kept in your history
+burn-received = Burn-after-reading message from { $sender }, press Ctrl+B to read it
+burn-read = Burn-after-reading message from { $sender } (read and deleted)
+burn-none = No unread burn-after-reading messages
+burn-confirm-title = Burn-after-reading message from { $sender }
+burn-confirm = It's deleted as soon as you close it again. Press y to show it, any other key to read it later.
+burn-title = { $sender } (any key deletes the message)
+
+receipts = { $received } receipts for our messages received this session, { $awaiting } still missing. All receipts are kept in { $path }
+receipts-in-memory = { $received } receipts for our messages received this session, { $awaiting } still missing. Incognito sessions keep them in memory only
+receipts-disabled = Receipts aren't checked, start the client with --server-key to keep them
+receipts-invalid = The server sent { $count } messages without a valid receipt
+messages-dropped = { $server } didn't deliver { $count } messages of { $sender }, the server or the network dropped them
+messages-missed = Missed { $count } messages, { $url } deleted them before we fetched them
+receipt-save-failed = Couldn't save a receipt: { $error }
+tree-head-save-failed = Couldn't save the server's tree head: { $error }
+room-created = Created room { $name } ({ $id }) on this server, others can join with { $invite }
+room-created-read-only = Read-only members can join with { $invite }
+invite-usage = Usage: /invite [read] [no-history], read leaves out the permission to post, no-history lets new members only read from now on
+invite-no-history-unsupported = Members of this room can read its whole history, only rooms created with /create-protected can hide it from new members
+
+room-archived = Archived the room: its history stays readable, but nothing is fetched or sent until /unarchive
+room-archived-notice = This room is archived, nothing is fetched or sent until /unarchive
+room-archived-read-only = The room is archived, /unarchive it to send messages again
+room-unarchived = The room isn't archived anymore, fetching resumed
+archive-save-failed = Couldn't change whether the room is archived: { $error }
+room-left = Left the room, its key and everything stored about it are gone
+room-left-kept = Left the room, it's no longer in the profile but its history stays in the data directory
+room-wipe-failed = Left the room, but couldn't delete everything stored about it: { $error }
+room-created-no-profile = The room isn't kept anywhere, start the client without a command to keep created rooms in your profile
+http-config-invalid = Couldn't use the HTTP config: { $error }
+url-without-room-key = --url needs the room to join, give its --room-key or --passphrase
+room-key-missing = Give the room key or the --passphrase it's derived from
+snippets-invalid = Couldn't load the snippets: { $error }
+snippets-none = No snippets, start the client with --snippets <file> to define some
+snippets-list = Snippets: { $names }, send one with /snip <name> <value> | <value>
+snippet-unknown = There is no snippet { $name }, /snip lists them
+snippet-missing-value = The snippet needs a value for {"{"}{ $number }{"}"}, separate the values with |
+snippet-invalid-placeholder = The snippet has an invalid placeholder { $placeholder }, use {"{{"} and {"}}"} for braces
+snippet-not-sent = Too much going on, the snippet wasn't sent, try again
+profile-save-failed = Couldn't save the profile: { $error }
+stats =
+    { $sent } messages sent this session, { $delivered } came back, { $pending } still on their way
+    Round trip: { $min }s min, { $median }s median, { $p90 }s for 90%, { $max }s max (fetching every { $interval }s)
+stats-health = Mixnet: { $nym_errors } errors of the Nym client, { $reconnects } reconnects, { $send_failures } failed sends, { $fetch_failures } failed fetches
+stats-empty = { $sent } messages sent this session, none came back yet, { $pending } still on their way (fetching every { $interval }s)
+keygen-result =
+    Signing key (keep it secret, pass it with --signing-key to post): { $signing }
+    Verifying key (share it, pass it with --broadcast-key to read): { $verifying }
+moderator-keygen-result =
+    Moderator key (keep it secret, pass it with --moderator-key to read reports): { $moderator }
+    Report key (share it, pass it with --report-key or create invites with it): { $report }
+report-usage = Usage: /report <sender> <reason>
+report-no-moderator = This room has no moderator to report to, invites of moderated rooms contain the report key (or pass --report-key)
+report-not-found = No recent message from { $sender } to report
+report-failed = Couldn't read the history: { $error }
+report-sent = Reported the newest message of { $sender } to the moderator
+report-received = Report by { $reporter } about { $sender }: "{ $text }" ({ $reason })
+reports-not-moderator = Only the moderator can read reports, start the client with --moderator-key
+reports-none = No reports so far
+reports-message = { $sender }: "{ $text }" reported { $count } times ({ $reasons })
+reports-save-failed = Couldn't save the reports: { $error }
+mirror-announced = Mirror announced: { $url }
+archive-exported = Exported { $count } messages
+archive-export-failed = Couldn't export the archive: { $error }
+sharekey-exported = Wrote the room's invite encrypted to the public key to { $path }, send it to the recipient, who reads it with `nym-chat import-key <file>`.
+sharekey-export-failed = Couldn't export the room key: { $error }
+sharekey-usage = Usage: /sharekey <public key file> [<output file>], the file has to contain an age or an ASCII armored OpenPGP public key
+sharekey-imported = The file contains this invite, join with `nym-chat join <invite> <name>`:
+    { $invite }
+sharekey-import-failed = Couldn't import the room key: { $error }
+archive-import-at-startup = Archives can only be imported on startup, start the client with --import-archive <file>
+archive-import-failed = Couldn't import the archive: { $error }
+backup-usage = Usage: /backup <file>, writes the profile with all rooms encrypted to <file>
+backup-written = Wrote the backup to { $path }. Write down this phrase, the backup can only be restored with it using `nym-chat restore <file> "<phrase>"`:
+    { $phrase }
+backup-failed = Couldn't write the backup: { $error }
+transcript-write-failed = Couldn't write to the transcript: { $error }
+
+## Startup
+
+unknown-locale = Unknown locale { $locale }, available are: { $available }
+attach-failed = Couldn't attach to daemon: { $error }
+transcript-open-failed = Couldn't open the transcript: { $error }
+transcript-valid = The transcript is intact, { $entries } entries verified
+transcript-invalid = The transcript doesn't verify: { $error }
+doctor-nym-ok = The Nym client at { $websocket } is running, its address is { $address }
+doctor-nym-unreachable = Can't reach the Nym client at { $websocket }: { $error }. Start nym-client or point --websocket at it.
+doctor-nym-silent = The Nym client at { $websocket } accepted the connection but didn't answer, restart it
+doctor-server-unreachable = Can't reach the server at { $url }: { $error }. Check the URL in the invite.
+doctor-version-unknown = The server doesn't report its version, it's older than this client ({ $ours }). Ask its operator to update.
+doctor-version-same = The server runs the same version as this client ({ $ours })
+doctor-version-different = The server runs version { $theirs }, this client { $ours }. Newer features may be missing on one side, update the older one.
+doctor-fetch-failed = Fetching messages from { $url } failed: { $error }
+doctor-no-messages = The server has no messages yet, the room key can't be checked until someone writes
+doctor-decrypted = { $count } of the server's { $total } newest messages decrypted with the room key, the key is right
+doctor-wrong-key = None of the server's { $count } newest messages decrypted with room key { $room }. Compare the key id with someone in the room: either the key is wrong or the room is on another server than { $url }. If the room is quiet and shares the server with busy rooms, its messages may just be older.
+doctor-unknown-payloads = { $count } messages decrypted with the room key but couldn't be read, they were sent by a newer client. Update nym-chat.
+doctor-problems = { $count ->
+    [one] Found one problem
+   *[other] Found { $count } problems
+}
+onboarding-welcome = Welcome to nym-chat! Let's set up your profile, it's stored encrypted in the data directory.
+onboarding-name = Your name in chats:
+onboarding-new-passphrase = Passphrase for the profile (may be empty, the input is visible):
+onboarding-passphrase = Passphrase of your profile:
+onboarding-created = Created your identity key, its fingerprint is { $fingerprint }
+onboarding-incognito = Incognito: nothing is stored on disk, your throwaway identity { $fingerprint } is gone when you quit
+onboarding-room = Enter the number of a room, paste an invite link or enter a server as <url> or <url>=<nym address> to create a new room:
+onboarding-room-name = Name of the new room (optional):
+onboarding-room-created = Created the room, others can join with { $invite }
+onboarding-invalid = That's neither a room number, an invite link with a key nor a server
+onboarding-provider-failed = Couldn't ask the server for its Nym address, enter the server as <url>=<nym address> instead: { $error }
+onboarding-failed = Couldn't set up the profile: { $error }
+invite-without-key = The invite doesn't contain a room key, please supply it using --room
+invite-without-key-hint = The invite doesn't contain a room key, please supply it using --room (hint: { $hint })
+provider-address-failed = Couldn't ask the server for its Nym address, please supply it using --service-provider: { $error }
+invalid-device-link = Not a valid device link, generate one using /link
+device-sync-waiting = Waiting for the other device's data to arrive through the mixnet...
+device-sync-missing = Didn't receive the device sync bundle within { $minutes } minutes, try generating a new link
+device-sync-unreachable = Couldn't fetch the device sync bundle from the server: { $error }
+directory-unreachable = Couldn't read the directory room, continuing without its mirrors: { $error }
+
+## Screen reader mode
+
+screen-reader-help = Screen reader mode. Type a message and press enter to send it, enter /quit to leave.
+screen-reader-notice = Notice: { $text }
+screen-reader-burn-received = Burn-after-reading message from { $sender }, type /reveal to read it once
+screen-reader-burn = Burn-after-reading message, now deleted: { $message }
+
+screen-reader-warning = Warning: { $text }
+screen-reader-undecryptable = { $count } messages couldn't be decrypted, maybe the room key is wrong or they were sent by a newer client. /debug shows details.
+screen-reader-message = Message { $index } from { $sender }: { $text }
+screen-reader-annotation = Beneath message { $index }: { $text }
+
+## Platform support
+
+daemon-unsupported = Daemon mode needs Unix sockets, which aren't available on this platform
+history-open-failed = Couldn't open the room's message history
+history-write-failed = Couldn't write the room's message history
+address-book-open-failed = Couldn't open the address book
+petnames-move-failed = Couldn't move the profile's petnames to the address book
+linked-room-invalid = The linked device sent an invalid room key
+linked-profile-failed = Couldn't take over the linked device's profile
+restore-incognito = Incognito sessions have no profile to restore a backup to
+backup-restored = Restored the profile with { $rooms } rooms, start the client without a command to use it
+backup-restore-failed = Couldn't restore the backup: { $error }
+nym-unreachable = Couldn't connect to the Nym client at { $url }, is it running?
+statsd-unreachable = Couldn't reach the statsd server
+daemon-bind-failed = Couldn't open the control socket { $path }
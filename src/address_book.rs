@@ -0,0 +1,185 @@
+//! Local address book of everyone whose authored messages we've seen, keyed by the fingerprint of
+//! their identity key. It lives in the data directory next to the profile, so petnames, notes and
+//! whether a fingerprint was verified out of band carry over between rooms and profiles. Which
+//! rooms a contact wrote in and which names they claimed are tracked as well, the same person
+//! showing up in another room is recognized by their key, not by their name.
+
+use crate::broadcast::VerifyingKey;
+use crate::identity::short_fingerprint;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Contact {
+    /// Our name for them, replaces whatever name they claim
+    pub petname: Option<String>,
+    /// Set once the fingerprint was compared out of band
+    pub verified: bool,
+    pub notes: String,
+    /// Names they claimed, in the order we saw them first
+    pub claimed: Vec<String>,
+    /// Ids of the rooms we saw them in
+    pub rooms: BTreeSet<String>,
+}
+
+impl Contact {
+    /// The contact's name in listings: the petname or the last name they claimed
+    pub fn name(&self) -> &str {
+        self.petname
+            .as_deref()
+            .or_else(|| self.claimed.last().map(String::as_str))
+            .unwrap_or_default()
+    }
+
+    /// Take over what an imported entry knows, our own petname and notes win over imported ones
+    fn merge(&mut self, other: Contact) {
+        if self.petname.is_none() {
+            self.petname = other.petname;
+        }
+        if self.notes.is_empty() {
+            self.notes = other.notes;
+        }
+        self.verified |= other.verified;
+        for name in other.claimed {
+            if !self.claimed.contains(&name) {
+                self.claimed.push(name);
+            }
+        }
+        self.rooms.extend(other.rooms);
+    }
+}
+
+/// Why a shortened fingerprint didn't pick a contact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Unknown,
+    Ambiguous,
+}
+
+pub struct AddressBook {
+    /// Missing in incognito sessions, the address book is only kept in memory then
+    path: Option<PathBuf>,
+    contacts: BTreeMap<String, Contact>,
+}
+
+impl AddressBook {
+    /// Open the address book in `data_dir`, it's empty if there is none yet
+    pub fn open(data_dir: &Path) -> Result<AddressBook, anyhow::Error> {
+        let path = data_dir.join("contacts");
+        let contacts = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(AddressBook {
+            path: Some(path),
+            contacts,
+        })
+    }
+
+    /// Empty address book that's never saved
+    pub fn in_memory() -> AddressBook {
+        AddressBook {
+            path: None,
+            contacts: BTreeMap::new(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_vec_pretty(&self.contacts)?)?;
+        }
+        Ok(())
+    }
+
+    /// Contacts ordered by fingerprint
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Contact)> {
+        self.contacts
+            .iter()
+            .map(|(fingerprint, contact)| (fingerprint.as_str(), contact))
+    }
+
+    pub fn get(&self, fingerprint: &str) -> Option<&Contact> {
+        self.contacts.get(fingerprint)
+    }
+
+    /// The contact with `fingerprint`, created if we don't know them yet
+    pub fn entry(&mut self, fingerprint: String) -> &mut Contact {
+        self.contacts.entry(fingerprint).or_default()
+    }
+
+    /// Forget a contact entirely, returns whether we knew them
+    pub fn remove(&mut self, fingerprint: &str) -> bool {
+        self.contacts.remove(fingerprint).is_some()
+    }
+
+    /// Note that `author` wrote in the room `room_id` calling themselves `claimed`. Returns whether
+    /// that's news, so the caller knows when to save.
+    pub fn seen(&mut self, author: &VerifyingKey, claimed: &str, room_id: &str) -> bool {
+        let contact = self.entry(author.fingerprint());
+        let mut changed = contact.rooms.insert(room_id.to_owned());
+        if !contact.claimed.iter().any(|name| name == claimed) {
+            contact.claimed.push(claimed.to_owned());
+            changed = true;
+        }
+        changed
+    }
+
+    /// The full fingerprint of the one contact whose fingerprint starts with `prefix`, spaces are
+    /// optional
+    pub fn find(&self, prefix: &str) -> Result<String, Lookup> {
+        let prefix = prefix.replace(' ', "").to_lowercase();
+        let mut matches = self
+            .contacts
+            .keys()
+            .filter(|fingerprint| fingerprint.replace(' ', "").starts_with(&prefix));
+        match (matches.next(), matches.next()) {
+            (Some(fingerprint), None) if !prefix.is_empty() => Ok(fingerprint.clone()),
+            (None, _) => Err(Lookup::Unknown),
+            _ => Err(Lookup::Ambiguous),
+        }
+    }
+
+    /// What to show as the sender of a message by `author` who calls themselves `claimed`: the
+    /// petname if there is one, otherwise the claimed name and the start of the fingerprint
+    pub fn display_name(&self, claimed: &str, author: &VerifyingKey) -> String {
+        let fingerprint = author.fingerprint();
+        match self
+            .get(&fingerprint)
+            .and_then(|contact| contact.petname.clone())
+        {
+            Some(petname) => petname,
+            None => format!(
+                "{} ({})",
+                claimed_name(claimed),
+                short_fingerprint(&fingerprint)
+            ),
+        }
+    }
+
+    /// Write all contacts to `path` in the same format the address book is stored in
+    pub fn export(&self, path: &Path) -> Result<usize, anyhow::Error> {
+        std::fs::write(path, serde_json::to_vec_pretty(&self.contacts)?)?;
+        Ok(self.contacts.len())
+    }
+
+    /// Merge the contacts exported to `path` into the address book, returns how many there were
+    pub fn import(&mut self, path: &Path) -> Result<usize, anyhow::Error> {
+        let imported: BTreeMap<String, Contact> = serde_json::from_slice(&std::fs::read(path)?)?;
+        let count = imported.len();
+        for (fingerprint, contact) in imported {
+            self.entry(fingerprint).merge(contact);
+        }
+        Ok(count)
+    }
+}
+
+/// A name someone gave themselves without the brackets and parentheses senders are marked with,
+/// so nobody can name themselves `alice (ab12 cd34)` to pass as a verified author
+pub fn claimed_name(claimed: &str) -> String {
+    claimed
+        .chars()
+        .filter(|c| !matches!(c, '(' | ')' | '[' | ']'))
+        .collect()
+}
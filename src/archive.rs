@@ -0,0 +1,43 @@
+//! Portable archives of a room's history. An archive contains the decrypted messages, the read
+//! positions on the room's servers and the digests of recently received ciphertexts, encrypted
+//! with the room key. It can be imported on another machine (or by another client implementing
+//! this format) without involving the server, which may not have the old messages anymore.
+//!
+//! Files start with [`MAGIC`] followed by a bincode serialized [`EncryptedMessage`] containing
+//! the bincode serialized [`Archive`].
+
+use crate::{EncryptedMessage, Key, Message};
+use serde::{Deserialize, Serialize};
+
+/// Identifies archive files and their format version
+pub const MAGIC: &[u8; 8] = b"NYMARCH1";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub room_name: Option<String>,
+    /// All messages in the order they were received
+    pub messages: Vec<Message>,
+    /// Number of messages fetched from each server, by server URL
+    pub read_positions: Vec<(String, usize)>,
+    /// Digests of the newest received ciphertexts, so messages fetched again aren't shown twice
+    pub seen: Vec<[u8; 32]>,
+}
+
+impl Archive {
+    /// Encrypt the archive with the room key
+    pub fn seal(&self, room: &Key) -> Result<Vec<u8>, anyhow::Error> {
+        let mut file = MAGIC.to_vec();
+        file.extend(bincode::serialize(&EncryptedMessage::seal(self, room))?);
+        Ok(file)
+    }
+
+    /// Decrypt an archive, fails if it isn't one or belongs to another room
+    pub fn open(file: &[u8], room: &Key) -> Result<Archive, anyhow::Error> {
+        if !file.starts_with(MAGIC) {
+            return Err(anyhow::Error::msg("not a nym-chat archive"));
+        }
+        bincode::deserialize::<EncryptedMessage>(&file[MAGIC.len()..])?
+            .open(room)
+            .map_err(|_| anyhow::Error::msg("the archive belongs to another room"))
+    }
+}
@@ -0,0 +1,85 @@
+//! Auto-replies to mentions while the user is away. Being away is stored in the data directory, so
+//! `/away` in one room applies to the clients of all rooms, but only rooms that opted in with
+//! `/autoreply on` answer mentions. Replies start with [`MARKER`] and are rate limited, so two
+//! away users mentioning each other don't keep a conversation going on their own.
+
+use crate::notify;
+use crate::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Start of every auto-reply, messages starting with it are never answered
+pub const MARKER: &str = "[away]";
+
+/// How long a sender who got an auto-reply doesn't get another one
+const PER_SENDER: Duration = Duration::from_secs(60 * 60);
+
+/// At most this many auto-replies per hour, no matter to whom
+const PER_HOUR: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Away {
+    /// What the auto-replies tell
+    pub message: String,
+    /// Unix seconds
+    pub since: u64,
+}
+
+impl Away {
+    /// Whether the user is away, according to the file in `data_dir`
+    pub fn load(data_dir: &Path) -> Option<Away> {
+        let bytes = std::fs::read(data_dir.join("away")).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<(), anyhow::Error> {
+        std::fs::write(data_dir.join("away"), serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Back again, returns whether the user was away
+    pub fn clear(data_dir: &Path) -> Result<bool, anyhow::Error> {
+        match std::fs::remove_file(data_dir.join("away")) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Decides which mentions get an auto-reply
+#[derive(Debug, Default)]
+pub struct Responder {
+    last_reply: HashMap<String, Instant>,
+    recent: VecDeque<Instant>,
+}
+
+impl Responder {
+    /// Whether to answer `msg` from someone else mentioning `own_name`, counts the reply if so
+    pub fn should_reply(&mut self, msg: &Message, own_name: &str, now: Instant) -> bool {
+        if msg.sender == own_name
+            || msg.msg.starts_with(MARKER)
+            || !notify::mentions(&msg.msg, own_name)
+        {
+            return false;
+        }
+        while let Some(oldest) = self.recent.front() {
+            match now.duration_since(*oldest) > Duration::from_secs(60 * 60) {
+                true => self.recent.pop_front(),
+                false => break,
+            };
+        }
+        let replied_recently = self
+            .last_reply
+            .get(&msg.sender)
+            .map_or(false, |last| now.duration_since(*last) < PER_SENDER);
+        if replied_recently || self.recent.len() >= PER_HOUR {
+            return false;
+        }
+        self.last_reply.insert(msg.sender.clone(), now);
+        self.recent.push_back(now);
+        true
+    }
+}
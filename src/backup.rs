@@ -0,0 +1,75 @@
+//! Backups of the profile (`/backup` and the restore command of the client): the name, the
+//! identity key and the invite links of all rooms with everything they carry, so a lost device
+//! doesn't take the persona and the rooms' secrets with it. The backup is encrypted to a fresh key
+//! which is shown as a 24 word mnemonic phrase, the phrase is written down and the backup file can
+//! be kept anywhere.
+
+use crate::{EncryptedMessage, Key};
+use serde::{Deserialize, Serialize};
+
+/// Start of the text of a backup file, followed by the hex encoded encrypted backup
+const BACKUP_PREFIX: &str = "nymchat-backup:";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Backup {
+    pub name: String,
+    /// Hex encoded identity key of the profile, `None` for sessions without one
+    pub identity: Option<String>,
+    /// Invite links of the rooms, with secrets and chain positions
+    pub rooms: Vec<String>,
+}
+
+impl Backup {
+    /// Encrypt the backup to a fresh key, returns the key and the text to write to the backup file
+    pub fn seal(&self) -> (Key, String) {
+        let key = Key::generate();
+        let sealed = bincode::serialize(&EncryptedMessage::seal(self, &key))
+            .expect("serializing to memory doesn't fail");
+        (key, format!("{}{}\n", BACKUP_PREFIX, hex::encode(sealed)))
+    }
+
+    /// Decrypt the text of a backup file with the key [`Backup::seal`] returned
+    pub fn open(text: &str, key: &Key) -> Result<Backup, anyhow::Error> {
+        let sealed = text
+            .trim()
+            .strip_prefix(BACKUP_PREFIX)
+            .ok_or_else(|| anyhow::Error::msg("not a backup file"))?;
+        let sealed: EncryptedMessage = bincode::deserialize(&hex::decode(sealed)?)?;
+        sealed
+            .open(key)
+            .map_err(|()| anyhow::Error::msg("the backup doesn't belong to this phrase"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn backup() -> Backup {
+        Backup {
+            name: "alice".to_owned(),
+            identity: Some(hex::encode([7; 32])),
+            rooms: vec![
+                "nymchat://join?server=http://127.0.0.1:3030&secret=00".to_owned(),
+                "nymchat://join?server=http://127.0.0.1:3031&key=11&chain=22".to_owned(),
+            ],
+        }
+    }
+
+    #[test]
+    fn backups_round_trip_through_the_phrase() {
+        let backup = backup();
+        let (key, text) = backup.seal();
+        let phrase = Key::from_str(&key.to_mnemonic()).unwrap();
+        assert_eq!(Backup::open(&text, &phrase).unwrap(), backup);
+    }
+
+    #[test]
+    fn backups_dont_open_with_another_phrase() {
+        let (_, text) = backup().seal();
+        assert!(Backup::open(&text, &Key::generate()).is_err());
+        assert!(Backup::open("nymchat-backup:00", &Key::generate()).is_err());
+        assert!(Backup::open("something else", &Key::generate()).is_err());
+    }
+}
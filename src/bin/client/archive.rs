@@ -0,0 +1,75 @@
+//! Exporting and importing room archives (`/export-archive` and `--import-archive`), see the
+//! archive module of the library for the format
+
+use nym_chat::archive::Archive;
+use nym_chat::history::History;
+use nym_chat::transport::Server;
+use nym_chat::Key;
+use std::path::Path;
+
+/// Write the whole history of the room to `path`, returns the number of exported messages. Rooms
+/// excluded from exports by their retention settings only export read positions and digests, so
+/// another device can continue without the messages.
+pub fn export(
+    history: &mut History,
+    room: &Key,
+    room_name: Option<String>,
+    servers: &[Server],
+    path: &Path,
+) -> Result<usize, anyhow::Error> {
+    let messages = match history.retention().export {
+        true => history.load(0..history.len())?,
+        false => Vec::new(),
+    };
+    let read_positions = servers
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, server)| {
+            let position = match idx {
+                0 => Some(history.read_position()),
+                _ => history.mirror_position(&server.url),
+            };
+            Some((server.url.clone(), position?))
+        })
+        .collect();
+    let archive = Archive {
+        room_name,
+        messages,
+        read_positions,
+        seen: history.seen().copied().collect(),
+    };
+    std::fs::write(path, archive.seal(room)?)?;
+    Ok(archive.messages.len())
+}
+
+/// Import the archive at `path` into an empty history before fetching starts. Messages can't be
+/// inserted before the ones we already have, so importing into a history that isn't empty fails.
+/// Read positions of servers we don't use are ignored.
+pub fn import(
+    history: &mut History,
+    room: &Key,
+    servers: &[Server],
+    path: &Path,
+) -> Result<(), anyhow::Error> {
+    if !history.is_empty() {
+        return Err(anyhow::Error::msg(
+            "the local history isn't empty, import the archive into a new data directory instead",
+        ));
+    }
+    let archive = Archive::open(&std::fs::read(path)?, room)?;
+
+    for digest in archive.seen {
+        history.mark_seen(digest)?;
+    }
+    for (url, position) in archive.read_positions {
+        match servers.iter().position(|server| server.url == url) {
+            Some(0) => history.set_read_position(position)?,
+            Some(_) => history.set_mirror_position(&url, position)?,
+            None => {}
+        }
+    }
+    for msg in &archive.messages {
+        history.append(msg)?;
+    }
+    Ok(())
+}
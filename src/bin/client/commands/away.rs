@@ -0,0 +1,74 @@
+//! Being away (`/away`, `/back`) and answering while away (`/autoreply`)
+
+use super::{split, Action, Handler};
+use crate::i18n::{tr, tr_args};
+use crate::local_time;
+use crate::session::Session;
+use nym_chat::away;
+use nym_chat::determinism;
+use std::path::Path;
+
+pub struct Away;
+
+impl Handler for Away {
+    fn commands(&self) -> &[&'static str] {
+        &["/away", "/back", "/autoreply"]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        let data_dir = session.data_dir;
+        Action::Reply(match split(cmd) {
+            ("/away", message) => {
+                let message = match message {
+                    "" => tr("away-default"),
+                    message => message.to_owned(),
+                };
+                let since = determinism::now();
+                let away = away::Away { message, since };
+                // Being away is shared with other rooms through the data directory
+                match data_dir.map(|data_dir| away.save(data_dir)) {
+                    Some(Ok(())) if session.auto_reply => tr("away-set"),
+                    Some(Ok(())) => tr("away-set-no-auto-reply"),
+                    Some(Err(e)) => tr_args("away-save-failed", &[("error", e.to_string().into())]),
+                    None => tr("incognito-unsupported"),
+                }
+            }
+            ("/back", _) => match data_dir.map(away::Away::clear) {
+                Some(Ok(true)) => tr("away-cleared"),
+                Some(Ok(false)) => tr("away-not-away"),
+                Some(Err(e)) => tr_args("away-save-failed", &[("error", e.to_string().into())]),
+                None => tr("incognito-unsupported"),
+            },
+            (_, "") => describe_auto_reply(session.auto_reply, data_dir),
+            (_, enabled) => {
+                let enabled = match enabled {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Action::Reply(tr("autoreply-usage")),
+                };
+                match session.history.lock().unwrap().set_auto_reply(enabled) {
+                    Ok(()) => {
+                        session.auto_reply = enabled;
+                        describe_auto_reply(enabled, data_dir)
+                    }
+                    Err(e) => tr_args("away-save-failed", &[("error", e.to_string().into())]),
+                }
+            }
+        })
+    }
+}
+
+fn describe_auto_reply(enabled: bool, data_dir: Option<&Path>) -> String {
+    let away = data_dir.and_then(away::Away::load);
+    match (enabled, away) {
+        (true, Some(away)) => tr_args(
+            "autoreply-active",
+            &[
+                ("since", local_time(away.since).into()),
+                ("message", away.message.into()),
+            ],
+        ),
+        (true, None) => tr("autoreply-on"),
+        (false, _) => tr("autoreply-off"),
+    }
+}
@@ -0,0 +1,171 @@
+//! The address book (`/contacts`, `/petname`, `/verify`, `/unverify`, `/note`, `/forget`) and
+//! contact cards (`/card`)
+
+use super::{saved, split, Action, Handler};
+use crate::i18n::{tr, tr_args};
+use crate::session::Session;
+use nym_chat::address_book::{AddressBook, Lookup};
+use nym_chat::contact::ContactCard;
+use nym_chat::Payload;
+use std::path::Path;
+
+pub struct Contacts;
+
+impl Handler for Contacts {
+    fn commands(&self) -> &[&'static str] {
+        &[
+            "/card",
+            "/contacts",
+            "/petname",
+            "/verify",
+            "/unverify",
+            "/note",
+            "/forget",
+        ]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        match split(cmd) {
+            ("/card", rooms) => {
+                let outgoing = &session.outgoing;
+                let key = match (outgoing.broadcast_key, outgoing.signing_key) {
+                    (Some(key), Some(_)) => key,
+                    _ => session.identity().verifying_key(),
+                };
+                let card = ContactCard {
+                    name: session.name.clone(),
+                    fingerprint: Some(key.fingerprint()),
+                    rooms: rooms.split_whitespace().map(str::to_owned).collect(),
+                };
+                Action::Send {
+                    payload: Payload::Contact(card),
+                    text: cmd.to_owned(),
+                    reply: tr("card-sent"),
+                }
+            }
+            ("/contacts", "") => Action::Reply(describe_contacts(&session.address_book)),
+            _ => match change_contacts(&mut session.address_book, cmd) {
+                Ok(reply) => saved(session.address_book.save(), reply, "contacts-save-failed"),
+                Err(e) => Action::Reply(e),
+            },
+        }
+    }
+}
+
+pub fn describe_card(card: ContactCard) -> String {
+    let none = || tr("card-none");
+    tr_args(
+        "card",
+        &[
+            ("name", card.name.into()),
+            ("fingerprint", card.fingerprint.unwrap_or_else(none).into()),
+            (
+                "rooms",
+                match card.rooms.is_empty() {
+                    true => none(),
+                    false => card.rooms.join(" "),
+                }
+                .into(),
+            ),
+        ],
+    )
+}
+
+fn describe_contacts(address_book: &AddressBook) -> String {
+    let contacts = address_book
+        .iter()
+        .map(|(fingerprint, contact)| {
+            tr_args(
+                match contact.verified {
+                    true => "contact-verified-entry",
+                    false => "contact-entry",
+                },
+                &[
+                    ("name", contact.name().to_owned().into()),
+                    ("fingerprint", fingerprint.to_owned().into()),
+                    ("rooms", contact.rooms.len().into()),
+                    ("notes", contact.notes.clone().into()),
+                ],
+            )
+        })
+        .collect::<Vec<_>>();
+    match contacts.is_empty() {
+        true => tr("contacts-none"),
+        false => contacts.join("; "),
+    }
+}
+
+/// Handle `/contacts export|import <path>`, `/petname <fingerprint> [<name>]`,
+/// `/verify <fingerprint>`, `/unverify <fingerprint>`, `/note <fingerprint> [<text>]` and
+/// `/forget <fingerprint>`. Fingerprints may be shortened and given without spaces as long as they
+/// match only one contact, leaving out the name or text removes it.
+fn change_contacts(address_book: &mut AddressBook, cmd: &str) -> Result<String, String> {
+    let (command, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+    let (first, rest) = args
+        .trim()
+        .split_once(' ')
+        .map(|(first, rest)| (first, rest.trim()))
+        .unwrap_or((args.trim(), ""));
+    if command == "/contacts" {
+        let path = Path::new(rest);
+        let failed =
+            |e: anyhow::Error| tr_args("contacts-file-failed", &[("error", e.to_string().into())]);
+        let args = |count: usize| [("count", count.into()), ("path", rest.to_owned().into())];
+        return match first {
+            _ if rest.is_empty() => Err(tr("contacts-usage")),
+            "export" => address_book
+                .export(path)
+                .map(|count| tr_args("contacts-exported", &args(count)))
+                .map_err(failed),
+            "import" => address_book
+                .import(path)
+                .map(|count| tr_args("contacts-imported", &args(count)))
+                .map_err(failed),
+            _ => Err(tr("contacts-usage")),
+        };
+    }
+    let fingerprint = address_book.find(first).map_err(|lookup| {
+        let id = match lookup {
+            Lookup::Unknown => "contact-unknown",
+            Lookup::Ambiguous => "contact-ambiguous",
+        };
+        tr_args(id, &[("fingerprint", first.into())])
+    })?;
+    if command == "/forget" {
+        address_book.remove(&fingerprint);
+        return Ok(tr_args(
+            "contact-forgotten",
+            &[("fingerprint", fingerprint.into())],
+        ));
+    }
+    let contact = address_book.entry(fingerprint.clone());
+    let id = match command {
+        "/petname" if rest.is_empty() => {
+            contact.petname = None;
+            "petname-removed"
+        }
+        "/petname" => {
+            contact.petname = Some(rest.to_owned());
+            "petname-set"
+        }
+        "/verify" => {
+            contact.verified = true;
+            "contact-verified"
+        }
+        "/unverify" => {
+            contact.verified = false;
+            "contact-unverified"
+        }
+        _ => {
+            contact.notes = rest.to_owned();
+            "contact-noted"
+        }
+    };
+    Ok(tr_args(
+        id,
+        &[
+            ("name", contact.name().to_owned().into()),
+            ("fingerprint", fingerprint.into()),
+        ],
+    ))
+}
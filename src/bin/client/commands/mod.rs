@@ -0,0 +1,110 @@
+//! The client's own slash commands, grouped by feature. Each feature implements [`Handler`] in a
+//! module next to this one and is listed in [`Handlers::default`]. Handlers work on the [`Session`]
+//! and return an [`Action`], the main loop carries it out (it owns the fetch tasks and can wait
+//! for sends to finish).
+//!
+//! Plugins (see the plugins module) bring their own commands, theirs are tried first.
+
+use crate::i18n::tr_args;
+use crate::session::Session;
+use nym_chat::{Envelope, Payload};
+
+mod away;
+mod contacts;
+mod moderation;
+mod polls;
+mod queue;
+mod room;
+mod settings;
+mod status;
+
+pub use contacts::describe_card;
+pub use moderation::describe_report;
+pub use polls::describe_poll;
+
+pub trait Handler {
+    /// Slash commands the handler takes, including the slash
+    fn commands(&self) -> &[&'static str];
+
+    /// One of the handler's commands was entered (the whole line)
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action;
+}
+
+/// What the main loop does once a command was handled
+pub enum Action {
+    /// Show the reply as a notice
+    Reply(String),
+    /// Show each of them as a notice of its own, like the rows of a QR code
+    Notices(Vec<String>),
+    /// Send the payload to the room (see [`crate::session::Outgoing::send`]) and show the reply.
+    /// `text` stands for it in the outbox.
+    Send {
+        payload: Payload,
+        text: String,
+        reply: String,
+    },
+    /// Hand the envelope to the primary server only, then show the notices
+    SendToPrimary {
+        envelope: Envelope,
+        notices: Vec<String>,
+    },
+    /// Stop the fetch tasks and show the reply
+    StopFetching(String),
+    /// Start fetching again from the stored read positions and show the reply
+    ResumeFetching(String),
+    /// Leave the room, `wipe` deletes everything stored about it
+    Leave { wipe: bool },
+    /// Nothing to show
+    Nothing,
+}
+
+/// The built-in commands by feature
+pub struct Handlers {
+    handlers: Vec<Box<dyn Handler>>,
+}
+
+impl Default for Handlers {
+    fn default() -> Handlers {
+        Handlers {
+            handlers: vec![
+                Box::new(room::Room),
+                Box::new(polls::Polls),
+                Box::new(contacts::Contacts),
+                Box::new(away::Away),
+                Box::new(queue::Queue),
+                Box::new(moderation::Moderation),
+                Box::new(settings::Settings),
+                Box::new(status::Status),
+            ],
+        }
+    }
+}
+
+impl Handlers {
+    /// Let the handler owning the command handle it
+    pub fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        let name = cmd.split_whitespace().next().unwrap_or_default();
+        let owner = self
+            .handlers
+            .iter_mut()
+            .find(|handler| handler.commands().contains(&name));
+        match owner {
+            Some(handler) => handler.command(cmd, session),
+            None => Action::Reply(tr_args("unknown-command", &[("command", cmd.into())])),
+        }
+    }
+}
+
+/// Split a command into its name and the rest of the line
+fn split(cmd: &str) -> (&str, &str) {
+    let (name, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+    (name, args.trim())
+}
+
+/// Reply with the result of saving a change, `failed` names the notice for errors
+fn saved(result: Result<(), anyhow::Error>, reply: String, failed: &str) -> Action {
+    Action::Reply(match result {
+        Ok(()) => reply,
+        Err(e) => tr_args(failed, &[("error", e.to_string().into())]),
+    })
+}
@@ -0,0 +1,102 @@
+//! Abuse reports: members send them with `/report`, the moderator lists them with `/reports`
+
+use super::{split, Action, Handler};
+use crate::i18n::{tr, tr_args};
+use crate::session::Session;
+use nym_chat::history::History;
+use nym_chat::report::{Report, Reports};
+use nym_chat::Payload;
+use std::sync::Mutex;
+
+/// Number of the newest messages `/report` looks for the reported one in
+const REPORT_SEARCH: usize = 1000;
+
+pub struct Moderation;
+
+impl Handler for Moderation {
+    fn commands(&self) -> &[&'static str] {
+        &["/report", "/reports"]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        match split(cmd) {
+            ("/reports", _) => Action::Reply(match session.moderator_key {
+                Some(_) => describe_reports(&session.reports),
+                None => tr("reports-not-moderator"),
+            }),
+            (_, args) => {
+                let key = match &session.invite.report {
+                    Some(key) => key,
+                    None => return Action::Reply(tr("report-no-moderator")),
+                };
+                match report_message(session.history, &session.name, args) {
+                    Ok(report) => Action::Send {
+                        payload: Payload::Report(report.seal(key)),
+                        text: "/report".to_owned(),
+                        reply: tr_args("report-sent", &[("sender", report.reported.sender.into())]),
+                    },
+                    Err(e) => Action::Reply(e),
+                }
+            }
+        }
+    }
+}
+
+/// Handle `/report <sender> <reason>`: the report quotes the newest message of `sender`
+fn report_message(history: &Mutex<History>, name: &str, args: &str) -> Result<Report, String> {
+    let (sender, reason) = args.split_once(' ').ok_or_else(|| tr("report-usage"))?;
+    let mut history = history.lock().unwrap();
+    let end = history.len();
+    let recent = history
+        .load(end.saturating_sub(REPORT_SEARCH)..end)
+        .map_err(|e| tr_args("report-failed", &[("error", e.to_string().into())]))?;
+    let reported = recent
+        .into_iter()
+        .rev()
+        .find(|msg| msg.sender == sender)
+        .ok_or_else(|| tr_args("report-not-found", &[("sender", sender.into())]))?;
+    Ok(Report {
+        reporter: name.to_owned(),
+        reported,
+        reason: reason.trim().to_owned(),
+    })
+}
+
+pub fn describe_report(report: &Report) -> String {
+    tr_args(
+        "report-received",
+        &[
+            ("reporter", report.reporter.clone().into()),
+            ("sender", report.reported.sender.clone().into()),
+            ("text", report.reported.msg.clone().into()),
+            ("reason", report.reason.clone().into()),
+        ],
+    )
+}
+
+/// Reported messages for `/reports`, the most reported first
+fn describe_reports(reports: &Reports) -> String {
+    if reports.is_empty() {
+        return tr("reports-none");
+    }
+    reports
+        .summary()
+        .into_iter()
+        .map(|(sender, text, reports)| {
+            let reasons = reports
+                .iter()
+                .map(|(reporter, reason)| format!("{}: {}", reporter, reason))
+                .collect::<Vec<_>>();
+            tr_args(
+                "reports-message",
+                &[
+                    ("sender", sender.into()),
+                    ("text", text.into()),
+                    ("count", reports.len().into()),
+                    ("reasons", reasons.join("; ").into()),
+                ],
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,105 @@
+//! `/poll`, `/vote` and `/polls`
+
+use super::{split, Action, Handler};
+use crate::i18n::{tr, tr_args};
+use crate::session::Session;
+use nym_chat::poll::{self, Poll, Vote};
+use nym_chat::Payload;
+
+pub struct Polls;
+
+impl Handler for Polls {
+    fn commands(&self) -> &[&'static str] {
+        &["/poll", "/vote", "/polls"]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        let result = match split(cmd) {
+            ("/polls", _) => {
+                let polls = &session.polls;
+                return Action::Reply(match polls.polls().is_empty() {
+                    true => tr("polls-none"),
+                    false => polls
+                        .polls()
+                        .iter()
+                        .map(|poll| describe_poll(poll, polls.results(poll.id)))
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                });
+            }
+            ("/poll", args) => new_poll(&session.name, args),
+            (_, args) => vote(&session.polls, &session.name, args),
+        };
+        match result {
+            Ok((payload, reply)) => Action::Send {
+                payload,
+                text: cmd.to_owned(),
+                reply,
+            },
+            Err(e) => Action::Reply(e),
+        }
+    }
+}
+
+/// `/poll <question> | <option> | <option>...`, returns the payload and the reply shown to the user
+fn new_poll(name: &str, args: &str) -> Result<(Payload, String), String> {
+    let mut parts = args
+        .split('|')
+        .map(|part| part.trim().to_owned())
+        .filter(|part| !part.is_empty());
+    let question = parts.next().unwrap_or_default();
+    let options = parts.collect::<Vec<_>>();
+    if options.len() < 2 {
+        return Err(tr("poll-invalid"));
+    }
+    let poll = Poll::new(name.to_owned(), question, options);
+    Ok((Payload::Poll(poll), tr("poll-sent")))
+}
+
+/// `/vote <poll id> <option number>`
+fn vote(polls: &poll::Polls, name: &str, args: &str) -> Result<(Payload, String), String> {
+    let mut args = args.split_whitespace();
+    let poll = args
+        .next()
+        .and_then(|id| u32::from_str_radix(id, 16).ok())
+        .and_then(|id| polls.get(id))
+        .ok_or_else(|| tr("vote-unknown-poll"))?;
+    let option = args
+        .next()
+        .and_then(|option| option.parse::<usize>().ok())
+        .filter(|option| (1..=poll.options.len()).contains(option))
+        .ok_or_else(|| tr("vote-invalid-option"))?;
+    let vote = Vote {
+        poll: poll.id,
+        voter: name.to_owned(),
+        option: option - 1,
+    };
+    let reply = tr_args(
+        "vote-sent",
+        &[("option", poll.options[option - 1].clone().into())],
+    );
+    Ok((Payload::Vote(vote), reply))
+}
+
+/// One line per poll, with the number of votes per option if `results` are given
+pub fn describe_poll(poll: &Poll, results: Option<Vec<usize>>) -> String {
+    let options = poll
+        .options
+        .iter()
+        .enumerate()
+        .map(|(idx, option)| match &results {
+            Some(results) => format!("{}) {} ({})", idx + 1, option, results[idx]),
+            None => format!("{}) {}", idx + 1, option),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    tr_args(
+        "poll",
+        &[
+            ("id", format!("{:08x}", poll.id).into()),
+            ("sender", poll.sender.clone().into()),
+            ("question", poll.question.clone().into()),
+            ("options", options.into()),
+        ],
+    )
+}
@@ -0,0 +1,176 @@
+//! Messages that don't go out as typed: the outbox (`/queue`, `/unqueue`), scheduled messages
+//! (`/schedule`, `/unschedule`), snippets (`/snip`) and burn-after-reading messages (`/burn`)
+
+use super::{saved, split, Action, Handler};
+use crate::events::AppEvent;
+use crate::i18n::{tr, tr_args};
+use crate::session::Session;
+use crate::{local_now, local_time};
+use nym_chat::outbox::Outbox;
+use nym_chat::schedule::{self, Schedule, Scheduled};
+use nym_chat::snippet::{ExpandError, Snippets};
+use nym_chat::{Message, Payload};
+
+pub struct Queue;
+
+impl Handler for Queue {
+    fn commands(&self) -> &[&'static str] {
+        &[
+            "/queue",
+            "/unqueue",
+            "/schedule",
+            "/unschedule",
+            "/snip",
+            "/burn",
+        ]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        match split(cmd) {
+            ("/queue", _) => Action::Reply(describe_outbox(&session.outgoing.outbox)),
+            ("/unqueue", number) => match cancel_queued(&mut session.outgoing.outbox, number) {
+                Ok(reply) => {
+                    let result = session
+                        .history
+                        .lock()
+                        .unwrap()
+                        .set_outbox(&session.outgoing.outbox);
+                    saved(result, reply, "queue-save-failed")
+                }
+                Err(e) => Action::Reply(e),
+            },
+            ("/schedule", "") => Action::Reply(describe_schedule(&session.schedule)),
+            (command, args) if command == "/schedule" || command == "/unschedule" => {
+                match change_schedule(&mut session.schedule, command, args) {
+                    Ok(reply) => {
+                        let result = session
+                            .history
+                            .lock()
+                            .unwrap()
+                            .set_schedule(&session.schedule);
+                        saved(result, reply, "schedule-save-failed")
+                    }
+                    Err(e) => Action::Reply(e),
+                }
+            }
+            ("/snip", "") => Action::Reply(match session.snippets.is_empty() {
+                true => tr("snippets-none"),
+                false => {
+                    let names = session.snippets.names().collect::<Vec<_>>().join(", ");
+                    tr_args("snippets-list", &[("names", names.into())])
+                }
+            }),
+            // The expanded text is sent like anything typed, so it shows up as our message instead
+            // of a notice
+            ("/snip", args) => match expand_snippet(&session.snippets, &session.name, args) {
+                Ok(text) => match session.scheduler.try_send(AppEvent::Send(text)) {
+                    Ok(()) => Action::Nothing,
+                    Err(_) => Action::Reply(tr("snippet-not-sent")),
+                },
+                Err(e) => Action::Reply(e),
+            },
+            // The outbox only shows that there was one, the text isn't kept anywhere
+            ("/burn", text) if !text.is_empty() => Action::Send {
+                payload: Payload::BurnAfterReading(Message::new(
+                    session.name.clone(),
+                    text.to_owned(),
+                )),
+                text: "/burn".to_owned(),
+                reply: tr("burn-sent"),
+            },
+            _ => Action::Reply(tr_args("unknown-command", &[("command", cmd.into())])),
+        }
+    }
+}
+
+fn describe_outbox(outbox: &Outbox) -> String {
+    let queued = outbox
+        .iter()
+        .enumerate()
+        .map(|(index, queued)| format!("{}. {}", index + 1, queued.text))
+        .collect::<Vec<_>>();
+    match queued.is_empty() {
+        true => tr("queue-none"),
+        false => tr_args("queue", &[("queued", queued.join("; ").into())]),
+    }
+}
+
+/// Handle `/unqueue <number>`, numbers are the ones `/queue` lists
+fn cancel_queued(outbox: &mut Outbox, number: &str) -> Result<String, String> {
+    let cancelled = number
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| outbox.cancel(index.checked_sub(1)?));
+    match cancelled {
+        Some(queued) => Ok(tr_args("queue-cancelled", &[("text", queued.text.into())])),
+        None => Err(tr_args("queue-unknown", &[("number", number.into())])),
+    }
+}
+
+fn describe_schedule(schedule: &Schedule) -> String {
+    let pending = schedule
+        .iter()
+        .enumerate()
+        .map(|(index, scheduled)| {
+            format!(
+                "{}. {} {}",
+                index + 1,
+                local_time(scheduled.at),
+                scheduled.msg
+            )
+        })
+        .collect::<Vec<_>>();
+    match pending.is_empty() {
+        true => tr("schedule-none"),
+        false => tr_args("schedule", &[("pending", pending.join("; ").into())]),
+    }
+}
+
+/// The text of a `/snip <name> <value> | <value>...` command
+fn expand_snippet(snippets: &Snippets, sender: &str, args: &str) -> Result<String, String> {
+    let (snippet, values) = args.split_once(' ').unwrap_or((args, ""));
+    // Values are numbered by position, so empty ones between two `|` still count
+    let values = match values.trim() {
+        "" => Vec::new(),
+        values => values.split('|').map(str::trim).collect(),
+    };
+    snippets
+        .expand(snippet, &values, sender, local_now())
+        .map_err(|e| match e {
+            ExpandError::Unknown => tr_args("snippet-unknown", &[("name", snippet.into())]),
+            ExpandError::MissingValue(number) => {
+                tr_args("snippet-missing-value", &[("number", number.into())])
+            }
+            ExpandError::InvalidPlaceholder(placeholder) => tr_args(
+                "snippet-invalid-placeholder",
+                &[("placeholder", placeholder.into())],
+            ),
+        })
+}
+
+/// Handle `/schedule <HH:MM> <text>` and `/unschedule <number>`, numbers are the ones `/schedule`
+/// lists
+fn change_schedule(schedule: &mut Schedule, command: &str, args: &str) -> Result<String, String> {
+    if command == "/unschedule" {
+        let cancelled = args
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| schedule.cancel(number.checked_sub(1)?));
+        return match cancelled {
+            Some(scheduled) => Ok(tr_args(
+                "schedule-cancelled",
+                &[("text", scheduled.msg.into())],
+            )),
+            None => Err(tr_args("schedule-unknown", &[("number", args.into())])),
+        };
+    }
+    let usage = || tr("schedule-usage");
+    let (time, text) = args.split_once(' ').ok_or_else(usage)?;
+    let at = schedule::next_time(time, local_now()).map_err(|_| usage())?;
+    let text = text.trim().to_owned();
+    if text.is_empty() {
+        return Err(usage());
+    }
+    schedule.add(Scheduled { at, msg: text });
+    Ok(tr_args("scheduled", &[("time", local_time(at).into())]))
+}
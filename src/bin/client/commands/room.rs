@@ -0,0 +1,350 @@
+//! Handing out and backing up the room (`/invite`, `/sharekey`, `/backup`, `/link`), creating
+//! rooms, archiving, exporting and leaving the room, announcing mirrors and `/who`
+
+use super::{split, Action, Handler};
+use crate::archive;
+use crate::i18n::{tr, tr_args};
+use crate::onboarding::SavedProfile;
+use crate::session::Session;
+use crate::sharekey;
+use nym_chat::backup::Backup;
+use nym_chat::broadcast::RoomSecret;
+use nym_chat::directory::Announcement;
+use nym_chat::epoch::{ChainKey, DEFAULT_EPOCH_SECS};
+use nym_chat::history::History;
+use nym_chat::invite::{Invite, InviteKind};
+use nym_chat::link::DeviceSync;
+use nym_chat::transport::Server;
+use nym_chat::{Envelope, Key, Payload};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub struct Room;
+
+impl Handler for Room {
+    fn commands(&self) -> &[&'static str] {
+        &[
+            "/invite",
+            "/sharekey",
+            "/backup",
+            "/link",
+            "/create",
+            "/create-restricted",
+            "/create-protected",
+            "/archive",
+            "/unarchive",
+            "/export-archive",
+            "/import-archive",
+            "/leave",
+            "/announce",
+            "/who",
+        ]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        match split(cmd) {
+            ("/invite", args) => Action::Reply(invite(session, args)),
+            ("/sharekey", args) => Action::Reply(share_key(session, args)),
+            ("/backup", "") => Action::Reply(tr("backup-usage")),
+            ("/backup", path) => Action::Reply(backup(session, path)),
+            ("/link", "") => link(session),
+            (command, room_name) if command.starts_with("/create") && !room_name.is_empty() => {
+                create(session, command, room_name)
+            }
+            ("/archive", "") => match session.history.lock().unwrap().set_archived(true) {
+                Ok(()) => {
+                    session.archived = true;
+                    Action::StopFetching(tr("room-archived"))
+                }
+                Err(e) => Action::Reply(archive_failed(e)),
+            },
+            ("/unarchive", "") => match session.history.lock().unwrap().set_archived(false) {
+                // Fetching continues where /archive stopped it
+                Ok(()) if session.archived => {
+                    session.archived = false;
+                    Action::ResumeFetching(tr("room-unarchived"))
+                }
+                Ok(()) => Action::Reply(tr("room-unarchived")),
+                Err(e) => Action::Reply(archive_failed(e)),
+            },
+            ("/export-archive", path) if !path.is_empty() => {
+                let result = archive::export(
+                    &mut session.history.lock().unwrap(),
+                    session.room,
+                    session.invite.room_name.clone(),
+                    session.servers,
+                    Path::new(path),
+                );
+                Action::Reply(match result {
+                    Ok(count) => tr_args("archive-exported", &[("count", count.into())]),
+                    Err(e) => tr_args("archive-export-failed", &[("error", e.to_string().into())]),
+                })
+            }
+            // Running fetches would overwrite the archive's read positions
+            ("/import-archive", path) if !path.is_empty() => {
+                Action::Reply(tr("archive-import-at-startup"))
+            }
+            ("/leave", "") => Action::Leave { wipe: true },
+            ("/leave", "keep-history") => Action::Leave { wipe: false },
+            ("/announce", server) => match Server::from_str(server) {
+                Ok(server) => Action::Send {
+                    payload: Payload::Announcement(Announcement {
+                        url: server.url.clone(),
+                        service_provider: server.service_provider.to_string(),
+                    }),
+                    text: cmd.to_owned(),
+                    reply: tr_args("announce-sent", &[("url", server.url.into())]),
+                },
+                Err(e) => Action::Reply(tr_args("announce-invalid", &[("error", e.into())])),
+            },
+            ("/who", "") => Action::Reply(
+                session
+                    .roster
+                    .members()
+                    .into_iter()
+                    .map(|(name, status)| {
+                        let status = tr(&format!("presence-{}", status.as_str()));
+                        format!("{} ({})", name, status)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+            _ => Action::Reply(tr_args("unknown-command", &[("command", cmd.into())])),
+        }
+    }
+}
+
+/// `/invite [read] [no-history]`
+fn invite(session: &Session, args: &str) -> String {
+    let args = args.split_whitespace().collect::<Vec<_>>();
+    let read_only = args.contains(&"read");
+    let no_history = args.contains(&"no-history");
+    // New members start reading at the current epoch's chain position
+    let chain = match no_history {
+        true => session.keys.current_chain(),
+        false => session.keys.chain(),
+    };
+    if args.iter().any(|arg| !["read", "no-history"].contains(arg)) {
+        return tr("invite-usage");
+    }
+    if no_history && chain.is_none() {
+        return tr("invite-no-history-unsupported");
+    }
+    let mut invite = session.invite.clone();
+    invite.chain = chain;
+    if read_only {
+        invite.secret = None;
+    }
+    invite.to_string()
+}
+
+/// `/sharekey <public key file> [<output file>]` encrypts the same invite `/invite` creates,
+/// including the room's secret
+fn share_key(session: &Session, args: &str) -> String {
+    let mut args = args.split_whitespace();
+    let recipient = match args.next() {
+        Some(recipient) => PathBuf::from(recipient),
+        None => return tr("sharekey-usage"),
+    };
+    let output = args.next().map(PathBuf::from);
+    let mut invite = session.invite.clone();
+    invite.chain = session.keys.chain();
+    match sharekey::export(&invite.to_string(), &recipient, output.as_deref()) {
+        Ok(path) => tr_args(
+            "sharekey-exported",
+            &[("path", path.display().to_string().into())],
+        ),
+        Err(e) => tr_args("sharekey-export-failed", &[("error", e.to_string().into())]),
+    }
+}
+
+/// `/backup <file>`: the profile's rooms and this one, which sessions without a profile only have
+fn backup(session: &Session, path: &str) -> String {
+    let mut rooms = session
+        .profile
+        .as_ref()
+        .map_or_else(Vec::new, |saved| saved.profile.rooms.clone());
+    let known = rooms.iter().any(|invite| {
+        let invite = Invite::from_str(invite).ok();
+        invite.and_then(|invite| invite.key).as_ref() == Some(session.room)
+    });
+    if !known {
+        let mut invite = session.invite.clone();
+        invite.chain = session.keys.chain();
+        rooms.push(invite.to_string());
+    }
+    let backup = Backup {
+        name: session.name.clone(),
+        identity: session
+            .profile
+            .as_ref()
+            .map(|saved| saved.profile.identity().to_hex()),
+        rooms,
+    };
+    let (phrase, text) = backup.seal();
+    match std::fs::write(path, text) {
+        Ok(()) => tr_args(
+            "backup-written",
+            &[
+                ("path", path.into()),
+                ("phrase", phrase.to_mnemonic().into()),
+            ],
+        ),
+        Err(e) => tr_args("backup-failed", &[("error", e.to_string().into())]),
+    }
+}
+
+/// `/link` sends our state encrypted to a one-time key, only a device that knows the key (from
+/// the link) can find and decrypt it. It lands after what we fetched so far, the new device looks
+/// from there.
+fn link(session: &Session) -> Action {
+    let one_time_key = Key::generate();
+    let rooms = session
+        .profile
+        .as_ref()
+        .map_or_else(Vec::new, |saved| saved.profile.rooms.clone());
+    let read_positions =
+        room_read_positions(&rooms, session.room, session.last_fetch, session.data_dir);
+    let sync = DeviceSync {
+        name: session.name.clone(),
+        room: session.room.to_hex(),
+        room_name: session.invite.room_name.clone(),
+        read_position: session.last_fetch,
+        chain: session.keys.chain().map(|chain| chain.to_string()),
+        identity: session
+            .profile
+            .as_ref()
+            .map(|saved| saved.profile.identity().to_hex()),
+        rooms,
+        read_positions,
+    };
+    let envelope = Envelope {
+        msg: sync.encrypt(&one_time_key),
+        max_age_secs: None,
+    };
+    let link = Invite {
+        kind: InviteKind::Link,
+        server: session.invite.server.clone(),
+        service_provider: session.invite.service_provider,
+        key: Some(one_time_key),
+        hint: None,
+        room_name: None,
+        max_age: None,
+        broadcast: None,
+        secret: None,
+        report: None,
+        chain: None,
+        from: Some(session.last_fetch),
+    }
+    .to_string();
+    let mut notices = vec![link.clone()];
+    if session.draw_qr_codes {
+        notices.extend(qr_code(&link));
+    }
+    Action::SendToPrimary { envelope, notices }
+}
+
+/// `/create <name>`, `/create-restricted <name>` (only members with the room's secret may post)
+/// and `/create-protected <name>` (with a key chain) create a room on our server
+fn create(session: &mut Session, command: &str, room_name: &str) -> Action {
+    let secret = Some(RoomSecret::generate()).filter(|_| command == "/create-restricted");
+    let chain =
+        Some(ChainKey::generate(DEFAULT_EPOCH_SECS)).filter(|_| command == "/create-protected");
+    let invite = Invite {
+        kind: InviteKind::Join,
+        server: session.invite.server.clone(),
+        service_provider: session.invite.service_provider,
+        key: Some(
+            secret
+                .as_ref()
+                .map_or_else(Key::generate, RoomSecret::read_key),
+        ),
+        hint: None,
+        room_name: Some(room_name.to_owned()),
+        max_age: None,
+        broadcast: secret
+            .as_ref()
+            .map(|secret| secret.write_key().verifying_key()),
+        secret,
+        report: None,
+        chain,
+        from: None,
+    };
+    let mut notices = create_room(&invite, session.profile.as_mut());
+    if session.draw_qr_codes {
+        notices.extend(qr_code(&invite.to_string()));
+    }
+    Action::Notices(notices)
+}
+
+fn archive_failed(e: anyhow::Error) -> String {
+    tr_args("archive-save-failed", &[("error", e.to_string().into())])
+}
+
+/// Remember a room created with `/create` in the profile and describe it
+fn create_room(invite: &Invite, profile: Option<&mut SavedProfile>) -> Vec<String> {
+    let key = invite.key.as_ref().expect("created rooms have a key");
+    let mut notices = vec![tr_args(
+        "room-created",
+        &[
+            ("name", invite.room_name.clone().unwrap_or_default().into()),
+            ("id", key.id().into()),
+            ("invite", invite.to_string().into()),
+        ],
+    )];
+    if invite.secret.is_some() {
+        notices.push(tr_args(
+            "room-created-read-only",
+            &[("invite", invite.read_only().to_string().into())],
+        ));
+    }
+    match profile {
+        Some(saved) => {
+            saved.profile.add_room(invite.to_string());
+            if let Err(e) = saved.save() {
+                notices.push(tr_args(
+                    "profile-save-failed",
+                    &[("error", e.to_string().into())],
+                ));
+            }
+        }
+        None => notices.push(tr("room-created-no-profile")),
+    }
+    notices
+}
+
+/// Read positions of the rooms with the invite links `rooms` for `/link`, by room id. The current
+/// `room` is at `last_fetch`, the others at what their history stored.
+fn room_read_positions(
+    rooms: &[String],
+    room: &Key,
+    last_fetch: usize,
+    data_dir: Option<&Path>,
+) -> BTreeMap<String, usize> {
+    let mut positions = rooms
+        .iter()
+        .filter_map(|invite| Invite::from_str(invite).ok()?.key)
+        .filter_map(|key| {
+            let position = History::stored_read_position(data_dir?, &key)?;
+            Some((key.id(), position))
+        })
+        .collect::<BTreeMap<_, _>>();
+    positions.insert(room.id(), last_fetch);
+    positions
+}
+
+/// Rows of a QR code for `text` drawn with half blocks, light on dark like most terminals
+fn qr_code(text: &str) -> Vec<String> {
+    let code = match qrcode::QrCode::new(text) {
+        Ok(code) => code,
+        Err(_) => return Vec::new(),
+    };
+    code.render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build()
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
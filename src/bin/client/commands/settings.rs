@@ -0,0 +1,173 @@
+//! Per-room settings: notification rules (`/notify`, `/watch`, `/unwatch`, `/quiet`), how often
+//! the room is fetched (`/priority`) and how long its history is kept (`/retention`)
+
+use super::{saved, split, Action, Handler};
+use crate::i18n::{tr, tr_args};
+use crate::session::Session;
+use nym_chat::history::{Priority, Retention};
+use nym_chat::notify::{Level, QuietHours, Rules};
+use std::str::FromStr;
+use tokio::time::Duration;
+
+pub struct Settings;
+
+impl Handler for Settings {
+    fn commands(&self) -> &[&'static str] {
+        &[
+            "/notify",
+            "/watch",
+            "/unwatch",
+            "/quiet",
+            "/priority",
+            "/retention",
+        ]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        match split(cmd) {
+            ("/notify", "") => Action::Reply(describe_rules(&session.notify_rules)),
+            ("/priority", "") => {
+                Action::Reply(describe_priority(session.priority, session.fetch_interval))
+            }
+            ("/priority", priority) => {
+                let priority = match priority.parse::<Priority>() {
+                    Ok(priority) => priority,
+                    Err(()) => return Action::Reply(tr("priority-usage")),
+                };
+                match session.history.lock().unwrap().set_priority(priority) {
+                    Ok(()) => {
+                        session.priority = priority;
+                        let _ = session
+                            .interval
+                            .send(priority.interval(session.fetch_interval));
+                        Action::Reply(describe_priority(priority, session.fetch_interval))
+                    }
+                    Err(e) => Action::Reply(tr_args(
+                        "priority-save-failed",
+                        &[("error", e.to_string().into())],
+                    )),
+                }
+            }
+            ("/retention", args) => {
+                let history = session.history.lock().unwrap();
+                let mut retention = history.retention();
+                if args.is_empty() {
+                    return Action::Reply(describe_retention(&retention));
+                }
+                match change_retention(&mut retention, args) {
+                    Ok(()) => saved(
+                        history.set_retention(&retention),
+                        describe_retention(&retention),
+                        "retention-save-failed",
+                    ),
+                    Err(e) => Action::Reply(e),
+                }
+            }
+            (command, arg) => match change_rules(&mut session.notify_rules, command, arg) {
+                Ok(()) => {
+                    let rules = &session.notify_rules;
+                    let result = session.history.lock().unwrap().set_notify_rules(rules);
+                    saved(result, describe_rules(rules), "notify-save-failed")
+                }
+                Err(e) => Action::Reply(e),
+            },
+        }
+    }
+}
+
+/// Apply one of the `/notify`, `/watch`, `/unwatch` and `/quiet` commands, errors are shown to
+/// the user as they are
+fn change_rules(rules: &mut Rules, cmd: &str, arg: &str) -> Result<(), String> {
+    match cmd {
+        "/notify" => {
+            rules.level = Level::from_str(arg)
+                .map_err(|_| tr_args("notify-invalid-level", &[("level", arg.into())]))?
+        }
+        "/watch" if !arg.is_empty() => {
+            rules.watch(arg);
+        }
+        "/unwatch" => {
+            if !rules.unwatch(arg) {
+                return Err(tr_args("notify-not-watched", &[("keyword", arg.into())]));
+            }
+        }
+        "/quiet" if arg == "off" => rules.quiet_hours = None,
+        "/quiet" => {
+            rules.quiet_hours = Some(
+                QuietHours::from_str(arg)
+                    .map_err(|_| tr_args("notify-invalid-quiet-hours", &[("hours", arg.into())]))?,
+            )
+        }
+        cmd => return Err(tr_args("unknown-command", &[("command", cmd.into())])),
+    }
+    Ok(())
+}
+
+fn describe_rules(rules: &Rules) -> String {
+    let keywords = match rules.keywords.is_empty() {
+        true => tr("notify-none"),
+        false => rules.keywords.join(", "),
+    };
+    let quiet_hours = match rules.quiet_hours {
+        Some(quiet_hours) => quiet_hours.to_string(),
+        None => tr("notify-none"),
+    };
+    tr_args(
+        "notify-rules",
+        &[
+            (
+                "level",
+                tr(&format!("notify-level-{}", rules.level.as_str())).into(),
+            ),
+            ("keywords", keywords.into()),
+            ("quiet", quiet_hours.into()),
+        ],
+    )
+}
+
+/// Apply `/retention persist on|off`, `/retention keep <days>|forever` or `/retention export
+/// on|off`
+fn change_retention(retention: &mut Retention, args: &str) -> Result<(), String> {
+    let invalid = || tr("retention-invalid");
+    let (setting, value) = args.split_once(' ').ok_or_else(invalid)?;
+    let on_off = |value: &str| match value.trim() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(invalid()),
+    };
+    match setting {
+        "persist" => retention.persist = on_off(value)?,
+        "export" => retention.export = on_off(value)?,
+        "keep" if value.trim() == "forever" => retention.keep_days = None,
+        "keep" => retention.keep_days = Some(value.trim().parse().map_err(|_| invalid())?),
+        _ => return Err(invalid()),
+    }
+    Ok(())
+}
+
+fn describe_priority(priority: Priority, fetch_interval: Duration) -> String {
+    let interval = format!("{:.1}", priority.interval(fetch_interval).as_secs_f64());
+    tr_args(
+        "priority",
+        &[
+            ("priority", priority.to_string().into()),
+            ("interval", interval.into()),
+        ],
+    )
+}
+
+fn describe_retention(retention: &Retention) -> String {
+    let on_off = |on: bool| tr(if on { "retention-on" } else { "retention-off" });
+    let keep = match retention.keep_days {
+        Some(days) => tr_args("retention-days", &[("days", days.into())]),
+        None => tr("retention-forever"),
+    };
+    tr_args(
+        "retention",
+        &[
+            ("persist", on_off(retention.persist).into()),
+            ("keep", keep.into()),
+            ("export", on_off(retention.export).into()),
+        ],
+    )
+}
@@ -0,0 +1,113 @@
+//! What the client observed this session: `/stats`, `/receipts`, `/padding` and `/debug`
+
+use super::{Action, Handler};
+use crate::i18n::{tr, tr_args};
+use crate::latency::Latency;
+use crate::session::Session;
+use fluent_bundle::FluentValue;
+use nym_chat::health::{Health, Problem};
+use nym_chat::padding::{self, Traffic};
+use std::path::Path;
+use tokio::time::Duration;
+
+pub struct Status;
+
+impl Handler for Status {
+    fn commands(&self) -> &[&'static str] {
+        &["/stats", "/receipts", "/padding", "/debug"]
+    }
+
+    fn command(&mut self, cmd: &str, session: &mut Session) -> Action {
+        let outgoing = &session.outgoing;
+        Action::Reply(match cmd.split_whitespace().next().unwrap_or_default() {
+            "/stats" => format!(
+                "{}\n{}",
+                describe_latency(
+                    &session.latency,
+                    session.priority.interval(session.fetch_interval)
+                ),
+                describe_health(session.health)
+            ),
+            "/receipts" => match outgoing.receipts {
+                true => describe_receipts(
+                    session.receipts_received,
+                    outgoing.awaiting_receipts.len(),
+                    session.history.lock().unwrap().receipts_path(),
+                ),
+                false => tr("receipts-disabled"),
+            },
+            "/padding" => describe_padding(outgoing.pad, &outgoing.traffic.lock().unwrap()),
+            _ => session
+                .debug_log
+                .describe()
+                .unwrap_or_else(|| tr("debug-empty")),
+        })
+    }
+}
+
+/// Round trip statistics of our messages for `/stats`
+fn describe_latency(latency: &Latency, interval: Duration) -> String {
+    let seconds = |duration: Duration| format!("{:.1}", duration.as_secs_f64());
+    let mut args: Vec<(&str, FluentValue)> = vec![
+        ("sent", latency.sent_count().into()),
+        ("delivered", latency.delivered_count().into()),
+        ("pending", latency.pending_count().into()),
+        ("interval", seconds(interval).into()),
+    ];
+    let id = match latency.summary() {
+        Some(summary) => {
+            args.push(("min", seconds(summary.min).into()));
+            args.push(("median", seconds(summary.median).into()));
+            args.push(("p90", seconds(summary.p90).into()));
+            args.push(("max", seconds(summary.max).into()));
+            "stats"
+        }
+        None => "stats-empty",
+    };
+    tr_args(id, &args)
+}
+
+/// What went wrong between us and the servers this session, for `/stats`
+fn describe_health(health: &Health) -> String {
+    let args = Problem::ALL
+        .iter()
+        .map(|problem| (problem.name(), health.count(*problem).into()))
+        .collect::<Vec<(&str, FluentValue)>>();
+    tr_args("stats-health", &args)
+}
+
+fn describe_receipts(received: usize, awaiting: usize, path: Option<&Path>) -> String {
+    let mut args = vec![("received", received.into()), ("awaiting", awaiting.into())];
+    match path {
+        Some(path) => {
+            args.push(("path", path.display().to_string().into()));
+            tr_args("receipts", &args)
+        }
+        None => tr_args("receipts-in-memory", &args),
+    }
+}
+
+fn describe_padding(policy: padding::Policy, traffic: &Traffic) -> String {
+    let buckets = policy.buckets(traffic);
+    if buckets.is_empty() {
+        return tr("padding-off");
+    }
+    let overhead = (traffic.overhead(&buckets) * 100.0).round() as u64;
+    tr_args(
+        "padding-buckets",
+        &[
+            ("policy", policy.to_string().into()),
+            (
+                "buckets",
+                buckets
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+                    .into(),
+            ),
+            ("samples", traffic.len().into()),
+            ("overhead", overhead.into()),
+        ],
+    )
+}
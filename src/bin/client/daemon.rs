@@ -0,0 +1,412 @@
+//! Daemon mode: instead of running the TUI the client keeps the Nym connection, fetching,
+//! decryption and persistence running in the background and front-ends attach to it over a local
+//! Unix socket. The socket speaks the JSON-RPC API from the rpc module, which maps closely to the
+//! [`UiEvent`]/[`AppEvent`] interface of the events module, so the TUI can be closed and restarted
+//! without losing the mixnet session. For editors and shell tools there is also a plain text mode,
+//! see [`PlainConnection`].
+
+use crate::events::{self, AppEvent, UiChannels, UiEvent};
+use crate::i18n::tr_args;
+use crate::rpc::{self, Outgoing, Request, Response};
+use crate::ui::UiOptions;
+use nym_chat::history::History;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::select;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::Sender;
+use tracing::{debug, warn};
+
+/// Number of recent messages replayed to a front-end when it attaches
+const REPLAY_MESSAGES: usize = 100;
+
+/// Number of events buffered per front-end before it starts missing some
+const BROADCAST_CAPACITY: usize = 256;
+
+/// Room served by the daemon as returned by `list_rooms`
+#[derive(Clone, Serialize)]
+pub struct RoomInfo {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+/// Bind the control socket at `path`, before anything else is started so a bad path stops us
+pub fn bind(path: &Path) -> Result<UnixListener, anyhow::Error> {
+    // A stale socket from a previous run would make binding fail, anything else at the path is
+    // left alone in case it was a typo
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => anyhow::bail!("{} exists and isn't a socket", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(UnixListener::bind(path)?)
+}
+
+/// Serve front-ends on the control socket `listener` bound at `path` until the main thread exits
+pub async fn serve(
+    path: PathBuf,
+    listener: UnixListener,
+    channels: UiChannels,
+    history: Arc<Mutex<History>>,
+    room: RoomInfo,
+) {
+    let UiChannels {
+        events: mut incoming,
+        app,
+    } = channels;
+    let (broadcast, _) = broadcast::channel::<UiEvent>(BROADCAST_CAPACITY);
+
+    loop {
+        select! {
+            Ok((stream, _)) = listener.accept() => {
+                debug!("Front-end attached");
+                tokio::spawn(handle_frontend(
+                    stream,
+                    broadcast.subscribe(),
+                    app.clone(),
+                    history.clone(),
+                    room.clone(),
+                ));
+            },
+            event = incoming.recv() => match event {
+                // It's fine if no front-end is attached right now
+                Some(event) => { let _ = broadcast.send(event); },
+                None => break,
+            },
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+}
+
+async fn handle_frontend(
+    stream: UnixStream,
+    events: broadcast::Receiver<UiEvent>,
+    app: Sender<AppEvent>,
+    history: Arc<Mutex<History>>,
+    room: RoomInfo,
+) {
+    let (read, write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    // The first line decides the protocol: `plain` (optionally followed by the room id or name)
+    // switches to the line based text protocol, everything else is JSON-RPC
+    let first = match lines.next_line().await {
+        Ok(Some(line)) => line,
+        _ => return,
+    };
+    let result = if first == "plain" || first.starts_with("plain ") {
+        let requested = first["plain".len()..].trim();
+        let connection = PlainConnection { write, app };
+        connection
+            .serve(requested, lines, events, &history, &room)
+            .await
+    } else {
+        let connection = RpcConnection {
+            write,
+            app,
+            history,
+            room,
+            subscribed: false,
+        };
+        connection.serve(first, lines, events).await
+    };
+
+    match result {
+        Ok(()) => debug!("Front-end detached"),
+        Err(e) => debug!("Front-end connection failed: {}", e),
+    }
+}
+
+type Lines = tokio::io::Lines<BufReader<OwnedReadHalf>>;
+
+/// Front-end speaking JSON-RPC, see the rpc module
+struct RpcConnection {
+    write: OwnedWriteHalf,
+    app: Sender<AppEvent>,
+    history: Arc<Mutex<History>>,
+    room: RoomInfo,
+    /// Events are only streamed after the front-end asked for them
+    subscribed: bool,
+}
+
+impl RpcConnection {
+    async fn serve(
+        mut self,
+        first: String,
+        mut lines: Lines,
+        mut events: broadcast::Receiver<UiEvent>,
+    ) -> std::io::Result<()> {
+        self.handle_line(&first).await?;
+        loop {
+            select! {
+                event = events.recv() => match event {
+                    Ok(event) if self.subscribed => {
+                        let notification = Outgoing::Notification(rpc::notification(&event));
+                        write_line(&mut self.write, &notification).await?;
+                    }
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Front-end is too slow, it missed {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                line = lines.next_line() => match line? {
+                    Some(line) => self.handle_line(&line).await?,
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+
+    async fn handle_line(&mut self, line: &str) -> std::io::Result<()> {
+        let request = match serde_json::from_str::<Request>(line) {
+            Ok(request) => request,
+            Err(e) => {
+                let response = Response::error(Value::Null, rpc::PARSE_ERROR, &e.to_string());
+                return write_line(&mut self.write, &Outgoing::Response(response)).await;
+            }
+        };
+
+        let result = if request.method == "subscribe" {
+            // Give the front-end some context first
+            for event in recent_messages(&self.history) {
+                let notification = Outgoing::Notification(rpc::notification(&event));
+                write_line(&mut self.write, &notification).await?;
+            }
+            self.subscribed = true;
+            Ok(Value::Null)
+        } else {
+            dispatch(&request, &self.app, &self.room).await
+        };
+
+        // Requests without id are notifications and don't get an answer
+        let id = match request.id {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let response = match result {
+            Ok(result) => Response::result(id, result),
+            Err((code, message)) => Response::error(id, code, &message),
+        };
+        write_line(&mut self.write, &Outgoing::Response(response)).await
+    }
+}
+
+/// Front-end speaking the plain text protocol, meant for editors and shell tools in the spirit
+/// of ii: every line received is sent to the room (or run as a command if it starts with `/`),
+/// every message is written as `sender: message` and command output as `* output`. Line breaks
+/// inside messages are escaped as `\n`.
+struct PlainConnection {
+    write: OwnedWriteHalf,
+    app: Sender<AppEvent>,
+}
+
+impl PlainConnection {
+    async fn serve(
+        mut self,
+        requested_room: &str,
+        mut lines: Lines,
+        mut events: broadcast::Receiver<UiEvent>,
+        history: &Mutex<History>,
+        room: &RoomInfo,
+    ) -> std::io::Result<()> {
+        if !requested_room.is_empty()
+            && requested_room != room.id
+            && Some(requested_room) != room.name.as_deref()
+        {
+            let error = format!("* unknown room {}\n", requested_room);
+            return self.write.write_all(error.as_bytes()).await;
+        }
+
+        for event in recent_messages(history) {
+            self.write_event(&event).await?;
+        }
+
+        loop {
+            select! {
+                event = events.recv() => match event {
+                    Ok(event) => self.write_event(&event).await?,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Front-end is too slow, it missed {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                line = lines.next_line() => match line? {
+                    Some(line) if line.trim().is_empty() => {}
+                    Some(line) => {
+                        if self.app.send(AppEvent::from_input(line)).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+
+    async fn write_event(&mut self, event: &UiEvent) -> std::io::Result<()> {
+        let line = match event {
+            UiEvent::Message(_, msg) => format!("{}: {}\n", msg.sender, escape(&msg.msg)),
+            UiEvent::Notice(notice) => format!("* {}\n", escape(notice)),
+            // Indented, so they read as belonging to the message before
+            UiEvent::Annotation(_, text) => format!("  {}\n", escape(text)),
+            UiEvent::Warning(warning) => format!("! {}\n", escape(warning)),
+            UiEvent::Undecryptable(count) => format!(
+                "! {}\n",
+                tr_args("status-undecryptable", &[("count", (*count).into())])
+            ),
+            UiEvent::BurnAfterReading(msg) => {
+                format!("~ {}: {}\n", msg.sender, escape(&msg.msg))
+            }
+            // The plain text stream has no way to express them
+            UiEvent::Alert(_) | UiEvent::Custom(_) => return Ok(()),
+        };
+        self.write.write_all(line.as_bytes()).await
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Handle all requests that don't need access to the connection itself
+async fn dispatch(
+    request: &Request,
+    app: &Sender<AppEvent>,
+    room: &RoomInfo,
+) -> Result<Value, (i64, String)> {
+    let event = match request.method.as_str() {
+        "version" => return Ok(json!({ "version": rpc::RPC_VERSION })),
+        "list_rooms" => return Ok(json!([room])),
+        "send" => match request.params["text"].as_str() {
+            Some(text) => AppEvent::Send(text.into()),
+            None => return Err((rpc::INVALID_PARAMS, "missing text".into())),
+        },
+        "command" => match request.params["command"].as_str() {
+            Some(command) if command.starts_with('/') => AppEvent::Command(command.into()),
+            _ => return Err((rpc::INVALID_PARAMS, "command has to start with /".into())),
+        },
+        "send_custom" => {
+            let type_uri = request.params["type"].as_str();
+            let data = request.params["data"].as_str().map(base64::decode);
+            match (type_uri, data) {
+                (Some(type_uri), Some(Ok(data))) => AppEvent::SendCustom {
+                    type_uri: type_uri.into(),
+                    data,
+                },
+                _ => return Err((rpc::INVALID_PARAMS, "expected type and base64 data".into())),
+            }
+        }
+        "join_room" => {
+            return Err((
+                rpc::UNSUPPORTED,
+                "a daemon serves a single room, start another daemon to join a room".into(),
+            ))
+        }
+        _ => return Err((rpc::METHOD_NOT_FOUND, "unknown method".into())),
+    };
+
+    app.send(event)
+        .await
+        .map(|_| Value::Null)
+        .map_err(|_| (rpc::UNSUPPORTED, "client is shutting down".into()))
+}
+
+fn recent_messages(history: &Mutex<History>) -> Vec<UiEvent> {
+    let mut history = history.lock().unwrap();
+    let end = history.len();
+    let start = end.saturating_sub(REPLAY_MESSAGES);
+    history
+        .load(start..end)
+        .unwrap_or_default()
+        .into_iter()
+        .zip(start..)
+        .map(|(msg, index)| UiEvent::Message(index, msg))
+        .collect()
+}
+
+async fn write_line<W: AsyncWrite + Unpin, T: Serialize>(
+    write: &mut W,
+    value: &T,
+) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(value).expect("serialization can't fail");
+    line.push(b'\n');
+    write.write_all(&line).await
+}
+
+/// Run the TUI as a front-end of the daemon listening on `path`
+pub async fn attach(path: &Path, options: UiOptions) -> Result<(), anyhow::Error> {
+    let stream = UnixStream::connect(path).await?;
+    let (app, ui_channels) = events::channels();
+    // The daemon doesn't tell us our name, message sizes shown while typing are a bit too low
+    let ui = tokio::task::spawn_blocking(move || {
+        crate::ui::run_ui(ui_channels, None, String::new(), options)
+    });
+
+    let result = forward(stream, app).await;
+    // Dropping our end of the channels makes the UI exit and restore the terminal
+    let _ = ui.await;
+    result
+}
+
+/// Pass events between the daemon and the local UI until one of them goes away
+async fn forward(stream: UnixStream, mut app: events::AppChannels) -> Result<(), anyhow::Error> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut next_id = 0u64;
+
+    write_line(
+        &mut write,
+        &Request::new(Some(next_id), "subscribe", Value::Null),
+    )
+    .await?;
+
+    loop {
+        select! {
+            line = lines.next_line() => match line? {
+                Some(line) => match serde_json::from_str::<Outgoing>(&line)? {
+                    Outgoing::Notification(notification) => {
+                        if let Some(event) = rpc::parse_notification(&notification) {
+                            if app.ui.send(event).is_err() {
+                                return Ok(());
+                            }
+                        }
+                    }
+                    Outgoing::Response(Response { error: Some(error), .. }) => {
+                        let notice = tr_args("daemon-error", &[("error", error.message.into())]);
+                        let _ = app.ui.send(UiEvent::Notice(notice));
+                    }
+                    Outgoing::Response(_) => {}
+                },
+                None => return Err(anyhow::Error::msg("the daemon closed the connection")),
+            },
+            event = app.events.recv() => {
+                next_id += 1;
+                let request = match event {
+                    Some(AppEvent::Send(text)) => {
+                        Request::new(Some(next_id), "send", json!({ "text": text }))
+                    }
+                    Some(AppEvent::Command(command)) => {
+                        Request::new(Some(next_id), "command", json!({ "command": command }))
+                    }
+                    Some(AppEvent::SendCustom { type_uri, data }) => Request::new(
+                        Some(next_id),
+                        "send_custom",
+                        json!({ "type": type_uri, "data": base64::encode(&data) }),
+                    ),
+                    // Only detach, the daemon keeps running
+                    Some(AppEvent::Quit) | None => return Ok(()),
+                };
+                write_line(&mut write, &request).await?;
+            },
+        }
+    }
+}
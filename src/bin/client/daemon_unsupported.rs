@@ -0,0 +1,35 @@
+//! Stand-in for the daemon module on platforms without Unix sockets (i.e. Windows), daemon mode
+//! and attaching only report that they aren't available there.
+
+use crate::events::UiChannels;
+use crate::i18n::tr;
+use crate::ui::UiOptions;
+use nym_chat::history::History;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub struct RoomInfo {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+/// Can't exist here, binding always fails
+pub enum UnixListener {}
+
+pub fn bind(_path: &Path) -> Result<UnixListener, anyhow::Error> {
+    Err(anyhow::Error::msg(tr("daemon-unsupported")))
+}
+
+pub async fn serve(
+    _path: PathBuf,
+    listener: UnixListener,
+    _channels: UiChannels,
+    _history: Arc<Mutex<History>>,
+    _room: RoomInfo,
+) {
+    match listener {}
+}
+
+pub async fn attach(_path: &Path, _options: UiOptions) -> Result<(), anyhow::Error> {
+    Err(anyhow::Error::msg(tr("daemon-unsupported")))
+}
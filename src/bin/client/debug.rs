@@ -0,0 +1,263 @@
+//! What happened to incoming messages, for `/debug`. The fetch tasks record every fetch, its
+//! latency and what came of trial decrypting the fetched messages into a ring buffer, so users can
+//! see for themselves why messages don't show up: the server isn't reachable, nothing new arrives,
+//! or messages arrive but don't decrypt with the room key. With `--show-undecryptable` the room's
+//! undecryptable messages are also counted for the status bar.
+
+use crate::i18n::tr_args;
+use fluent_bundle::FluentValue;
+use nym_chat::{Key, Payload, Undecryptable};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of events kept
+const CAPACITY: usize = 500;
+
+/// Number of events `/debug` shows
+const SHOWN: usize = 30;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugEvent {
+    /// A fetch returned `messages` new messages after `latency`
+    Fetched {
+        server: String,
+        messages: usize,
+        latency: Duration,
+    },
+    /// Fetches that returned nothing new, consecutive ones are counted in one event
+    NothingNew {
+        server: String,
+        fetches: usize,
+    },
+    FetchFailed {
+        server: String,
+        error: String,
+    },
+    /// A fetched message decrypted with the room key, `digest` is the start of its hex digest
+    Decrypted {
+        server: String,
+        digest: String,
+    },
+    /// Fetched messages that didn't decrypt with any of the `candidates` (ids of the keys we
+    /// tried). On busy servers most messages belong to other rooms, so they are only counted.
+    OtherKey {
+        server: String,
+        count: usize,
+        candidates: Vec<String>,
+    },
+    /// A message decrypted with the room key but its payload is unknown to us
+    UnknownPayload {
+        server: String,
+        digest: String,
+    },
+}
+
+impl DebugEvent {
+    /// Count `next` into this event instead of keeping both, if they are of the kind that piles up
+    fn merge(&mut self, next: &DebugEvent) -> bool {
+        match (self, next) {
+            (
+                DebugEvent::NothingNew { server, fetches },
+                DebugEvent::NothingNew {
+                    server: next_server,
+                    fetches: next_fetches,
+                },
+            ) if server == next_server => {
+                *fetches += next_fetches;
+                true
+            }
+            (
+                DebugEvent::OtherKey {
+                    server,
+                    count,
+                    candidates,
+                },
+                DebugEvent::OtherKey {
+                    server: next_server,
+                    count: next_count,
+                    candidates: next_candidates,
+                },
+            ) if server == next_server && candidates == next_candidates => {
+                *count += next_count;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let (id, args): (&str, Vec<(&str, FluentValue)>) = match self {
+            DebugEvent::Fetched {
+                server,
+                messages,
+                latency,
+            } => (
+                "debug-fetched",
+                vec![
+                    ("server", server.as_str().into()),
+                    ("count", (*messages).into()),
+                    ("latency", latency.as_millis().to_string().into()),
+                ],
+            ),
+            DebugEvent::NothingNew { server, fetches } => (
+                "debug-nothing-new",
+                vec![
+                    ("server", server.as_str().into()),
+                    ("count", (*fetches).into()),
+                ],
+            ),
+            DebugEvent::FetchFailed { server, error } => (
+                "debug-fetch-failed",
+                vec![
+                    ("server", server.as_str().into()),
+                    ("error", error.as_str().into()),
+                ],
+            ),
+            DebugEvent::Decrypted { server, digest } => (
+                "debug-decrypted",
+                vec![
+                    ("server", server.as_str().into()),
+                    ("digest", digest.as_str().into()),
+                ],
+            ),
+            DebugEvent::OtherKey {
+                server,
+                count,
+                candidates,
+            } => (
+                "debug-other-key",
+                vec![
+                    ("server", server.as_str().into()),
+                    ("count", (*count).into()),
+                    ("candidates", candidates.join(", ").into()),
+                ],
+            ),
+            DebugEvent::UnknownPayload { server, digest } => (
+                "debug-unknown-payload",
+                vec![
+                    ("server", server.as_str().into()),
+                    ("digest", digest.as_str().into()),
+                ],
+            ),
+        };
+        tr_args(id, &args)
+    }
+}
+
+/// Ring buffer of the newest events, shared by the fetch tasks
+#[derive(Default)]
+pub struct DebugLog {
+    /// Local time of each event and the event
+    events: Mutex<VecDeque<(String, DebugEvent)>>,
+}
+
+impl DebugLog {
+    pub fn record(&self, event: DebugEvent) {
+        let time = chrono::Local::now().format("%H:%M:%S").to_string();
+        let mut events = self.events.lock().unwrap();
+        if let Some((last_time, last)) = events.back_mut() {
+            if last.merge(&event) {
+                *last_time = time;
+                return;
+            }
+        }
+        if events.len() == CAPACITY {
+            events.pop_front();
+        }
+        events.push_back((time, event));
+    }
+
+    /// Record what came of trial decrypting the messages of one fetch from `server` with `room`
+    pub fn decryption(
+        &self,
+        server: &str,
+        room: &Key,
+        payloads: &[([u8; 32], Payload)],
+        failures: &[([u8; 32], Undecryptable)],
+    ) {
+        let short = |digest: &[u8; 32]| hex::encode(&digest[..4]);
+        for (digest, _) in payloads {
+            self.record(DebugEvent::Decrypted {
+                server: server.to_owned(),
+                digest: short(digest),
+            });
+        }
+        let other_key = failures
+            .iter()
+            .filter(|(_, reason)| *reason == Undecryptable::OtherKey)
+            .count();
+        if other_key > 0 {
+            self.record(DebugEvent::OtherKey {
+                server: server.to_owned(),
+                count: other_key,
+                candidates: vec![room.id()],
+            });
+        }
+        for (digest, reason) in failures {
+            if *reason == Undecryptable::UnknownPayload {
+                self.record(DebugEvent::UnknownPayload {
+                    server: server.to_owned(),
+                    digest: short(digest),
+                });
+            }
+        }
+    }
+
+    /// The newest events, oldest first, one per line
+    pub fn describe(&self) -> Option<String> {
+        let events = self.events.lock().unwrap();
+        let shown = events
+            .iter()
+            .skip(events.len().saturating_sub(SHOWN))
+            .map(|(time, event)| format!("{} {}", time, event.describe()))
+            .collect::<Vec<_>>();
+        (!shown.is_empty()).then(|| shown.join("\n"))
+    }
+}
+
+/// Messages of the room that couldn't be decrypted, for `--show-undecryptable`. Messages that
+/// decrypted with the room key but aren't understood are certainly ours. Messages encrypted to
+/// other keys usually belong to other rooms on the same server, they are only counted while no
+/// message from that server decrypted at all, that's what a wrong room key looks like.
+#[derive(Default)]
+pub struct UndecryptableCount {
+    /// Digests of the messages we don't understand, mirrors deliver the same ones
+    unknown: HashSet<[u8; 32]>,
+    /// Messages encrypted to other keys per server, `None` once one of its messages decrypted
+    other_key: HashMap<usize, Option<usize>>,
+}
+
+impl UndecryptableCount {
+    /// Count the result of one fetch from `server`, returns the new total if it changed
+    pub fn count(
+        &mut self,
+        server: usize,
+        payloads: &[([u8; 32], Payload)],
+        failures: &[([u8; 32], Undecryptable)],
+    ) -> Option<usize> {
+        let before = self.total();
+        for (digest, reason) in failures {
+            if *reason == Undecryptable::UnknownPayload {
+                self.unknown.insert(*digest);
+            }
+        }
+        let other_key = self.other_key.entry(server).or_insert(Some(0));
+        if !payloads.is_empty() {
+            *other_key = None;
+        } else if let Some(count) = other_key {
+            *count += failures
+                .iter()
+                .filter(|(_, reason)| *reason == Undecryptable::OtherKey)
+                .count();
+        }
+        let total = self.total();
+        (total != before).then(|| total)
+    }
+
+    fn total(&self) -> usize {
+        // Mirrors serve the same messages, so the server with the most counts for all of them
+        let other_key = self.other_key.values().flatten().max().unwrap_or(&0);
+        self.unknown.len() + other_key
+    }
+}
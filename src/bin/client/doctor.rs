@@ -0,0 +1,197 @@
+//! `nym-chat doctor`: checks why a room doesn't work for someone. It talks to the Nym client, asks
+//! the server for its version and trial decrypts the server's newest messages with the room key,
+//! then prints what it found together with what to do about it.
+
+use crate::i18n::tr_args;
+use fluent_bundle::FluentValue;
+use futures::{SinkExt, StreamExt};
+use nym_chat::epoch::RoomKeys;
+use nym_chat::receipt::TreeHead;
+use nym_chat::transport;
+use nym_chat::Undecryptable;
+use nym_websocket::requests::ClientRequest;
+use nym_websocket::responses::ServerResponse;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Number of the server's newest messages that are trial decrypted
+const SAMPLE: usize = 100;
+
+/// How long the Nym client gets to tell us its address
+const NYM_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Ok,
+    /// Works, but might explain odd behaviour
+    Warning,
+    /// Keeps the room from working
+    Problem,
+}
+
+pub struct Finding {
+    pub verdict: Verdict,
+    pub text: String,
+}
+
+impl Finding {
+    fn new(verdict: Verdict, id: &str, args: &[(&str, FluentValue)]) -> Finding {
+        Finding {
+            verdict,
+            text: tr_args(id, args),
+        }
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self.verdict {
+            Verdict::Ok => "✓",
+            Verdict::Warning => "!",
+            Verdict::Problem => "✗",
+        };
+        write!(f, "{} {}", symbol, self.text)
+    }
+}
+
+/// Run all checks against the Nym client at `websocket` and the room of `keys` at `url`
+pub async fn run(
+    http: &reqwest::Client,
+    websocket: &str,
+    url: &str,
+    keys: &RoomKeys,
+) -> Vec<Finding> {
+    let mut findings = vec![check_nym_client(websocket).await];
+    findings.push(check_version(http, url).await);
+    findings.extend(check_messages(http, url, keys).await);
+    findings
+}
+
+async fn check_nym_client(websocket: &str) -> Finding {
+    match tokio::time::timeout(NYM_TIMEOUT, self_address(websocket)).await {
+        Ok(Ok(address)) => Finding::new(
+            Verdict::Ok,
+            "doctor-nym-ok",
+            &[("websocket", websocket.into()), ("address", address.into())],
+        ),
+        Ok(Err(e)) => Finding::new(
+            Verdict::Problem,
+            "doctor-nym-unreachable",
+            &[
+                ("websocket", websocket.into()),
+                ("error", e.to_string().into()),
+            ],
+        ),
+        Err(_) => Finding::new(
+            Verdict::Problem,
+            "doctor-nym-silent",
+            &[("websocket", websocket.into())],
+        ),
+    }
+}
+
+/// Ask the Nym client for our Nym address, proving it's running and answering
+async fn self_address(websocket: &str) -> Result<String, anyhow::Error> {
+    let (mut ws, _) = tokio_tungstenite::connect_async(websocket).await?;
+    ws.send(Message::Binary(ClientRequest::SelfAddress.serialize()))
+        .await?;
+    while let Some(frame) = ws.next().await {
+        if let Message::Binary(bytes) = frame? {
+            match ServerResponse::deserialize(&bytes) {
+                Ok(ServerResponse::SelfAddress(address)) => return Ok(address.to_string()),
+                Ok(ServerResponse::Error(e)) => anyhow::bail!("{}", e),
+                _ => continue,
+            }
+        }
+    }
+    anyhow::bail!("connection closed")
+}
+
+async fn check_version(http: &reqwest::Client, url: &str) -> Finding {
+    let ours = env!("CARGO_PKG_VERSION");
+    let response = match http.get(format!("{}/version", url)).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            return Finding::new(
+                Verdict::Problem,
+                "doctor-server-unreachable",
+                &[("url", url.into()), ("error", e.to_string().into())],
+            )
+        }
+    };
+    if !response.status().is_success() {
+        // Servers only report their version since the doctor exists
+        return Finding::new(
+            Verdict::Warning,
+            "doctor-version-unknown",
+            &[("ours", ours.into())],
+        );
+    }
+    let theirs = response.text().await.unwrap_or_default();
+    let args = [("ours", ours.into()), ("theirs", theirs.trim().into())];
+    match theirs.trim() == ours {
+        true => Finding::new(Verdict::Ok, "doctor-version-same", &args),
+        false => Finding::new(Verdict::Warning, "doctor-version-different", &args),
+    }
+}
+
+/// Trial decrypt the newest messages of the server. Servers that sign tree heads tell us how many
+/// messages they have, others send all of them.
+async fn check_messages(http: &reqwest::Client, url: &str, keys: &RoomKeys) -> Vec<Finding> {
+    let size = match http.get(format!("{}/tree-head", url)).send().await {
+        Ok(response) => response.json::<TreeHead>().await.ok().map(|head| head.size),
+        Err(_) => None,
+    };
+    let start = size.map_or(0, |size| (size as usize).saturating_sub(SAMPLE));
+    let mut messages = match transport::fetch(http, url, start, false, false).await {
+        Ok(batch) => batch.messages,
+        Err(e) => {
+            return vec![Finding::new(
+                Verdict::Problem,
+                "doctor-fetch-failed",
+                &[("url", url.into()), ("error", e.to_string().into())],
+            )]
+        }
+    };
+    let fetched = messages.len();
+    let sample = messages.split_off(fetched.saturating_sub(SAMPLE));
+    if sample.is_empty() {
+        return vec![Finding::new(Verdict::Warning, "doctor-no-messages", &[])];
+    }
+
+    let total = sample.len();
+    let (mut decrypted, mut other_key, mut unknown) = (0usize, 0usize, 0usize);
+    for msg in sample {
+        match keys.try_decrypt(msg) {
+            Ok(_) => decrypted += 1,
+            Err(Undecryptable::OtherKey) => other_key += 1,
+            Err(Undecryptable::UnknownPayload) => unknown += 1,
+        }
+    }
+    let mut findings = Vec::new();
+    if decrypted > 0 {
+        findings.push(Finding::new(
+            Verdict::Ok,
+            "doctor-decrypted",
+            &[("count", decrypted.into()), ("total", total.into())],
+        ));
+    } else if unknown == 0 {
+        findings.push(Finding::new(
+            Verdict::Problem,
+            "doctor-wrong-key",
+            &[
+                ("count", other_key.into()),
+                ("room", keys.room().id().into()),
+                ("url", url.into()),
+            ],
+        ));
+    }
+    if unknown > 0 {
+        findings.push(Finding::new(
+            Verdict::Warning,
+            "doctor-unknown-payloads",
+            &[("count", unknown.into())],
+        ));
+    }
+    findings
+}
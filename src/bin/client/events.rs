@@ -0,0 +1,161 @@
+//! Interface between the main thread and the UI thread.
+//!
+//! The two sides only talk through a pair of channels: the main thread sends [`UiEvent`]s to the
+//! UI and the UI sends [`AppEvent`]s back. The UI's channel is bounded, if it's full the UI (which
+//! runs on a blocking thread and must never stall) queues events locally and retries on its next
+//! iteration. The main thread's channel is unbounded instead: catching up on thousands of
+//! messages must not stall the main loop (and with it sending) until a slow UI drew them all. It
+//! counts the queued events, so the UI can show that it's behind.
+//!
+//! Any front-end that speaks these two enums can replace the TUI, in daemon mode they are
+//! exchanged as JSON lines over a Unix socket (see the daemon module).
+
+use nym_chat::custom::Custom;
+use nym_chat::Message;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::error::{SendError, TryRecvError};
+use tokio::sync::mpsc::{
+    channel, unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender,
+};
+
+/// Number of events the UI can send before it has to queue them itself
+pub const CHANNEL_CAPACITY: usize = 16;
+
+/// Sent by the main thread to the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum UiEvent {
+    /// Message received in the room together with its index in the local history
+    Message(usize, Message),
+    /// Local information like the output of a command, it's never persisted
+    Notice(String),
+    /// The message with this index deserves a notification according to the room's rules, always
+    /// sent right after the message itself
+    Alert(usize),
+    /// Third-party payload, only daemon subscribers (e.g. bots) get to see it
+    Custom(Custom),
+    /// Something the user has to know about until the client exits, like the server rewriting its
+    /// log. Shown in the status bar, a new warning replaces the previous one.
+    Warning(String),
+    /// Burn-after-reading message, front-ends show it at most once and don't keep it anywhere
+    BurnAfterReading(Message),
+    /// Number of the room's messages that couldn't be decrypted so far (with
+    /// `--show-undecryptable`), front-ends show one indicator instead of the messages
+    Undecryptable(usize),
+    /// Text a plugin attached to the message with this index (like a translation), front-ends
+    /// show it beneath the message
+    Annotation(usize, String),
+}
+
+/// Sent by the UI to the main thread
+#[derive(Debug, Serialize, Deserialize)]
+pub enum AppEvent {
+    /// The user wants to send a message to the room
+    Send(String),
+    /// The user entered a command (a line starting with `/`), including the leading slash
+    Command(String),
+    /// A bot wants to send a third-party payload, see [`Custom`]
+    SendCustom { type_uri: String, data: Vec<u8> },
+    /// The user closed the UI
+    Quit,
+}
+
+impl AppEvent {
+    /// Interpret a line the user entered
+    pub fn from_input(line: String) -> AppEvent {
+        if line.starts_with('/') {
+            AppEvent::Command(line)
+        } else {
+            AppEvent::Send(line)
+        }
+    }
+}
+
+/// The main thread's end of the channels
+pub struct AppChannels {
+    pub events: Receiver<AppEvent>,
+    pub ui: UiSender,
+}
+
+/// The UI's end of the channels
+pub struct UiChannels {
+    pub events: UiReceiver,
+    pub app: Sender<AppEvent>,
+}
+
+pub fn channels() -> (AppChannels, UiChannels) {
+    let (ui_send, ui_receive) = unbounded_channel();
+    let (app_send, app_receive) = channel(CHANNEL_CAPACITY);
+    let queued = Arc::new(AtomicUsize::new(0));
+    (
+        AppChannels {
+            events: app_receive,
+            ui: UiSender {
+                sender: ui_send,
+                queued: queued.clone(),
+            },
+        },
+        UiChannels {
+            events: UiReceiver {
+                receiver: ui_receive,
+                queued,
+            },
+            app: app_send,
+        },
+    )
+}
+
+/// Sends events to the UI without ever waiting for it
+#[derive(Clone)]
+pub struct UiSender {
+    sender: UnboundedSender<UiEvent>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl UiSender {
+    /// Queue an event, fails only if the UI is gone
+    pub fn send(&self, event: UiEvent) -> Result<(), SendError<UiEvent>> {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.sender.send(event).map_err(|e| {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            e
+        })
+    }
+}
+
+/// Receives the events of the main thread and keeps track of how many are still queued
+pub struct UiReceiver {
+    receiver: UnboundedReceiver<UiEvent>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl UiReceiver {
+    pub async fn recv(&mut self) -> Option<UiEvent> {
+        let event = self.receiver.recv().await;
+        self.received(event)
+    }
+
+    pub fn blocking_recv(&mut self) -> Option<UiEvent> {
+        let event = self.receiver.blocking_recv();
+        self.received(event)
+    }
+
+    pub fn try_recv(&mut self) -> Result<UiEvent, TryRecvError> {
+        let event = self.receiver.try_recv()?;
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        Ok(event)
+    }
+
+    /// Number of events waiting to be received
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    fn received(&self, event: Option<UiEvent>) -> Option<UiEvent> {
+        if event.is_some() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        event
+    }
+}
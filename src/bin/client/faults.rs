@@ -0,0 +1,97 @@
+//! Fault injection for development (`--inject-faults`). Fetches are delayed, fail or return
+//! messages out of order and sent messages get lost on their way to a server, so retries,
+//! deduplication of messages from several servers and late messages can be tried out locally
+//! without waiting for the mixnet to misbehave.
+
+use nym_chat::determinism;
+use rand::Rng;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static FAULTS: OnceLock<Faults> = OnceLock::new();
+
+/// Faults to inject, parsed from e.g. `delay=2000,drop=0.1,reorder=0.2`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Faults {
+    /// Fetches wait a random time up to this long
+    pub delay: Duration,
+    /// Probability of a fetch failing and of a sent message getting lost on its way to a server
+    pub drop: f64,
+    /// Probability of two neighbouring fetched messages being swapped
+    pub reorder: f64,
+}
+
+/// Enable fault injection, without calling this everything works normally
+pub fn init(faults: Faults) {
+    // Only the first call has an effect, later ones (there shouldn't be any) are ignored
+    let _ = FAULTS.set(faults);
+}
+
+fn get() -> Faults {
+    FAULTS.get().copied().unwrap_or_default()
+}
+
+/// Wait before a fetch
+pub async fn delay() {
+    let max = get().delay.as_millis() as u64;
+    if max > 0 {
+        let delay = determinism::with_rng(|rng| rng.gen_range(0..=max));
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+}
+
+/// Whether a fetch should fail or a message get lost
+pub fn dropped() -> bool {
+    determinism::with_rng(|rng| rng.gen_bool(get().drop))
+}
+
+/// Swap neighbouring items at random
+pub fn reorder<T>(items: &mut [T]) {
+    let probability = get().reorder;
+    determinism::with_rng(|rng| {
+        for idx in 1..items.len() {
+            if rng.gen_bool(probability) {
+                items.swap(idx - 1, idx);
+            }
+        }
+    })
+}
+
+impl FromStr for Faults {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let probability = |value: &str| match value.parse::<f64>() {
+            Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+            _ => Err(format!(
+                "expected a probability between 0 and 1, got {}",
+                value
+            )),
+        };
+        let mut faults = Faults::default();
+        for setting in s.split(',').filter(|setting| !setting.is_empty()) {
+            let (name, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("expected <fault>=<value>, got {}", setting))?;
+            match name.trim() {
+                "delay" => {
+                    let millis = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("expected milliseconds, got {}", value))?;
+                    faults.delay = Duration::from_millis(millis);
+                }
+                "drop" => faults.drop = probability(value.trim())?,
+                "reorder" => faults.reorder = probability(value.trim())?,
+                name => {
+                    return Err(format!(
+                        "unknown fault {}, expected delay, drop or reorder",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(faults)
+    }
+}
@@ -0,0 +1,122 @@
+//! Translations of the client's user facing strings. Every locale is a [Fluent] file in `locales/`
+//! that is compiled into the binary, English is used for anything a translation is missing.
+//! Adding a language only means adding its file to [`LOCALES`].
+//!
+//! [Fluent]: https://projectfluent.org
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+/// Available locales and their translations
+const LOCALES: &[(&str, &str)] = &[
+    ("en", include_str!("../../../locales/en.ftl")),
+    ("de", include_str!("../../../locales/de.ftl")),
+];
+
+const FALLBACK: &str = "en";
+
+static TRANSLATIONS: OnceLock<Translations> = OnceLock::new();
+
+struct Translations {
+    bundle: FluentBundle<FluentResource>,
+    fallback: FluentBundle<FluentResource>,
+}
+
+/// Select the locale, `None` picks it from the environment (`LC_ALL`, `LC_MESSAGES`, `LANG`).
+/// Explicitly asking for a locale we don't have is an error, an unknown one from the environment
+/// silently falls back to English.
+pub fn init(locale: Option<&str>) -> Result<(), anyhow::Error> {
+    let locale = match locale {
+        Some(locale) if find(locale).is_some() => locale.to_owned(),
+        Some(locale) => {
+            let available = LOCALES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            return Err(anyhow::Error::msg(tr_args(
+                "unknown-locale",
+                &[
+                    ("locale", locale.into()),
+                    ("available", available.join(", ").into()),
+                ],
+            )));
+        }
+        None => from_env().unwrap_or_else(|| FALLBACK.to_owned()),
+    };
+
+    // Only the first call has an effect, later ones (there shouldn't be any) are ignored
+    let _ = TRANSLATIONS.set(Translations::load(&locale));
+    Ok(())
+}
+
+/// Translate the string `id`
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+/// Translate the string `id` filling in the given arguments
+pub fn tr_args(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+
+    let translations = TRANSLATIONS.get_or_init(|| Translations::load(FALLBACK));
+    format(&translations.bundle, id, &fluent_args)
+        .or_else(|| format(&translations.fallback, id, &fluent_args))
+        .unwrap_or_else(|| id.to_owned())
+}
+
+impl Translations {
+    fn load(locale: &str) -> Translations {
+        let locale = find(locale).unwrap_or(FALLBACK);
+        Translations {
+            bundle: bundle(locale),
+            fallback: bundle(FALLBACK),
+        }
+    }
+}
+
+/// Map e.g. `de_DE.UTF-8` to `de`
+fn find(locale: &str) -> Option<&'static str> {
+    let language = locale.split(|c| c == '_' || c == '-' || c == '.').next()?;
+    LOCALES
+        .iter()
+        .map(|(name, _)| *name)
+        .find(|name| name.eq_ignore_ascii_case(language))
+}
+
+fn from_env() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .filter(|value| find(value).is_some())
+}
+
+fn bundle(locale: &str) -> FluentBundle<FluentResource> {
+    let source = LOCALES
+        .iter()
+        .find(|(name, _)| *name == locale)
+        .map(|(_, source)| *source)
+        .expect("only called with known locales");
+    let resource = FluentResource::try_new(source.to_owned()).expect("invalid translation file");
+    let language: LanguageIdentifier = locale.parse().expect("invalid locale name");
+
+    let mut bundle = FluentBundle::new_concurrent(vec![language]);
+    // Unicode isolation marks around arguments show up as garbage in some terminals
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("duplicate strings in translation file");
+    bundle
+}
+
+fn format(bundle: &FluentBundle<FluentResource>, id: &str, args: &FluentArgs) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+    let mut errors = Vec::new();
+    Some(
+        bundle
+            .format_pattern(pattern, Some(args), &mut errors)
+            .into_owned(),
+    )
+}
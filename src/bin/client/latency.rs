@@ -0,0 +1,74 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Number of round trips the statistics are computed over
+const MAX_SAMPLES: usize = 1000;
+
+/// Round trips of our own messages: from handing them to the Nym client until a fetch returned
+/// them. That's the mixnet, the server and the fetch interval together, the part users wait for.
+#[derive(Default)]
+pub struct Latency {
+    /// Messages that didn't come back yet, by digest
+    pending: HashMap<[u8; 32], Instant>,
+    /// The newest round trips
+    samples: VecDeque<Duration>,
+    sent: usize,
+    delivered: usize,
+}
+
+/// Distribution of the recorded round trips
+pub struct Summary {
+    pub min: Duration,
+    pub median: Duration,
+    pub p90: Duration,
+    pub max: Duration,
+}
+
+impl Latency {
+    pub fn sent(&mut self, digest: [u8; 32]) {
+        self.sent += 1;
+        self.pending.insert(digest, Instant::now());
+    }
+
+    /// A fetch returned the message with this digest, only the first server it comes back from
+    /// counts
+    pub fn observed(&mut self, digest: &[u8; 32]) {
+        if let Some(sent) = self.pending.remove(digest) {
+            self.delivered += 1;
+            self.samples.push_back(sent.elapsed());
+            if self.samples.len() > MAX_SAMPLES {
+                self.samples.pop_front();
+            }
+        }
+    }
+
+    /// Number of messages sent this session
+    pub fn sent_count(&self) -> usize {
+        self.sent
+    }
+
+    pub fn delivered_count(&self) -> usize {
+        self.delivered
+    }
+
+    /// Messages that are still on their way (or were lost)
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut samples = self.samples.iter().copied().collect::<Vec<_>>();
+        samples.sort();
+        // Nearest rank percentile
+        let percentile = |p: usize| samples[(samples.len() * p - 1) / 100];
+        Some(Summary {
+            min: samples[0],
+            median: percentile(50),
+            p90: percentile(90),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
@@ -0,0 +1,2243 @@
+use chrono::TimeZone;
+use nym_addressing::clients::Recipient;
+use nym_chat::address_book::{claimed_name, AddressBook};
+use nym_chat::away::{self, Away, Responder};
+use nym_chat::backup::Backup;
+use nym_chat::broadcast::{RoomSecret, SigningKey, VerifyingKey};
+use nym_chat::counter::{Counter, Gaps};
+use nym_chat::custom::Custom;
+use nym_chat::determinism;
+use nym_chat::directory;
+use nym_chat::epoch::{ChainKey, RoomKeys};
+use nym_chat::exit::{ExitCode, Fatal, OrExit};
+use nym_chat::health::Health;
+use nym_chat::history::History;
+use nym_chat::invite::{Invite, InviteKind};
+use nym_chat::link::DeviceSync;
+use nym_chat::merkle::{self, Hash};
+use nym_chat::outbox::Outbox;
+use nym_chat::padding::{self, Traffic};
+use nym_chat::poll::Polls;
+use nym_chat::presence::{Presence, PresenceStatus, Roster, BEACON_INTERVAL_SECS};
+use nym_chat::profile::Profile;
+use nym_chat::receipt::{Receipt, TreeHead};
+use nym_chat::report::{ModeratorKey, ReportKey};
+use nym_chat::snippet::Snippets;
+use nym_chat::transcript::{self, Transcript};
+use nym_chat::transport::{self, Counted, HttpConfig, Loopback, Server, Transport, Websocket};
+use nym_chat::usage::{self, Feature, Usage};
+use nym_chat::{EncryptedMessage, Envelope, Key, Message, Payload, Undecryptable};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+use tokio::select;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+mod archive;
+mod commands;
+#[cfg(unix)]
+mod daemon;
+// Daemon mode needs Unix sockets, elsewhere it only tells the user that it isn't available
+#[cfg(not(unix))]
+#[path = "daemon_unsupported.rs"]
+mod daemon;
+mod debug;
+mod doctor;
+mod events;
+mod faults;
+mod i18n;
+mod latency;
+mod onboarding;
+mod plugins;
+#[cfg(unix)]
+mod rpc;
+mod session;
+mod sharekey;
+mod simulate;
+mod statsd;
+mod ui;
+
+use commands::{describe_card, describe_poll, describe_report, Action, Handlers};
+use debug::{DebugEvent, DebugLog, UndecryptableCount};
+use events::{AppEvent, UiEvent, UiSender};
+use i18n::{tr, tr_args};
+use latency::Latency;
+use onboarding::SavedProfile;
+use plugins::Plugins;
+use session::{Outgoing, Session};
+
+// Command line options
+#[derive(StructOpt)]
+struct Options {
+    // Nym client to use
+    #[structopt(short, long, default_value = "ws://127.0.0.1:1977")]
+    websocket: String,
+    /// Send straight to servers started with --loopback over plain HTTP instead of through the
+    /// Nym client. There is no privacy at all, it's for development and tests.
+    #[structopt(long)]
+    loopback: bool,
+    /// Don't send presence beacons, other participants won't see us in their roster
+    #[structopt(long)]
+    no_presence: bool,
+    /// Directory the local message history is stored in, defaults to the platform's data directory
+    /// (e.g. ~/.local/share/nym-chat)
+    #[structopt(long, parse(from_os_str))]
+    data_dir: Option<PathBuf>,
+    /// Keep nothing on disk: no history, read positions, profile or contacts. The session gets a
+    /// throwaway identity and what it kept in memory is wiped when the client exits.
+    #[structopt(long, conflicts_with_all = &["data-dir", "transcript", "import-archive"])]
+    incognito: bool,
+    /// Show messages as they are instead of rendering Markdown (bold, italics, code and links)
+    #[structopt(long)]
+    raw_text: bool,
+    /// Ask for confirmation before opening a link picked with Ctrl+O
+    #[structopt(long)]
+    confirm_open: bool,
+    /// Write plain lines instead of drawing the terminal UI, for use with screen readers
+    #[structopt(long)]
+    screen_reader: bool,
+    /// How much the screen reader mode announces: terse, normal or verbose
+    #[structopt(long, default_value = "normal")]
+    verbosity: ui::Verbosity,
+    /// Show when messages were sent: off, local (time of day), utc or relative (e.g. 5m ago,
+    /// kept up to date while the client runs)
+    #[structopt(long, default_value = "off")]
+    timestamps: ui::Timestamps,
+    /// Language of the client (e.g. de), defaults to the one set in the environment
+    #[structopt(long)]
+    locale: Option<String>,
+    /// Ask the server to pad fetch responses to fixed size classes, so observers of the
+    /// connection to the server can't tell how active the rooms are
+    #[structopt(long)]
+    pad_fetches: bool,
+    /// Pad our messages so their size says less about their length: off, fixed (to fixed size
+    /// buckets) or adaptive[:<buckets>] (to the most common sizes in the room, 4 buckets unless
+    /// given). /padding shows the buckets and what they cost.
+    #[structopt(long, default_value = "off")]
+    pad_messages: padding::Policy,
+    /// Number our messages so others notice when the server drops some of them. Clients from
+    /// before numbering skip numbered messages, only use it once everyone in the room updated.
+    #[structopt(long)]
+    count_messages: bool,
+    /// In rooms with a key chain, keep the keys of past epochs so /invite can hand out the full
+    /// history. By default they are forgotten once fetched and whoever steals the keys can't read
+    /// those epochs. Rooms without a chain are always readable with the room key, also back in time.
+    #[structopt(long)]
+    keep_old_epochs: bool,
+    /// Count messages of the room that couldn't be decrypted (sent by a newer client or with
+    /// another room key) and show the count in the status bar instead of dropping them silently
+    #[structopt(long)]
+    show_undecryptable: bool,
+    /// Milliseconds between two fetches from each server, shorter intervals deliver messages
+    /// sooner but send more requests. /stats shows how long our messages take to come back.
+    #[structopt(long, default_value = "1000")]
+    fetch_interval: u64,
+    /// Another server serving the same room as `<url>=<service provider address>`, can be given
+    /// several times. Messages are sent to all servers and fetched from all of them, so the room
+    /// stays usable while one of them is down.
+    #[structopt(long = "mirror")]
+    mirrors: Vec<Server>,
+    /// TOML file with the user agent, headers, timeouts, HTTP version and connection pool size
+    /// of the requests to the servers, for servers behind a CDN or firewall with rules to pass
+    #[structopt(long, parse(from_os_str))]
+    http_config: Option<PathBuf>,
+    /// TOML file with named snippets of text (`standup = "Yesterday: {1}, today: {2}"`) that
+    /// `/snip <name> <value> | <value>` sends with the placeholders filled in
+    #[structopt(long, parse(from_os_str))]
+    snippets: Option<PathBuf>,
+    /// Ask the servers to delete our messages this many seconds after receiving them. Everyone in
+    /// the room should use the same setting, invites created with /invite carry it.
+    #[structopt(long)]
+    max_age: Option<u64>,
+    /// Verifying key of a broadcast room, messages without a valid signature are dropped. Invites
+    /// of broadcast rooms contain it.
+    #[structopt(long)]
+    broadcast_key: Option<VerifyingKey>,
+    /// Signing key (see the keygen command) to post to a broadcast room, implies --broadcast-key
+    #[structopt(long)]
+    signing_key: Option<SigningKey>,
+    /// Key of the room's moderator (see the moderator-keygen command) that /report seals abuse
+    /// reports to. Invites of moderated rooms contain it.
+    #[structopt(long)]
+    report_key: Option<ReportKey>,
+    /// Secret key of the moderator, reports sent to its report key show up and /reports lists
+    /// them. Implies --report-key.
+    #[structopt(long, env = "NYM_CHAT_MODERATOR_KEY", hide_env_values = true)]
+    moderator_key: Option<ModeratorKey>,
+    /// Verifying key of the server's receipts (see its --receipt-key), the receipts for our
+    /// messages are checked and kept in the data directory as proof that the server accepted them
+    #[structopt(long)]
+    server_key: Option<VerifyingKey>,
+    /// Key of a directory room on the server, every server announced in it is added as a mirror
+    #[structopt(long)]
+    directory: Option<Key>,
+    /// Import an archive created with /export-archive before connecting, the data directory must
+    /// not contain any messages of the room yet
+    #[structopt(long, parse(from_os_str))]
+    import_archive: Option<PathBuf>,
+    /// Development aid: delay, fail and reorder fetches and lose sent messages at random, e.g.
+    /// `delay=2000,drop=0.1,reorder=0.2` (milliseconds and probabilities)
+    #[structopt(long)]
+    inject_faults: Option<faults::Faults>,
+    /// Development aid: take all randomness (keys, nonces, jitter, simulated participants) from
+    /// this seed and let the clock start at a fixed time, so runs and encrypted messages can be
+    /// reproduced. Anyone knowing the seed can read the room, never use it for real rooms. Only
+    /// in builds with the `deterministic` feature and only together with --incognito.
+    #[cfg(feature = "deterministic")]
+    #[structopt(long, requires = "incognito")]
+    deterministic: Option<u64>,
+    /// Add this many scripted participants that post to the room through our Nym client, for
+    /// demos and stress testing the UI. Their messages are real, use a throwaway room.
+    #[structopt(long)]
+    simulate: Option<usize>,
+    /// Enable a plugin, `<name>` or `<name>:<config>`, can be given several times. /plugins lists
+    /// the enabled ones and their commands.
+    #[structopt(long)]
+    plugin: Vec<String>,
+    /// Send the mixnet health counters /stats shows (errors of the Nym client, reconnects, failed
+    /// sends and fetches) to this statsd server (`host:port`) every 10 seconds
+    #[structopt(long)]
+    statsd: Option<String>,
+    /// Prefix of the statsd metric names, give every client its own to tell them apart
+    #[structopt(long, default_value = "nym_chat.client")]
+    statsd_prefix: String,
+    /// Opt into sending anonymous usage beacons to this collector (`<url>=<service provider
+    /// address>`): how often features were used, at most once a day and through the mixnet. The
+    /// usage module of the library describes exactly what is sent.
+    #[structopt(long, requires = "usage-room")]
+    usage_collector: Option<Server>,
+    /// Key of the room the usage collector reads beacons from
+    #[structopt(long, requires = "usage-collector")]
+    usage_room: Option<Key>,
+    /// Keep a signed, hash chained transcript of everything sent, received and done in this file,
+    /// check it with the verify-transcript command
+    #[structopt(long, parse(from_os_str), requires = "transcript-key")]
+    transcript: Option<PathBuf>,
+    /// Signing key for the transcript (see the keygen command), keep its verifying key to check it
+    #[structopt(long)]
+    transcript_key: Option<SigningKey>,
+    /// Run in the background without a UI, front-ends can attach using the given socket
+    #[structopt(long, parse(from_os_str))]
+    daemon: Option<PathBuf>,
+    /// Without a command: join the room with --room-key (or --passphrase) on the server at this
+    /// URL instead of asking, the server's Nym address is asked from the server and cached in the
+    /// profile
+    #[structopt(long)]
+    url: Option<String>,
+    /// Key of the room to join with --url (32 bytes hex encoded or its mnemonic backup phrase)
+    #[structopt(long, requires = "url")]
+    room_key: Option<Key>,
+    /// Passphrase the room key is derived from, instead of --room-key for --url or the room key
+    /// of connect, join and doctor. Anyone can try to guess it offline, so it has to be long and
+    /// random.
+    #[structopt(
+        long,
+        conflicts_with = "room-key",
+        env = "NYM_CHAT_ROOM_PASSPHRASE",
+        hide_env_values = true
+    )]
+    passphrase: Option<String>,
+    /// Without a command the client loads (or on the first run creates) the profile and asks
+    /// which room to join
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Connect to a room by specifying the server and room key manually
+    #[structopt(setting = AppSettings::AllowMissingPositional)]
+    Connect {
+        // The server's Nym address, asked from the server's /provider-address if not given
+        #[structopt(
+        short,
+        long,
+        parse(try_from_str = Recipient::try_from_base58_string),
+        )]
+        service_provider: Option<Recipient>,
+        // The server's HTTP server to query the messages from
+        url: String,
+        // The key defining the chatroom (32 bytes hex encoded or its mnemonic backup phrase),
+        // derived from --passphrase if not given
+        room: Option<Key>,
+        // Our name to be attached to messages
+        name: String,
+    },
+    /// Join a room using a nymchat:// invite link
+    Join {
+        // The invite link
+        invite: Invite,
+        // Our name to be attached to messages
+        name: String,
+        // The key defining the chatroom, required if the invite doesn't contain it
+        #[structopt(long)]
+        room: Option<Key>,
+    },
+    /// Attach the UI to a client running in daemon mode
+    Attach {
+        // The daemon's control socket
+        #[structopt(parse(from_os_str))]
+        socket: PathBuf,
+    },
+    /// Take over the persona of another device using a nymchat://link URI generated with /link
+    Link {
+        // The device link
+        link: Invite,
+    },
+    /// Generate a signing key for a broadcast room and print it together with its verifying key
+    Keygen,
+    /// Generate a moderator key and print it together with the report key members report to
+    ModeratorKeygen,
+    /// Check that a transcript written with --transcript is complete and unaltered
+    VerifyTranscript {
+        // The transcript file
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        // Verifying key of the --transcript-key it was written with
+        key: VerifyingKey,
+    },
+    /// Decrypt a room key file created with /sharekey and print the invite it contains
+    ImportKey {
+        // The file created with /sharekey
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// age identity file with the secret key, not needed for OpenPGP (gpg finds the key)
+        #[structopt(long, parse(from_os_str))]
+        identity: Option<PathBuf>,
+    },
+    /// Restore the profile from a file written with /backup
+    Restore {
+        // The file written with /backup
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        // The 24 word phrase /backup showed (quote it so it is passed as a single argument)
+        phrase: Key,
+    },
+    /// Check the Nym client, the server's version and whether the room key decrypts the server's
+    /// newest messages, for rooms that stay empty or can't be reached
+    Doctor {
+        // The server's HTTP server to query the messages from
+        url: String,
+        // The key defining the chatroom (32 bytes hex encoded or its mnemonic backup phrase),
+        // derived from --passphrase if not given
+        room: Option<Key>,
+    },
+}
+
+/// Payloads the fetch task of one server decrypted
+struct Fetched {
+    /// Index of the server in the server list, 0 is the one from the command line or invite
+    server: usize,
+    read_position: usize,
+    /// Decrypted payloads together with the digest of their ciphertext
+    payloads: Vec<([u8; 32], Payload)>,
+    /// Fetched messages that didn't decrypt, with the digest of their ciphertext
+    failures: Vec<([u8; 32], Undecryptable)>,
+    /// Valid receipts of the fetched messages, only if we know the server's receipt key
+    receipts: Vec<Receipt>,
+    /// Number of fetched messages whose receipt was missing or invalid
+    invalid_receipts: usize,
+    /// Messages the server stopped serving before we got to them
+    missed: usize,
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(fatal) = run().await {
+        fatal.exit();
+    }
+}
+
+async fn run() -> Result<(), Fatal> {
+    // Parse command line arguments
+    let opts: Options = StructOpt::from_args();
+    if let Err(e) = i18n::init(opts.locale.as_deref()) {
+        return Err(Fatal::new(ExitCode::Config, e));
+    }
+    if let Some(faults) = opts.inject_faults {
+        faults::init(faults);
+    }
+    #[cfg(feature = "deterministic")]
+    if let Some(seed) = opts.deterministic {
+        determinism::init(seed);
+    }
+    let mut plugins = match Plugins::load(&opts.plugin) {
+        Ok(plugins) => plugins,
+        Err(e) => return Err(Fatal::new(ExitCode::Config, e)),
+    };
+    let websocket = opts.websocket;
+    let presence_enabled = !opts.no_presence;
+    let mut max_age = opts.max_age;
+    let mut signing_key = opts.signing_key;
+    let mut broadcast_key = opts
+        .broadcast_key
+        .or_else(|| signing_key.as_ref().map(SigningKey::verifying_key));
+    let moderator_key = opts.moderator_key;
+    let mut report_key = opts
+        .report_key
+        .or_else(|| moderator_key.as_ref().map(ModeratorKey::report_key));
+    // Set when we joined a room with read and write permissions as a member who may post
+    let mut room_secret: Option<RoomSecret> = None;
+    // Chain position from an invite or linked device in rooms with a key chain
+    let mut chain: Option<ChainKey> = None;
+    // QR codes are only drawn for sighted users
+    let draw_qr_codes = !opts.screen_reader;
+    let ui_options = ui::UiOptions {
+        raw_text: opts.raw_text,
+        confirm_open: opts.confirm_open,
+        screen_reader: opts.screen_reader,
+        verbosity: opts.verbosity,
+        wipe_on_exit: opts.incognito,
+        timestamps: opts.timestamps,
+    };
+    // Incognito sessions don't have a data directory, nothing is read from or written to it
+    let data_dir = match opts.incognito {
+        true => None,
+        false => Some(opts.data_dir.unwrap_or_else(default_data_dir)),
+    };
+    // Every request to the servers is made with this client, so they all look the same
+    let http = match &opts.http_config {
+        Some(path) => match HttpConfig::load(path).and_then(|config| config.client()) {
+            Ok(http) => http,
+            Err(e) => {
+                let error = tr_args("http-config-invalid", &[("error", e.to_string().into())]);
+                return Err(Fatal::new(ExitCode::Config, error));
+            }
+        },
+        None => reqwest::Client::new(),
+    };
+    let passphrase_key = opts.passphrase.as_deref().map(Key::from_passphrase);
+    let room_key = opts.room_key.or_else(|| passphrase_key.clone());
+    if opts.url.is_some() && room_key.is_none() {
+        return Err(Fatal::new(ExitCode::Config, tr("url-without-room-key")));
+    }
+    let snippets = match &opts.snippets {
+        Some(path) => Snippets::load(path).map_err(|e| {
+            let error = tr_args("snippets-invalid", &[("error", e.to_string().into())]);
+            Fatal::new(ExitCode::Config, error)
+        })?,
+        None => Snippets::default(),
+    };
+    // Only loaded when started without a command, rooms created with /create are added to it
+    let mut profile = None;
+    let command = match opts.command {
+        Some(command) => command,
+        None => match load_profile(data_dir.as_deref(), &http, opts.url.zip(room_key)).await {
+            Ok((saved, invite)) => {
+                let name = saved.profile.name.clone();
+                profile = Some(saved);
+                Command::Join {
+                    invite,
+                    name,
+                    room: None,
+                }
+            }
+            Err(e) => {
+                let error = tr_args("onboarding-failed", &[("error", e.to_string().into())]);
+                return Err(Fatal::new(ExitCode::Failure, error));
+            }
+        },
+    };
+    let (service_provider, url, room, room_name, name, read_position) = match command {
+        Command::Attach { socket } => {
+            if let Err(e) = daemon::attach(&socket, ui_options).await {
+                let error = tr_args("attach-failed", &[("error", e.to_string().into())]);
+                return Err(Fatal::new(ExitCode::Failure, error));
+            }
+            return Ok(());
+        }
+        Command::Keygen => {
+            let signing_key = SigningKey::generate();
+            println!(
+                "{}",
+                tr_args(
+                    "keygen-result",
+                    &[
+                        ("signing", signing_key.to_hex().into()),
+                        ("verifying", signing_key.verifying_key().to_string().into()),
+                    ],
+                )
+            );
+            return Ok(());
+        }
+        Command::ModeratorKeygen => {
+            let moderator_key = ModeratorKey::generate();
+            println!(
+                "{}",
+                tr_args(
+                    "moderator-keygen-result",
+                    &[
+                        ("moderator", moderator_key.to_hex().into()),
+                        ("report", moderator_key.report_key().to_string().into()),
+                    ],
+                )
+            );
+            return Ok(());
+        }
+        Command::VerifyTranscript { path, key } => {
+            match transcript::verify(&path, &key) {
+                Ok(entries) => {
+                    println!(
+                        "{}",
+                        tr_args("transcript-valid", &[("entries", entries.into())])
+                    );
+                }
+                Err(e) => {
+                    let error = tr_args("transcript-invalid", &[("error", e.to_string().into())]);
+                    return Err(Fatal::new(ExitCode::Failure, error));
+                }
+            }
+            return Ok(());
+        }
+        Command::ImportKey { file, identity } => {
+            match sharekey::import(&file, identity.as_deref()) {
+                Ok(invite) => println!(
+                    "{}",
+                    tr_args("sharekey-imported", &[("invite", invite.into())])
+                ),
+                Err(e) => {
+                    let error =
+                        tr_args("sharekey-import-failed", &[("error", e.to_string().into())]);
+                    return Err(Fatal::new(ExitCode::Failure, error));
+                }
+            }
+            return Ok(());
+        }
+        Command::Restore { file, phrase } => {
+            let data_dir = data_dir
+                .as_deref()
+                .ok_or_else(|| Fatal::new(ExitCode::Config, tr("restore-incognito")))?;
+            let restored = std::fs::read_to_string(&file)
+                .map_err(anyhow::Error::from)
+                .and_then(|text| Backup::open(&text, &phrase))
+                .and_then(|backup| onboarding::restore(data_dir, &backup));
+            match restored {
+                Ok(saved) => println!(
+                    "{}",
+                    tr_args(
+                        "backup-restored",
+                        &[("rooms", saved.profile.rooms.len().into())]
+                    )
+                ),
+                Err(e) => {
+                    let error =
+                        tr_args("backup-restore-failed", &[("error", e.to_string().into())]);
+                    return Err(Fatal::new(ExitCode::Failure, error));
+                }
+            }
+            return Ok(());
+        }
+        Command::Doctor { url, room } => {
+            let room = room
+                .or(passphrase_key)
+                .ok_or_else(|| Fatal::new(ExitCode::Config, tr("room-key-missing")))?;
+            let chain = data_dir
+                .as_deref()
+                .and_then(|data_dir| History::stored_chain(data_dir, &room));
+            let keys = RoomKeys::new(room.clone(), chain);
+            let findings = doctor::run(&http, &websocket, &url, &keys).await;
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            let problems = findings
+                .iter()
+                .filter(|finding| finding.verdict == doctor::Verdict::Problem)
+                .count();
+            if problems > 0 {
+                // The findings say what's wrong already, this only counts them
+                let summary = tr_args("doctor-problems", &[("count", problems.into())]);
+                return Err(Fatal::new(ExitCode::Failure, summary));
+            }
+            return Ok(());
+        }
+        Command::Connect {
+            service_provider,
+            url,
+            room,
+            name,
+        } => {
+            let service_provider = match service_provider {
+                Some(service_provider) => service_provider,
+                None => match transport::provider_address(&http, &url).await {
+                    Ok(service_provider) => service_provider,
+                    Err(e) => {
+                        let error = tr_args(
+                            "provider-address-failed",
+                            &[("error", e.to_string().into())],
+                        );
+                        return Err(Fatal::new(ExitCode::ServerUnreachable, error));
+                    }
+                },
+            };
+            let room = room
+                .or(passphrase_key)
+                .ok_or_else(|| Fatal::new(ExitCode::Config, tr("room-key-missing")))?;
+            (service_provider, url, room, None, name, None)
+        }
+        Command::Join { invite, name, room } => {
+            let room = match room.or(passphrase_key).or(invite.key) {
+                Some(room) => room,
+                None => {
+                    let error = match invite.hint {
+                        Some(hint) => tr_args("invite-without-key-hint", &[("hint", hint.into())]),
+                        None => tr("invite-without-key"),
+                    };
+                    return Err(Fatal::new(ExitCode::Config, error));
+                }
+            };
+            max_age = max_age.or(invite.max_age);
+            broadcast_key = broadcast_key.or(invite.broadcast);
+            report_key = report_key.or(invite.report);
+            chain = invite.chain;
+            if let Some(secret) = invite.secret {
+                signing_key = signing_key.or_else(|| Some(secret.write_key()));
+                room_secret = Some(secret);
+            }
+            (
+                invite.service_provider,
+                invite.server,
+                room,
+                invite.room_name,
+                name,
+                None,
+            )
+        }
+        Command::Link { link } => {
+            let one_time_key = match (link.kind, link.key) {
+                (InviteKind::Link, Some(key)) => key,
+                _ => return Err(Fatal::new(ExitCode::Config, tr("invalid-device-link"))),
+            };
+            let from = link.from.unwrap_or(0);
+            let interval = Duration::from_millis(opts.fetch_interval.max(1));
+            let sync = wait_for_device_sync(
+                &http,
+                &link.server,
+                &one_time_key,
+                from,
+                interval,
+                opts.pad_fetches,
+            )
+            .await?;
+            let room =
+                Key::from_str(&sync.room).or_exit(ExitCode::Failure, tr("linked-room-invalid"))?;
+            chain = sync.chain.as_deref().and_then(|chain| chain.parse().ok());
+            if let Some(linked) = onboarding::link(data_dir.as_deref(), &sync)
+                .or_exit(ExitCode::Failure, tr("linked-profile-failed"))?
+            {
+                profile = Some(linked);
+            }
+            (
+                link.service_provider,
+                link.server,
+                room,
+                sync.room_name,
+                sync.name,
+                Some(sync.read_position),
+            )
+        }
+    };
+
+    // Open the local history, unless we took over another device's state we continue fetching
+    // where we stopped last time
+    let mut history = match &data_dir {
+        Some(data_dir) => {
+            History::open(data_dir, &room).or_exit(ExitCode::Failure, tr("history-open-failed"))?
+        }
+        None => History::in_memory(&room),
+    };
+    // Of the stored chain position and the one we joined with, the older one reads further back,
+    // but never further than where forgetting old epochs moved it
+    let floor = history.chain_floor().unwrap_or(0);
+    let chain = history
+        .chain()
+        .into_iter()
+        .chain(chain)
+        .min_by_key(ChainKey::epoch)
+        .and_then(|chain| chain.at(chain.epoch().max(floor)));
+    if let Some(chain) = &chain {
+        history
+            .set_chain(chain)
+            .or_exit(ExitCode::Failure, tr("history-write-failed"))?;
+    }
+    let keys = Arc::new(RoomKeys::new(room.clone(), chain));
+    let mut address_book = match &data_dir {
+        Some(data_dir) => AddressBook::open(data_dir)
+            .or_exit(ExitCode::Failure, tr("address-book-open-failed"))?,
+        None => AddressBook::in_memory(),
+    };
+    // Commands don't load the profile, incognito sessions still sign with a throwaway identity
+    if data_dir.is_none() && profile.is_none() {
+        profile = Some(SavedProfile::throwaway(Profile::new(name.clone())));
+    }
+    // Petnames used to be stored in the profile, they are shared between profiles now
+    if let Some(saved) = profile
+        .as_mut()
+        .filter(|saved| !saved.profile.petnames.is_empty())
+    {
+        for (fingerprint, name) in std::mem::take(&mut saved.profile.petnames).iter() {
+            let contact = address_book.entry(fingerprint.to_owned());
+            contact.petname = contact.petname.take().or_else(|| Some(name.to_owned()));
+        }
+        address_book
+            .save()
+            .and_then(|()| saved.save())
+            .or_exit(ExitCode::Failure, tr("petnames-move-failed"))?;
+    }
+
+    let mut servers = std::iter::once(Server {
+        url: url.clone(),
+        service_provider,
+    })
+    .chain(opts.mirrors)
+    .collect::<Vec<_>>();
+    if let Some(directory) = &opts.directory {
+        let announced = bootstrap_mirrors(&http, &url, directory, opts.pad_fetches, &servers).await;
+        servers.extend(announced);
+    }
+    // Importing happens before fetching starts, so we continue at the archived read positions.
+    // That's also why there's no importing once the client runs.
+    if let Some(path) = &opts.import_archive {
+        if let Err(e) = archive::import(&mut history, &room, &servers, path) {
+            let error = tr_args("archive-import-failed", &[("error", e.to_string().into())]);
+            return Err(Fatal::new(ExitCode::Failure, error));
+        }
+    }
+    let read_position = read_position.unwrap_or_else(|| history.read_position());
+    // Mirrors number messages differently, so each one has its own read position. New mirrors
+    // start at the end of their log, see fetch_loop.
+    let mut read_positions = history.fetch_positions(servers.iter().map(|s| s.url.as_str()));
+    read_positions[0] = Some(read_position);
+    let notify_rules = history.notify_rules();
+    let schedule = history.schedule();
+    let outbox = history.outbox();
+    let reports = history.reports();
+    let auto_reply = history.auto_reply();
+    let mut responder = Responder::default();
+    let archived = history.archived();
+    let priority = history.priority();
+    let history = Arc::new(Mutex::new(history));
+
+    // Opened before connecting so a transcript that doesn't verify stops us right away
+    let mut transcript = match (opts.transcript, opts.transcript_key) {
+        (Some(path), Some(key)) => match Transcript::open(&path, key) {
+            Ok(transcript) => Some(transcript),
+            Err(e) => {
+                let error = tr_args("transcript-open-failed", &[("error", e.to_string().into())]);
+                return Err(Fatal::new(ExitCode::Failure, error));
+            }
+        },
+        _ => None,
+    };
+
+    // Connect to Nym native client, or with --loopback talk to the servers directly. Everything
+    // that goes wrong on the way is counted for /stats and statsd.
+    let health = Arc::new(Health::default());
+    let inner: Arc<dyn Transport> = match opts.loopback {
+        true => Arc::new(Loopback::new(http.clone())),
+        false => Arc::new(
+            Websocket::connect(&websocket, http.clone(), health.clone())
+                .await
+                .or_exit(
+                    ExitCode::NymUnreachable,
+                    tr_args("nym-unreachable", &[("url", websocket.clone().into())]),
+                )?,
+        ),
+    };
+    let transport: Arc<dyn Transport> = Arc::new(Counted {
+        inner,
+        health: health.clone(),
+    });
+    if let Some(addr) = opts.statsd.as_deref() {
+        let socket = statsd::connect(addr)
+            .await
+            .or_exit(ExitCode::Config, tr("statsd-unreachable"))?;
+        tokio::spawn(statsd::export(
+            socket,
+            opts.statsd_prefix.clone(),
+            health.clone(),
+        ));
+    }
+
+    // Channels to communicate with the UI: the UI sends us messages to encrypt and send via the
+    // Nym client as well as commands, we send it the messages we received and could decrypt. See
+    // the events module for details.
+    let (mut app, ui_channels) = events::channels();
+    // Scheduled messages are handed to ourselves like the UI hands over what the user typed
+    let scheduler = ui_channels.app.clone();
+    let mut schedule_timer = tokio::time::interval(Duration::from_secs(1));
+    let mut outbox_timer = tokio::time::interval(OUTBOX_RETRY);
+
+    // Spawn the UI thread, see the ui module for how it's structured. In daemon mode the UI
+    // channels are served on a socket instead.
+    let ui_history = history.clone();
+    let mut ui = match opts.daemon {
+        Some(socket) => {
+            let room_info = daemon::RoomInfo {
+                id: room.id(),
+                name: room_name.clone(),
+            };
+            let listener = daemon::bind(&socket).or_exit(
+                ExitCode::Config,
+                tr_args(
+                    "daemon-bind-failed",
+                    &[("path", socket.display().to_string().into())],
+                ),
+            )?;
+            tokio::spawn(daemon::serve(
+                socket,
+                listener,
+                ui_channels,
+                ui_history,
+                room_info,
+            ))
+        }
+        None => {
+            let ui_name = name.clone();
+            tokio::task::spawn_blocking(move || {
+                ui::run_ui(ui_channels, Some(ui_history), ui_name, ui_options)
+            })
+        }
+    };
+
+    // Fetching and trial decryption happen in a background task per server so that busy rooms
+    // (or unreachable servers) don't block sending or the UI. They report batches of decrypted
+    // payloads together with the new read position, messages we already got from another server
+    // are dropped by comparing digests.
+    let (fetched_send, mut fetched_receive) = tokio::sync::mpsc::channel(16);
+    // Receipts are only checked for the primary server, the key belongs to it
+    let server_key = opts.server_key;
+    // The room's priority decides how often it's fetched, /priority changes it for running fetches
+    let fetch_interval = Duration::from_millis(opts.fetch_interval.max(1));
+    let (interval_send, interval) = watch::channel(priority.interval(fetch_interval));
+    let debug_log = Arc::new(DebugLog::default());
+    let traffic = Arc::new(Mutex::new(Traffic::default()));
+    let pad_messages = opts.pad_messages;
+    let forget_old_epochs = !opts.keep_old_epochs;
+    let mut gaps = Gaps::default();
+    let polling = Polling {
+        pad: opts.pad_fetches,
+        interval,
+        transport: transport.clone(),
+        http: http.clone(),
+        debug: debug_log.clone(),
+        traffic: traffic.clone(),
+    };
+    // Archived rooms aren't fetched at all, /archive stops fetching right away
+    let spawn_fetchers = |read_positions: Vec<Option<usize>>| {
+        let positions = servers.iter().zip(read_positions).enumerate();
+        let spawn = |(idx, (server, position)): (usize, (&Server, _))| {
+            tokio::spawn(fetch_loop(
+                idx,
+                server.url.clone(),
+                keys.clone(),
+                position,
+                polling.clone(),
+                server_key
+                    .filter(|_| idx == 0)
+                    .map(|key| (key, server.service_provider.to_string())),
+                fetched_send.clone(),
+            ))
+        };
+        positions.map(spawn).collect::<Vec<_>>()
+    };
+    let mut fetchers = match archived {
+        true => Vec::new(),
+        false => spawn_fetchers(read_positions),
+    };
+    // With its receipt key we also audit the primary server's log, see audit_loop
+    let (audit_send, mut audit_receive) = tokio::sync::mpsc::channel(1);
+    if let Some(key) = server_key {
+        let last = history.lock().unwrap().tree_head();
+        let url = servers[0].url.clone();
+        let provider = servers[0].service_provider.to_string();
+        let audit = audit_loop(http.clone(), url, provider, key, last, audit_send);
+        tokio::spawn(audit);
+    }
+    // Scripted participants hand us their messages to send
+    let (simulated_send, mut simulated_receive) = tokio::sync::mpsc::channel(16);
+    if let Some(participants) = opts.simulate {
+        tokio::spawn(simulate::run(participants, simulated_send));
+    }
+    let mut undecryptable = opts.show_undecryptable.then(UndecryptableCount::default);
+
+    // Presence beacons are sent at random intervals, the first one shortly after joining
+    let mut presence_timer = Box::pin(tokio::time::sleep(Duration::from_secs(1)));
+    let mut presence_status = PresenceStatus::Joined;
+    // The first usage beacon goes out at a random time of the first interval, so beacons don't
+    // tell when the client was started
+    let usage_collector = opts.usage_collector.clone().zip(opts.usage_room.clone());
+    let mut usage = Usage::default();
+    let first_usage_beacon = usage::INTERVAL.mul_f64(determinism::with_rng(|rng| rng.gen()));
+    let mut usage_timer = Box::pin(tokio::time::sleep(first_usage_beacon));
+    let mut last_activity = Instant::now();
+    // Commands don't load the profile, their messages are signed with an identity that only lasts
+    // for the session
+    let session_identity = SigningKey::generate();
+    let own_fingerprint = profile
+        .as_ref()
+        .map(|saved: &SavedProfile| saved.profile.identity())
+        .unwrap_or(&session_identity)
+        .verifying_key()
+        .fingerprint();
+    let mut handlers = Handlers::default();
+    let mut session = Session {
+        name: name.clone(),
+        room: &room,
+        invite: Invite {
+            kind: InviteKind::Join,
+            server: url.clone(),
+            service_provider,
+            key: Some(room.clone()),
+            hint: None,
+            room_name: room_name.clone(),
+            max_age,
+            broadcast: broadcast_key,
+            secret: room_secret.clone(),
+            report: report_key,
+            chain: None,
+            from: None,
+        },
+        keys: &keys,
+        servers: &servers,
+        history: &history,
+        data_dir: data_dir.as_deref(),
+        profile,
+        session_identity,
+        moderator_key: moderator_key.as_ref(),
+        draw_qr_codes,
+        last_fetch: read_position,
+        archived,
+        outgoing: Outgoing {
+            room: &room,
+            keys: &keys,
+            signing_key: signing_key.as_ref(),
+            broadcast_key,
+            counter: opts.count_messages.then(Counter::new),
+            pad: pad_messages,
+            traffic: &traffic,
+            transport: &*transport,
+            servers: &servers,
+            history: &history,
+            max_age,
+            outbox,
+            receipts: server_key.is_some(),
+            awaiting_receipts: HashSet::new(),
+        },
+        polls: Polls::default(),
+        roster: Roster::default(),
+        address_book,
+        notify_rules,
+        schedule,
+        reports,
+        auto_reply,
+        snippets,
+        scheduler: scheduler.clone(),
+        priority,
+        fetch_interval,
+        interval: interval_send,
+        latency: Latency::default(),
+        receipts_received: 0,
+        health: &health,
+        debug_log: &debug_log,
+    };
+    let joined = transcript::Event::Control {
+        description: format!("joined room {} as {}", room.id(), name),
+    };
+    record(&mut transcript, joined, &app.ui);
+    if session.archived {
+        let _ = app.ui.send(UiEvent::Notice(tr("room-archived-notice")));
+    }
+    // Set by /leave, whether to delete the room's files once the UI is gone
+    let mut leaving = None;
+    // Set when we have to give up, it's reported once the UI restored the terminal
+    let mut fatal = None;
+    // Set on SIGTERM, the UI still has to save the draft and restore the terminal
+    let mut terminated = false;
+    let mut terminate = Terminate::new();
+
+    // Run forever and wait for one of the following events to happen:
+    'main: loop {
+        select! {
+            // The UI thread sent an event, messages have to be encrypted and sent via the Nym
+            // client, commands are handled locally and never leave the client
+            Some(event) = app.events.recv() => {
+                last_activity = Instant::now();
+
+                match event {
+                    AppEvent::Send(_) if session.archived => {
+                        let _ = app.ui.send(UiEvent::Notice(tr("room-archived-read-only")));
+                    }
+                    AppEvent::Send(msg) => {
+                        // Plugins may rewrite what the user typed or keep it from being sent
+                        let msg = match plugins.outgoing(msg) {
+                            Some(msg) => msg,
+                            None => continue,
+                        };
+                        usage.record(Feature::Message);
+                        let msg = Message::new(name.clone(), msg);
+                        let payload = session.outgoing.sign_message(msg.clone(), session.identity());
+                        let payload = match payload {
+                            Some(payload) => payload,
+                            None => {
+                                let _ = app.ui.send(UiEvent::Notice(tr("broadcast-read-only")));
+                                continue;
+                            }
+                        };
+                        let (digest, notice) = session.outgoing.queue(payload, msg.msg.clone()).await;
+                        session.latency.sent(digest);
+                        let sent = transcript::Event::Sent {
+                            sender: name.clone(),
+                            msg: msg.msg,
+                            digest: hex::encode(digest),
+                        };
+                        record(&mut transcript, sent, &app.ui);
+                        if let Some(notice) = notice {
+                            let _ = app.ui.send(UiEvent::Notice(notice));
+                        }
+                    }
+                    AppEvent::SendCustom { .. } if session.archived => {
+                        let _ = app.ui.send(UiEvent::Notice(tr("room-archived-read-only")));
+                    }
+                    AppEvent::SendCustom { type_uri, data } => {
+                        let text = type_uri.clone();
+                        let custom = Payload::Custom(Custom {
+                            sender: name.clone(),
+                            type_uri,
+                            data,
+                        });
+                        let reply = session.outgoing.send(custom, &text, String::new(), &app.ui).await;
+                        if !reply.is_empty() {
+                            let _ = app.ui.send(UiEvent::Notice(reply));
+                        }
+                    }
+                    AppEvent::Command(cmd) => {
+                        // Only the command's name: arguments can be secrets (`/backup`, `/link`),
+                        // file names or the text of burn-after-reading messages
+                        let name = cmd.split_whitespace().next().unwrap_or_default();
+                        let command = transcript::Event::Control {
+                            description: format!("command {}", name),
+                        };
+                        record(&mut transcript, command, &app.ui);
+                        usage.record(match plugins.handles(cmd.trim()) {
+                            true => Feature::Plugin,
+                            false => command_feature(cmd.trim()),
+                        });
+                        let action = match cmd.trim() {
+                            cmd if plugins.handles(cmd) => Action::Reply(plugins.command(cmd, &app.ui)),
+                            "/plugins" => Action::Reply(match plugins.is_empty() {
+                                true => tr("plugins-none"),
+                                false => plugins.describe().join("\n"),
+                            }),
+                            cmd => handlers.command(cmd, &mut session),
+                        };
+                        let reply = match action {
+                            Action::Reply(reply) => reply,
+                            Action::Notices(notices) => {
+                                for notice in notices {
+                                    let _ = app.ui.send(UiEvent::Notice(notice));
+                                }
+                                continue;
+                            }
+                            Action::Send { .. } if session.archived => tr("room-archived-read-only"),
+                            Action::Send { payload, text, reply } => {
+                                session.outgoing.send(payload, &text, reply, &app.ui).await
+                            }
+                            Action::SendToPrimary { envelope, notices } => {
+                                send_to_provider(&*transport, &servers[0], &envelope).await;
+                                for notice in notices {
+                                    let _ = app.ui.send(UiEvent::Notice(notice));
+                                }
+                                continue;
+                            }
+                            Action::StopFetching(reply) => {
+                                for fetcher in fetchers.drain(..) {
+                                    fetcher.abort();
+                                }
+                                reply
+                            }
+                            Action::ResumeFetching(reply) => {
+                                let urls = servers.iter().map(|s| s.url.as_str());
+                                let positions = history.lock().unwrap().fetch_positions(urls);
+                                fetchers = spawn_fetchers(positions);
+                                reply
+                            }
+                            Action::Leave { wipe } => {
+                                leaving = Some(wipe);
+                                break;
+                            }
+                            Action::Nothing => continue,
+                        };
+                        let _ = app.ui.send(UiEvent::Notice(reply));
+                    }
+                    AppEvent::Quit => {
+                        let quit = transcript::Event::Control {
+                            description: "quit".to_owned(),
+                        };
+                        record(&mut transcript, quit, &app.ui);
+                        break;
+                    }
+                }
+            },
+            // A fetch task decrypted new payloads, messages are sent to the UI thread
+            Some(fetched) = fetched_receive.recv() => {
+                // Receipts of our own messages are kept as proof that the server accepted them
+                for receipt in fetched.receipts {
+                    if session.outgoing.awaiting_receipts.remove(&receipt.digest) {
+                        session.receipts_received += 1;
+                        if let Err(e) = history.lock().unwrap().save_receipt(&receipt) {
+                            let notice =
+                                tr_args("receipt-save-failed", &[("error", e.to_string().into())]);
+                            let _ = app.ui.send(UiEvent::Notice(notice));
+                        }
+                    }
+                }
+                if fetched.missed > 0 {
+                    let notice = tr_args(
+                        "messages-missed",
+                        &[
+                            ("count", fetched.missed.into()),
+                            ("url", servers[fetched.server].url.clone().into()),
+                        ],
+                    );
+                    let _ = app.ui.send(UiEvent::Notice(notice));
+                }
+                if fetched.invalid_receipts > 0 {
+                    let notice =
+                        tr_args("receipts-invalid", &[("count", fetched.invalid_receipts.into())]);
+                    let _ = app.ui.send(UiEvent::Notice(notice));
+                }
+                if let Some(undecryptable) = &mut undecryptable {
+                    let server = fetched.server;
+                    if let Some(count) =
+                        undecryptable.count(server, &fetched.payloads, &fetched.failures)
+                    {
+                        let _ = app.ui.send(UiEvent::Undecryptable(count));
+                    }
+                }
+                for (digest, payload) in fetched.payloads {
+                    session.latency.observed(&digest);
+                    // Numbers are checked per server, before copies from mirrors are dropped
+                    let payload = match payload {
+                        Payload::Counted(counted) => {
+                            let skipped = gaps.check(fetched.server, &counted);
+                            if skipped > 0 {
+                                let sender = counted.sender().unwrap_or("?").to_owned();
+                                let warning = tr_args(
+                                    "messages-dropped",
+                                    &[
+                                        ("server", servers[fetched.server].url.clone().into()),
+                                        ("count", skipped.into()),
+                                        ("sender", sender.into()),
+                                    ],
+                                );
+                                let _ = app.ui.send(UiEvent::Warning(warning));
+                            }
+                            *counted.payload
+                        }
+                        payload => payload,
+                    };
+                    let new = history
+                        .lock()
+                        .unwrap()
+                        .mark_seen(digest)
+                        .or_exit(ExitCode::Failure, tr("history-write-failed"));
+                    let new = match new {
+                        Ok(new) => new,
+                        Err(e) => {
+                            fatal = Some(e);
+                            break 'main;
+                        }
+                    };
+                    if !new {
+                        continue;
+                    }
+                    // Broadcast rooms only accept messages signed by the room's signing key, other
+                    // payloads are signed as a whole and count as unsigned ones once checked
+                    let (payload, required_key) = match payload {
+                        Payload::SignedPayload(signed) => match &broadcast_key {
+                            Some(key) => {
+                                let sender = claimed_sender(&signed.payload);
+                                match signed.verify(key, &room) {
+                                    Ok(payload) => (payload, None),
+                                    Err(()) => {
+                                        let notice = tr_args(
+                                            "broadcast-rejected",
+                                            &[("sender", sender.into())],
+                                        );
+                                        let _ = app.ui.send(UiEvent::Notice(notice));
+                                        continue;
+                                    }
+                                }
+                            }
+                            None => (*signed.payload, None),
+                        },
+                        payload => (payload, broadcast_key.as_ref()),
+                    };
+                    let msg = match (payload, required_key) {
+                        // Without a signature we can check anyone could have sent it
+                        (Payload::Message(mut msg), None) => {
+                            msg.sender = unverified(&msg.sender);
+                            Ok(msg)
+                        }
+                        (Payload::Message(msg), Some(_)) => Err(msg.sender),
+                        (Payload::Signed(mut signed), None) => {
+                            signed.msg.sender = unverified(&signed.msg.sender);
+                            Ok(signed.msg)
+                        }
+                        (Payload::Signed(signed), Some(key)) => {
+                            let sender = signed.msg.sender.clone();
+                            signed.verify(key, &room).map_err(|_| sender)
+                        }
+                        (Payload::Authored(authored), None) => {
+                            let sender = authored.msg.sender.clone();
+                            match authored.verify(&room) {
+                                Ok((mut msg, author)) => {
+                                    let claimed = msg.sender.clone();
+                                    if own_fingerprint != author.fingerprint() {
+                                        let address_book = &mut session.address_book;
+                                        msg.sender = address_book.display_name(&claimed, &author);
+                                        if address_book.seen(&author, &claimed, &room.id()) {
+                                            if let Err(e) = address_book.save() {
+                                                let notice = tr_args(
+                                                    "contacts-save-failed",
+                                                    &[("error", e.to_string().into())],
+                                                );
+                                                let _ = app.ui.send(UiEvent::Notice(notice));
+                                            }
+                                        }
+                                    }
+                                    Ok(msg)
+                                }
+                                Err(()) => {
+                                    let notice =
+                                        tr_args("author-invalid", &[("sender", sender.into())]);
+                                    let _ = app.ui.send(UiEvent::Notice(notice));
+                                    continue;
+                                }
+                            }
+                        }
+                        (Payload::Authored(authored), Some(_)) => Err(authored.msg.sender),
+                        (Payload::BurnAfterReading(msg), None) => {
+                            let description =
+                                format!("burn-after-reading message from {}", msg.sender);
+                            let received = transcript::Event::Control { description };
+                            record(&mut transcript, received, &app.ui);
+                            let _ = app.ui.send(UiEvent::BurnAfterReading(msg));
+                            continue;
+                        }
+                        (Payload::BurnAfterReading(msg), Some(_)) => Err(msg.sender),
+                        (Payload::Presence(presence), _) => {
+                            session.roster.update(presence);
+                            continue;
+                        }
+                        (Payload::Poll(poll), None) => {
+                            let notice = describe_poll(&poll, None);
+                            session.polls.add_poll(poll);
+                            let _ = app.ui.send(UiEvent::Notice(notice));
+                            continue;
+                        }
+                        (Payload::Poll(poll), Some(_)) => Err(poll.sender),
+                        (Payload::Contact(card), None) => {
+                            let notice = describe_card(card);
+                            let _ = app.ui.send(UiEvent::Notice(notice));
+                            continue;
+                        }
+                        (Payload::Contact(card), Some(_)) => Err(card.name),
+                        (Payload::Custom(custom), None) => {
+                            let _ = app.ui.send(UiEvent::Custom(custom));
+                            continue;
+                        }
+                        (Payload::Custom(custom), Some(_)) => Err(custom.sender),
+                        (Payload::Vote(vote), None) => {
+                            session.polls.add_vote(vote);
+                            continue;
+                        }
+                        (Payload::Vote(vote), Some(_)) => Err(vote.voter),
+                        // Other members can't open reports, they only see that one was sent
+                        (Payload::Report(sealed), _) => {
+                            let report = moderator_key
+                                .as_ref()
+                                .and_then(|key| sealed.open(key).ok());
+                            if let Some(report) = report {
+                                let notice = describe_report(&report);
+                                session.reports.add(report);
+                                let result = history.lock().unwrap().set_reports(&session.reports);
+                                if let Err(e) = result {
+                                    let notice = tr_args(
+                                        "reports-save-failed",
+                                        &[("error", e.to_string().into())],
+                                    );
+                                    let _ = app.ui.send(UiEvent::Notice(notice));
+                                }
+                                let _ = app.ui.send(UiEvent::Notice(notice));
+                            }
+                            continue;
+                        }
+                        // Numbered and signed payloads are only unwrapped once
+                        (Payload::Counted(_), _) | (Payload::SignedPayload(_), _) => continue,
+                        (Payload::Announcement(announcement), _) => {
+                            let notice = tr_args(
+                                "mirror-announced",
+                                &[("url", announcement.url.into())],
+                            );
+                            let _ = app.ui.send(UiEvent::Notice(notice));
+                            continue;
+                        }
+                    };
+                    let msg = match msg {
+                        Ok(msg) => msg,
+                        Err(sender) => {
+                            let notice =
+                                tr_args("broadcast-rejected", &[("sender", sender.into())]);
+                            let _ = app.ui.send(UiEvent::Notice(notice));
+                            continue;
+                        }
+                    };
+                    let index = history
+                        .lock()
+                        .unwrap()
+                        .append(&msg)
+                        .or_exit(ExitCode::Failure, tr("history-write-failed"));
+                    let index = match index {
+                        Ok(index) => index,
+                        Err(e) => {
+                            fatal = Some(e);
+                            break 'main;
+                        }
+                    };
+                    let received = transcript::Event::Received {
+                        sender: msg.sender.clone(),
+                        msg: msg.msg.clone(),
+                        digest: hex::encode(digest),
+                    };
+                    record(&mut transcript, received, &app.ui);
+                    let alert = session.notify_rules.evaluate_now(&msg, &name).is_some();
+                    // Answered like a bot would, through the same channel as scheduled messages
+                    if session.auto_reply && responder.should_reply(&msg, &name, Instant::now()) {
+                        if let Some(away) = data_dir.as_deref().and_then(Away::load) {
+                            let reply =
+                                format!("{} @{}: {}", away::MARKER, msg.sender, away.message);
+                            let _ = scheduler.try_send(AppEvent::Send(reply));
+                        }
+                    }
+                    let _ = app.ui.send(UiEvent::Message(index, msg.clone()));
+                    if alert {
+                        let _ = app.ui.send(UiEvent::Alert(index));
+                    }
+                    plugins.incoming(index, &msg, &app.ui);
+                }
+                let history = history.lock().unwrap();
+                let result = match fetched.server {
+                    0 => {
+                        session.last_fetch = fetched.read_position;
+                        // Caught up, so the keys of older epochs can go
+                        let forwarded = forget_old_epochs.then(|| keys.forget_old_epochs());
+                        let result = history.set_read_position(fetched.read_position);
+                        match forwarded.flatten() {
+                            Some(chain) => result.and_then(|()| {
+                                store_chain(&history, session.profile.as_mut(), &room, &chain)
+                            }),
+                            None => result,
+                        }
+                    }
+                    idx => history.set_mirror_position(&servers[idx].url, fetched.read_position),
+                };
+                if let Err(e) = result.or_exit(ExitCode::Failure, tr("history-write-failed")) {
+                    fatal = Some(e);
+                    break;
+                }
+            },
+            // A scripted participant wants to post, it goes out like our own messages
+            Some(msg) = simulated_receive.recv() => {
+                let msg = keys.encrypt(&Payload::Message(msg));
+                session.latency.sent(msg.digest());
+                send_to_all(&*transport, &servers, msg, max_age).await;
+            },
+            // The audit task checked the server's log, a failed audit stays in the status bar
+            Some(audit) = audit_receive.recv() => {
+                let warning = match audit {
+                    Audit::Verified(head) => {
+                        let result = history.lock().unwrap().set_tree_head(&head);
+                        if let Err(e) = result {
+                            let error = e.to_string().into();
+                            let notice = tr_args("tree-head-save-failed", &[("error", error)]);
+                            let _ = app.ui.send(UiEvent::Notice(notice));
+                        }
+                        None
+                    }
+                    Audit::InvalidSignature => Some(tr("status-tree-head-invalid")),
+                    Audit::Rewritten => Some(tr("status-log-rewritten")),
+                };
+                if let Some(warning) = warning {
+                    let event = transcript::Event::Control {
+                        description: format!("warning {}", warning),
+                    };
+                    record(&mut transcript, event, &app.ui);
+                    let _ = app.ui.send(UiEvent::Warning(warning));
+                }
+            },
+            // Time to tell the room we are still around, unless the user opted out
+            _ = &mut presence_timer, if presence_enabled && !session.archived => {
+                let presence = Payload::Presence(Presence {
+                    sender: name.clone(),
+                    status: presence_status,
+                });
+                send_to_all(&*transport, &servers, keys.encrypt(&presence), max_age).await;
+
+                let idle = last_activity.elapsed() >= Duration::from_secs(5 * 60);
+                let away = data_dir.as_deref().and_then(Away::load);
+                presence_status = if !idle && away.is_none() {
+                    PresenceStatus::Active
+                } else {
+                    PresenceStatus::Away
+                };
+                let next_beacon = Duration::from_secs(determinism::with_rng(|rng| {
+                    rng.gen_range(BEACON_INTERVAL_SECS.0..=BEACON_INTERVAL_SECS.1)
+                }));
+                presence_timer.as_mut().reset(tokio::time::Instant::now() + next_beacon);
+            },
+            // Opted-in usage beacons, nothing is sent for days nothing was used
+            _ = &mut usage_timer, if usage_collector.is_some() => {
+                let (collector, room) = usage_collector.as_ref().expect("checked by the guard");
+                if let Some(beacon) = usage.beacon() {
+                    let beacon =
+                        Payload::Custom(beacon).encrypt_padded(room, padding::FIXED_BUCKETS);
+                    send_to_all(&*transport, std::slice::from_ref(collector), beacon, None).await;
+                }
+                usage_timer.as_mut().reset(tokio::time::Instant::now() + usage::INTERVAL);
+            },
+            // Scheduled messages that are due go out as if the user typed them just now, whatever
+            // doesn't fit into the channel waits for the next tick
+            _ = schedule_timer.tick(), if !session.schedule.is_empty() && !session.archived => {
+                let now = determinism::current();
+                let mut sent = false;
+                while let Some(scheduled) = session.schedule.due(now) {
+                    if scheduler.try_send(AppEvent::Send(scheduled.msg.clone())).is_err() {
+                        break;
+                    }
+                    session.schedule.sent();
+                    sent = true;
+                }
+                if sent {
+                    if let Err(e) = history.lock().unwrap().set_schedule(&session.schedule) {
+                        let error = e.to_string().into();
+                        let notice = tr_args("schedule-save-failed", &[("error", error)]);
+                        let _ = app.ui.send(UiEvent::Notice(notice));
+                    }
+                }
+            },
+            // Queued messages are retried until every server took them, the first time right after
+            // starting
+            _ = outbox_timer.tick(), if !session.outgoing.outbox.is_empty() && !session.archived => {
+                let outbox = &mut session.outgoing.outbox;
+                let queued = outbox.len();
+                let result = send_queued(&*transport, &servers, outbox, &history).await;
+                let notice = match result {
+                    Err(e) => {
+                        let error = e.to_string().into();
+                        Some(tr_args("queue-save-failed", &[("error", error)]))
+                    }
+                    Ok(()) if outbox.len() < queued => {
+                        Some(tr_args("queue-sent", &[("count", (queued - outbox.len()).into())]))
+                    }
+                    Ok(()) => None,
+                };
+                if let Some(notice) = notice {
+                    let _ = app.ui.send(UiEvent::Notice(notice));
+                }
+            },
+            // SIGTERM ends the session like quitting, after saving everything the next start needs
+            // to resume where we stopped: read position, queued and scheduled messages
+            _ = terminate.recv() => {
+                let saved = {
+                    let history = history.lock().unwrap();
+                    history
+                        .set_read_position(session.last_fetch)
+                        .and_then(|_| history.set_outbox(&session.outgoing.outbox))
+                        .and_then(|_| history.set_schedule(&session.schedule))
+                };
+                if let Err(e) = saved.or_exit(ExitCode::Failure, tr("history-write-failed")) {
+                    fatal = Some(e);
+                    break;
+                }
+                let terminate = transcript::Event::Control {
+                    description: "terminated".to_owned(),
+                };
+                record(&mut transcript, terminate, &app.ui);
+                terminated = true;
+                break;
+            },
+            // The UI thread exited, we exit the infinite loop to stop the application
+            _ = &mut ui => {
+                break;
+            }
+        }
+    }
+
+    // Gracefully disconnect from the Nym native client, there's nothing left to do if that fails
+    let _ = transport.close().await;
+    // Leaving the room still changes the profile
+    let mut profile = session.profile.take();
+    drop(session);
+
+    if let Some(fatal) = fatal {
+        drop(app);
+        let _ = ui.await;
+        return Err(fatal);
+    }
+    // The UI notices the closed channel, saves the draft and restores the terminal
+    if terminated {
+        drop(app);
+        let _ = ui.await;
+        return Ok(());
+    }
+
+    // The room's files can only be deleted once the UI stopped, it saves the draft when exiting
+    if let Some(wipe) = leaving {
+        drop(app);
+        let _ = ui.await;
+        drop(history);
+        println!(
+            "{}",
+            leave_room(data_dir.as_deref(), &room, profile.as_mut(), wipe)
+        );
+    }
+    Ok(())
+}
+
+/// Waits for SIGTERM (sent by service managers or when logging out), it never arrives on platforms
+/// without signals
+struct Terminate {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Terminate {
+    fn new() -> Terminate {
+        Terminate {
+            // If listening fails SIGTERM kills us right away, like it used to
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok(),
+        }
+    }
+
+    #[cfg(unix)]
+    async fn recv(&mut self) {
+        match &mut self.signal {
+            Some(signal) => {
+                signal.recv().await;
+            }
+            None => futures::future::pending().await,
+        }
+    }
+
+    #[cfg(not(unix))]
+    async fn recv(&mut self) {
+        futures::future::pending().await
+    }
+}
+
+/// Forget the room we left: it's removed from the profile and, if `wipe` is set, everything stored
+/// about it is deleted. Incognito sessions (without `data_dir`) didn't store anything.
+fn leave_room(
+    data_dir: Option<&Path>,
+    room: &Key,
+    profile: Option<&mut SavedProfile>,
+    wipe: bool,
+) -> String {
+    if let Some(saved) = profile {
+        if saved.profile.remove_room(&room.id()) {
+            if let Err(e) = saved.save() {
+                return tr_args("profile-save-failed", &[("error", e.to_string().into())]);
+            }
+        }
+    }
+    let data_dir = match (data_dir, wipe) {
+        (Some(data_dir), true) => data_dir,
+        (Some(_), false) => return tr("room-left-kept"),
+        (None, _) => return tr("room-left"),
+    };
+    match History::wipe(data_dir, room) {
+        Ok(_) => tr("room-left"),
+        Err(e) => tr_args("room-wipe-failed", &[("error", e.to_string().into())]),
+    }
+}
+
+/// Append to the transcript if there is one, failures are shown but don't stop the client
+fn record(transcript: &mut Option<Transcript>, event: transcript::Event, ui: &UiSender) {
+    if let Some(transcript) = transcript {
+        if let Err(e) = transcript.record(event) {
+            let error = e.to_string().into();
+            let notice = tr_args("transcript-write-failed", &[("error", error)]);
+            let _ = ui.send(UiEvent::Notice(notice));
+        }
+    }
+}
+
+/// Send an encrypted message to the server via the transport. A failed send is counted by the
+/// transport, the message is lost like one the mixnet dropped.
+async fn send_to_provider(transport: &dyn Transport, server: &Server, msg: &Envelope) {
+    let _ = transport.send(server, msg).await;
+}
+
+/// Send an encrypted message to every server of the room, asking them to delete it after
+/// `max_age` seconds if set
+async fn send_to_all(
+    transport: &dyn Transport,
+    servers: &[Server],
+    msg: EncryptedMessage,
+    max_age: Option<u64>,
+) {
+    let envelope = Envelope {
+        msg,
+        max_age_secs: max_age,
+    };
+    for server in servers {
+        if faults::dropped() {
+            continue;
+        }
+        send_to_provider(transport, server, &envelope).await;
+    }
+}
+
+/// How often messages in the outbox are sent again
+const OUTBOX_RETRY: Duration = Duration::from_secs(30);
+
+/// Try to send everything in the outbox, messages are kept for the servers that didn't take them.
+/// The outbox is saved before sending, so a crash while sending doesn't lose anything, and again
+/// afterwards. Servers that no longer serve the room are dropped.
+async fn send_queued(
+    transport: &dyn Transport,
+    servers: &[Server],
+    outbox: &mut Outbox,
+    history: &Mutex<History>,
+) -> Result<(), anyhow::Error> {
+    history.lock().unwrap().set_outbox(outbox)?;
+    for mut queued in outbox.take() {
+        let mut left = Vec::new();
+        for url in queued.servers.drain(..) {
+            let server = match servers.iter().find(|server| server.url == url) {
+                Some(server) => server,
+                None => continue,
+            };
+            // Lost on its way like in the mixnet, we don't learn about that either
+            if faults::dropped() {
+                continue;
+            }
+            if transport.send(server, &queued.envelope).await.is_err() {
+                left.push(url);
+            }
+        }
+        queued.servers = left;
+        if !queued.servers.is_empty() {
+            outbox.push(queued);
+        }
+    }
+    history.lock().unwrap().set_outbox(outbox)
+}
+
+/// Number of messages decrypted by one blocking task, small enough to spread a big fetch over
+/// several threads and big enough to not drown in scheduling overhead
+const DECRYPT_BATCH_SIZE: usize = 64;
+
+/// How the fetch tasks poll their servers
+#[derive(Clone)]
+struct Polling {
+    /// See `--pad-fetches`
+    pad: bool,
+    /// Time between two fetches, it changes with the room's priority
+    interval: watch::Receiver<Duration>,
+    transport: Arc<dyn Transport>,
+    /// For snapshots, which aren't fetched through the transport
+    http: reqwest::Client,
+    /// What happened to fetched messages, for `/debug`
+    debug: Arc<DebugLog>,
+    /// Sizes of the room's messages for adaptive padding, only the primary server's are counted
+    /// as mirrors serve the same messages
+    traffic: Arc<Mutex<Traffic>>,
+}
+
+/// Fetch new messages from one server every polling interval and send the ones we could decrypt,
+/// in order, together with the new read position to the main thread. Failed fetches are retried
+/// on the next tick. With the server's receipt key (and the service provider address its receipts
+/// are signed for) the receipts of all fetched messages are checked.
+///
+/// Mirrors we never fetched from (`last_fetch` is `None`) start at the end of their log. The
+/// history comes from the primary server, replaying it from a mirror would show everything older
+/// than the digests we remember (see `History::mark_seen`) a second time.
+async fn fetch_loop(
+    server: usize,
+    url: String,
+    keys: Arc<RoomKeys>,
+    last_fetch: Option<usize>,
+    polling: Polling,
+    server_key: Option<(VerifyingKey, String)>,
+    fetched: Sender<Fetched>,
+) {
+    let mut last_fetch = match last_fetch {
+        Some(last_fetch) => last_fetch,
+        None => end_of_log(&url, &polling).await,
+    };
+    // Starting from scratch we download the server's snapshot first, that's a single compressed
+    // response instead of the whole history as JSON. Old servers don't have snapshots, then we
+    // just fetch everything. Snapshots come without receipts, with the server's receipt key we
+    // only take one that its signed tree head covers and otherwise fetch everything as well.
+    let snapshot = match last_fetch {
+        0 => fetch_snapshot(&polling.http, &url).await.ok(),
+        _ => None,
+    };
+    let snapshot = match (snapshot, &server_key) {
+        (Some(msgs), Some((key, provider))) => {
+            let covered = snapshot_covered(&polling.http, &url, key, provider, &msgs).await;
+            Some(msgs).filter(|_| covered.unwrap_or(false))
+        }
+        (snapshot, _) => snapshot,
+    };
+    if let Some(msgs) = snapshot {
+        last_fetch = msgs.len();
+        let sizes = ciphertext_sizes(&msgs);
+        let (payloads, failures) = decrypt_messages(msgs, keys.clone()).await;
+        polling
+            .debug
+            .decryption(&url, keys.room(), &payloads, &failures);
+        if server == 0 {
+            observe_traffic(&polling.traffic, &sizes, &payloads);
+        }
+        let batch = Fetched {
+            server,
+            read_position: last_fetch,
+            payloads,
+            failures,
+            receipts: Vec::new(),
+            invalid_receipts: 0,
+            missed: 0,
+        };
+        if fetched.send(batch).await.is_err() {
+            return;
+        }
+    }
+
+    let mut interval = polling.interval.clone();
+    let mut fetch_timer = tokio::time::interval(*interval.borrow());
+    loop {
+        select! {
+            _ = fetch_timer.tick() => {}
+            // A new priority applies right away, starting with a fetch
+            Ok(()) = interval.changed() => {
+                fetch_timer = tokio::time::interval(*interval.borrow());
+                continue;
+            }
+        }
+        faults::delay().await;
+        if faults::dropped() {
+            continue;
+        }
+        let started = Instant::now();
+        let response = polling
+            .transport
+            .fetch(&url, last_fetch, polling.pad, server_key.is_some())
+            .await;
+        let (mut msgs, receipts, missed) = match response {
+            Ok(batch) => {
+                // The server no longer has the messages up to the gap, they were pruned
+                let missed = batch.gap.map_or(0, |gap| gap.pruned);
+                if let Some(gap) = batch.gap {
+                    last_fetch = gap.first_available;
+                }
+                (batch.messages, batch.receipts, missed)
+            }
+            Err(e) => {
+                polling.debug.record(DebugEvent::FetchFailed {
+                    server: url.clone(),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        polling.debug.record(match msgs.len() {
+            0 => DebugEvent::NothingNew {
+                server: url.clone(),
+                fetches: 1,
+            },
+            messages => DebugEvent::Fetched {
+                server: url.clone(),
+                messages,
+                latency: started.elapsed(),
+            },
+        });
+        let (receipts, invalid_receipts) = match &server_key {
+            Some((key, provider)) => check_receipts(&msgs, receipts, last_fetch, key, provider),
+            None => (Vec::new(), 0),
+        };
+        last_fetch += msgs.len();
+        // Only after checking the receipts, they follow the server's order
+        faults::reorder(&mut msgs);
+        let sizes = ciphertext_sizes(&msgs);
+        let (payloads, failures) = decrypt_messages(msgs, keys.clone()).await;
+        polling
+            .debug
+            .decryption(&url, keys.room(), &payloads, &failures);
+        if server == 0 {
+            observe_traffic(&polling.traffic, &sizes, &payloads);
+        }
+        let batch = Fetched {
+            server,
+            read_position: last_fetch,
+            payloads,
+            failures,
+            receipts,
+            invalid_receipts,
+            missed,
+        };
+        if fetched.send(batch).await.is_err() {
+            // Main thread exited
+            return;
+        }
+    }
+}
+
+/// How often the primary server's log is audited
+const AUDIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Outcome of one audit of the server's log
+enum Audit {
+    /// The server's current tree head extends the previous one (or is the first we saw)
+    Verified(TreeHead),
+    /// The tree head wasn't signed with the server's receipt key
+    InvalidSignature,
+    /// The current tree head doesn't extend the previous one: messages were removed, reordered or
+    /// replaced since
+    Rewritten,
+}
+
+/// Check the server's signed tree head against the previous one every [`AUDIT_INTERVAL`]. Servers
+/// without a receipt key (or too old to sign tree heads) never answer, they just aren't audited.
+/// Auditing stops at the first failure, since the stored tree head isn't replaced the warning
+/// shows up again after a restart.
+async fn audit_loop(
+    http: reqwest::Client,
+    url: String,
+    provider: String,
+    key: VerifyingKey,
+    mut last: Option<TreeHead>,
+    audits: Sender<Audit>,
+) {
+    let mut audit_timer = tokio::time::interval(AUDIT_INTERVAL);
+    loop {
+        audit_timer.tick().await;
+        let audit = match audit_log(&http, &url, &provider, &key, last.as_ref()).await {
+            Ok(audit) => audit,
+            Err(_) => continue,
+        };
+        let failed = match &audit {
+            Audit::Verified(head) => {
+                last = Some(head.clone());
+                false
+            }
+            _ => true,
+        };
+        if audits.send(audit).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Fetch the server's tree head and, if it grew, the proof that it extends `last`
+async fn audit_log(
+    http: &reqwest::Client,
+    url: &str,
+    provider: &str,
+    key: &VerifyingKey,
+    last: Option<&TreeHead>,
+) -> Result<Audit, anyhow::Error> {
+    let head = fetch_tree_head(http, url).await?;
+    if !head.verify(key, provider) {
+        return Ok(Audit::InvalidSignature);
+    }
+    let last = match last {
+        Some(last) => last,
+        None => return Ok(Audit::Verified(head)),
+    };
+    // A log that shrank can't be consistent, a log of the same size has to have the same root
+    let proof: Vec<Hash> = match head.size > last.size {
+        true => {
+            http.get(format!("{}/consistency/{}/{}", url, last.size, head.size))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?
+        }
+        false => Vec::new(),
+    };
+    match merkle::verify_consistency(last.size, head.size, &last.root, &head.root, &proof) {
+        true => Ok(Audit::Verified(head)),
+        false => Ok(Audit::Rewritten),
+    }
+}
+
+async fn fetch_tree_head(http: &reqwest::Client, url: &str) -> Result<TreeHead, anyhow::Error> {
+    Ok(http
+        .get(format!("{}/tree-head", url))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Whether the snapshot `msgs` is the start of the log the server's current tree head was signed
+/// for: the tree over the snapshot is the tree head's or consistent with it
+async fn snapshot_covered(
+    http: &reqwest::Client,
+    url: &str,
+    key: &VerifyingKey,
+    provider: &str,
+    msgs: &[EncryptedMessage],
+) -> Result<bool, anyhow::Error> {
+    let head = fetch_tree_head(http, url).await?;
+    let size = msgs.len() as u64;
+    if !head.verify(key, provider) || head.size < size {
+        return Ok(false);
+    }
+    let leaves = msgs
+        .iter()
+        .map(|msg| merkle::leaf_hash(&msg.digest()))
+        .collect::<Vec<_>>();
+    let proof: Vec<Hash> = match head.size > size {
+        true => {
+            http.get(format!("{}/consistency/{}/{}", url, size, head.size))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?
+        }
+        false => Vec::new(),
+    };
+    let root = merkle::root(&leaves);
+    Ok(merkle::verify_consistency(
+        size, head.size, &root, &head.root, &proof,
+    ))
+}
+
+/// Keep the receipts that match the messages fetched starting at `first_index` and were signed by
+/// the server, returns them together with the number of messages without a valid receipt
+fn check_receipts(
+    msgs: &[EncryptedMessage],
+    receipts: Vec<Receipt>,
+    first_index: usize,
+    key: &VerifyingKey,
+    provider: &str,
+) -> (Vec<Receipt>, usize) {
+    let valid = msgs
+        .iter()
+        .zip(receipts)
+        .enumerate()
+        .filter(|(idx, (msg, receipt))| {
+            receipt.index == (first_index + idx) as u64
+                && receipt.digest == msg.digest()
+                && receipt.verify(key, provider)
+        })
+        .map(|(_, (_, receipt))| receipt)
+        .collect::<Vec<_>>();
+    let invalid = msgs.len() - valid.len();
+    (valid, invalid)
+}
+
+/// Ciphertext sizes of fetched messages by digest, the ones that decrypt go into the room's
+/// traffic statistics
+fn ciphertext_sizes(msgs: &[EncryptedMessage]) -> HashMap<[u8; 32], usize> {
+    msgs.iter().map(|msg| (msg.digest(), msg.len())).collect()
+}
+
+fn observe_traffic(
+    traffic: &Mutex<Traffic>,
+    sizes: &HashMap<[u8; 32], usize>,
+    payloads: &[([u8; 32], Payload)],
+) {
+    let mut traffic = traffic.lock().unwrap();
+    for (digest, _) in payloads {
+        if let Some(size) = sizes.get(digest) {
+            traffic.observe(*size);
+        }
+    }
+}
+
+/// Payloads we could decrypt and why the others failed, each with the digest of its ciphertext
+type Decrypted = (Vec<([u8; 32], Payload)>, Vec<([u8; 32], Undecryptable)>);
+
+/// Trial-decrypt messages on tokio's blocking thread pool. Batches are decrypted in parallel but
+/// the results are returned in the order the messages were received by the server, together with
+/// the digests of the ciphertexts.
+async fn decrypt_messages(msgs: Vec<EncryptedMessage>, keys: Arc<RoomKeys>) -> Decrypted {
+    let mut batches = Vec::new();
+    let mut msgs = msgs.into_iter().peekable();
+    while msgs.peek().is_some() {
+        let batch = msgs.by_ref().take(DECRYPT_BATCH_SIZE).collect::<Vec<_>>();
+        let keys = keys.clone();
+        batches.push(tokio::task::spawn_blocking(move || {
+            batch
+                .into_iter()
+                .map(|msg| {
+                    let digest = msg.digest();
+                    keys.try_decrypt(msg)
+                        .map(|payload| (digest, payload))
+                        .map_err(|reason| (digest, reason))
+                })
+                .collect::<Vec<_>>()
+        }));
+    }
+
+    let mut payloads = Vec::new();
+    let mut failures = Vec::new();
+    for batch in futures::future::join_all(batches).await {
+        for result in batch.expect("decryption task panicked") {
+            match result {
+                Ok(decrypted) => payloads.push(decrypted),
+                Err(failed) => failures.push(failed),
+            }
+        }
+    }
+    (payloads, failures)
+}
+
+/// Store the chain position we moved forward to in place of the old one, in the history and in
+/// the profile's invites of the room, and keep older ones from being used again
+fn store_chain(
+    history: &History,
+    profile: Option<&mut SavedProfile>,
+    room: &Key,
+    chain: &ChainKey,
+) -> Result<(), anyhow::Error> {
+    history.set_chain(chain)?;
+    history.set_chain_floor(chain.epoch())?;
+    if let Some(saved) = profile {
+        if saved.profile.forward_chain(&room.id(), chain) {
+            saved.save()?;
+        }
+    }
+    Ok(())
+}
+
+/// Feature a command counts towards in usage beacons, only the command's name is looked at
+fn command_feature(cmd: &str) -> Feature {
+    match cmd.split_whitespace().next().unwrap_or_default() {
+        "/invite" => Feature::Invite,
+        "/poll" | "/polls" | "/vote" => Feature::Poll,
+        "/schedule" | "/unschedule" => Feature::Schedule,
+        "/burn" => Feature::BurnAfterReading,
+        "/export-archive" | "/import-archive" => Feature::Archive,
+        _ => Feature::Command,
+    }
+}
+
+/// Name a payload claims to be from, for notices about payloads that were dropped
+fn claimed_sender(payload: &Payload) -> String {
+    match payload {
+        Payload::Poll(poll) => poll.sender.clone(),
+        Payload::Vote(vote) => vote.voter.clone(),
+        Payload::Contact(card) => card.name.clone(),
+        Payload::Custom(custom) => custom.sender.clone(),
+        Payload::Message(msg) | Payload::BurnAfterReading(msg) => msg.sender.clone(),
+        _ => "?".to_owned(),
+    }
+}
+
+/// Sender of a message whose author we can't verify, marked so it can't pass as a verified one
+fn unverified(claimed: &str) -> String {
+    tr_args(
+        "sender-unverified",
+        &[("sender", claimed_name(claimed).into())],
+    )
+}
+
+/// Local date and time of a unix timestamp, for telling the user when scheduled messages go out
+fn local_time(unix: u64) -> String {
+    chrono::Local
+        .timestamp_opt(unix as i64, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// The current local time, from the deterministic clock if the run is seeded
+fn local_now() -> chrono::DateTime<chrono::Local> {
+    chrono::Local
+        .timestamp_opt(determinism::current() as i64, 0)
+        .single()
+        .unwrap_or_else(chrono::Local::now)
+}
+
+/// Load the profile from `data_dir` and pick a room, incognito sessions get a throwaway profile
+async fn load_profile(
+    data_dir: Option<&Path>,
+    http: &reqwest::Client,
+    direct: Option<(String, Key)>,
+) -> Result<(SavedProfile, Invite), anyhow::Error> {
+    match data_dir {
+        Some(data_dir) => onboarding::run(data_dir, http, direct).await,
+        None => onboarding::incognito(http, direct).await,
+    }
+}
+
+/// Where the history is kept if not configured otherwise: `~/.local/share/nym-chat` on Linux,
+/// `~/Library/Application Support/nym-chat` on macOS and `%APPDATA%\nym-chat\data` on Windows.
+/// Older versions always used `~/.nym-chat`, if that exists it's still used.
+fn default_data_dir() -> PathBuf {
+    let legacy = directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".nym-chat"));
+    match legacy {
+        Some(legacy) if legacy.is_dir() => legacy,
+        _ => directories::ProjectDirs::from("", "", "nym-chat")
+            .map(|dirs| dirs.data_dir().to_owned())
+            .unwrap_or_else(|| PathBuf::from(".nym-chat")),
+    }
+}
+
+/// Read the announcements of a directory room from the server at `base_url` and return the
+/// announced servers that aren't in `known` yet. The room is read from the start every time, so
+/// withdrawn servers disappear after a restart. Without a reachable directory we just have fewer
+/// mirrors.
+async fn bootstrap_mirrors(
+    http: &reqwest::Client,
+    base_url: &str,
+    directory: &Key,
+    pad: bool,
+    known: &[Server],
+) -> Vec<Server> {
+    let msgs = match transport::fetch(http, base_url, 0, pad, false).await {
+        Ok(batch) => batch.messages,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                tr_args("directory-unreachable", &[("error", e.to_string().into())])
+            );
+            return Vec::new();
+        }
+    };
+    let announcements = decrypt_messages(msgs, Arc::new(RoomKeys::new(directory.clone(), None)))
+        .await
+        .0
+        .into_iter()
+        .map(|(_, payload)| match payload {
+            Payload::Counted(counted) => *counted.payload,
+            payload => payload,
+        })
+        // Only the directory's key is known here, so signatures of broadcast directories can't be
+        // checked and the announcements are taken as they are
+        .filter_map(|payload| match payload {
+            Payload::Announcement(announcement) => Some(announcement),
+            Payload::SignedPayload(signed) => match *signed.payload {
+                Payload::Announcement(announcement) => Some(announcement),
+                _ => None,
+            },
+            _ => None,
+        });
+    directory::servers(announcements)
+        .into_iter()
+        .filter(|announcement| known.iter().all(|server| server.url != announcement.url))
+        .filter_map(|announcement| {
+            let service_provider =
+                Recipient::try_from_base58_string(&announcement.service_provider).ok()?;
+            Some(Server {
+                url: announcement.url,
+                service_provider,
+            })
+        })
+        .collect()
+}
+
+/// How long a new device waits for the device sync bundle of its link
+const LINK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Poll the server until the device sync bundle encrypted to `one_time_key` shows up, looking from
+/// the index `from` the link names. It may take a while since it has to travel through the
+/// mixnet first, after [`LINK_TIMEOUT`] we give up and say why.
+async fn wait_for_device_sync(
+    http: &reqwest::Client,
+    base_url: &str,
+    one_time_key: &Key,
+    from: usize,
+    interval: Duration,
+    pad: bool,
+) -> Result<DeviceSync, Fatal> {
+    eprintln!("{}", tr("device-sync-waiting"));
+    let deadline = Instant::now() + LINK_TIMEOUT;
+    let mut last_fetch = from;
+    let mut last_error = None;
+    while Instant::now() < deadline {
+        match transport::fetch(http, base_url, last_fetch, pad, false).await {
+            Ok(batch) => {
+                if let Some(gap) = batch.gap {
+                    last_fetch = gap.first_available;
+                }
+                last_fetch += batch.messages.len();
+                for msg in batch.messages {
+                    if let Ok(sync) = DeviceSync::decrypt(msg, one_time_key) {
+                        return Ok(sync);
+                    }
+                }
+                last_error = None;
+            }
+            Err(e) => last_error = Some(e.to_string()),
+        }
+        tokio::time::sleep(interval).await;
+    }
+
+    let error = match last_error {
+        Some(error) => tr_args("device-sync-unreachable", &[("error", error.into())]),
+        None => tr_args(
+            "device-sync-missing",
+            &[("minutes", (LINK_TIMEOUT.as_secs() / 60).into())],
+        ),
+    };
+    Err(Fatal::new(ExitCode::Failure, error))
+}
+
+/// Read position after the last message a server has, from its snapshot and whatever arrived
+/// since. Retried every polling interval until the server answers.
+async fn end_of_log(url: &str, polling: &Polling) -> usize {
+    let start = match fetch_snapshot(&polling.http, url).await {
+        Ok(msgs) => msgs.len(),
+        Err(_) => 0,
+    };
+    loop {
+        let response = polling.transport.fetch(url, start, polling.pad, false);
+        match response.await {
+            Ok(batch) => {
+                let first = batch.gap.map_or(start, |gap| gap.first_available);
+                return first + batch.messages.len();
+            }
+            Err(e) => {
+                polling.debug.record(DebugEvent::FetchFailed {
+                    server: url.to_owned(),
+                    error: e.to_string(),
+                });
+                let interval = *polling.interval.borrow();
+                tokio::time::sleep(interval).await;
+            }
+        }
+    }
+}
+
+/// Download the server's latest snapshot of all messages, see the snapshot module of the library
+async fn fetch_snapshot(
+    http: &reqwest::Client,
+    base_url: &str,
+) -> Result<Vec<EncryptedMessage>, anyhow::Error> {
+    let snapshot = http
+        .get(format!("{}/snapshot", base_url))
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    nym_chat::snapshot::decode(&snapshot)
+}
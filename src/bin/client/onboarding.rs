@@ -0,0 +1,366 @@
+//! Starting the client without a command: the first run creates a profile with the user's name
+//! and a fresh identity key, every run then offers the rooms of the profile, joining one by its
+//! invite link or creating a new one on a server. With `--url` and `--room-key` it joins that room
+//! right away.
+//!
+//! Servers can be given by their URL alone, their service provider address is then asked from
+//! the server and cached in the profile.
+//!
+//! Incognito sessions skip the profile: they get a throwaway identity that's never saved.
+//!
+//! Linked devices (see `/link`) take over the identity and rooms of the other device's profile,
+//! restoring a backup (see `/backup`) takes over those of the backed up one.
+
+use crate::i18n::{tr, tr_args};
+use nym_addressing::clients::Recipient;
+use nym_chat::backup::Backup;
+use nym_chat::broadcast::SigningKey;
+use nym_chat::history::History;
+use nym_chat::invite::{Invite, InviteKind};
+use nym_chat::link::DeviceSync;
+use nym_chat::profile::Profile;
+use nym_chat::transport::{self, Server};
+use nym_chat::Key;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Passphrases can be passed in the environment instead of typing them every time
+const PASSPHRASE_VAR: &str = "NYM_CHAT_PASSPHRASE";
+
+/// The loaded profile together with what's needed to save it again
+pub struct SavedProfile {
+    pub profile: Profile,
+    /// Missing for throwaway profiles, saving them does nothing
+    path: Option<PathBuf>,
+    passphrase: String,
+}
+
+impl SavedProfile {
+    /// A profile of an incognito session, it's gone once the client exits
+    pub fn throwaway(profile: Profile) -> SavedProfile {
+        SavedProfile {
+            profile,
+            path: None,
+            passphrase: String::new(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        match &self.path {
+            Some(path) => self.profile.save(path, &self.passphrase),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Walk the user through loading or creating the profile in `data_dir` and picking a room, unless
+/// `direct` names the server URL and key of the room to join
+pub async fn run(
+    data_dir: &Path,
+    http: &reqwest::Client,
+    direct: Option<(String, Key)>,
+) -> Result<(SavedProfile, Invite), anyhow::Error> {
+    let path = data_dir.join("profile");
+    let (mut profile, passphrase) = match path.exists() {
+        true => {
+            let passphrase = passphrase("onboarding-passphrase")?;
+            let profile = Profile::load(&path, &passphrase)?
+                .ok_or_else(|| anyhow::Error::msg("the profile disappeared"))?;
+            (profile, passphrase)
+        }
+        false => {
+            println!("{}", tr("onboarding-welcome"));
+            let name = ask_name()?;
+            let passphrase = passphrase("onboarding-new-passphrase")?;
+            let profile = Profile::new(name);
+            profile.save(&path, &passphrase)?;
+            let fingerprint = profile.identity().verifying_key().fingerprint();
+            println!(
+                "{}",
+                tr_args("onboarding-created", &[("fingerprint", fingerprint.into())])
+            );
+            (profile, passphrase)
+        }
+    };
+
+    let providers = profile.providers.len();
+    let invite = match direct {
+        Some((url, key)) => {
+            // A room of the profile keeps its name and everything else its invite carried
+            let known = profile
+                .rooms
+                .iter()
+                .filter_map(|invite| Invite::from_str(invite).ok())
+                .find(|invite| invite.server == url && invite.key.as_ref() == Some(&key));
+            match known {
+                Some(invite) => invite,
+                None => {
+                    let service_provider = provider_address(&mut profile, http, &url).await?;
+                    let server = Server {
+                        url,
+                        service_provider,
+                    };
+                    room_invite(server, key, None)
+                }
+            }
+        }
+        None => choose_room(&mut profile, http).await?,
+    };
+    if profile.add_room(invite.to_string()) || profile.providers.len() > providers {
+        profile.save(&path, &passphrase)?;
+    }
+    let saved = SavedProfile {
+        profile,
+        path: Some(path),
+        passphrase,
+    };
+    Ok((saved, invite))
+}
+
+/// Like [`run`] for `--incognito`: the identity is generated for this session and neither it nor
+/// the room are saved anywhere
+pub async fn incognito(
+    http: &reqwest::Client,
+    direct: Option<(String, Key)>,
+) -> Result<(SavedProfile, Invite), anyhow::Error> {
+    let mut profile = Profile::new(ask_name()?);
+    let fingerprint = profile.identity().verifying_key().fingerprint();
+    println!(
+        "{}",
+        tr_args(
+            "onboarding-incognito",
+            &[("fingerprint", fingerprint.into())]
+        )
+    );
+    let invite = match direct {
+        Some((url, key)) => {
+            let service_provider = provider_address(&mut profile, http, &url).await?;
+            let server = Server {
+                url,
+                service_provider,
+            };
+            room_invite(server, key, None)
+        }
+        None => choose_room(&mut profile, http).await?,
+    };
+    Ok((SavedProfile::throwaway(profile), invite))
+}
+
+/// Take over the profile of the device that sent `sync`: its identity, its rooms and how far they
+/// were read. An existing profile in `data_dir` keeps its name and other rooms. Bundles of devices
+/// without a profile leave ours alone, `None` then means there is none to use.
+pub fn link(
+    data_dir: Option<&Path>,
+    sync: &DeviceSync,
+) -> Result<Option<SavedProfile>, anyhow::Error> {
+    let identity = match &sync.identity {
+        Some(identity) => SigningKey::from_str(identity)?,
+        None => return Ok(None),
+    };
+    let saved = adopt(data_dir, &sync.name, Some(identity), &sync.rooms)?;
+
+    // The other device's history stays there, but we don't fetch what it read already
+    let data_dir = match data_dir {
+        Some(data_dir) => data_dir,
+        None => return Ok(Some(saved)),
+    };
+    for invite in sync
+        .rooms
+        .iter()
+        .filter_map(|invite| Invite::from_str(invite).ok())
+    {
+        let key = match invite.key {
+            Some(key) => key,
+            None => continue,
+        };
+        let position = match sync.read_positions.get(&key.id()) {
+            Some(&position) => position,
+            None => continue,
+        };
+        if History::stored_read_position(data_dir, &key).unwrap_or(0) < position {
+            History::open(data_dir, &key)?.set_read_position(position)?;
+        }
+    }
+    Ok(Some(saved))
+}
+
+/// Restore the profile in `data_dir` from `backup`, see `/backup`. Like with [`link`] an existing
+/// profile keeps its name and other rooms.
+pub fn restore(data_dir: &Path, backup: &Backup) -> Result<SavedProfile, anyhow::Error> {
+    let identity = match &backup.identity {
+        Some(identity) => Some(SigningKey::from_str(identity)?),
+        None => None,
+    };
+    adopt(Some(data_dir), &backup.name, identity, &backup.rooms)
+}
+
+/// Add `rooms` to the profile in `data_dir`, which is created with `name` if there is none, and
+/// make it present `identity`. Without a data directory the profile is a throwaway one.
+fn adopt(
+    data_dir: Option<&Path>,
+    name: &str,
+    identity: Option<SigningKey>,
+    rooms: &[String],
+) -> Result<SavedProfile, anyhow::Error> {
+    let path = data_dir.map(|data_dir| data_dir.join("profile"));
+    let (mut profile, passphrase) = match &path {
+        Some(path) if path.exists() => {
+            let passphrase = passphrase("onboarding-passphrase")?;
+            let profile = Profile::load(path, &passphrase)?
+                .ok_or_else(|| anyhow::Error::msg("the profile disappeared"))?;
+            (profile, passphrase)
+        }
+        Some(_) => (
+            Profile::new(name.to_owned()),
+            passphrase("onboarding-new-passphrase")?,
+        ),
+        None => (Profile::new(name.to_owned()), String::new()),
+    };
+    if let Some(identity) = identity {
+        profile.set_identity(identity);
+    }
+    for invite in rooms {
+        profile.add_room(invite.clone());
+    }
+    let saved = SavedProfile {
+        profile,
+        path,
+        passphrase,
+    };
+    saved.save()?;
+    Ok(saved)
+}
+
+fn ask_name() -> Result<String, anyhow::Error> {
+    loop {
+        let name = prompt(&tr("onboarding-name"))?;
+        if !name.is_empty() {
+            return Ok(name);
+        }
+    }
+}
+
+/// Ask until the user picks a known room, pastes an invite or names a server for a new room
+async fn choose_room(
+    profile: &mut Profile,
+    http: &reqwest::Client,
+) -> Result<Invite, anyhow::Error> {
+    let mut rooms = profile
+        .rooms
+        .iter()
+        .filter_map(|invite| Invite::from_str(invite).ok())
+        .collect::<Vec<_>>();
+    for (idx, room) in rooms.iter().enumerate() {
+        let name = room
+            .room_name
+            .clone()
+            .unwrap_or_else(|| room.server.clone());
+        println!("  {}) {}", idx + 1, name);
+    }
+    loop {
+        let answer = prompt(&tr("onboarding-room"))?;
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=rooms.len()).contains(&number) => {
+                return Ok(rooms.swap_remove(number - 1))
+            }
+            _ => {}
+        }
+        if let Ok(invite) = Invite::from_str(&answer) {
+            if invite.kind == InviteKind::Join && invite.key.is_some() {
+                return Ok(invite);
+            }
+        }
+        let server = match Server::from_str(&answer) {
+            Ok(server) => Some(server),
+            Err(_) if answer.starts_with("http://") || answer.starts_with("https://") => {
+                match provider_address(profile, http, &answer).await {
+                    Ok(service_provider) => Some(Server {
+                        url: answer.clone(),
+                        service_provider,
+                    }),
+                    Err(e) => {
+                        let error = e.to_string().into();
+                        println!(
+                            "{}",
+                            tr_args("onboarding-provider-failed", &[("error", error)])
+                        );
+                        continue;
+                    }
+                }
+            }
+            Err(_) => None,
+        };
+        if let Some(server) = server {
+            let room_name = prompt(&tr("onboarding-room-name"))?;
+            let room_name = Some(room_name).filter(|name| !name.is_empty());
+            let invite = room_invite(server, Key::generate(), room_name);
+            println!(
+                "{}",
+                tr_args(
+                    "onboarding-room-created",
+                    &[("invite", invite.to_string().into())]
+                )
+            );
+            return Ok(invite);
+        }
+        println!("{}", tr("onboarding-invalid"));
+    }
+}
+
+/// Invite to the room with `key` on `server`
+fn room_invite(server: Server, key: Key, room_name: Option<String>) -> Invite {
+    Invite {
+        kind: InviteKind::Join,
+        server: server.url,
+        service_provider: server.service_provider,
+        key: Some(key),
+        hint: None,
+        room_name,
+        max_age: None,
+        broadcast: None,
+        secret: None,
+        report: None,
+        chain: None,
+        from: None,
+    }
+}
+
+/// Service provider address of the server at `url`, from the profile's cache or asked from the
+/// server (and then cached)
+async fn provider_address(
+    profile: &mut Profile,
+    http: &reqwest::Client,
+    url: &str,
+) -> Result<Recipient, anyhow::Error> {
+    let cached = profile
+        .providers
+        .get(url)
+        .and_then(|address| Recipient::try_from_base58_string(address).ok());
+    if let Some(address) = cached {
+        return Ok(address);
+    }
+    let address = transport::provider_address(http, url).await?;
+    profile
+        .providers
+        .insert(url.to_owned(), address.to_string());
+    Ok(address)
+}
+
+fn passphrase(prompt_id: &str) -> Result<String, anyhow::Error> {
+    match std::env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => Ok(passphrase),
+        Err(_) => prompt(&tr(prompt_id)),
+    }
+}
+
+/// Print `text` and read a line from stdin, without the line break
+fn prompt(text: &str) -> Result<String, anyhow::Error> {
+    print!("{} ", text);
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    if std::io::stdin().lock().read_line(&mut line)? == 0 {
+        anyhow::bail!("no more input");
+    }
+    Ok(line.trim().to_owned())
+}
@@ -0,0 +1,57 @@
+//! Example plugin: replaces codes like `:shrug:` in sent messages by the emoticon, `/emoticons`
+//! lists them. More codes can be configured, e.g. `--plugin emoticons:wave=o/,cheers=\o/`.
+
+use super::Plugin;
+use crate::events::UiSender;
+
+const DEFAULT: &[(&str, &str)] = &[
+    ("shrug", r"¯\_(ツ)_/¯"),
+    ("tableflip", "(╯°□°)╯︵ ┻━┻"),
+    ("unflip", "┬─┬ノ( º _ ºノ)"),
+    ("lenny", "( ͡° ͜ʖ ͡°)"),
+];
+
+pub struct Emoticons {
+    codes: Vec<(String, String)>,
+}
+
+impl Emoticons {
+    pub fn new(config: &str) -> Result<Box<dyn Plugin>, anyhow::Error> {
+        let mut codes = DEFAULT
+            .iter()
+            .map(|(code, emoticon)| (code.to_string(), emoticon.to_string()))
+            .collect::<Vec<_>>();
+        for pair in config.split(',').filter(|pair| !pair.is_empty()) {
+            let (code, emoticon) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("expected <code>=<emoticon>, got {}", pair))?;
+            codes.push((code.to_owned(), emoticon.to_owned()));
+        }
+        Ok(Box::new(Emoticons { codes }))
+    }
+}
+
+impl Plugin for Emoticons {
+    fn name(&self) -> &'static str {
+        "emoticons"
+    }
+
+    fn commands(&self) -> &[&'static str] {
+        &["/emoticons"]
+    }
+
+    fn outgoing(&mut self, mut text: String) -> Option<String> {
+        for (code, emoticon) in &self.codes {
+            text = text.replace(&format!(":{}:", code), emoticon);
+        }
+        Some(text)
+    }
+
+    fn command(&mut self, _cmd: &str, _ui: &UiSender) -> String {
+        self.codes
+            .iter()
+            .map(|(code, emoticon)| format!(":{}: {}", code, emoticon))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,125 @@
+//! Plugins extend the client without touching the main loop: they observe incoming messages,
+//! rewrite what the user sends and bring their own slash commands. They are compiled in and
+//! enabled per run with `--plugin <name>[:<config>]`, the config string is handed to the plugin
+//! as is. To add one, implement [`Plugin`] in a module next to this one and list it in
+//! [`BUILTIN`].
+//!
+//! Hooks run on the main loop, so they must return quickly. Plugins that need to wait for
+//! something (e.g. a web service) spawn a task and report back through the [`UiSender`].
+
+use crate::events::UiSender;
+use crate::i18n::tr_args;
+use nym_chat::Message;
+
+mod emoticons;
+mod translate;
+
+/// Plugins that can be enabled, by name
+const BUILTIN: &[(&str, Constructor)] = &[
+    ("emoticons", emoticons::Emoticons::new),
+    ("translate", translate::Translate::new),
+];
+
+type Constructor = fn(&str) -> Result<Box<dyn Plugin>, anyhow::Error>;
+
+pub trait Plugin: Send {
+    fn name(&self) -> &'static str;
+
+    /// Slash commands the plugin handles, including the slash. They take precedence over the
+    /// client's own commands, so better pick names the client doesn't use.
+    fn commands(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// A message arrived and was stored in the history at `index`
+    fn incoming(&mut self, _index: usize, _msg: &Message, _ui: &UiSender) {}
+
+    /// The user is about to send `text`, returns what to send instead or `None` to send nothing
+    fn outgoing(&mut self, text: String) -> Option<String> {
+        Some(text)
+    }
+
+    /// One of the plugin's commands was entered (the whole line), returns the reply to show
+    fn command(&mut self, _cmd: &str, _ui: &UiSender) -> String {
+        String::new()
+    }
+}
+
+/// The enabled plugins, hooks run in the order the plugins were given on the command line
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Plugins {
+    /// Enable the plugins in `specs`, each `<name>` or `<name>:<config>`
+    pub fn load(specs: &[String]) -> Result<Plugins, anyhow::Error> {
+        let mut plugins = Vec::new();
+        for spec in specs {
+            let (name, config) = spec.split_once(':').unwrap_or((spec, ""));
+            let constructor = BUILTIN
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, constructor)| constructor)
+                .ok_or_else(|| {
+                    let known = BUILTIN.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                    anyhow::Error::msg(tr_args(
+                        "plugin-unknown",
+                        &[("name", name.into()), ("known", known.join(", ").into())],
+                    ))
+                })?;
+            plugins.push(constructor(config).map_err(|e| {
+                anyhow::Error::msg(tr_args(
+                    "plugin-config-invalid",
+                    &[("name", name.into()), ("error", e.to_string().into())],
+                ))
+            })?);
+        }
+        Ok(Plugins { plugins })
+    }
+
+    pub fn incoming(&mut self, index: usize, msg: &Message, ui: &UiSender) {
+        for plugin in &mut self.plugins {
+            plugin.incoming(index, msg, ui);
+        }
+    }
+
+    /// Pass `text` through all plugins, `None` if one of them swallowed it
+    pub fn outgoing(&mut self, text: String) -> Option<String> {
+        self.plugins
+            .iter_mut()
+            .try_fold(text, |text, plugin| plugin.outgoing(text))
+    }
+
+    /// Whether one of the plugins owns the command
+    pub fn handles(&self, cmd: &str) -> bool {
+        self.owner(cmd).is_some()
+    }
+
+    /// Let the plugin owning the command handle it
+    pub fn command(&mut self, cmd: &str, ui: &UiSender) -> String {
+        match self.owner(cmd) {
+            Some(idx) => self.plugins[idx].command(cmd, ui),
+            None => tr_args("unknown-command", &[("command", cmd.into())]),
+        }
+    }
+
+    fn owner(&self, cmd: &str) -> Option<usize> {
+        let name = cmd.split_whitespace().next().unwrap_or_default();
+        self.plugins
+            .iter()
+            .position(|plugin| plugin.commands().contains(&name))
+    }
+
+    /// Names of the enabled plugins and their commands, for `/plugins`
+    pub fn describe(&self) -> Vec<String> {
+        self.plugins
+            .iter()
+            .map(|plugin| format!("{} {}", plugin.name(), plugin.commands().join(" ")))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+}
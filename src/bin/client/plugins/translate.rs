@@ -0,0 +1,126 @@
+//! Translates incoming messages with a LibreTranslate compatible service and shows the translation
+//! beneath the message, e.g. `--plugin translate:en` or `--plugin translate:de,http://host:5000`.
+//! The service gets to read every message of the room in plain text, so only use one running on
+//! your own machine. `/translate off` pauses translating, `/translate on` resumes.
+//!
+//! Messages are translated one after another by a background task, so catching up on a busy room
+//! doesn't flood the service.
+
+use super::Plugin;
+use crate::events::{UiEvent, UiSender};
+use crate::i18n::{tr, tr_args};
+use nym_chat::Message;
+use serde::Deserialize;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+const DEFAULT_URL: &str = "http://127.0.0.1:5000";
+
+pub struct Translate {
+    target: String,
+    paused: bool,
+    jobs: UnboundedSender<(usize, String, UiSender)>,
+}
+
+#[derive(Deserialize)]
+struct Translation {
+    #[serde(rename = "translatedText")]
+    text: String,
+    #[serde(rename = "detectedLanguage")]
+    detected: Option<Detected>,
+}
+
+#[derive(Deserialize)]
+struct Detected {
+    language: String,
+}
+
+impl Translate {
+    /// `config` is the language to translate to, optionally followed by the service's URL
+    pub fn new(config: &str) -> Result<Box<dyn Plugin>, anyhow::Error> {
+        let (target, url) = config.split_once(',').unwrap_or((config, DEFAULT_URL));
+        if target.is_empty() {
+            anyhow::bail!("expected <language>[,<url>], e.g. en,{}", DEFAULT_URL);
+        }
+        let url = url::Url::parse(url)?.join("translate")?;
+        let (jobs, queue) = unbounded_channel();
+        tokio::spawn(translate(url, target.to_owned(), queue));
+        Ok(Box::new(Translate {
+            target: target.to_owned(),
+            paused: false,
+            jobs,
+        }))
+    }
+}
+
+impl Plugin for Translate {
+    fn name(&self) -> &'static str {
+        "translate"
+    }
+
+    fn commands(&self) -> &[&'static str] {
+        &["/translate"]
+    }
+
+    fn incoming(&mut self, index: usize, msg: &Message, ui: &UiSender) {
+        if !self.paused && !msg.msg.trim().is_empty() {
+            let _ = self.jobs.send((index, msg.msg.clone(), ui.clone()));
+        }
+    }
+
+    fn command(&mut self, cmd: &str, _ui: &UiSender) -> String {
+        match cmd["/translate".len()..].trim() {
+            "on" => self.paused = false,
+            "off" => self.paused = true,
+            "" => {}
+            _ => return tr("translate-usage"),
+        }
+        match self.paused {
+            true => tr("translate-paused"),
+            false => tr_args(
+                "translate-active",
+                &[("language", self.target.clone().into())],
+            ),
+        }
+    }
+}
+
+/// Work through the queue until the plugin is gone. Failures are reported once until the service
+/// works again, otherwise an unreachable service would add a notice to every message.
+async fn translate(
+    url: url::Url,
+    target: String,
+    mut queue: UnboundedReceiver<(usize, String, UiSender)>,
+) {
+    let http = reqwest::Client::new();
+    let mut failing = false;
+    while let Some((index, text, ui)) = queue.recv().await {
+        let request = serde_json::json!({
+            "q": text,
+            "source": "auto",
+            "target": target,
+            "format": "text",
+        });
+        let result = async {
+            let response = http.post(url.clone()).json(&request).send().await?;
+            response.error_for_status()?.json::<Translation>().await
+        }
+        .await;
+        match result {
+            Ok(translation) => {
+                failing = false;
+                let same_language = translation
+                    .detected
+                    .map_or(false, |detected| detected.language == target);
+                if !same_language && translation.text.trim() != text.trim() {
+                    let _ = ui.send(UiEvent::Annotation(index, translation.text));
+                }
+            }
+            Err(e) if !failing => {
+                failing = true;
+                let notice = tr_args("translate-failed", &[("error", e.to_string().into())]);
+                let _ = ui.send(UiEvent::Notice(notice));
+            }
+            Err(_) => {}
+        }
+    }
+}
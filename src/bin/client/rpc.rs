@@ -0,0 +1,170 @@
+//! JSON-RPC 2.0 control API of the daemon, one JSON object per line on the control socket.
+//!
+//! Methods (version 1):
+//! * `version` → `{"version": 1}`
+//! * `list_rooms` → `[{"id": "<room id>", "name": "<room name or null>"}]`
+//! * `send` with `{"text": "..."}` sends a message to the room
+//! * `send_custom` with `{"type": "<type URI>", "data": "<base64>"}` sends a third-party payload
+//! * `command` with `{"command": "/invite"}` runs a client command, the output arrives as a
+//!   `notice` notification
+//! * `subscribe` replays recent messages and then streams `message` (`{"index", "sender", "msg",
+//!   "sent"}`, `sent` is a unix timestamp or null), `notice` (`{"text"}`) and `alert` (`{"index"}`,
+//!   the message deserves a notification), `custom` (`{"sender", "type", "data"}`) and `warning`
+//!   (`{"text"}`, e.g. the server rewrote its log) notifications
+//! * `join_room` with `{"invite": "nymchat://..."}`, currently every daemon serves a single room so
+//!   this always fails, start another daemon instead
+//!
+//! Breaking changes to this surface increase [`RPC_VERSION`].
+
+use crate::events::UiEvent;
+use nym_chat::custom::Custom;
+use nym_chat::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Version of the RPC surface, increased on every breaking change
+pub const RPC_VERSION: u32 = 1;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The daemon can't do what was asked for, e.g. joining a second room
+pub const UNSUPPORTED: i64 = -32000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Requests without id are notifications and don't get a response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Everything the daemon writes to the socket: responses to requests and notifications for
+/// subscribers
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Outgoing {
+    Response(Response),
+    Notification(Request),
+}
+
+impl Request {
+    pub fn new(id: Option<u64>, method: &str, params: Value) -> Request {
+        Request {
+            jsonrpc: "2.0".into(),
+            id: id.map(Value::from),
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+impl Response {
+    pub fn result(id: Value, result: Value) -> Response {
+        Response {
+            jsonrpc: "2.0".into(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Value, code: i64, message: &str) -> Response {
+        Response {
+            jsonrpc: "2.0".into(),
+            id,
+            result: None,
+            error: Some(RpcError {
+                code,
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+/// Notification streamed to subscribers for an event of the main thread
+pub fn notification(event: &UiEvent) -> Request {
+    match event {
+        UiEvent::Message(index, msg) => Request::new(
+            None,
+            "message",
+            json!({"index": index, "sender": msg.sender, "msg": msg.msg, "sent": msg.sent}),
+        ),
+        UiEvent::Notice(text) => Request::new(None, "notice", json!({ "text": text })),
+        UiEvent::Alert(index) => Request::new(None, "alert", json!({ "index": index })),
+        UiEvent::Warning(text) => Request::new(None, "warning", json!({ "text": text })),
+        UiEvent::Undecryptable(count) => {
+            Request::new(None, "undecryptable", json!({ "count": count }))
+        }
+        UiEvent::Annotation(index, text) => {
+            Request::new(None, "annotation", json!({"index": index, "text": text}))
+        }
+        UiEvent::BurnAfterReading(msg) => Request::new(
+            None,
+            "burn_after_reading",
+            json!({"sender": msg.sender, "msg": msg.msg, "sent": msg.sent}),
+        ),
+        UiEvent::Custom(custom) => Request::new(
+            None,
+            "custom",
+            json!({
+                "sender": custom.sender,
+                "type": custom.type_uri,
+                "data": base64::encode(&custom.data),
+            }),
+        ),
+    }
+}
+
+/// Inverse of [`notification`], `None` for unknown notifications
+pub fn parse_notification(notification: &Request) -> Option<UiEvent> {
+    let params = &notification.params;
+    match notification.method.as_str() {
+        "message" => Some(UiEvent::Message(
+            params["index"].as_u64()? as usize,
+            Message {
+                sender: params["sender"].as_str()?.into(),
+                msg: params["msg"].as_str()?.into(),
+                sent: params["sent"].as_u64(),
+            },
+        )),
+        "notice" => Some(UiEvent::Notice(params["text"].as_str()?.into())),
+        "alert" => Some(UiEvent::Alert(params["index"].as_u64()? as usize)),
+        "warning" => Some(UiEvent::Warning(params["text"].as_str()?.into())),
+        "undecryptable" => Some(UiEvent::Undecryptable(params["count"].as_u64()? as usize)),
+        "annotation" => Some(UiEvent::Annotation(
+            params["index"].as_u64()? as usize,
+            params["text"].as_str()?.into(),
+        )),
+        "burn_after_reading" => Some(UiEvent::BurnAfterReading(Message {
+            sender: params["sender"].as_str()?.into(),
+            msg: params["msg"].as_str()?.into(),
+            sent: params["sent"].as_u64(),
+        })),
+        "custom" => Some(UiEvent::Custom(Custom {
+            sender: params["sender"].as_str()?.into(),
+            type_uri: params["type"].as_str()?.into(),
+            data: base64::decode(params["data"].as_str()?).ok()?,
+        })),
+        _ => None,
+    }
+}
@@ -0,0 +1,202 @@
+//! State of the room the client is in that outlives single events: what the main loop and the
+//! commands (see the commands module) work with. The main loop owns the [`Session`] and hands it
+//! to the command handlers, which change it or tell the main loop what to do through an action.
+
+use crate::debug::DebugLog;
+use crate::events::{AppEvent, UiEvent, UiSender};
+use crate::i18n::{tr, tr_args};
+use crate::latency::Latency;
+use crate::onboarding::SavedProfile;
+use crate::send_queued;
+use nym_chat::address_book::AddressBook;
+use nym_chat::broadcast::{SigningKey, VerifyingKey};
+use nym_chat::counter::Counter;
+use nym_chat::epoch::RoomKeys;
+use nym_chat::health::Health;
+use nym_chat::history::{History, Priority};
+use nym_chat::identity::AuthoredMessage;
+use nym_chat::invite::Invite;
+use nym_chat::notify::Rules;
+use nym_chat::outbox::{Outbox, Queued};
+use nym_chat::padding::{self, Traffic};
+use nym_chat::poll::Polls;
+use nym_chat::presence::Roster;
+use nym_chat::report::{ModeratorKey, Reports};
+use nym_chat::schedule::Schedule;
+use nym_chat::snippet::Snippets;
+use nym_chat::transport::{Server, Transport};
+use nym_chat::{Envelope, Key, Message, Payload};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+pub struct Session<'a> {
+    pub name: String,
+    pub room: &'a Key,
+    /// Invite to the room with all we know about it, including the room's secret if we have it.
+    /// It has no chain position, commands add the one they hand out.
+    pub invite: Invite,
+    pub keys: &'a RoomKeys,
+    pub servers: &'a [Server],
+    pub history: &'a Mutex<History>,
+    /// `None` in incognito sessions
+    pub data_dir: Option<&'a Path>,
+    /// Only loaded when started without a command, see [`Session::identity`]
+    pub profile: Option<SavedProfile>,
+    /// Signs our messages if there's no profile, it only lasts for the session
+    pub session_identity: SigningKey,
+    pub moderator_key: Option<&'a ModeratorKey>,
+    /// QR codes are only drawn for sighted users
+    pub draw_qr_codes: bool,
+    /// Last message fetched from the primary server, needed when handing our state to another
+    /// device
+    pub last_fetch: usize,
+    /// Archived rooms are read-only and aren't fetched
+    pub archived: bool,
+    pub outgoing: Outgoing<'a>,
+    pub polls: Polls,
+    pub roster: Roster,
+    pub address_book: AddressBook,
+    pub notify_rules: Rules,
+    pub schedule: Schedule,
+    pub reports: Reports,
+    pub auto_reply: bool,
+    pub snippets: Snippets,
+    /// Hands messages to ourselves like the UI hands over what the user typed
+    pub scheduler: Sender<AppEvent>,
+    pub priority: Priority,
+    /// `--fetch-interval`, the priority scales it
+    pub fetch_interval: Duration,
+    /// Tells running fetches about a new interval
+    pub interval: watch::Sender<Duration>,
+    pub latency: Latency,
+    pub receipts_received: usize,
+    pub health: &'a Health,
+    pub debug_log: &'a DebugLog,
+}
+
+impl Session<'_> {
+    /// The profile's identity, or the session's one without a profile
+    pub fn identity(&self) -> &SigningKey {
+        self.profile
+            .as_ref()
+            .map(|saved| saved.profile.identity())
+            .unwrap_or(&self.session_identity)
+    }
+}
+
+/// How our messages and other payloads get to the room's servers: signed the way the room
+/// requires, numbered, padded and queued in the outbox until every server took them
+pub struct Outgoing<'a> {
+    pub room: &'a Key,
+    pub keys: &'a RoomKeys,
+    /// Posts to broadcast rooms
+    pub signing_key: Option<&'a SigningKey>,
+    /// Set in broadcast rooms, without the signing key we can only read them
+    pub broadcast_key: Option<VerifyingKey>,
+    /// Numbers everything we send with `--count-messages`
+    pub counter: Option<Counter>,
+    pub pad: padding::Policy,
+    pub traffic: &'a Mutex<Traffic>,
+    pub transport: &'a dyn Transport,
+    pub servers: &'a [Server],
+    pub history: &'a Mutex<History>,
+    pub max_age: Option<u64>,
+    pub outbox: Outbox,
+    /// Whether the primary server's receipts are checked, see `--server-key`
+    pub receipts: bool,
+    /// Digests of what we sent whose receipt didn't arrive yet
+    pub awaiting_receipts: HashSet<[u8; 32]>,
+}
+
+impl Outgoing<'_> {
+    /// Sign a message with the room's signing key or else our `identity`, `None` in broadcast
+    /// rooms we can only read
+    pub fn sign_message(&self, msg: Message, identity: &SigningKey) -> Option<Payload> {
+        match (self.signing_key, self.broadcast_key) {
+            (Some(signing_key), _) => Some(Payload::Signed(signing_key.sign(msg, self.room))),
+            (None, Some(_)) => None,
+            (None, None) => Some(Payload::Authored(AuthoredMessage::sign(
+                msg, identity, self.room,
+            ))),
+        }
+    }
+
+    /// Sign any other payload in broadcast rooms, other rooms take it as it is. `None` in broadcast
+    /// rooms we can only read.
+    pub fn sign_payload(&self, payload: Payload) -> Option<Payload> {
+        match (self.signing_key, self.broadcast_key) {
+            (_, None) => Some(payload),
+            (Some(signing_key), Some(_)) => Some(Payload::SignedPayload(
+                signing_key.sign_payload(payload, self.room),
+            )),
+            (None, Some(_)) => None,
+        }
+    }
+
+    /// Number, pad and encrypt a signed `payload`, queue it for every server and send the outbox.
+    /// `text` is what `/queue` shows for it. Returns the digest of what was sent and a notice if it
+    /// stays queued or the outbox couldn't be saved.
+    pub async fn queue(&mut self, payload: Payload, text: String) -> ([u8; 32], Option<String>) {
+        let payload = match &mut self.counter {
+            Some(counter) => counter.count(payload),
+            None => payload,
+        };
+        let buckets = self.pad.buckets(&self.traffic.lock().unwrap());
+        let msg = self.keys.encrypt_padded(&payload, &buckets);
+        let digest = msg.digest();
+        if self.receipts {
+            self.awaiting_receipts.insert(digest);
+        }
+        let queued = self.outbox.len();
+        self.outbox.push(Queued {
+            text,
+            envelope: Envelope {
+                msg,
+                max_age_secs: self.max_age,
+            },
+            servers: self
+                .servers
+                .iter()
+                .map(|server| server.url.clone())
+                .collect(),
+        });
+        let result =
+            send_queued(self.transport, self.servers, &mut self.outbox, self.history).await;
+        let notice = match result {
+            Err(e) => Some(tr_args(
+                "queue-save-failed",
+                &[("error", e.to_string().into())],
+            )),
+            Ok(()) if self.outbox.len() > queued => Some(tr_args(
+                "queue-added",
+                &[("count", self.outbox.len().into())],
+            )),
+            Ok(()) => None,
+        };
+        (digest, notice)
+    }
+
+    /// [`Outgoing::sign_payload`] and [`Outgoing::queue`] for everything but messages, returns
+    /// `reply` or why it couldn't be sent
+    pub async fn send(
+        &mut self,
+        payload: Payload,
+        text: &str,
+        reply: String,
+        ui: &UiSender,
+    ) -> String {
+        let payload = match self.sign_payload(payload) {
+            Some(payload) => payload,
+            None => return tr("broadcast-read-only"),
+        };
+        let (_, notice) = self.queue(payload, text.to_owned()).await;
+        if let Some(notice) = notice {
+            let _ = ui.send(UiEvent::Notice(notice));
+        }
+        reply
+    }
+}
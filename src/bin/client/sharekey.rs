@@ -0,0 +1,154 @@
+//! Handing a room to someone outside the room (`/sharekey` and the import-key command). The invite
+//! with the room key is encrypted to the recipient's age or OpenPGP public key and written to a
+//! file, which can then travel over any channel: mail, a chat, a USB stick. Only the recipient can
+//! turn it back into an invite.
+//!
+//! age keys are handled by the client itself, OpenPGP keys by the `gpg` binary, so they need a
+//! working GnuPG installation on both ends.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+const PGP_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+const PGP_MESSAGE: &str = "-----BEGIN PGP MESSAGE-----";
+
+enum Recipient {
+    Age(age::x25519::Recipient),
+    /// File with an ASCII armored OpenPGP public key
+    Gpg(PathBuf),
+}
+
+impl Recipient {
+    fn load(path: &Path) -> Result<Recipient, anyhow::Error> {
+        let content = std::fs::read_to_string(path)?;
+        if content.contains(PGP_PUBLIC_KEY) {
+            return Ok(Recipient::Gpg(path.to_owned()));
+        }
+        // age public key files may contain comments
+        let key = content
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("age1"))
+            .ok_or_else(|| anyhow::anyhow!("{} contains no age or OpenPGP key", path.display()))?;
+        let recipient = age::x25519::Recipient::from_str(key).map_err(anyhow::Error::msg)?;
+        Ok(Recipient::Age(recipient))
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Recipient::Age(_) => "age",
+            Recipient::Gpg(_) => "asc",
+        }
+    }
+
+    /// ASCII armored ciphertext of `plaintext`
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+        match self {
+            Recipient::Age(recipient) => {
+                let recipient = Box::new(recipient.clone()) as Box<dyn age::Recipient>;
+                let encryptor = age::Encryptor::with_recipients(vec![recipient]);
+                let mut encrypted = Vec::new();
+                let armored = age::armor::ArmoredWriter::wrap_output(
+                    &mut encrypted,
+                    age::armor::Format::AsciiArmor,
+                )?;
+                let mut writer = encryptor.wrap_output(armored)?;
+                writer.write_all(plaintext)?;
+                writer.finish()?.finish()?;
+                Ok(encrypted)
+            }
+            Recipient::Gpg(key_file) => {
+                let key_file = path_arg(key_file)?;
+                let args = [
+                    "--batch",
+                    "--armor",
+                    "--trust-model",
+                    "always",
+                    "--recipient-file",
+                    key_file,
+                    "--encrypt",
+                ];
+                gpg(&args, plaintext)
+            }
+        }
+    }
+}
+
+/// Encrypt `invite` to the public key in `recipient` and write it to `output`, by default next to
+/// the public key. Returns the path written to.
+pub fn export(
+    invite: &str,
+    recipient: &Path,
+    output: Option<&Path>,
+) -> Result<PathBuf, anyhow::Error> {
+    let key = Recipient::load(recipient)?;
+    let output = match output {
+        Some(output) => output.to_owned(),
+        None => recipient.with_extension(format!("nymchat.{}", key.extension())),
+    };
+    std::fs::write(&output, key.encrypt(invite.as_bytes())?)?;
+    Ok(output)
+}
+
+/// Decrypt a file written by [`export`], `identity` is the age identity file and not needed for
+/// OpenPGP, gpg finds the secret key itself. Returns the invite.
+pub fn import(path: &Path, identity: Option<&Path>) -> Result<String, anyhow::Error> {
+    let encrypted = std::fs::read(path)?;
+    let plaintext = match String::from_utf8_lossy(&encrypted).contains(PGP_MESSAGE) {
+        true => gpg(&["--batch", "--quiet", "--decrypt"], &encrypted)?,
+        false => {
+            let identity = identity.ok_or_else(|| anyhow::anyhow!("age files need --identity"))?;
+            decrypt_age(&encrypted, identity)?
+        }
+    };
+    Ok(String::from_utf8(plaintext)?.trim().to_owned())
+}
+
+fn decrypt_age(encrypted: &[u8], identity: &Path) -> Result<Vec<u8>, anyhow::Error> {
+    let identity = std::fs::read_to_string(identity)?;
+    let identity = identity
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("AGE-SECRET-KEY-"))
+        .ok_or_else(|| anyhow::anyhow!("the identity file contains no age secret key"))?;
+    let identity = age::x25519::Identity::from_str(identity).map_err(anyhow::Error::msg)?;
+    let decryptor = match age::Decryptor::new(age::armor::ArmoredReader::new(encrypted))? {
+        age::Decryptor::Recipients(decryptor) => decryptor,
+        _ => anyhow::bail!("the file is encrypted with a passphrase, not to a key"),
+    };
+    let mut reader = decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))?;
+    let mut decrypted = Vec::new();
+    reader.read_to_end(&mut decrypted)?;
+    Ok(decrypted)
+}
+
+/// Run gpg with `input` on stdin, returns its stdout
+fn gpg(args: &[&str], input: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut child = Command::new("gpg")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("couldn't run gpg: {}", e))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "gpg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+fn path_arg(path: &Path) -> Result<&str, anyhow::Error> {
+    path.to_str()
+        .ok_or_else(|| anyhow::anyhow!("{} isn't valid UTF-8", path.display()))
+}
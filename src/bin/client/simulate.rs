@@ -0,0 +1,83 @@
+//! Scripted participants for demos, screenshots and stress testing the UI (`--simulate <n>`). They
+//! run inside the client and post through the same path as the user: their messages are encrypted
+//! to the room, sent through the Nym client to the servers and fetched back like everyone else's.
+//! The messages are real, so better use a throwaway room.
+
+use nym_chat::{determinism, Message};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+
+const NAMES: &[&str] = &[
+    "alice", "bob", "carol", "dave", "erin", "frank", "grace", "heidi",
+];
+
+const LINES: &[&str] = &[
+    "hi everyone!",
+    "did anyone try the new mixnet release?",
+    "coffee break, back in five",
+    "the latency is surprisingly ok today",
+    "**bold** claim: privacy is a feature",
+    "has anyone seen https://nymtech.net lately?",
+    "brb",
+    "I think we should write more docs",
+    "that's a great idea",
+    "lol",
+];
+
+/// Lines addressed to another participant, so mentions show up too
+const REPLIES: &[&str] = &[
+    "{} what do you think?",
+    "agreed with {}",
+    "{} are you still around?",
+    "thanks {}!",
+];
+
+/// Time between two messages of the same participant
+const DELAY_MS: (u64, u64) = (1_000, 8_000);
+
+/// Names of `count` participants, repeating the list with a number appended if it's too short
+fn names(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|idx| match idx / NAMES.len() {
+            0 => NAMES[idx].to_owned(),
+            round => format!("{}{}", NAMES[idx % NAMES.len()], round + 1),
+        })
+        .collect()
+}
+
+/// Let `count` participants chat until the main thread stops listening
+pub async fn run(count: usize, messages: Sender<Message>) {
+    let names = names(count);
+    let participants = names.iter().map(|name| {
+        let others = names
+            .iter()
+            .filter(|other| *other != name)
+            .cloned()
+            .collect::<Vec<_>>();
+        chat(name.clone(), others, messages.clone())
+    });
+    futures::future::join_all(participants).await;
+}
+
+async fn chat(name: String, others: Vec<String>, messages: Sender<Message>) {
+    loop {
+        let delay = determinism::with_rng(|rng| rng.gen_range(DELAY_MS.0..=DELAY_MS.1));
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        let msg = Message::new(name.clone(), script_line(&others));
+        if messages.send(msg).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn script_line(others: &[String]) -> String {
+    determinism::with_rng(|mut rng| match others.choose(&mut rng) {
+        Some(other) if rng.gen_bool(0.3) => REPLIES
+            .choose(&mut rng)
+            .expect("not empty")
+            .replace("{}", &format!("@{}", other)),
+        _ => LINES.choose(&mut rng).expect("not empty").to_string(),
+    })
+}
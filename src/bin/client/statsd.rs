@@ -0,0 +1,39 @@
+//! Export of the mixnet health counters to a statsd server (`--statsd`). Every [`INTERVAL`] the
+//! problems counted since the last report are sent as counters named `<prefix>.<problem>`, e.g.
+//! `nym_chat.client.fetch_failures`, so they add up correctly over restarts of the client.
+
+use nym_chat::health::{Health, Problem};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// Open a socket sending to the statsd server at `addr` (`host:port`)
+pub async fn connect(addr: &str) -> Result<UdpSocket, anyhow::Error> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(addr).await?;
+    Ok(socket)
+}
+
+/// Report the counters of `health` until the client exits
+pub async fn export(socket: UdpSocket, prefix: String, health: Arc<Health>) {
+    let mut reported = [0; Problem::ALL.len()];
+    let mut timer = tokio::time::interval(INTERVAL);
+    loop {
+        timer.tick().await;
+        let mut packet = Vec::new();
+        for (problem, reported) in Problem::ALL.iter().zip(&mut reported) {
+            let count = health.count(*problem);
+            packet.push(format!(
+                "{}.{}:{}|c",
+                prefix,
+                problem.name(),
+                count - *reported
+            ));
+            *reported = count;
+        }
+        // Statsd is best effort, a report that doesn't arrive is just missing from the graphs
+        let _ = socket.send(packet.join("\n").as_bytes()).await;
+    }
+}
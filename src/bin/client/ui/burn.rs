@@ -0,0 +1,74 @@
+use super::{markdown, Component, Handled, State};
+use crate::i18n::{tr, tr_args};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use ratatui::Frame;
+
+enum Step {
+    /// Asking whether to show the message now
+    Confirming,
+    /// The message is on screen, closing the popup drops it
+    Revealed,
+    Done,
+}
+
+/// Popup for the oldest burn-after-reading message. It asks before showing the message, since it's
+/// gone for good once the popup is closed again.
+pub struct BurnViewer {
+    sender: String,
+    step: Step,
+}
+
+impl BurnViewer {
+    pub fn new(sender: String) -> BurnViewer {
+        BurnViewer {
+            sender,
+            step: Step::Confirming,
+        }
+    }
+
+    /// Whether the viewer is done and should be closed
+    pub fn is_done(&self) -> bool {
+        matches!(self.step, Step::Done)
+    }
+}
+
+impl Component for BurnViewer {
+    fn handle_key(&mut self, key: KeyEvent, state: &mut State) -> Handled {
+        self.step = match self.step {
+            Step::Confirming if key.code == KeyCode::Char('y') => Step::Revealed,
+            // Declining keeps the message sealed for later
+            Step::Confirming => Step::Done,
+            Step::Revealed | Step::Done => {
+                state.burn_oldest();
+                Step::Done
+            }
+        };
+        Handled::Consumed
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, state: &State) {
+        let sender = markdown::sanitize(&self.sender);
+        let (title, text) = match (&self.step, state.sealed.front()) {
+            (Step::Revealed, Some(msg)) => (
+                tr_args("burn-title", &[("sender", sender.into())]),
+                Line::from(markdown::render(&msg.msg)),
+            ),
+            _ => (
+                tr_args("burn-confirm-title", &[("sender", sender.into())]),
+                Line::raw(tr("burn-confirm")),
+            ),
+        };
+        let popup = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red))
+                .title(title),
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(popup, area);
+    }
+}
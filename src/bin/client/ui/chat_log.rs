@@ -0,0 +1,256 @@
+use super::{markdown, Component, Entry, Handled, State};
+use crate::i18n::{tr, tr_args};
+use chrono::{DateTime, Datelike, FixedOffset, Local, Offset, TimeZone, Utc};
+use crossterm::event::{KeyCode, KeyEvent};
+use nym_chat::determinism;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListDirection, ListItem};
+use ratatui::Frame;
+use std::str::FromStr;
+
+/// How the times of messages are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamps {
+    /// Only the date separators, in local time
+    Off,
+    /// Time of day in the local time zone
+    Local,
+    /// Time of day in UTC, date separators switch days at midnight UTC as well
+    Utc,
+    /// How long ago the message was sent, e.g. "5m ago", updated while the chat log is shown
+    Relative,
+}
+
+impl Default for Timestamps {
+    fn default() -> Self {
+        Timestamps::Off
+    }
+}
+
+impl FromStr for Timestamps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Timestamps::Off),
+            "local" => Ok(Timestamps::Local),
+            "utc" => Ok(Timestamps::Utc),
+            "relative" => Ok(Timestamps::Relative),
+            _ => Err("expected off, local, utc or relative".into()),
+        }
+    }
+}
+
+/// Scrollable list of messages, newest at the bottom. Consecutive messages of the same sender are
+/// grouped under one name and every day starts with a date separator.
+pub struct ChatLog {
+    /// Number of messages that fit into the chat log, updated on every render
+    page_size: usize,
+    /// Show messages as they are instead of rendering Markdown
+    raw_text: bool,
+    timestamps: Timestamps,
+}
+
+impl ChatLog {
+    pub fn new(raw_text: bool, timestamps: Timestamps) -> ChatLog {
+        ChatLog {
+            page_size: 0,
+            raw_text,
+            timestamps,
+        }
+    }
+
+    /// Whether what's shown changes with time alone, so the chat log has to be redrawn now and
+    /// then even if nothing happens
+    pub fn ages(&self) -> bool {
+        self.timestamps == Timestamps::Relative
+    }
+
+    /// Only the messages that are actually visible are formatted, so huge rooms don't slow down
+    /// redraws. If we scrolled past the messages kept in memory the rest of the page is loaded
+    /// from the local history. Rows are returned newest first.
+    fn visible_rows(&self, state: &State) -> Vec<ListItem<'static>> {
+        // One more entry than fits, the oldest visible one needs its predecessor to know whether
+        // it continues a group or starts a new day
+        let wanted = self.page_size + 1;
+        let mut entries = state
+            .entries
+            .iter()
+            .rev()
+            .skip(state.scroll)
+            .take(wanted)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let missing = wanted - entries.len();
+        if let Some(history) = state.history.as_ref().filter(|_| missing > 0) {
+            let end = state
+                .oldest_in_memory()
+                .saturating_sub(state.scroll.saturating_sub(state.entries.len()));
+            let start = end.saturating_sub(missing);
+            let older = history.lock().unwrap().load(start..end).unwrap_or_default();
+            entries.extend(
+                older
+                    .into_iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(offset, msg)| Entry::message(start + offset, msg)),
+            );
+        }
+
+        // Oldest first from here on
+        entries.reverse();
+        let context = match entries.len() > self.page_size {
+            true => Some(entries.remove(0)),
+            false => None,
+        };
+        // Days change at midnight in the time zone of the timestamps
+        let utc = self.timestamps == Timestamps::Utc;
+        let mut last_day = context.as_ref().and_then(|entry| day(entry.sent, utc));
+        let mut last_sender = context
+            .filter(|entry| !entry.is_notice())
+            .map(|entry| entry.sender);
+
+        let mut rows = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let entry_day = day(entry.sent, utc);
+            if entry_day.is_some() && entry_day != last_day {
+                rows.push(date_separator(entry.sent.unwrap_or_default(), utc));
+                last_day = entry_day;
+                last_sender = None;
+            }
+            let continued =
+                !entry.is_notice() && last_sender.as_deref() == Some(entry.sender.as_str());
+            rows.push(self.format_row(&entry, continued));
+            last_sender = match entry.is_notice() {
+                true => None,
+                false => Some(entry.sender),
+            };
+        }
+        rows.reverse();
+        rows
+    }
+
+    /// Messages continuing a group are indented instead of repeating the sender, annotations of
+    /// plugins are indented beneath the message
+    fn format_row(&self, entry: &Entry, continued: bool) -> ListItem<'static> {
+        let sender = format!("{}: ", markdown::sanitize(&entry.sender));
+        let sender_width = sender.chars().count();
+        let time = self.time(entry.sent);
+        let time_width = time.as_ref().map_or(0, |time| time.chars().count());
+        let mut spans = Vec::new();
+        if let Some(time) = time {
+            spans.push(Span::styled(
+                time,
+                Style::default().add_modifier(Modifier::DIM),
+            ));
+        }
+        spans.push(match continued {
+            true => Span::raw(" ".repeat(sender_width)),
+            false => Span::styled(sender, Style::default().add_modifier(Modifier::BOLD)),
+        });
+        if self.raw_text {
+            spans.push(Span::raw(markdown::sanitize(&entry.msg)));
+        } else {
+            spans.extend(markdown::render(&entry.msg));
+        }
+        let indent = " ".repeat(time_width + sender_width);
+        let mut lines = vec![Line::from(spans)];
+        lines.extend(entry.annotations.iter().map(|annotation| {
+            let annotation = format!("{}{}", indent, markdown::sanitize(annotation));
+            let style = Style::default().add_modifier(Modifier::DIM | Modifier::ITALIC);
+            Line::styled(annotation, style)
+        }));
+        let row = ListItem::new(lines);
+        match entry.sealed {
+            true => row.style(Style::default().fg(Color::Red)),
+            false => row,
+        }
+    }
+
+    /// The time shown in front of a message, followed by a space
+    fn time(&self, sent: Option<u64>) -> Option<String> {
+        let sent = sent?;
+        let time = match self.timestamps {
+            Timestamps::Off => return None,
+            Timestamps::Local => datetime(sent, false)?.format("%H:%M").to_string(),
+            Timestamps::Utc => datetime(sent, true)?.format("%H:%MZ").to_string(),
+            // The sender picks the timestamp, ones in the future are shown as just now
+            Timestamps::Relative => relative_time(determinism::current().saturating_sub(sent)),
+        };
+        Some(format!("{} ", time))
+    }
+}
+
+impl Component for ChatLog {
+    fn handle_key(&mut self, key: KeyEvent, state: &mut State) -> Handled {
+        match key.code {
+            KeyCode::PageUp => {
+                let max_scroll = state.total_entries().saturating_sub(1);
+                state.scroll = (state.scroll + self.page_size).min(max_scroll);
+                Handled::Consumed
+            }
+            KeyCode::PageDown => {
+                state.scroll = state.scroll.saturating_sub(self.page_size);
+                Handled::Consumed
+            }
+            _ => Handled::Ignored,
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, state: &State) {
+        // Minus the borders
+        self.page_size = area.height.saturating_sub(2) as usize;
+
+        let list = List::new(self.visible_rows(state))
+            .direction(ListDirection::BottomToTop)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(tr("chat-log-title")),
+            );
+        frame.render_widget(list, area);
+    }
+}
+
+/// How long ago something happened `secs` seconds ago, coarser the longer ago it was
+fn relative_time(secs: u64) -> String {
+    match secs {
+        secs if secs < 60 => tr("time-just-now"),
+        secs if secs < 60 * 60 => tr_args("time-minutes-ago", &[("minutes", (secs / 60).into())]),
+        secs if secs < 24 * 60 * 60 => {
+            tr_args("time-hours-ago", &[("hours", (secs / (60 * 60)).into())])
+        }
+        secs => tr_args("time-days-ago", &[("days", (secs / (24 * 60 * 60)).into())]),
+    }
+}
+
+/// Calendar day (year and day of the year) of a timestamp, in UTC or the local time zone
+fn day(sent: Option<u64>, utc: bool) -> Option<(i32, u32)> {
+    let time = datetime(sent?, utc)?;
+    Some((time.year(), time.ordinal()))
+}
+
+fn datetime(sent: u64, utc: bool) -> Option<DateTime<FixedOffset>> {
+    match utc {
+        true => Some(
+            Utc.timestamp_opt(sent as i64, 0)
+                .single()?
+                .with_timezone(&Utc.fix()),
+        ),
+        false => {
+            let time = Local.timestamp_opt(sent as i64, 0).single()?;
+            Some(time.with_timezone(time.offset()))
+        }
+    }
+}
+
+fn date_separator(sent: u64, utc: bool) -> ListItem<'static> {
+    let date = datetime(sent, utc)
+        .map(|time| time.format(&tr("date-format")).to_string())
+        .unwrap_or_default();
+    let separator = tr_args("date-separator", &[("date", date.into())]);
+    ListItem::new(Line::styled(separator, Style::default().add_modifier(Modifier::DIM)).centered())
+}
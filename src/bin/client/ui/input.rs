@@ -0,0 +1,224 @@
+use super::{Component, Handled, State};
+use crate::events::UiEvent;
+use crate::i18n::{tr, tr_args};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use nym_chat::{Message, DEFAULT_MAX_MESSAGE_BYTES, FRAGMENT_CAPACITY};
+use ratatui::layout::{Position, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Frame;
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// Room reserved for numbering the parts of a split paste, like `(12/345) `
+const PART_NUMBER_LEN: usize = 16;
+
+/// Single line text input, submits on enter. The title shows how much room is left in a single
+/// mixnet packet, messages that need several packets are only sent after a second enter.
+pub struct InputBox {
+    value: String,
+    /// Cursor position in characters (not bytes)
+    cursor: usize,
+    /// Our name, it's sent with every message so it counts towards the message size
+    sender: String,
+    /// Set after warning about a message that needs several packets, reset by any other key
+    warned: bool,
+    /// A paste that doesn't fit into one message, split into parts until the user sends (enter)
+    /// or drops (escape) them
+    pending_paste: Option<Vec<String>>,
+}
+
+impl InputBox {
+    pub fn new(sender: String) -> InputBox {
+        InputBox {
+            value: String::new(),
+            cursor: 0,
+            sender,
+            warned: false,
+            pending_paste: None,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Overwrite the input and a pending paste in memory
+    pub fn wipe(&mut self) {
+        self.value.zeroize();
+        self.cursor = 0;
+        if let Some(parts) = &mut self.pending_paste {
+            parts.iter_mut().for_each(Zeroize::zeroize);
+        }
+    }
+
+    /// Replace the input, the cursor is placed at the end
+    pub fn set_value(&mut self, value: String) {
+        self.cursor = value.chars().count();
+        self.value = value;
+    }
+
+    fn byte_index(&self) -> usize {
+        self.value
+            .char_indices()
+            .nth(self.cursor)
+            .map(|(idx, _)| idx)
+            .unwrap_or_else(|| self.value.len())
+    }
+
+    /// The message as it would be sent right now
+    fn message(&self) -> Message {
+        Message::new(self.sender.clone(), self.value.clone())
+    }
+
+    /// Commands aren't sent to the room, so their size doesn't matter
+    fn is_command(&self) -> bool {
+        self.value.starts_with('/')
+    }
+
+    pub fn has_pending_paste(&self) -> bool {
+        self.pending_paste.is_some()
+    }
+
+    /// Insert pasted text at the cursor. Several lines or more than a server accepts in one
+    /// message can't go into the input, they are offered to be sent as numbered parts instead.
+    pub fn paste(&mut self, text: String, state: &mut State) {
+        let text = text.replace('\r', "\n");
+        let lines = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        let size = Message::new(self.sender.clone(), text.clone()).encrypted_len();
+        if lines.len() <= 1 && size as u64 <= DEFAULT_MAX_MESSAGE_BYTES {
+            let line = lines.first().copied().unwrap_or_default();
+            let idx = self.byte_index();
+            self.value.insert_str(idx, line);
+            self.cursor += line.chars().count();
+            return;
+        }
+        let parts = split_paste(&self.sender, &lines);
+        state.push(UiEvent::Notice(tr_args(
+            "paste-split",
+            &[("parts", parts.len().into())],
+        )));
+        self.pending_paste = Some(parts);
+    }
+}
+
+impl Component for InputBox {
+    fn handle_key(&mut self, key: KeyEvent, state: &mut State) -> Handled {
+        if let Some(parts) = self.pending_paste.take() {
+            match key.code {
+                KeyCode::Enter => return Handled::SubmitParts(parts),
+                KeyCode::Esc => state.push(UiEvent::Notice(tr("paste-dropped"))),
+                _ => self.pending_paste = Some(parts),
+            }
+            return Handled::Consumed;
+        }
+        let warned = std::mem::take(&mut self.warned);
+        match key.code {
+            // Control combinations are shortcuts, not text
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let idx = self.byte_index();
+                self.value.insert(idx, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let idx = self.byte_index();
+                self.value.remove(idx);
+            }
+            KeyCode::Delete if self.cursor < self.value.chars().count() => {
+                let idx = self.byte_index();
+                self.value.remove(idx);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.value.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.value.chars().count(),
+            KeyCode::Enter if !self.value.is_empty() => {
+                let estimate = self.message().estimate();
+                if estimate.packets > 1 && !self.is_command() && !warned {
+                    self.warned = true;
+                    state.push(UiEvent::Notice(tr_args(
+                        "fragment-warning",
+                        &[
+                            ("packets", estimate.packets.into()),
+                            ("seconds", seconds(estimate.latency).into()),
+                        ],
+                    )));
+                    return Handled::Consumed;
+                }
+                self.cursor = 0;
+                return Handled::Submit(std::mem::take(&mut self.value));
+            }
+            KeyCode::Backspace | KeyCode::Delete | KeyCode::Enter => {}
+            _ => return Handled::Ignored,
+        }
+        Handled::Consumed
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, _state: &State) {
+        let mut title = tr("input-title");
+        let mut style = Style::default();
+        if let Some(parts) = &self.pending_paste {
+            title = tr_args("input-paste", &[("parts", parts.len().into())]);
+            style = style.fg(Color::Yellow);
+        } else if !self.value.is_empty() && !self.is_command() {
+            let estimate = self.message().estimate();
+            if estimate.packets == 1 {
+                let bytes = FRAGMENT_CAPACITY - estimate.bytes;
+                title = tr_args("input-bytes-left", &[("bytes", bytes.into())]);
+            } else {
+                title = tr_args(
+                    "input-packets",
+                    &[
+                        ("packets", estimate.packets.into()),
+                        ("seconds", seconds(estimate.latency).into()),
+                    ],
+                );
+                style = style.fg(Color::Yellow);
+            }
+        }
+
+        let input = Paragraph::new(self.value.as_str()).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(style)
+                .title(title),
+        );
+        frame.render_widget(input, area);
+        frame.set_cursor_position(Position::new(area.x + 1 + self.cursor as u16, area.y + 1));
+    }
+}
+
+/// One part per line of a paste, lines too long for a single packet are cut into several. Parts
+/// are numbered like `(2/7)` so readers notice missing or reordered ones.
+fn split_paste(sender: &str, lines: &[&str]) -> Vec<String> {
+    let empty = Message::new(sender.to_owned(), String::new()).encrypted_len();
+    let budget = FRAGMENT_CAPACITY
+        .saturating_sub(empty + PART_NUMBER_LEN)
+        .max(4);
+    let mut chunks = Vec::new();
+    for line in lines {
+        let mut chunk = String::new();
+        for c in line.chars() {
+            if chunk.len() + c.len_utf8() > budget {
+                chunks.push(std::mem::take(&mut chunk));
+            }
+            chunk.push(c);
+        }
+        chunks.push(chunk);
+    }
+    let total = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, chunk)| format!("({}/{}) {}", idx + 1, total, chunk))
+        .collect()
+}
+
+/// Latency estimates are rough, tenths of a second are plenty
+fn seconds(latency: Duration) -> String {
+    format!("{:.1}", latency.as_secs_f64())
+}
@@ -0,0 +1,49 @@
+//! Suspending with Ctrl+Z and continuing with `fg`. In raw mode the terminal doesn't turn Ctrl+Z
+//! into SIGTSTP, so the UI does what the shell expects itself: it restores the terminal, stops the
+//! process like the default handler would and sets the terminal up again once it's continued.
+//! SIGTSTP from elsewhere (e.g. `kill -TSTP`) is handled the same way. SIGCONT alone also sets the
+//! terminal up again, after `kill -STOP` (which can't be caught) the shell may have reset it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub struct JobControl {
+    stop: Arc<AtomicBool>,
+    continued: Arc<AtomicBool>,
+}
+
+impl JobControl {
+    /// Start listening for the signals, if that fails signals from elsewhere just aren't noticed
+    pub fn new() -> JobControl {
+        let stop = Arc::new(AtomicBool::new(false));
+        let continued = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        {
+            use signal_hook::consts::{SIGCONT, SIGTSTP};
+            let _ = signal_hook::flag::register(SIGTSTP, stop.clone());
+            let _ = signal_hook::flag::register(SIGCONT, continued.clone());
+        }
+        JobControl { stop, continued }
+    }
+
+    /// Ask to be stopped, like the user pressing Ctrl+Z
+    pub fn request_stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether we should stop, only true once per request
+    pub fn stop_requested(&self) -> bool {
+        self.stop.swap(false, Ordering::SeqCst)
+    }
+
+    /// Whether we were continued since the last call
+    pub fn continued(&self) -> bool {
+        self.continued.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Stop the process until it's continued, the terminal has to be restored before
+pub fn stop() {
+    #[cfg(unix)]
+    let _ = signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP);
+}
@@ -0,0 +1,92 @@
+//! Rendering of the Markdown subset supported in messages: `**bold**`, `*italics*` (or
+//! `_italics_`), `` `inline code` `` and `[links](https://example.com)`. Anything else, including
+//! unbalanced markup, is shown as is.
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Span;
+
+/// Replace control characters so messages can't mess with the terminal (e.g. escape sequences)
+pub fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+        .collect()
+}
+
+/// Turn message text into styled spans
+pub fn render(text: &str) -> Vec<Span<'static>> {
+    let text = sanitize(text);
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text.as_str();
+    // `_` only starts italics at the beginning of a word, so snake_case stays intact
+    let mut word_start = true;
+
+    while let Some(c) = rest.chars().next() {
+        if let Some((markup, consumed)) = parse_markup(rest, word_start) {
+            if !plain.is_empty() {
+                spans.push(Span::raw(std::mem::take(&mut plain)));
+            }
+            spans.extend(markup);
+            rest = &rest[consumed..];
+            word_start = false;
+            continue;
+        }
+
+        plain.push(c);
+        rest = &rest[c.len_utf8()..];
+        word_start = !c.is_alphanumeric();
+    }
+    if !plain.is_empty() {
+        spans.push(Span::raw(plain));
+    }
+
+    spans
+}
+
+/// Try to parse markup at the beginning of `text`, returns the spans and the number of bytes
+/// consumed
+fn parse_markup(text: &str, word_start: bool) -> Option<(Vec<Span<'static>>, usize)> {
+    if let Some(inner) = text.strip_prefix("**") {
+        let end = inner.find("**")?;
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        return enclosed(&inner[..end]).map(|inner| (vec![Span::styled(inner, bold)], end + 4));
+    }
+
+    let first = text.chars().next()?;
+    match first {
+        '`' => {
+            let end = text[1..].find('`')?;
+            let code = Style::default().add_modifier(Modifier::REVERSED);
+            enclosed(&text[1..end + 1]).map(|inner| (vec![Span::styled(inner, code)], end + 2))
+        }
+        '*' | '_' if first == '*' || word_start => {
+            let end = text[1..].find(first)?;
+            let italic = Style::default().add_modifier(Modifier::ITALIC);
+            enclosed(&text[1..end + 1]).map(|inner| (vec![Span::styled(inner, italic)], end + 2))
+        }
+        '[' => {
+            let text_end = text.find("](")?;
+            let url_end = text_end + 2 + text[text_end + 2..].find(')')?;
+            let label = enclosed(&text[1..text_end])?;
+            let url = &text[text_end + 2..url_end];
+            let spans = vec![
+                Span::styled(label, Style::default().add_modifier(Modifier::UNDERLINED)),
+                Span::styled(
+                    format!(" <{}>", url),
+                    Style::default().add_modifier(Modifier::DIM),
+                ),
+            ];
+            Some((spans, url_end + 1))
+        }
+        _ => None,
+    }
+}
+
+/// Markup has to enclose some text that doesn't start or end with whitespace, so `2 * 3 * 4` isn't
+/// turned into italics
+fn enclosed(inner: &str) -> Option<String> {
+    if inner.is_empty() || inner.trim() != inner {
+        return None;
+    }
+    Some(inner.to_owned())
+}
@@ -0,0 +1,568 @@
+//! Terminal UI of the client.
+//!
+//! The UI is split into a shared [`State`] (the chat log and everything else that's fed in by the
+//! main thread) and [`Component`]s that render parts of it and react to key presses. The event
+//! loop in [`run_ui`] only knows about the component trait, so adding a new widget means
+//! implementing [`Component`] and giving it a place in [`Ui::layout`].
+
+use crate::events::{AppEvent, UiChannels, UiEvent};
+use crate::i18n::{tr, tr_args};
+use nym_chat::history::History;
+use nym_chat::Message;
+use std::collections::VecDeque;
+use std::io::{stdout, Stdout};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+use zeroize::Zeroize;
+
+use crossterm::event::{
+    poll, read, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent,
+    KeyEventKind, KeyModifiers,
+};
+use crossterm::execute;
+use crossterm::style::Print;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Margin, Rect};
+use ratatui::{Frame, Terminal};
+
+mod burn;
+mod chat_log;
+mod input;
+mod job_control;
+mod markdown;
+mod screen_reader;
+mod status_bar;
+mod url_picker;
+
+use burn::BurnViewer;
+use chat_log::ChatLog;
+use input::InputBox;
+use job_control::JobControl;
+use status_bar::StatusBar;
+use url_picker::UrlPicker;
+
+pub use chat_log::Timestamps;
+pub use screen_reader::Verbosity;
+
+/// Maximum number of chat log entries kept in memory, older messages are loaded from the local
+/// history when scrolling up
+const MAX_IN_MEMORY: usize = 1000;
+
+/// Events taken from the main thread before handling keys and redrawing again
+const MAX_EVENTS_PER_ITERATION: usize = 200;
+
+/// Shortest time between two redraws (about 30 frames per second). Bursts of events, like the
+/// hundreds of messages of an initial sync, are drawn once per frame instead of once per chunk,
+/// which keeps the terminal from flickering while catching up.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// How often relative times are redrawn when nothing else changes
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Pause in typing after which the draft is saved, a crash loses at most what was typed since
+const DRAFT_DELAY: Duration = Duration::from_secs(2);
+
+/// User preferences affecting the UI
+#[derive(Clone, Copy, Default)]
+pub struct UiOptions {
+    /// Show messages as they are instead of rendering the supported Markdown subset
+    pub raw_text: bool,
+    /// Ask before opening a link with the system opener
+    pub confirm_open: bool,
+    /// Write plain lines instead of drawing the TUI, see the screen_reader module
+    pub screen_reader: bool,
+    /// How much the screen reader mode announces
+    pub verbosity: Verbosity,
+    /// Overwrite the chat log and input in memory when exiting, for incognito sessions
+    pub wipe_on_exit: bool,
+    pub timestamps: Timestamps,
+}
+
+/// Result of passing a key press to a component
+pub enum Handled {
+    /// The component doesn't care about the key, it may be handled by someone else
+    Ignored,
+    /// The key was handled, nothing else to do
+    Consumed,
+    /// The user submitted a line of input
+    Submit(String),
+    /// The user agreed to send a paste as several numbered messages
+    SubmitParts(Vec<String>),
+    /// The user wants to leave
+    Quit,
+}
+
+/// A part of the UI
+pub trait Component {
+    /// React to a key press, only called for the focused component
+    fn handle_key(&mut self, key: KeyEvent, state: &mut State) -> Handled;
+
+    /// Draw the component into `area`
+    fn render(&mut self, frame: &mut Frame, area: Rect, state: &State);
+}
+
+#[derive(Clone)]
+pub struct Entry {
+    pub history_index: Option<usize>,
+    pub sender: String,
+    pub msg: String,
+    /// When the message was sent, notices and messages of older clients don't have a time
+    pub sent: Option<u64>,
+    /// Placeholder of a burn-after-reading message that wasn't read yet, drawn highlighted
+    pub sealed: bool,
+    /// What plugins attached to the message, drawn beneath it
+    pub annotations: Vec<String>,
+}
+
+impl Entry {
+    fn message(history_index: usize, msg: Message) -> Entry {
+        Entry {
+            history_index: Some(history_index),
+            sender: msg.sender,
+            msg: msg.msg,
+            sent: msg.sent,
+            sealed: false,
+            annotations: Vec::new(),
+        }
+    }
+
+    fn notice(notice: String) -> Entry {
+        Entry {
+            history_index: None,
+            sender: "*".into(),
+            msg: notice,
+            sent: None,
+            sealed: false,
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Notices are shown like messages from `*`
+    pub fn is_notice(&self) -> bool {
+        self.history_index.is_none()
+    }
+}
+
+/// Everything the components render, shared between all of them
+pub struct State {
+    /// The newest entries of the chat log
+    pub entries: VecDeque<Entry>,
+    /// Local history to page older messages from, not available when attached to a daemon
+    pub history: Option<Arc<Mutex<History>>>,
+    /// Number of messages scrolled back from the newest one
+    pub scroll: usize,
+    /// Number of events the main thread sent that we didn't get to yet
+    pub backlog: usize,
+    /// Latest warning of the main thread, shown in the status bar
+    pub warning: Option<String>,
+    /// Messages of the room that couldn't be decrypted, shown in the status bar
+    pub undecryptable: usize,
+    /// Burn-after-reading messages nobody looked at yet, oldest first. They are only ever kept
+    /// here, the chat log just shows placeholders.
+    pub sealed: VecDeque<Message>,
+}
+
+impl State {
+    /// History index of the oldest message kept in memory, everything before has to be loaded
+    /// from the history
+    pub fn oldest_in_memory(&self) -> usize {
+        self.entries
+            .iter()
+            .find_map(|entry| entry.history_index)
+            .unwrap_or_else(|| match &self.history {
+                Some(history) => history.lock().unwrap().len(),
+                None => 0,
+            })
+    }
+
+    /// Total number of entries that can be scrolled through
+    pub fn total_entries(&self) -> usize {
+        self.entries.len() + self.oldest_in_memory()
+    }
+
+    fn push(&mut self, event: UiEvent) {
+        let entry = match event {
+            UiEvent::Message(index, msg) => Entry::message(index, msg),
+            // Handled by the event loop, they don't show up in the chat log
+            UiEvent::Alert(_) => return,
+            UiEvent::Custom(_) => return,
+            UiEvent::Warning(warning) => {
+                self.warning = Some(warning);
+                return;
+            }
+            UiEvent::Undecryptable(count) => {
+                self.undecryptable = count;
+                return;
+            }
+            // Annotations of messages that are no longer in memory are dropped
+            UiEvent::Annotation(index, text) => {
+                let entry = self
+                    .entries
+                    .iter_mut()
+                    .rev()
+                    .find(|entry| entry.history_index == Some(index));
+                if let Some(entry) = entry {
+                    entry.annotations.push(text);
+                }
+                return;
+            }
+            UiEvent::Notice(notice) => Entry::notice(notice),
+            UiEvent::BurnAfterReading(msg) => {
+                let placeholder =
+                    tr_args("burn-received", &[("sender", msg.sender.clone().into())]);
+                self.sealed.push_back(msg);
+                Entry {
+                    sealed: true,
+                    ..Entry::notice(placeholder)
+                }
+            }
+        };
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_IN_MEMORY {
+            self.entries.pop_front();
+        }
+        // Keep the view steady if the user is reading older messages
+        if self.scroll > 0 {
+            self.scroll += 1;
+        }
+    }
+
+    /// The oldest sealed message was read (and dropped), its placeholder says so from now on
+    fn burn_oldest(&mut self) {
+        if let Some(msg) = self.sealed.pop_front() {
+            if let Some(entry) = self.entries.iter_mut().find(|entry| entry.sealed) {
+                entry.sealed = false;
+                entry.msg = tr_args("burn-read", &[("sender", msg.sender.into())]);
+            }
+        }
+    }
+}
+
+/// Sets up the terminal and restores it when dropped, even if we panic
+struct Tui {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Tui {
+    fn new() -> Tui {
+        let terminal = Terminal::new(CrosstermBackend::new(stdout())).unwrap();
+        let mut tui = Tui { terminal };
+        tui.resume();
+        tui
+    }
+
+    /// Give the terminal back to the shell, e.g. before being stopped
+    fn suspend(&mut self) {
+        let _ = execute!(
+            self.terminal.backend_mut(),
+            DisableBracketedPaste,
+            LeaveAlternateScreen
+        );
+        let _ = disable_raw_mode();
+    }
+
+    /// Take the terminal (again), everything has to be drawn anew afterwards
+    fn resume(&mut self) {
+        let _ = enable_raw_mode();
+        // Pastes arrive as one event instead of keys, otherwise every line break would send
+        let _ = execute!(
+            self.terminal.backend_mut(),
+            EnterAlternateScreen,
+            EnableBracketedPaste
+        );
+        let _ = self.terminal.clear();
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.suspend();
+    }
+}
+
+struct Ui {
+    chat_log: ChatLog,
+    input: InputBox,
+    status_bar: StatusBar,
+    /// Popup for opening links, shown on top of the chat log while it's open
+    url_picker: Option<UrlPicker>,
+    /// Popup showing a burn-after-reading message, same place as the link picker
+    burn_viewer: Option<BurnViewer>,
+    options: UiOptions,
+}
+
+impl Ui {
+    fn layout(&mut self, frame: &mut Frame, state: &State) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(5),
+                Constraint::Length(1),
+            ])
+            .split(frame.area());
+
+        self.input.render(frame, chunks[0], state);
+        self.chat_log.render(frame, chunks[1], state);
+        self.status_bar.render(frame, chunks[2], state);
+        if let Some(url_picker) = &mut self.url_picker {
+            let area = chunks[1].inner(Margin::new(4, 2));
+            url_picker.render(frame, area, state);
+        }
+        if let Some(burn_viewer) = &mut self.burn_viewer {
+            let area = chunks[1].inner(Margin::new(4, 2));
+            burn_viewer.render(frame, area, state);
+        }
+    }
+
+    /// An open link picker gets all key presses. Otherwise they go to the input box first,
+    /// everything it doesn't need (like scrolling) is passed on to the chat log.
+    fn handle_key(&mut self, key: KeyEvent, state: &mut State) -> Handled {
+        if let Some(url_picker) = &mut self.url_picker {
+            if key.code == KeyCode::Esc {
+                self.url_picker = None;
+            } else {
+                url_picker.handle_key(key, state);
+                if url_picker.is_done() {
+                    self.url_picker = None;
+                }
+            }
+            return Handled::Consumed;
+        }
+        if let Some(burn_viewer) = &mut self.burn_viewer {
+            burn_viewer.handle_key(key, state);
+            if burn_viewer.is_done() {
+                self.burn_viewer = None;
+            }
+            return Handled::Consumed;
+        }
+
+        // Esc also declines splitting a paste, only without one waiting it quits
+        if key.code == KeyCode::Esc && !self.input.has_pending_paste() {
+            return Handled::Quit;
+        }
+        if key.code == KeyCode::Char('o') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            let url_picker = UrlPicker::new(state, self.options.confirm_open);
+            if url_picker.is_done() {
+                state.push(UiEvent::Notice(tr("links-none")));
+            } else {
+                self.url_picker = Some(url_picker);
+            }
+            return Handled::Consumed;
+        }
+        if key.code == KeyCode::Char('b') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            match state.sealed.front() {
+                Some(msg) => self.burn_viewer = Some(BurnViewer::new(msg.sender.clone())),
+                None => state.push(UiEvent::Notice(tr("burn-none"))),
+            }
+            return Handled::Consumed;
+        }
+
+        match self.input.handle_key(key, state) {
+            Handled::Ignored => self.chat_log.handle_key(key, state),
+            handled => handled,
+        }
+    }
+}
+
+/// Run the UI until the user quits or the main thread goes away, `name` is the one attached to
+/// our messages
+pub fn run_ui(
+    channels: UiChannels,
+    history: Option<Arc<Mutex<History>>>,
+    name: String,
+    options: UiOptions,
+) {
+    if options.screen_reader {
+        return screen_reader::run(channels, history, options);
+    }
+
+    let UiChannels {
+        events: mut incoming,
+        app,
+    } = channels;
+    let mut tui = Tui::new();
+    let job_control = JobControl::new();
+
+    // Start with the newest messages from the previous session
+    let entries = match &history {
+        Some(history) => {
+            let mut history = history.lock().unwrap();
+            let end = history.len();
+            let start = end.saturating_sub(MAX_IN_MEMORY);
+            history
+                .load(start..end)
+                .unwrap_or_default()
+                .into_iter()
+                .zip(start..)
+                .map(|(msg, index)| Entry::message(index, msg))
+                .collect()
+        }
+        None => VecDeque::new(),
+    };
+    let mut state = State {
+        entries,
+        history,
+        scroll: 0,
+        backlog: 0,
+        warning: None,
+        undecryptable: 0,
+        sealed: VecDeque::new(),
+    };
+    let mut ui = Ui {
+        chat_log: ChatLog::new(options.raw_text, options.timestamps),
+        input: InputBox::new(name),
+        status_bar: StatusBar,
+        url_picker: None,
+        burn_viewer: None,
+        options,
+    };
+    if let Some(draft) = state
+        .history
+        .as_ref()
+        .and_then(|h| h.lock().unwrap().draft())
+    {
+        ui.input.set_value(draft);
+    }
+    // The draft is saved whenever typing pauses, so it also survives a crash
+    let mut saved_draft = ui.input.value().to_owned();
+    let mut last_input = Instant::now();
+
+    // Events for the main thread that didn't fit into the channel yet, we never block on sending
+    // so the UI stays responsive even if the main thread is busy
+    let mut outbox = VecDeque::new();
+    let mut redraw = true;
+    let mut last_draw: Option<Instant> = None;
+    let mut last_tick = Instant::now();
+    'main: loop {
+        if job_control.stop_requested() {
+            tui.suspend();
+            job_control::stop();
+        }
+        if job_control.continued() {
+            tui.resume();
+            redraw = true;
+        }
+
+        // Take what the main thread sent us since the last iteration. While catching up there may
+        // be thousands of messages, they are taken in chunks so keys are still handled in between.
+        let mut alert = false;
+        for _ in 0..MAX_EVENTS_PER_ITERATION {
+            match incoming.try_recv() {
+                Ok(UiEvent::Alert(_)) => alert = true,
+                Ok(event) => {
+                    state.push(event);
+                    redraw = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                // The main thread is gone, nothing left to do
+                Err(TryRecvError::Disconnected) => break 'main,
+            }
+        }
+        // Ring the terminal bell once, no matter how many messages deserve a notification
+        if alert {
+            let _ = execute!(tui.terminal.backend_mut(), Print('\x07'));
+        }
+        if state.backlog != incoming.queued() {
+            state.backlog = incoming.queued();
+            redraw = true;
+        }
+
+        while let Some(event) = outbox.pop_front() {
+            match app.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(event)) => {
+                    outbox.push_front(event);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => break 'main,
+            }
+        }
+
+        if last_input.elapsed() >= DRAFT_DELAY && ui.input.value() != saved_draft {
+            saved_draft = ui.input.value().to_owned();
+            if let Some(history) = &state.history {
+                let _ = history.lock().unwrap().set_draft(&saved_draft);
+            }
+        }
+
+        // Relative times age without any event, they're redrawn on a tick of their own
+        if ui.chat_log.ages() && last_tick.elapsed() >= TICK_INTERVAL {
+            last_tick = Instant::now();
+            redraw = true;
+        }
+
+        // Changes are coalesced until the next frame is due
+        let since_draw = last_draw.map_or(FRAME_INTERVAL, |last| last.elapsed());
+        if redraw && since_draw >= FRAME_INTERVAL {
+            let _ = tui.terminal.draw(|frame| ui.layout(frame, &state));
+            last_draw = Some(Instant::now());
+            redraw = false;
+        }
+
+        // Don't wait for keys while there are events left, and not past the next frame if
+        // something changed
+        let timeout = match (state.backlog, redraw) {
+            (0, false) => Duration::from_millis(10),
+            (0, true) => FRAME_INTERVAL.saturating_sub(since_draw),
+            _ => Duration::ZERO,
+        };
+        if !poll(timeout).unwrap_or(false) {
+            continue;
+        }
+        last_input = Instant::now();
+        let key = match read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(Event::Resize(_, _)) => {
+                redraw = true;
+                continue;
+            }
+            Ok(Event::Paste(text)) if ui.url_picker.is_none() && ui.burn_viewer.is_none() => {
+                ui.input.paste(text, &mut state);
+                redraw = true;
+                continue;
+            }
+            _ => continue,
+        };
+        redraw = true;
+
+        // Ctrl+Z stops us at the start of the next iteration, see the job_control module
+        if key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            job_control.request_stop();
+            continue;
+        }
+        match ui.handle_key(key, &mut state) {
+            Handled::Submit(line) => outbox.push_back(AppEvent::from_input(line)),
+            Handled::SubmitParts(parts) => outbox.extend(parts.into_iter().map(AppEvent::Send)),
+            Handled::Quit => {
+                // Best effort, if the main thread doesn't get it it will notice the UI exiting
+                let _ = app.try_send(AppEvent::Quit);
+                break;
+            }
+            Handled::Ignored | Handled::Consumed => {}
+        }
+    }
+
+    // Keep whatever the user was typing for the next session
+    if let Some(history) = &state.history {
+        let _ = history.lock().unwrap().set_draft(ui.input.value());
+    }
+    if ui.options.wipe_on_exit {
+        ui.input.wipe();
+        saved_draft.zeroize();
+        for entry in state.entries.iter_mut() {
+            entry.sender.zeroize();
+            entry.msg.zeroize();
+            entry.annotations.iter_mut().for_each(Zeroize::zeroize);
+        }
+        for msg in state.sealed.iter_mut() {
+            msg.sender.zeroize();
+            msg.msg.zeroize();
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Line based front-end for screen reader users: no alternate screen, no cursor movement, every
+//! event is written as one plain line to stdout and every line read from stdin is sent to the
+//! room. Markup is stripped and decorations that would be read out loud (like `*` for notices)
+//! are replaced by words.
+
+use super::{markdown, UiOptions};
+use crate::events::{AppEvent, UiChannels, UiEvent};
+use crate::i18n::{tr, tr_args};
+use nym_chat::history::History;
+use nym_chat::Message;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// How much the screen reader mode tells about each event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only `sender: message` for messages and the bare text of notices
+    Terse,
+    /// Notices are announced as such and the last few messages are repeated on start
+    Normal,
+    /// Every line says what it is, including the message's position in the history
+    Verbose,
+}
+
+impl Default for Verbosity {
+    fn default() -> Self {
+        Verbosity::Normal
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "terse" => Ok(Verbosity::Terse),
+            "normal" => Ok(Verbosity::Normal),
+            "verbose" => Ok(Verbosity::Verbose),
+            _ => Err("expected terse, normal or verbose".into()),
+        }
+    }
+}
+
+impl Verbosity {
+    /// Number of messages from the history repeated on start
+    fn replay(self) -> usize {
+        match self {
+            Verbosity::Terse => 0,
+            Verbosity::Normal => 5,
+            Verbosity::Verbose => 20,
+        }
+    }
+}
+
+pub fn run(channels: UiChannels, history: Option<Arc<Mutex<History>>>, options: UiOptions) {
+    let UiChannels {
+        events: mut incoming,
+        app,
+    } = channels;
+    let verbosity = options.verbosity;
+
+    if verbosity != Verbosity::Terse {
+        print_line(&tr("screen-reader-help"));
+    }
+    if let Some(history) = &history {
+        let mut history = history.lock().unwrap();
+        let end = history.len();
+        let start = end.saturating_sub(verbosity.replay());
+        for (msg, index) in history
+            .load(start..end)
+            .unwrap_or_default()
+            .iter()
+            .zip(start..)
+        {
+            print_line(&format_message(index, msg, verbosity));
+        }
+    }
+
+    // Burn-after-reading messages wait here until the user types `/reveal`
+    let sealed = Arc::new(Mutex::new(VecDeque::<Message>::new()));
+    let reveal = sealed.clone();
+
+    // Reading stdin blocks, so it gets its own thread. It ends the session on EOF or `/quit`.
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if line.trim() == "/quit" {
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if line.trim() == "/reveal" {
+                let line = match reveal.lock().unwrap().pop_front() {
+                    Some(msg) => tr_args(
+                        "screen-reader-burn",
+                        &[("message", format_message(0, &msg, Verbosity::Terse).into())],
+                    ),
+                    None => tr("burn-none"),
+                };
+                print_line(&line);
+                continue;
+            }
+            if app.blocking_send(AppEvent::from_input(line)).is_err() {
+                return;
+            }
+        }
+        let _ = app.blocking_send(AppEvent::Quit);
+    });
+
+    while let Some(event) = incoming.blocking_recv() {
+        let line = match event {
+            // The terminal bell, screen readers announce or play it
+            UiEvent::Alert(_) => {
+                print!("\x07");
+                let _ = std::io::stdout().flush();
+                continue;
+            }
+            UiEvent::Custom(_) => continue,
+            UiEvent::BurnAfterReading(msg) => {
+                let sender = markdown::sanitize(&msg.sender);
+                sealed.lock().unwrap().push_back(msg);
+                tr_args("screen-reader-burn-received", &[("sender", sender.into())])
+            }
+            UiEvent::Warning(text) => tr_args(
+                "screen-reader-warning",
+                &[("text", markdown::sanitize(&text).into())],
+            ),
+            UiEvent::Undecryptable(count) => {
+                tr_args("screen-reader-undecryptable", &[("count", count.into())])
+            }
+            UiEvent::Message(index, msg) => format_message(index, &msg, verbosity),
+            UiEvent::Annotation(index, text) => tr_args(
+                "screen-reader-annotation",
+                &[
+                    ("index", (index + 1).into()),
+                    ("text", markdown::sanitize(&text).into()),
+                ],
+            ),
+            UiEvent::Notice(text) if verbosity == Verbosity::Terse => markdown::sanitize(&text),
+            UiEvent::Notice(text) => tr_args(
+                "screen-reader-notice",
+                &[("text", markdown::sanitize(&text).into())],
+            ),
+        };
+        print_line(&line);
+    }
+}
+
+fn format_message(index: usize, msg: &Message, verbosity: Verbosity) -> String {
+    let sender = markdown::sanitize(&msg.sender);
+    let text = markdown::render(&msg.msg)
+        .into_iter()
+        .map(|span| span.content)
+        .collect::<String>();
+    match verbosity {
+        Verbosity::Terse | Verbosity::Normal => format!("{}: {}", sender, text),
+        Verbosity::Verbose => tr_args(
+            "screen-reader-message",
+            &[
+                ("index", (index + 1).into()),
+                ("sender", sender.into()),
+                ("text", text.into()),
+            ],
+        ),
+    }
+}
+
+fn print_line(line: &str) {
+    let mut stdout = std::io::stdout();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
@@ -0,0 +1,54 @@
+use super::{Component, Handled, State};
+use crate::i18n::{tr, tr_args};
+use crossterm::event::KeyEvent;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+
+/// One line of help and scroll information below the chat log, led by the main thread's warning
+/// and the number of undecryptable messages if there are any
+pub struct StatusBar;
+
+impl Component for StatusBar {
+    fn handle_key(&mut self, _key: KeyEvent, _state: &mut State) -> Handled {
+        Handled::Ignored
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, state: &State) {
+        let mut status = tr("status-help");
+        if state.backlog > 0 {
+            status.push_str("  ");
+            status.push_str(&tr_args(
+                "status-catching-up",
+                &[("count", state.backlog.into())],
+            ));
+        }
+        if state.scroll > 0 {
+            status.push_str("  ");
+            status.push_str(&tr_args(
+                "status-newer-messages",
+                &[("count", state.scroll.into())],
+            ));
+        }
+        let mut spans = Vec::new();
+        if let Some(warning) = &state.warning {
+            let style = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+            spans.push(Span::styled(format!("{}  ", warning), style));
+        }
+        if state.undecryptable > 0 {
+            let indicator = tr_args(
+                "status-undecryptable",
+                &[("count", state.undecryptable.into())],
+            );
+            let style = Style::default().fg(Color::Yellow);
+            spans.push(Span::styled(format!("{}  ", indicator), style));
+        }
+        spans.push(Span::styled(
+            status,
+            Style::default().add_modifier(Modifier::DIM),
+        ));
+        frame.render_widget(Paragraph::new(Line::from(spans)), area);
+    }
+}
@@ -0,0 +1,186 @@
+use super::{Component, Handled, State};
+use crate::events::UiEvent;
+use crate::i18n::{tr, tr_args};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState};
+use ratatui::Frame;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Maximum number of links offered, newest first
+const MAX_URLS: usize = 20;
+
+/// Popup listing the links found in the newest messages. Enter opens the selected link with the
+/// system opener, `c` copies it to the clipboard.
+pub struct UrlPicker {
+    urls: Vec<String>,
+    list: ListState,
+    /// Ask before opening a link
+    confirm: bool,
+    /// Set while asking whether to open the selected link
+    confirming: bool,
+}
+
+impl UrlPicker {
+    pub fn new(state: &State, confirm: bool) -> UrlPicker {
+        let mut urls = Vec::new();
+        for entry in state.entries.iter().rev() {
+            for url in find_urls(&entry.msg) {
+                if !urls.contains(&url) {
+                    urls.push(url);
+                }
+            }
+            if urls.len() >= MAX_URLS {
+                break;
+            }
+        }
+        urls.truncate(MAX_URLS);
+
+        let mut list = ListState::default();
+        if !urls.is_empty() {
+            list.select(Some(0));
+        }
+        UrlPicker {
+            urls,
+            list,
+            confirm,
+            confirming: false,
+        }
+    }
+
+    fn selected(&self) -> Option<&str> {
+        self.list
+            .selected()
+            .and_then(|idx| self.urls.get(idx))
+            .map(String::as_str)
+    }
+
+    /// Whether the picker is done and should be closed
+    pub fn is_done(&self) -> bool {
+        self.urls.is_empty()
+    }
+
+    fn finish(&mut self, state: &mut State, notice: String) {
+        state.push(UiEvent::Notice(notice));
+        self.urls.clear();
+    }
+}
+
+impl Component for UrlPicker {
+    fn handle_key(&mut self, key: KeyEvent, state: &mut State) -> Handled {
+        let url = match self.selected() {
+            Some(url) => url.to_owned(),
+            None => return Handled::Ignored,
+        };
+
+        if self.confirming {
+            match key.code {
+                KeyCode::Char('y') => {
+                    let notice = open_url(&url);
+                    self.finish(state, notice);
+                }
+                _ => self.confirming = false,
+            }
+            return Handled::Consumed;
+        }
+
+        let selected = self.list.selected().unwrap_or(0);
+        let last = self.urls.len() - 1;
+        match key.code {
+            KeyCode::Up => self.list.select(Some(selected.saturating_sub(1))),
+            KeyCode::Down => self.list.select(Some((selected + 1).min(last))),
+            KeyCode::Enter if self.confirm => self.confirming = true,
+            KeyCode::Enter => {
+                let notice = open_url(&url);
+                self.finish(state, notice);
+            }
+            KeyCode::Char('c') => {
+                let notice = copy_url(&url);
+                self.finish(state, notice);
+            }
+            _ => return Handled::Ignored,
+        }
+        Handled::Consumed
+    }
+
+    fn render(&mut self, frame: &mut Frame, area: Rect, _state: &State) {
+        let title = match (self.confirming, self.selected()) {
+            (true, Some(url)) => tr_args("links-confirm", &[("url", url.into())]),
+            _ => tr("links-title"),
+        };
+        let items = self
+            .urls
+            .iter()
+            .map(|url| ListItem::new(url.as_str()))
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+}
+
+/// Find http(s) links in a message, trailing punctuation isn't considered part of the link
+pub fn find_urls(text: &str) -> Vec<String> {
+    text.split(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '(')
+        .filter_map(|word| {
+            let start = word.find("https://").or_else(|| word.find("http://"))?;
+            let url = word[start..].trim_end_matches(|c: char| ".,;:!?)]'\"".contains(c));
+            if url.ends_with("//") {
+                return None;
+            }
+            Some(url.to_owned())
+        })
+        .collect()
+}
+
+/// Open a link with the platform's default handler, returns a notice for the user
+fn open_url(url: &str) -> String {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    // `cmd /C start` would interpret characters like `&` in the link, this takes it verbatim
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+
+    // The opener must not write into our terminal
+    let result = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match result {
+        Ok(_) => tr_args("link-opened", &[("url", url.into())]),
+        Err(e) => tr_args(
+            "link-open-failed",
+            &[("url", url.into()), ("error", e.to_string().into())],
+        ),
+    }
+}
+
+/// Copy a link to the clipboard using the OSC 52 escape sequence, which also works inside the
+/// alternate screen and over SSH if the terminal supports it
+fn copy_url(url: &str) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64::encode(url));
+    let mut stdout = std::io::stdout();
+    match stdout
+        .write_all(sequence.as_bytes())
+        .and_then(|_| stdout.flush())
+    {
+        Ok(()) => tr_args("link-copied", &[("url", url.into())]),
+        Err(e) => tr_args(
+            "link-copy-failed",
+            &[("url", url.into()), ("error", e.to_string().into())],
+        ),
+    }
+}
@@ -0,0 +1,147 @@
+//! Load generator for servers: sends synthetic encrypted messages through a Nym client to the
+//! server's service provider (the ingestion path) while several fetchers poll `/fetch` like
+//! clients do. Reports throughput and fetch latencies, meant for comparing server versions on the
+//! same setup, not for production servers.
+
+use futures::SinkExt;
+use nym_addressing::clients::Recipient;
+use nym_chat::{EncryptedMessage, Envelope, Key, Message};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+use tokio_tungstenite::connect_async;
+
+#[derive(StructOpt)]
+struct Options {
+    /// Nym client to send the messages through
+    #[structopt(short, long, default_value = "ws://127.0.0.1:1977")]
+    websocket: String,
+    /// The server's Nym address
+    #[structopt(short, long, parse(try_from_str = Recipient::try_from_base58_string))]
+    service_provider: Recipient,
+    /// The server's HTTP API
+    #[structopt(long, default_value = "http://127.0.0.1:3030")]
+    url: String,
+    /// Messages sent per second
+    #[structopt(long, default_value = "10")]
+    rate: u64,
+    /// Length of the text of every message in bytes
+    #[structopt(long, default_value = "200")]
+    size: usize,
+    /// Number of concurrent fetchers
+    #[structopt(long, default_value = "4")]
+    fetchers: usize,
+    /// How long to run in seconds
+    #[structopt(long, default_value = "60")]
+    duration: u64,
+}
+
+#[tokio::main]
+async fn main() {
+    let opts: Options = StructOpt::from_args();
+    let deadline = Instant::now() + Duration::from_secs(opts.duration);
+
+    // Fetchers start from the current end of the server's feed, so only the generated load counts
+    let start = fetch(&opts.url, 0)
+        .await
+        .expect("Couldn't fetch from the server")
+        .len();
+    let fetched = Arc::new(AtomicUsize::new(0));
+    let fetchers = (0..opts.fetchers)
+        .map(|_| tokio::spawn(fetcher(opts.url.clone(), start, deadline, fetched.clone())))
+        .collect::<Vec<_>>();
+
+    let sent = send(&opts, deadline).await;
+
+    let mut latencies = Vec::new();
+    for fetcher in fetchers {
+        latencies.extend(fetcher.await.expect("fetcher panicked"));
+    }
+    latencies.sort();
+    let percentile = |p: usize| {
+        latencies
+            .get(latencies.len() * p / 100)
+            .copied()
+            .unwrap_or_default()
+    };
+
+    println!(
+        "sent {} messages ({:.1}/s)",
+        sent,
+        sent as f64 / opts.duration as f64
+    );
+    println!(
+        "{} fetches, {} messages fetched",
+        latencies.len(),
+        fetched.load(Ordering::Relaxed)
+    );
+    println!(
+        "fetch latency p50 {:?} p90 {:?} p99 {:?} max {:?}",
+        percentile(50),
+        percentile(90),
+        percentile(99),
+        latencies.last().copied().unwrap_or_default()
+    );
+}
+
+/// Send messages at the configured rate until `deadline`, returns the number of sent messages
+async fn send(opts: &Options, deadline: Instant) -> usize {
+    let (mut ws, _) = connect_async(&opts.websocket)
+        .await
+        .expect("Couldn't connect to nym websocket");
+    // Messages are encrypted to a random room, nobody will ever read them
+    let room = Key::generate();
+    let mut timer = tokio::time::interval(Duration::from_micros(1_000_000 / opts.rate.max(1)));
+    let mut sent = 0;
+    while Instant::now() < deadline {
+        timer.tick().await;
+        let msg = Message::new("loadgen".into(), "x".repeat(opts.size));
+        let envelope = Envelope {
+            msg: msg.encrypt(&room),
+            max_age_secs: None,
+        };
+        let request = nym_websocket::requests::ClientRequest::Send {
+            recipient: opts.service_provider,
+            message: envelope.to_bytes(),
+            with_reply_surb: false,
+        };
+        ws.send(tokio_tungstenite::tungstenite::Message::Binary(
+            request.serialize(),
+        ))
+        .await
+        .expect("couldn't send request");
+        sent += 1;
+    }
+    let _ = ws.close(None).await;
+    sent
+}
+
+/// Poll the server as fast as possible until `deadline`, returns the latency of every fetch
+async fn fetcher(
+    url: String,
+    mut last_fetch: usize,
+    deadline: Instant,
+    fetched: Arc<AtomicUsize>,
+) -> Vec<Duration> {
+    let mut latencies = Vec::new();
+    while Instant::now() < deadline {
+        let started = Instant::now();
+        match fetch(&url, last_fetch).await {
+            Ok(msgs) => {
+                latencies.push(started.elapsed());
+                last_fetch += msgs.len();
+                fetched.fetch_add(msgs.len(), Ordering::Relaxed);
+            }
+            Err(e) => eprintln!("fetch failed: {}", e),
+        }
+    }
+    latencies
+}
+
+async fn fetch(url: &str, last_seen: usize) -> Result<Vec<EncryptedMessage>, reqwest::Error> {
+    reqwest::get(format!("{}/fetch/{}", url, last_seen))
+        .await?
+        .json()
+        .await
+}
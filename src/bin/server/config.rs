@@ -0,0 +1,162 @@
+use crate::store::Store;
+use crate::time::Clock;
+use nym_chat::DEFAULT_MAX_MESSAGE_BYTES;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::{error, info, Subscriber};
+use tracing_subscriber::reload::Handle;
+use tracing_subscriber::EnvFilter;
+
+/// How often expired messages are deleted
+const PRUNE_INTERVAL_SECS: u64 = 60;
+
+/// The store file is compacted once deleted messages take up this fraction of it (a quarter)...
+const COMPACT_DEAD_FRACTION: u64 = 4;
+
+/// ...or when deleted messages stayed on disk for this long
+const COMPACT_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// Settings that can be changed while the server is running, they are read from a TOML file and
+/// reloaded on SIGHUP without dropping the Nym connections or the HTTP listener
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Log filter in the same format as `RUST_LOG`, e.g. `info` or `server=debug`
+    pub log_level: Option<String>,
+    /// Maximum number of messages accepted per tenant and minute, further ones are dropped
+    pub rate_limit: Option<u32>,
+    /// Messages older than this many seconds aren't served anymore
+    pub retention_secs: Option<u64>,
+    /// Bigger messages are dropped before decoding them, see [`DEFAULT_MAX_MESSAGE_BYTES`]
+    pub max_message_bytes: Option<u64>,
+    /// Limits of single tenants by name (`[quotas.<tenant>]`), so one noisy community can't use up
+    /// the storage the others rely on
+    pub quotas: HashMap<String, Quota>,
+}
+
+/// Limits of one tenant. The server can't tell rooms apart (all messages look the same), so
+/// communities are the finest unit quotas can apply to, rooms that need their own quota get their
+/// own tenant.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    /// Maximum number of messages accepted per minute, instead of the global `rate_limit`
+    pub rate_limit: Option<u32>,
+    /// Messages are dropped while the tenant's served messages take up this many bytes, pruned
+    /// and expired messages don't count
+    pub max_stored_bytes: Option<u64>,
+}
+
+impl Config {
+    /// Messages the tenant `name` may send per minute, if it's limited
+    pub fn rate_limit(&self, name: &str) -> Option<u32> {
+        self.quotas
+            .get(name)
+            .and_then(|quota| quota.rate_limit)
+            .or(self.rate_limit)
+    }
+
+    /// Bytes the served messages of the tenant `name` may take up, if it's limited
+    pub fn max_stored_bytes(&self, name: &str) -> Option<u64> {
+        self.quotas
+            .get(name)
+            .and_then(|quota| quota.max_stored_bytes)
+    }
+
+    pub fn max_message_bytes(&self) -> u64 {
+        self.max_message_bytes.unwrap_or(DEFAULT_MAX_MESSAGE_BYTES)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, anyhow::Error> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// Reload the config from `path` on every SIGHUP and publish it to everyone watching
+pub async fn reload_on_hangup<S: Subscriber>(
+    path: PathBuf,
+    config: watch::Sender<Config>,
+    log: Handle<EnvFilter, S>,
+) {
+    let mut hangups = signal(SignalKind::hangup()).expect("Couldn't listen for SIGHUP");
+    while hangups.recv().await.is_some() {
+        match Config::load(&path) {
+            Ok(new_config) => {
+                info!("Reloaded configuration from {}", path.display());
+                apply_log_level(&new_config, &log);
+                let _ = config.send(new_config);
+            }
+            // Keep running with the old configuration
+            Err(e) => error!("Couldn't reload configuration: {}", e),
+        }
+    }
+}
+
+/// Switch to the configured log level, if there is one
+pub fn apply_log_level<S: Subscriber>(config: &Config, log: &Handle<EnvFilter, S>) {
+    let level = match &config.log_level {
+        Some(level) => level,
+        None => return,
+    };
+    match EnvFilter::try_new(level) {
+        Ok(filter) => {
+            if let Err(e) = log.reload(filter) {
+                error!("Couldn't change the log level: {}", e);
+            }
+        }
+        Err(e) => error!("Invalid log level {}: {}", level, e),
+    }
+}
+
+/// Periodically delete messages older than the configured retention time and the ones whose
+/// senders asked for them to expire. The store file is compacted once enough of it is deleted (or
+/// at least daily) so they are gone from disk too, without blocking the runtime or locking the
+/// store while the file is rewritten.
+pub async fn delete_expired(
+    store: Arc<Mutex<Store>>,
+    config: watch::Receiver<Config>,
+    clock: Clock,
+) {
+    let mut timer = tokio::time::interval(std::time::Duration::from_secs(PRUNE_INTERVAL_SECS));
+    let mut compacted = clock.now();
+    loop {
+        timer.tick().await;
+        let now = clock.now();
+        let retention = config.borrow().retention_secs;
+        let mut compaction = {
+            let mut store = store.lock().unwrap();
+            let pruned = match retention {
+                Some(retention) => store.prune(now.saturating_sub(retention)),
+                None => 0,
+            };
+            let expired = store.expire(now);
+            if pruned + expired > 0 {
+                info!(
+                    "Deleted {} messages past retention and {} expired ones",
+                    pruned, expired
+                );
+            }
+            let dead = store.dead_bytes();
+            let due = now >= compacted + COMPACT_INTERVAL_SECS;
+            if dead == 0 || (dead * COMPACT_DEAD_FRACTION < store.size() && !due) {
+                continue;
+            }
+            store.begin_compaction()
+        };
+        compacted = now;
+        let store = store.clone();
+        let compacting = tokio::task::spawn_blocking(move || {
+            compaction.write()?;
+            store.lock().unwrap().finish_compaction(compaction)
+        });
+        match compacting.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Couldn't compact the store: {}", e),
+            Err(e) => error!("Compacting the store failed: {}", e),
+        }
+    }
+}
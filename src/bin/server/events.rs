@@ -0,0 +1,174 @@
+//! Recent server events for operators, served at `/admin` so problems can be debugged without a
+//! shell on the host. Everything the server logs (ingestion, errors, reconnects, ...) passes
+//! through [`EventLog`] as a tracing layer, which keeps the newest events and streams new ones to
+//! connected admin pages. It sees what the configured log level lets through, `log_level = "debug"`
+//! in the config (reloaded with SIGHUP) adds every stored message. Times use the server's clock, so
+//! they are as coarse as the ones in the store.
+
+use crate::time::Clock;
+use futures::{Stream, StreamExt};
+use nym_chat::constant_time;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::convert::Infallible;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Number of events kept for admin pages opened later
+const RECENT_EVENTS: usize = 1000;
+
+/// Events buffered per admin page before it starts missing some
+const LIVE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerEvent {
+    /// Unix seconds, rounded like the store's arrival times
+    pub time: u64,
+    pub level: String,
+    /// Module the event comes from, e.g. `server::tenant`
+    pub target: String,
+    pub message: String,
+    /// Structured fields other than the message
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+#[derive(Clone)]
+pub struct EventLog {
+    recent: Arc<Mutex<VecDeque<ServerEvent>>>,
+    live: broadcast::Sender<ServerEvent>,
+    clock: Clock,
+}
+
+impl EventLog {
+    pub fn new(clock: Clock) -> EventLog {
+        let (live, _) = broadcast::channel(LIVE_CAPACITY);
+        EventLog {
+            recent: Arc::default(),
+            live,
+            clock,
+        }
+    }
+
+    /// The kept events followed by new ones as they happen, ready to be sent as server-sent
+    /// events. Admin pages that can't keep up skip events.
+    pub fn stream(&self) -> impl Stream<Item = Result<warp::sse::Event, Infallible>> {
+        // Subscribe first, an event showing up twice is better than one missing
+        let receiver = self.live.subscribe();
+        let recent = self
+            .recent
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let live = futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        futures::stream::iter(recent).chain(live).map(|event| {
+            Ok(warp::sse::Event::default()
+                .json_data(&event)
+                .unwrap_or_default())
+        })
+    }
+
+    fn push(&self, event: ServerEvent) {
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == RECENT_EVENTS {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        // Nobody watching is fine
+        let _ = self.live.send(event);
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EventLog {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        self.push(ServerEvent {
+            time: self.clock.now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_owned(),
+            message: fields.message,
+            fields: fields.other,
+        });
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    other: BTreeMap<String, String>,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            name => {
+                self.other.insert(name.to_owned(), format!("{:?}", value));
+            }
+        }
+    }
+}
+
+/// Whether a request carries the admin token, as a bearer token or (for the page, since browsers
+/// can't set headers on event streams) as the `token` query parameter
+pub fn authorized(token: &str, header: Option<&str>, query: Option<&str>) -> bool {
+    let given = header
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .or(query)
+        .unwrap_or_default();
+    // Compare in constant time, the token shouldn't be guessable byte by byte
+    constant_time::eq(given.as_bytes(), token.as_bytes())
+}
+
+/// The admin page, it shows the event stream as a table
+pub const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>nym-chat server events</title>
+<style>
+body { font-family: monospace; }
+td { padding: 0 1em 0 0; vertical-align: top; }
+.WARN { color: #b60; }
+.ERROR { color: #c00; }
+</style>
+</head>
+<body>
+<h1>nym-chat server events</h1>
+<table><tbody id="events"></tbody></table>
+<script>
+const token = new URLSearchParams(location.search).get("token") || "";
+const events = new EventSource("/admin/events?token=" + encodeURIComponent(token));
+events.onmessage = (message) => {
+  const event = JSON.parse(message.data);
+  const row = document.createElement("tr");
+  row.className = event.level;
+  const fields = Object.entries(event.fields || {}).map(([k, v]) => k + "=" + v).join(" ");
+  const time = new Date(event.time * 1000).toISOString();
+  for (const text of [time, event.level, event.target, event.message, fields]) {
+    const cell = document.createElement("td");
+    cell.textContent = text;
+    row.appendChild(cell);
+  }
+  document.getElementById("events").prepend(row);
+};
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,109 @@
+//! The HTTP API over HTTP/3 (QUIC), only built with the `http3` feature. Clients poll the fetch
+//! endpoint over and over with small requests, on lossy links QUIC spares them TCP's head-of-line
+//! blocking and the reconnects after a changed address. warp doesn't speak HTTP/3, so requests are
+//! read from the h3 streams and handed to the same filters as a `warp::service`. Responses are
+//! buffered before they are sent, so streaming endpoints (the admin events) only work over TCP.
+
+use anyhow::Context;
+use rustls_pemfile::Item;
+use std::convert::Infallible;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+use warp::hyper::body::{Buf, Bytes};
+use warp::hyper::service::Service;
+use warp::hyper::{Body, Request, Response};
+
+type RequestStream = h3::server::RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>;
+
+/// Listen for QUIC connections on `addr` with the PEM encoded certificate chain at `cert` and its
+/// private key at `key`
+pub fn listen(addr: SocketAddr, cert: &Path, key: &Path) -> Result<quinn::Endpoint, anyhow::Error> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key)?))?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => {
+                Some(rustls::PrivateKey(key))
+            }
+            _ => None,
+        })
+        .context("the key file contains no private key")?;
+    let mut tls = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+    let endpoint = quinn::Endpoint::server(quinn::ServerConfig::with_crypto(Arc::new(tls)), addr)?;
+    info!("Serving the HTTP API over HTTP/3 on {}", addr);
+    Ok(endpoint)
+}
+
+/// Answer the requests of all connections to `endpoint` with `service`
+pub async fn serve<S>(endpoint: quinn::Endpoint, service: S)
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>,
+    S: Clone + Send + 'static,
+    S::Future: Send,
+{
+    while let Some(connecting) = endpoint.accept().await {
+        let service = service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = connection(connecting, service).await {
+                debug!("HTTP/3 connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn connection<S>(connecting: quinn::Connecting, service: S) -> Result<(), anyhow::Error>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>,
+    S: Clone + Send + 'static,
+    S::Future: Send,
+{
+    let connection = h3_quinn::Connection::new(connecting.await?);
+    let mut connection = h3::server::Connection::<_, Bytes>::new(connection).await?;
+    while let Some((request, stream)) = connection.accept().await? {
+        let service = service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(request, stream, service).await {
+                debug!("HTTP/3 request failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn respond<S>(
+    request: Request<()>,
+    mut stream: RequestStream,
+    mut service: S,
+) -> Result<(), anyhow::Error>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Infallible>,
+{
+    let mut body = Vec::new();
+    while let Some(mut chunk) = stream.recv_data().await? {
+        body.extend_from_slice(&chunk.copy_to_bytes(chunk.remaining()));
+    }
+    let (parts, ()) = request.into_parts();
+    let response = match service.call(Request::from_parts(parts, body.into())).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+    let (parts, body) = response.into_parts();
+    let body = warp::hyper::body::to_bytes(body).await?;
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+    stream.send_data(body).await?;
+    stream.finish().await?;
+    Ok(())
+}
@@ -0,0 +1,387 @@
+#![feature(async_closure)]
+
+use config::Config;
+use events::EventLog;
+use futures::Stream;
+use nym_chat::broadcast::SigningKey;
+use nym_chat::exit::{ExitCode, Fatal, OrExit};
+use nym_chat::Key;
+use receipts::Receipts;
+use snapshot::Snapshots;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use store::Store;
+use structopt::StructOpt;
+use tenant::{Addresses, Ingest, Tenant};
+use time::Clock;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+use watchdog::Watchdog;
+
+mod config;
+mod events;
+#[cfg(feature = "http3")]
+mod http3;
+mod maintenance;
+mod receipts;
+mod routes;
+mod snapshot;
+mod stats;
+mod store;
+mod tenant;
+mod time;
+mod watchdog;
+#[cfg(feature = "web-ui")]
+mod web;
+
+#[derive(StructOpt)]
+struct Options {
+    /// The nym native client to use, can be given several times to serve multiple communities with
+    /// separate service provider addresses from one server (see `[name=]ws://...` in the README)
+    #[structopt(short, long = "websocket", default_value = "ws://127.0.0.1:1977")]
+    websockets: Vec<Tenant>,
+    /// Where to persist messages, without it they are lost when the server stops: a log file
+    /// (`<path>` or `log:<path>`) or a redb database (`redb:<path>`)
+    #[structopt(long)]
+    store: Option<store::Location>,
+    /// Key to encrypt the store at rest with (32 bytes hex encoded or a mnemonic phrase)
+    #[structopt(long, env = "NYM_CHAT_STORE_KEY", hide_env_values = true)]
+    store_key: Option<Key>,
+    /// File containing the store key, e.g. provided by a secrets manager
+    #[structopt(long, parse(from_os_str), conflicts_with = "store-key")]
+    store_key_file: Option<PathBuf>,
+    /// Sync the store to disk every this many milliseconds instead of after every message. New
+    /// messages are only served once they are on disk, so they are delayed by up to the interval.
+    #[structopt(long)]
+    fsync_interval: Option<u64>,
+    /// Check the sequence numbers and checksums of all records in the store and exit
+    #[structopt(long, requires = "store")]
+    verify_store: bool,
+    /// Copy all messages of the store into a new, empty one (e.g. `redb:messages.redb`) and exit,
+    /// then start the server with that as its --store
+    #[structopt(long, requires = "store")]
+    migrate_store: Option<store::Location>,
+    /// Round stored and logged arrival times down to buckets of this many seconds (e.g. 600),
+    /// exact times make it easier to correlate messages with mixnet traffic
+    #[structopt(long)]
+    timestamp_bucket: Option<u64>,
+    /// Rebuild the snapshots new clients download the whole history with every this many seconds,
+    /// 0 disables snapshots
+    #[structopt(long, default_value = "3600")]
+    snapshot_interval: u64,
+    /// Serve aggregate statistics (messages per day, storage size) at /stats
+    #[structopt(long)]
+    stats: bool,
+    /// Shell command run when the connection to a Nym client goes down or comes back, it gets the
+    /// details in NYM_CHAT_TENANT, NYM_CHAT_EVENT (down or up) and NYM_CHAT_REASON
+    #[structopt(long)]
+    alert_command: Option<String>,
+    /// Key to sign receipts for stored messages with (see `client keygen`), clients can prove that
+    /// the server accepted their messages with them
+    #[structopt(long, env = "NYM_CHAT_RECEIPT_KEY", hide_env_values = true)]
+    receipt_key: Option<SigningKey>,
+    /// TOML file with settings that can be reloaded with SIGHUP: log_level, rate_limit (messages
+    /// per tenant and minute), retention_secs and max_message_bytes
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Also accept envelopes POSTed to /submit over plain HTTP, bypassing the mixnet. That's for
+    /// local development and tests with the client's --loopback, never enable it on a real server.
+    #[structopt(long)]
+    loopback: bool,
+    /// Serve recent server events at /admin to requests carrying this token, as a bearer token or
+    /// the `token` query parameter. Without it there is no admin page.
+    #[structopt(long, env = "NYM_CHAT_ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Serve the HTTP API on this Unix socket (e.g. /run/nym-chat.sock) instead of port 3030, to
+    /// run behind a reverse proxy like nginx or caddy without exposing a TCP port
+    #[structopt(long, parse(from_os_str))]
+    http_uds: Option<PathBuf>,
+    /// Also serve the HTTP API over HTTP/3 on this UDP address (e.g. 0.0.0.0:3031), clients set
+    /// `http_version = "3"` in their --http-config to use it
+    #[cfg(feature = "http3")]
+    #[structopt(long, requires_all = &["tls-cert", "tls-key"])]
+    http3: Option<std::net::SocketAddr>,
+    /// PEM file with the certificate chain for --http3, clients check it like any HTTPS server's
+    #[cfg(feature = "http3")]
+    #[structopt(long, parse(from_os_str))]
+    tls_cert: Option<PathBuf>,
+    /// PEM file with the private key of --tls-cert
+    #[cfg(feature = "http3")]
+    #[structopt(long, parse(from_os_str))]
+    tls_key: Option<PathBuf>,
+    /// Maintain the --store without starting the server: prune, stats, verify or compact
+    #[structopt(subcommand)]
+    command: Option<maintenance::Command>,
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(fatal) = run().await {
+        fatal.exit();
+    }
+}
+
+async fn run() -> Result<(), Fatal> {
+    // Parse command line options
+    let options: Options = Options::from_args();
+    let clock = Clock::new(options.timestamp_bucket);
+
+    // Start the logging framework, the level can be changed later on by the config
+    let logging = tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_timer(clock)
+        .with_filter_reloading();
+    let log_handle = logging.reload_handle();
+    let event_log = EventLog::new(clock);
+    logging.finish().with(event_log.clone()).init();
+
+    // Settings that can change at runtime are passed around through a watch channel, so everyone
+    // always sees the newest ones
+    let initial_config = match &options.config {
+        Some(path) => {
+            Config::load(path).or_exit(ExitCode::Config, "Couldn't load configuration")?
+        }
+        None => Config::default(),
+    };
+    config::apply_log_level(&initial_config, &log_handle);
+    let (config_send, config) = watch::channel(initial_config);
+    if let Some(path) = options.config.clone() {
+        tokio::spawn(config::reload_on_hangup(path, config_send, log_handle));
+    }
+
+    let store_key = match &options.store_key_file {
+        Some(path) => {
+            let key = std::fs::read_to_string(path)
+                .or_exit(ExitCode::Config, "Couldn't read store key file")?;
+            Some(Key::from_str(key.trim()).or_exit(ExitCode::Config, "Invalid store key")?)
+        }
+        None => options.store_key.clone(),
+    };
+
+    if let Some(command) = options.command {
+        let location = options.store.as_ref().ok_or_else(|| {
+            Fatal::new(
+                ExitCode::Config,
+                "Maintenance commands need the --store to work on",
+            )
+        })?;
+        let report = maintenance::run(command, location, store_key)
+            .or_exit(ExitCode::Failure, "Maintenance of the store failed")?;
+        println!("{}", report);
+        return Ok(());
+    }
+
+    // Held until the server stops, so maintenance commands can't change the store meanwhile
+    let _lock = options
+        .store
+        .as_ref()
+        .map(store::Location::lock)
+        .transpose()
+        .or_exit(ExitCode::Config, "Couldn't lock the store")?;
+
+    if options.verify_store {
+        let location = options.store.as_ref().expect("required by structopt");
+        let records = store::verify(location, store_key.as_ref())
+            .or_exit(ExitCode::Failure, "The store is damaged")?;
+        println!("The store is intact, it contains {} records", records);
+        return Ok(());
+    }
+
+    // Name every tenant, the first one is also served at the root for clients that don't know
+    // about tenants
+    let tenants = options
+        .websockets
+        .into_iter()
+        .enumerate()
+        .map(|(idx, tenant)| {
+            (
+                tenant.name.unwrap_or_else(|| idx.to_string()),
+                tenant.websocket,
+            )
+        })
+        .collect::<Vec<_>>();
+    let default_tenant = tenants[0].0.clone();
+    let watchdog = Watchdog::new(
+        tenants.iter().map(|(name, _)| name.as_str()),
+        options.alert_command,
+    );
+
+    // First we open the message database that will contain all messages ever sent. It's kept in
+    // memory and optionally persisted to a file, see the store module. Arrival times are only
+    // stored for operators, fetch responses never contain them.
+    let store = match &options.store {
+        Some(location) => Store::open(location, store_key, options.fsync_interval.is_some())
+            .or_exit(ExitCode::Failure, "Couldn't open message store")?,
+        None => {
+            if store_key.is_some() {
+                warn!("Ignoring the store key, messages are only kept in memory without --store");
+            }
+            Store::in_memory()
+        }
+    };
+    info!("Loaded {} messages from the store", store.len());
+    if let Some(target) = &options.migrate_store {
+        let messages = store
+            .migrate(target)
+            .or_exit(ExitCode::Failure, "Couldn't migrate the store")?;
+        println!("Copied {} messages to {:?}", messages, target);
+        return Ok(());
+    }
+    let messages = Arc::new(Mutex::new(store));
+    let receipts = options.receipt_key.map(Receipts::new);
+    if let Some(receipts) = &receipts {
+        info!(
+            "Signing receipts, verifying key {}",
+            receipts.verifying_key()
+        );
+    }
+    let addresses = Addresses::default();
+    if let Some(interval) = options.fsync_interval {
+        tokio::spawn(sync_store(messages.clone(), interval));
+    }
+    tokio::spawn(config::delete_expired(
+        messages.clone(),
+        config.clone(),
+        clock,
+    ));
+    let snapshots = Snapshots::default();
+    if options.snapshot_interval > 0 {
+        tokio::spawn(snapshot::refresh(
+            messages.clone(),
+            addresses.clone(),
+            snapshots.clone(),
+            options.snapshot_interval,
+        ));
+    }
+
+    // Spawn a webserver that clients will use to sync up messages sent since they last checked.
+    // This happens without any privacy measures since everyone is querying all messages, so nothing
+    // can be learnt other than someone is using the chat service. No metadata about communication
+    // relations is leaked.
+    //
+    // Note that the recipient privacy is thus weaker than the sender privacy provided by Nym
+    // because the anonymity set is merely everyone using this particular service and not every
+    // other Nym user. Ideally this could be replaced with a SURB-based protocol once the we know
+    // how to build these safely.
+    if options.loopback {
+        warn!("Accepting messages over plain HTTP at /submit, they bypass the mixnet");
+    }
+    let state = routes::State {
+        default_tenant,
+        messages: messages.clone(),
+        addresses: addresses.clone(),
+        receipts,
+        snapshots,
+        watchdog: watchdog.clone(),
+        event_log,
+        tenants: Arc::new(tenants.iter().map(|(name, _)| name.clone()).collect()),
+        loopback: options.loopback.then(|| {
+            Arc::new(Mutex::new(Ingest::new(
+                messages.clone(),
+                config.clone(),
+                clock,
+            )))
+        }),
+        stats: options.stats,
+        admin_token: options.admin_token.map(Arc::new),
+    };
+    #[cfg(feature = "http3")]
+    if let Some((addr, (cert, key))) = options.http3.zip(options.tls_cert.zip(options.tls_key)) {
+        let endpoint = http3::listen(addr, &cert, &key).or_exit(
+            ExitCode::Config,
+            format!("Couldn't serve HTTP/3 on {}", addr),
+        )?;
+        tokio::spawn(http3::serve(endpoint, warp::service(routes::all(&state))));
+    }
+    let http_uds = options.http_uds;
+    let http = tokio::spawn(async move {
+        let server = warp::serve(routes::all(&state));
+        // Failing to listen ends the server, there's no point in running without the API
+        match http_uds {
+            Some(path) => server.run_incoming(listen_unix(&path)?).await,
+            None => {
+                let (_, serving) = server
+                    .try_bind_ephemeral(([0, 0, 0, 0], 3030))
+                    .or_exit(ExitCode::Config, "Couldn't listen on port 3030")?;
+                serving.await
+            }
+        }
+        Ok::<(), Fatal>(())
+    });
+
+    // We also listen for incoming Nym messages of all tenants in parallel
+    let receivers = tenants.into_iter().map(|(name, websocket)| {
+        tenant::receive(
+            name,
+            websocket,
+            messages.clone(),
+            addresses.clone(),
+            config.clone(),
+            clock,
+            watchdog.clone(),
+        )
+    });
+    // Receivers reconnect forever, so only the HTTP server can end the server
+    tokio::select! {
+        result = http => result?,
+        _ = futures::future::join_all(receivers) => Ok(()),
+    }
+}
+
+type UnixConnection = Result<tokio::net::UnixStream, std::io::Error>;
+
+/// Connections to the Unix socket at `path`, a socket left over from a previous run is replaced.
+/// Anything else at `path` is left alone and the server refuses to start, so a typo can't delete
+/// a file.
+fn listen_unix(path: &Path) -> Result<impl Stream<Item = UnixConnection> + Send + Unpin, Fatal> {
+    // Binding fails if the file exists, even if nobody listens anymore
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path).or_exit(
+            ExitCode::Config,
+            format!("Couldn't remove old socket {}", path.display()),
+        )?,
+        Ok(_) => {
+            let error = format!("{} exists and isn't a socket", path.display());
+            return Err(Fatal::new(ExitCode::Config, error));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e).or_exit(
+                ExitCode::Config,
+                format!("Couldn't check {}", path.display()),
+            )
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(path).or_exit(
+        ExitCode::Config,
+        format!("Couldn't listen on {}", path.display()),
+    )?;
+    info!("Serving the HTTP API on {}", path.display());
+    Ok(Box::pin(futures::stream::unfold(
+        listener,
+        |listener| async move {
+            let connection = listener.accept().await.map(|(stream, _)| stream);
+            Some((connection, listener))
+        },
+    )))
+}
+
+/// Sync the store every `interval` milliseconds, batching the disk syncs of all messages received
+/// in the meantime
+async fn sync_store(store: Arc<Mutex<Store>>, interval: u64) {
+    let mut timer = tokio::time::interval(std::time::Duration::from_millis(interval.max(1)));
+    loop {
+        timer.tick().await;
+        if let Err(e) = store.lock().unwrap().sync() {
+            error!("Couldn't sync the store: {}", e);
+        }
+    }
+}
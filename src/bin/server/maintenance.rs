@@ -0,0 +1,61 @@
+//! Maintenance of the persistent store from the command line, e.g. `server --store messages.log
+//! prune --before 1700000000`. The commands only open the store, they neither connect to a Nym
+//! client nor serve anything, so they can run from cron or while debugging a store. They refuse to
+//! run while a server (or another command) uses the same store, see [`store::StoreLock`].
+
+use crate::stats;
+use crate::store::{self, Location, Store};
+use nym_chat::Key;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub enum Command {
+    /// Replace messages that arrived before the given time by placeholders and compact the store
+    Prune {
+        /// Unix timestamp in seconds, messages that arrived before it are removed
+        #[structopt(long)]
+        before: u64,
+    },
+    /// Print the statistics the --stats page shows for the store
+    Stats,
+    /// Check the sequence numbers and checksums of all records (like --verify-store)
+    Verify,
+    /// Rewrite the store so pruned and expired messages are gone from disk
+    Compact,
+}
+
+/// Run `command` on the store at `location`, returns what to tell the operator
+pub fn run(
+    command: Command,
+    location: &Location,
+    key: Option<Key>,
+) -> Result<String, anyhow::Error> {
+    let _lock = location.lock()?;
+    Ok(match command {
+        Command::Prune { before } => {
+            let mut store = Store::open(location, key, false)?;
+            let pruned = store.prune(before);
+            store.compact()?;
+            format!(
+                "Pruned {} messages, the store has {} bytes left",
+                pruned,
+                store.size()
+            )
+        }
+        Command::Stats => stats::render(&Store::open(location, key, false)?, None),
+        Command::Verify => {
+            let records = store::verify(location, key.as_ref())?;
+            format!("The store is intact, it contains {} records", records)
+        }
+        Command::Compact => {
+            let mut store = Store::open(location, key, false)?;
+            let size = store.size();
+            store.compact()?;
+            format!(
+                "Compacted the store from {} to {} bytes",
+                size,
+                store.size()
+            )
+        }
+    })
+}
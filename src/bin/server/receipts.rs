@@ -0,0 +1,85 @@
+use crate::store::Store;
+use nym_chat::broadcast::{SigningKey, VerifyingKey};
+use nym_chat::merkle::{self, Hash, Tree};
+use nym_chat::receipt::{Receipt, TreeHead};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Signs the receipts handed out with fetch responses (see the receipt module of the library).
+/// Signing every message of a big room on every fetch would be slow, so receipts are signed once
+/// when they are first requested and kept in memory.
+///
+/// The same key signs the heads of the Merkle tree over the log. The trees are kept in memory too
+/// and only extended by the messages that arrived since they were last asked for.
+#[derive(Clone)]
+pub struct Receipts {
+    key: Arc<SigningKey>,
+    /// Receipts of the first messages of every service provider, in order
+    signed: Arc<Mutex<HashMap<String, Vec<Receipt>>>>,
+    /// Trees over the first messages of every service provider
+    trees: Arc<Mutex<HashMap<String, Tree>>>,
+}
+
+impl Receipts {
+    pub fn new(key: SigningKey) -> Receipts {
+        Receipts {
+            key: Arc::new(key),
+            signed: Default::default(),
+            trees: Default::default(),
+        }
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    /// Receipts of the messages `store.since(provider, last_seen)` returns
+    pub fn since(&self, store: &Store, provider: &str, last_seen: usize) -> Vec<Receipt> {
+        let mut signed = self.signed.lock().unwrap();
+        let signed = signed.entry(provider.to_owned()).or_default();
+        for (digest, arrived) in store.receipt_data(provider, signed.len()) {
+            let index = signed.len() as u64;
+            signed.push(Receipt::sign(&self.key, provider, digest, index, arrived));
+        }
+        signed.get(last_seen..).unwrap_or(&[]).to_vec()
+    }
+
+    /// Signed head of the tree over all messages of `provider` the store serves
+    pub fn tree_head(&self, store: &Store, provider: &str) -> TreeHead {
+        let mut trees = self.trees.lock().unwrap();
+        let tree = extend(&mut trees, store, provider);
+        TreeHead::sign(
+            &self.key,
+            provider,
+            tree.len() as u64,
+            tree.root(tree.len()),
+        )
+    }
+
+    /// Proof that the tree over the first `old_size` messages is a prefix of the one over the first
+    /// `new_size` messages, `None` if the log isn't that long (yet)
+    pub fn consistency(
+        &self,
+        store: &Store,
+        provider: &str,
+        old_size: usize,
+        new_size: usize,
+    ) -> Option<Vec<Hash>> {
+        let mut trees = self.trees.lock().unwrap();
+        let tree = extend(&mut trees, store, provider);
+        if old_size > new_size || new_size > tree.len() {
+            return None;
+        }
+        Some(tree.consistency_proof(old_size, new_size))
+    }
+}
+
+/// The tree of `provider` with the messages the store served since it was last extended added
+fn extend<'a>(trees: &'a mut HashMap<String, Tree>, store: &Store, provider: &str) -> &'a Tree {
+    let tree = trees.entry(provider.to_owned()).or_default();
+    let digests = store.digests(provider);
+    for digest in digests.get(tree.len()..).unwrap_or(&[]) {
+        tree.push(merkle::leaf_hash(digest));
+    }
+    tree
+}
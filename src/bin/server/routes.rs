@@ -0,0 +1,617 @@
+//! The HTTP API clients sync up with, see the comment in `main` on what it does and doesn't leak.
+//! Every endpoint is built by its own function from the shared [`State`], so it can be served (or
+//! tried out with `warp::test`) on its own and [`all`] only puts them together. Tenant endpoints
+//! are served at `/<tenant>/...` and for the default tenant also at the root, for clients that
+//! don't know about tenants.
+
+use crate::events::{self, EventLog};
+use crate::receipts::Receipts;
+use crate::snapshot::Snapshots;
+use crate::stats;
+use crate::store::Store;
+use crate::tenant::{Addresses, Ingest};
+use crate::watchdog::Watchdog;
+use nym_chat::receipt::Receipt;
+use nym_chat::{EncryptedMessage, Gap};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+use warp::http::StatusCode;
+use warp::reply::{Reply, WithHeader};
+use warp::{Filter, Rejection};
+
+/// Size classes padded fetch responses are rounded up to, bigger responses are padded to a multiple
+/// of the largest class
+const FETCH_SIZE_CLASSES: &[usize] = &[4 << 10, 16 << 10, 64 << 10, 256 << 10, 1 << 20];
+
+/// Query parameters of the fetch endpoint
+#[derive(Deserialize)]
+struct FetchQuery {
+    /// Pad the response to one of the [`FETCH_SIZE_CLASSES`]
+    #[serde(default)]
+    pad: bool,
+    /// Respond with a [`FetchResponse`] including receipts instead of only the messages
+    #[serde(default)]
+    receipts: bool,
+    /// Skip messages past the retention period instead of sending placeholders and say so in a
+    /// [`Gap`], also responds with a [`FetchResponse`]
+    #[serde(default)]
+    gaps: bool,
+}
+
+/// Query parameters of the admin endpoints
+#[derive(Deserialize)]
+struct AdminQuery {
+    token: Option<String>,
+}
+
+/// Fetch response for clients that asked for receipts, the receipts are in the same order as the
+/// messages. Servers without a receipt key send none.
+#[derive(Serialize)]
+struct FetchResponse<'a> {
+    messages: &'a [EncryptedMessage],
+    receipts: Vec<Receipt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gap: Option<Gap>,
+}
+
+/// Everything the endpoints serve from, cloned into each of them
+#[derive(Clone)]
+pub struct State {
+    /// Tenant also served at the root
+    pub default_tenant: String,
+    pub messages: Arc<Mutex<Store>>,
+    pub addresses: Addresses,
+    pub receipts: Option<Receipts>,
+    pub snapshots: Snapshots,
+    pub watchdog: Watchdog,
+    pub event_log: EventLog,
+    /// Names of all tenants, envelopes submitted for others are refused
+    pub tenants: Arc<Vec<String>>,
+    /// Ingestion of envelopes POSTed to /submit, only with --loopback
+    pub loopback: Option<Arc<Mutex<Ingest>>>,
+    /// Serve /stats
+    pub stats: bool,
+    /// Token of the admin endpoints, they don't exist without one
+    pub admin_token: Option<Arc<String>>,
+}
+
+/// `warp::path!` of a tenant endpoint, extracting the tenant's name before the path's parameters
+macro_rules! tenant_path {
+    ($state:expr, $($path:tt)*) => {{
+        let default_tenant = $state.default_tenant.clone();
+        warp::any()
+            .map(move || default_tenant.clone())
+            .and(warp::path!($($path)*))
+            .or(warp::path::param::<String>().and(warp::path!($($path)*)))
+            .unify()
+    }};
+}
+
+/// All endpoints of the API
+#[allow(clippy::let_and_return)] // only without the web-ui feature
+pub fn all(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let routes = fetch(state)
+        .or(snapshot(state))
+        .or(health(state))
+        .or(receipt_key(state))
+        .or(provider_address(state))
+        .or(version(state))
+        .or(schema(state))
+        .or(tree_head(state))
+        .or(consistency(state))
+        .or(submit(state))
+        .or(admin(state))
+        .or(events(state))
+        .or(metrics(state));
+    #[cfg(feature = "web-ui")]
+    let routes = routes.or(web(state));
+    routes
+}
+
+/// Messages since the given index, `/fetch/<last seen>`
+pub fn fetch(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let state = state.clone();
+    tenant_path!(state, "fetch" / usize)
+        .and(warp::query::<FetchQuery>())
+        .and_then(move |tenant: String, last_seen, query: FetchQuery| {
+            let body = fetch_body(
+                &state.messages,
+                &state.addresses,
+                state.receipts.as_ref(),
+                &tenant,
+                last_seen,
+                &query,
+            );
+            async move { body.ok_or_else(warp::reject::not_found) }
+        })
+}
+
+/// Clients that know the receipt key audit the log: they keep the latest signed tree head and
+/// ask for a proof that the next one only appended messages
+pub fn tree_head(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let state = state.clone();
+    tenant_path!(state, "tree-head").and_then(move |tenant: String| {
+        let body = signed_tree_head(
+            &state.messages,
+            &state.addresses,
+            state.receipts.as_ref(),
+            &tenant,
+        );
+        async move { body.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Proof that the log only grew between two tree heads, `/consistency/<old size>/<new size>`
+pub fn consistency(
+    state: &State,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let state = state.clone();
+    tenant_path!(state, "consistency" / usize / usize).and_then(
+        move |tenant: String, old_size, new_size| {
+            let body = consistency_proof(
+                &state.messages,
+                &state.addresses,
+                state.receipts.as_ref(),
+                &tenant,
+                old_size,
+                new_size,
+            );
+            async move { body.ok_or_else(warp::reject::not_found) }
+        },
+    )
+}
+
+/// Loopback clients hand us their envelopes directly instead of through a Nym client
+pub fn submit(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let state = state.clone();
+    // The method is checked after the path, otherwise every GET request that no endpoint serves
+    // would be answered with 405 instead of 404
+    tenant_path!(state, "submit")
+        .and(warp::post())
+        .and(warp::body::bytes())
+        .and_then(move |tenant: String, body: warp::hyper::body::Bytes| {
+            let accepted = state.loopback.as_ref().map_or(false, |ingest| {
+                submit_envelope(ingest, &state.addresses, &state.tenants, &tenant, &body)
+            });
+            async move {
+                match accepted {
+                    true => Ok(StatusCode::ACCEPTED),
+                    false => Err(warp::reject::not_found()),
+                }
+            }
+        })
+}
+
+/// Clients only given the URL learn where to send from here, it's unknown (404) until the tenant's
+/// Nym client told us its address
+pub fn provider_address(
+    state: &State,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let state = state.clone();
+    tenant_path!(state, "provider-address").and_then(move |tenant: String| {
+        let address = state.addresses.lock().unwrap().get(&tenant).cloned();
+        async move { address.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// New clients start with the latest snapshot and fetch the rest from there
+pub fn snapshot(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let state = state.clone();
+    tenant_path!(state, "snapshot").and_then(move |tenant: String| {
+        let body = latest_snapshot(&state.snapshots, &state.addresses, &tenant);
+        async move { body.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Ready once the Nym clients of all tenants are connected, for orchestrators and monitoring
+pub fn health(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let watchdog = state.watchdog.clone();
+    warp::path!("readyz").map(move || {
+        let status = match watchdog.ready() {
+            true => StatusCode::OK,
+            false => StatusCode::SERVICE_UNAVAILABLE,
+        };
+        warp::reply::with_status(watchdog.render(), status)
+    })
+}
+
+/// Clients can get the verifying key of receipts here, but should better compare it with one they
+/// got from the operator out of band
+pub fn receipt_key(
+    state: &State,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let receipts = state.receipts.clone();
+    warp::path!("receipt-key").and_then(move || {
+        let key = receipts
+            .as_ref()
+            .map(|receipts| receipts.verifying_key().to_string());
+        async move { key.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Lets clients (e.g. `nym-chat doctor`) tell whether they speak the same protocol version
+pub fn version(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    tenant_path!(state, "version").map(|_tenant: String| env!("CARGO_PKG_VERSION"))
+}
+
+/// Third-party implementations can check the wire types against the running version
+pub fn schema(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let schema = Arc::new(nym_chat::schema::wire_types().to_string());
+    tenant_path!(state, "schema").map(move |_tenant: String| {
+        warp::reply::with_header(
+            schema.to_string(),
+            "content-type",
+            "application/schema+json",
+        )
+    })
+}
+
+/// Operators see recent server events at /admin, without the token it doesn't exist
+pub fn admin(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("admin")
+        .and(authorized(state))
+        .map(|| warp::reply::html(events::PAGE))
+}
+
+/// New server events for the admin page as they happen, behind the admin token as well
+pub fn events(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let event_log = state.event_log.clone();
+    warp::path!("admin" / "events")
+        .and(authorized(state))
+        .map(move || warp::sse::reply(warp::sse::keep_alive().stream(event_log.stream())))
+}
+
+/// Operators can opt into a page of aggregate statistics to gauge the load, it never shows
+/// individual messages or arrival times
+pub fn metrics(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let state = state.clone();
+    warp::path!("stats").and_then(move || {
+        let page = state
+            .stats
+            .then(|| stats::render(&state.messages.lock().unwrap(), Some(&state.watchdog)));
+        async move { page.ok_or_else(warp::reject::not_found) }
+    })
+}
+
+/// Page to read (and with --loopback also write) a room in the browser, see the web module
+#[cfg(feature = "web-ui")]
+pub fn web(state: &State) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    tenant_path!(state, "web").map(|_tenant: String| warp::reply::html(crate::web::PAGE))
+}
+
+/// Passes requests carrying the admin token, as a bearer token or the `token` query parameter
+fn authorized(state: &State) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let admin_token = state.admin_token.clone();
+    warp::header::optional::<String>("authorization")
+        .and(warp::query::<AdminQuery>())
+        .and_then(move |header: Option<String>, query: AdminQuery| {
+            let authorized = admin_token.as_ref().map_or(false, |token| {
+                events::authorized(token, header.as_deref(), query.token.as_deref())
+            });
+            async move {
+                match authorized {
+                    true => Ok(()),
+                    false => Err(warp::reject::not_found()),
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// Body of a fetch response for `tenant`, `None` if there is no such tenant (yet)
+fn fetch_body(
+    store: &Mutex<Store>,
+    addresses: &Addresses,
+    receipts: Option<&Receipts>,
+    tenant: &str,
+    last_seen: usize,
+    query: &FetchQuery,
+) -> Option<WithHeader<String>> {
+    debug!(
+        "fetching messages of {} beginning from {}",
+        tenant, last_seen
+    );
+    let address = addresses.lock().unwrap().get(tenant)?.clone();
+    let store = store.lock().unwrap();
+    // Clients that understand gaps don't get the placeholders of pruned messages
+    let start = match query.gaps {
+        true => last_seen.max(store.first_available(&address)),
+        false => last_seen,
+    };
+    let messages = store.since(&address, start);
+    let mut body = match query.receipts || query.gaps {
+        true => serde_json::to_string(&FetchResponse {
+            messages,
+            receipts: receipts
+                .filter(|_| query.receipts)
+                .map(|receipts| receipts.since(&store, &address, start))
+                .unwrap_or_default(),
+            gap: Some(Gap {
+                first_available: start,
+                pruned: start - last_seen,
+            })
+            .filter(|gap| gap.pruned > 0),
+        }),
+        false => serde_json::to_string(messages),
+    }
+    .expect("serialization can't fail");
+    drop(store);
+    if query.pad {
+        // Trailing whitespace doesn't change the meaning of the JSON
+        let padding = padded_len(body.len()) - body.len();
+        body.extend(std::iter::repeat(' ').take(padding));
+    }
+    Some(warp::reply::with_header(
+        body,
+        "content-type",
+        "application/json",
+    ))
+}
+
+/// Store an envelope submitted over HTTP for `tenant`, false if there is no such tenant. Until
+/// its Nym client told us the tenant's address the messages are filed under a placeholder, so
+/// loopback tests don't need a Nym client at all.
+fn submit_envelope(
+    ingest: &Mutex<Ingest>,
+    addresses: &Addresses,
+    tenants: &[String],
+    tenant: &str,
+    body: &[u8],
+) -> bool {
+    if !tenants.iter().any(|name| name == tenant) {
+        return false;
+    }
+    let address = addresses
+        .lock()
+        .unwrap()
+        .entry(tenant.to_owned())
+        .or_insert_with(|| format!("loopback-{}", tenant))
+        .clone();
+    ingest.lock().unwrap().accept(tenant, &address, body);
+    true
+}
+
+/// Signed tree head of `tenant`'s log, `None` if there is no such tenant or no receipt key
+fn signed_tree_head(
+    store: &Mutex<Store>,
+    addresses: &Addresses,
+    receipts: Option<&Receipts>,
+    tenant: &str,
+) -> Option<warp::reply::Json> {
+    let receipts = receipts?;
+    let address = addresses.lock().unwrap().get(tenant)?.clone();
+    let head = receipts.tree_head(&store.lock().unwrap(), &address);
+    Some(warp::reply::json(&head))
+}
+
+/// Consistency proof between the trees over the first `old_size` and `new_size` messages of
+/// `tenant`'s log, `None` if there is no such tenant, no receipt key or the log is too short
+fn consistency_proof(
+    store: &Mutex<Store>,
+    addresses: &Addresses,
+    receipts: Option<&Receipts>,
+    tenant: &str,
+    old_size: usize,
+    new_size: usize,
+) -> Option<warp::reply::Json> {
+    let receipts = receipts?;
+    let address = addresses.lock().unwrap().get(tenant)?.clone();
+    let proof = receipts.consistency(&store.lock().unwrap(), &address, old_size, new_size)?;
+    Some(warp::reply::json(&proof))
+}
+
+/// The latest snapshot of `tenant`, `None` if there is no such tenant or no snapshot yet
+fn latest_snapshot(
+    snapshots: &Snapshots,
+    addresses: &Addresses,
+    tenant: &str,
+) -> Option<WithHeader<Vec<u8>>> {
+    let address = addresses.lock().unwrap().get(tenant)?.clone();
+    let snapshot = snapshots.lock().unwrap().get(&address)?.clone();
+    Some(warp::reply::with_header(
+        snapshot.to_vec(),
+        "content-type",
+        "application/octet-stream",
+    ))
+}
+
+/// Round the length of a fetch response up to its size class
+fn padded_len(len: usize) -> usize {
+    let largest = FETCH_SIZE_CLASSES[FETCH_SIZE_CLASSES.len() - 1];
+    FETCH_SIZE_CLASSES
+        .iter()
+        .copied()
+        .find(|class| *class >= len)
+        .unwrap_or_else(|| (len + largest - 1) / largest * largest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, Quota};
+    use crate::time::Clock;
+    use nym_chat::broadcast::SigningKey;
+    use nym_chat::merkle;
+    use nym_chat::receipt::TreeHead;
+    use nym_chat::{Envelope, Key, Message};
+    use std::collections::HashMap;
+    use tokio::sync::watch;
+    use warp::test::request;
+
+    const TENANT: &str = "default";
+
+    /// State of a loopback server with the single tenant [`TENANT`]
+    fn state(config: Config, receipts: Option<Receipts>) -> State {
+        let messages = Arc::new(Mutex::new(Store::in_memory()));
+        let (_, config) = watch::channel(config);
+        let clock = Clock::new(None);
+        State {
+            default_tenant: TENANT.into(),
+            messages: messages.clone(),
+            addresses: Addresses::default(),
+            receipts,
+            snapshots: Snapshots::default(),
+            watchdog: Watchdog::new(vec![TENANT], None),
+            event_log: EventLog::new(clock),
+            tenants: Arc::new(vec![TENANT.into()]),
+            loopback: Some(Arc::new(Mutex::new(Ingest::new(messages, config, clock)))),
+            stats: false,
+            admin_token: Some(Arc::new("secret".into())),
+        }
+    }
+
+    fn envelope(room: &Key, text: &str) -> Vec<u8> {
+        Envelope {
+            msg: Message::new("test".into(), text.into()).encrypt(room),
+            max_age_secs: None,
+        }
+        .to_bytes()
+    }
+
+    async fn submit_to(state: &State, path: &str, body: Vec<u8>) -> StatusCode {
+        let response = request()
+            .method("POST")
+            .path(path)
+            .body(body)
+            .reply(&all(state))
+            .await;
+        response.status()
+    }
+
+    async fn fetch_json(state: &State, path: &str) -> serde_json::Value {
+        let response = request().path(path).reply(&all(state)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn submitted_messages_are_fetched() {
+        let state = state(Config::default(), None);
+        let room = Key::generate();
+        for text in &["first", "second"] {
+            let status = submit_to(&state, "/submit", envelope(&room, text)).await;
+            assert_eq!(status, StatusCode::ACCEPTED);
+        }
+
+        let messages = fetch_json(&state, "/fetch/0").await;
+        assert_eq!(messages.as_array().unwrap().len(), 2);
+        let messages = fetch_json(&state, "/default/fetch/1").await;
+        assert_eq!(messages.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unknown_tenants_are_not_found() {
+        let state = state(Config::default(), None);
+        let room = Key::generate();
+        let status = submit_to(&state, "/other/submit", envelope(&room, "hi")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let response = request().path("/other/fetch/0").reply(&all(&state)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn submit_needs_loopback() {
+        let state = State {
+            loopback: None,
+            ..state(Config::default(), None)
+        };
+        let status = submit_to(&state, "/submit", envelope(&Key::generate(), "hi")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn receipts_verify() {
+        let receipts = Receipts::new(SigningKey::generate());
+        let key = receipts.verifying_key();
+        let state = state(Config::default(), Some(receipts));
+        let room = Key::generate();
+        submit_to(&state, "/submit", envelope(&room, "hi")).await;
+
+        let response = fetch_json(&state, "/fetch/0?receipts=true").await;
+        let messages: Vec<EncryptedMessage> =
+            serde_json::from_value(response["messages"].clone()).unwrap();
+        let receipts: Vec<Receipt> = serde_json::from_value(response["receipts"].clone()).unwrap();
+        assert_eq!(receipts.len(), 1);
+        // Receipts are only valid for the tenant's own log
+        assert!(receipts[0].verify(&key, "loopback-default"));
+        assert!(!receipts[0].verify(&key, "loopback-other"));
+        assert_eq!(receipts[0].digest, messages[0].digest());
+        assert_eq!(receipts[0].index, 0);
+    }
+
+    #[tokio::test]
+    async fn tree_head_covers_the_log() {
+        let receipts = Receipts::new(SigningKey::generate());
+        let key = receipts.verifying_key();
+        let state = state(Config::default(), Some(receipts));
+        let room = Key::generate();
+        for text in &["first", "second"] {
+            submit_to(&state, "/submit", envelope(&room, text)).await;
+        }
+
+        let messages: Vec<EncryptedMessage> =
+            serde_json::from_value(fetch_json(&state, "/fetch/0").await).unwrap();
+        let head: TreeHead =
+            serde_json::from_value(fetch_json(&state, "/tree-head").await).unwrap();
+        assert!(head.verify(&key, "loopback-default"));
+        assert!(!head.verify(&key, "loopback-other"));
+        let leaves = messages
+            .iter()
+            .map(|msg| merkle::leaf_hash(&msg.digest()))
+            .collect::<Vec<_>>();
+        assert_eq!(head.size, 2);
+        assert_eq!(head.root, merkle::root(&leaves));
+    }
+
+    #[tokio::test]
+    async fn storage_quota_drops_messages() {
+        let mut quotas = HashMap::new();
+        quotas.insert(
+            TENANT.to_owned(),
+            Quota {
+                max_stored_bytes: Some(1),
+                ..Quota::default()
+            },
+        );
+        let config = Config {
+            quotas,
+            ..Config::default()
+        };
+        let state = state(config, None);
+        let room = Key::generate();
+        for text in &["first", "second"] {
+            // Dropped messages are accepted all the same, senders can't tell
+            let status = submit_to(&state, "/submit", envelope(&room, text)).await;
+            assert_eq!(status, StatusCode::ACCEPTED);
+        }
+
+        let messages = fetch_json(&state, "/fetch/0").await;
+        assert_eq!(messages.as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn admin_needs_the_token() {
+        let state = state(Config::default(), None);
+        let response = request().path("/admin").reply(&all(&state)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = request()
+            .path("/admin?token=wrong")
+            .reply(&all(&state))
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = request()
+            .path("/admin?token=secret")
+            .reply(&all(&state))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = request()
+            .path("/admin")
+            .header("authorization", "Bearer secret")
+            .reply(&all(&state))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::store::Store;
+use crate::tenant::Addresses;
+use nym_chat::snapshot;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, error};
+
+/// Latest snapshot of every service provider's messages (see the snapshot module of the library)
+pub type Snapshots = Arc<Mutex<HashMap<String, Arc<Vec<u8>>>>>;
+
+/// Rebuild the snapshots every `interval` seconds if the store changed since the last time.
+/// Deleted messages are only removed from the snapshots by the next rebuild.
+pub async fn refresh(
+    store: Arc<Mutex<Store>>,
+    addresses: Addresses,
+    snapshots: Snapshots,
+    interval: u64,
+) {
+    let mut timer = tokio::time::interval(Duration::from_secs(interval));
+    let mut last_revision = None;
+    loop {
+        timer.tick().await;
+        let providers = addresses
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        let revision = store.lock().unwrap().revision();
+        if last_revision == Some(revision) || providers.is_empty() {
+            continue;
+        }
+
+        for provider in providers {
+            // Serializing is quick, compressing happens without holding the lock
+            let serialized = bincode::serialize(store.lock().unwrap().since(&provider, 0));
+            let serialized = match serialized {
+                Ok(serialized) => serialized,
+                Err(e) => {
+                    error!("Couldn't serialize snapshot: {}", e);
+                    continue;
+                }
+            };
+            let compressed =
+                tokio::task::spawn_blocking(move || snapshot::compress(&serialized)).await;
+            let compressed = compressed.expect("compression task panicked");
+            debug!("New snapshot of {} ({} bytes)", provider, compressed.len());
+            snapshots
+                .lock()
+                .unwrap()
+                .insert(provider, Arc::new(compressed));
+        }
+        last_revision = Some(revision);
+    }
+}
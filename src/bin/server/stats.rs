@@ -0,0 +1,38 @@
+use crate::store::{Store, SECONDS_PER_DAY};
+use crate::watchdog::Watchdog;
+use std::fmt::Write;
+
+/// Number of days shown on the stats page
+const DAYS_SHOWN: usize = 30;
+
+/// Render the operator stats page. It only contains aggregates: there are no room tags to count
+/// since all messages look the same to the server, so rooms can't be told apart. Without a
+/// watchdog (e.g. for `server stats`) the state of the Nym clients is left out.
+pub fn render(store: &Store, watchdog: Option<&Watchdog>) -> String {
+    let mut page = String::new();
+    let _ = writeln!(page, "nym-chat server statistics\n");
+    let _ = writeln!(page, "Messages stored: {}", store.len());
+    let _ = writeln!(page, "Storage size:    {} bytes", store.size());
+    let _ = writeln!(
+        page,
+        "Rooms:           unknown, messages of all rooms look the same to the server\n"
+    );
+
+    if let Some(watchdog) = watchdog {
+        let _ = writeln!(page, "Nym clients:\n{}", watchdog.render());
+    }
+
+    let _ = writeln!(
+        page,
+        "Messages per day (UTC), last {} active days:",
+        DAYS_SHOWN
+    );
+    for (day, count) in store.per_day().iter().rev().take(DAYS_SHOWN) {
+        // Days past what chrono can represent only come from a broken clock, they keep their number
+        let _ = match chrono::DateTime::from_timestamp((day * SECONDS_PER_DAY) as i64, 0) {
+            Some(time) => writeln!(page, "{}  {}", time.date_naive(), count),
+            None => writeln!(page, "day {}  {}", day, count),
+        };
+    }
+    page
+}
@@ -0,0 +1,1174 @@
+use fs2::FileExt;
+use nym_chat::{EncryptedMessage, Key};
+use redb::{Database, Durability, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::warn;
+
+/// Identifies store files and their format version
+const MAGIC: &[u8; 8] = b"NYMCHAT6";
+
+/// Header flag of stores whose records are encrypted
+const ENCRYPTED: u8 = 1;
+
+/// Length of the key id following the header flag of encrypted stores
+const KEY_ID_LEN: usize = 16;
+
+/// Every record is preceded by its length (u32), sequence number (u64) and checksum
+const FRAME_PREFIX_LEN: usize = 4 + 8 + 32;
+
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Records of a redb store by sequence number
+const RECORDS: TableDefinition<u64, &[u8]> = TableDefinition::new("records");
+
+/// Metadata of a redb store, only the header so far
+const META: TableDefinition<&str, &[u8]> = TableDefinition::new("meta");
+
+/// All messages ever sent to the server, tagged with the address of the service provider identity
+/// (see the tenant module) that received them. They are kept in memory for fetching and, if a path
+/// is configured, persisted to an append-only file of bincode records together with their
+/// (possibly coarsened, see the time module) arrival times so they survive restarts.
+///
+/// Records are persisted by a [`Backend`], see [`Location`] for the ones to choose from. The
+/// default is a log: every record is framed with its length, a sequence number and a checksum
+/// chained to the previous record's, so a record that was only partially written before a crash
+/// is detected and dropped when opening the store and reordered or missing records are detected
+/// by [`verify`]. Messages are synced to disk before they are served (see [`Store::since`]).
+///
+/// Payloads are end-to-end encrypted anyway, but the store still reveals metadata like message
+/// sizes and ordering to anyone who gets hold of the file. With a store key every record is
+/// additionally encrypted at rest. The file header contains the key's id, so opening a store with
+/// the wrong key (or without one) fails instead of silently mixing formats.
+///
+/// Messages can't be removed without changing the indices clients fetch by, so expired messages
+/// are replaced by placeholders. [`Store::compact`] rewrites the file so the expired ciphertexts
+/// are actually gone from disk, [`Compaction`] does the same without locking the store for long.
+pub struct Store {
+    /// Messages by the address of the service provider that received them
+    feeds: HashMap<String, Feed>,
+    /// Number of messages per day (days since the unix epoch), only aggregates are kept in memory
+    per_day: BTreeMap<u64, usize>,
+    /// Size of all records in bytes, also tracked for in-memory stores
+    size: u64,
+    /// Expiry times requested by clients and the (provider, index) of the messages they apply to
+    expiring: BTreeMap<u64, Vec<(String, usize)>>,
+    backend: Option<Box<dyn Backend>>,
+    key: Option<Key>,
+    /// Only sync when [`Store::sync`] is called instead of after every message
+    group_commit: bool,
+    /// Records were written since the last sync
+    unsynced: bool,
+    /// Changes whenever the served messages change
+    revision: u64,
+}
+
+impl Store {
+    /// A store that forgets everything when the server stops
+    pub fn in_memory() -> Store {
+        Store {
+            feeds: HashMap::new(),
+            per_day: BTreeMap::new(),
+            size: 0,
+            expiring: BTreeMap::new(),
+            backend: None,
+            key: None,
+            group_commit: false,
+            unsynced: false,
+            revision: 0,
+        }
+    }
+
+    /// Open (or create) the store at `location`, records are encrypted with `key` if one is
+    /// given. A last record that was only partially written (e.g. because the server crashed) is
+    /// dropped, damage anywhere else is an error. With `group_commit` new messages are only synced
+    /// to disk by [`Store::sync`] instead of immediately.
+    pub fn open(
+        location: &Location,
+        key: Option<Key>,
+        group_commit: bool,
+    ) -> Result<Store, anyhow::Error> {
+        let mut backend = location.open(&header(key.as_ref()), group_commit)?;
+        let mut feeds = HashMap::<_, Feed>::new();
+        let mut per_day = BTreeMap::new();
+        let mut expiring = BTreeMap::<_, Vec<_>>::new();
+        let size = backend.load(&mut |record| {
+            let record = decode(record, key.as_ref())?;
+            *per_day.entry(record.arrived / SECONDS_PER_DAY).or_default() += 1;
+            let feed = feeds.entry(record.provider.clone()).or_default();
+            if let Some(expires) = record.expires {
+                let index = feed.messages.len();
+                expiring
+                    .entry(expires)
+                    .or_default()
+                    .push((record.provider, index));
+            }
+            feed.push(record.msg, record.digest, record.arrived, record.expires);
+            Ok(())
+        })?;
+        for feed in feeds.values_mut() {
+            feed.synced = feed.messages.len();
+        }
+
+        Ok(Store {
+            feeds,
+            per_day,
+            size,
+            expiring,
+            backend: Some(backend),
+            key,
+            group_commit,
+            unsynced: false,
+            revision: 0,
+        })
+    }
+
+    /// Number of messages received by all service providers
+    pub fn len(&self) -> usize {
+        self.feeds.values().map(|feed| feed.messages.len()).sum()
+    }
+
+    /// Size of all stored records in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Size of the ciphertexts that were deleted but are still in the store file until it's
+    /// compacted
+    pub fn dead_bytes(&self) -> u64 {
+        self.feeds.values().map(|feed| feed.dead).sum()
+    }
+
+    /// Size of the ciphertexts `provider` received, without deleted messages
+    pub fn stored_bytes(&self, provider: &str) -> u64 {
+        self.feeds.get(provider).map_or(0, |feed| feed.bytes)
+    }
+
+    /// Number of messages per day, keyed by days since the unix epoch
+    pub fn per_day(&self) -> &BTreeMap<u64, usize> {
+        &self.per_day
+    }
+
+    /// All messages received by `provider` after the first `last_seen` ones. Only messages that
+    /// are safely on disk are served: if the server crashed before syncing a message, its index
+    /// would go to the next one and clients that already fetched it would skip that one.
+    pub fn since(&self, provider: &str, last_seen: usize) -> &[EncryptedMessage] {
+        self.feeds
+            .get(provider)
+            .and_then(|feed| feed.messages[..feed.synced].get(last_seen..))
+            .unwrap_or(&[])
+    }
+
+    /// Index of the oldest message of `provider` that wasn't pruned for being past the retention
+    /// period, expired messages in between are still only placeholders
+    pub fn first_available(&self, provider: &str) -> usize {
+        self.feeds
+            .get(provider)
+            .map(|feed| feed.pruned.min(feed.synced))
+            .unwrap_or(0)
+    }
+
+    /// Digest and arrival time of the messages [`Store::since`] serves, receipts are signed over
+    /// them
+    pub fn receipt_data(&self, provider: &str, last_seen: usize) -> Vec<([u8; 32], u64)> {
+        self.feeds
+            .get(provider)
+            .map(|feed| {
+                let end = feed.synced;
+                let start = last_seen.min(end);
+                feed.digests[start..end]
+                    .iter()
+                    .copied()
+                    .zip(feed.arrivals[start..end].iter().copied())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Digests of all messages [`Store::since`] serves, the leaves of the Merkle tree over the log
+    pub fn digests(&self, provider: &str) -> &[[u8; 32]] {
+        self.feeds
+            .get(provider)
+            .map(|feed| &feed.digests[..feed.synced])
+            .unwrap_or(&[])
+    }
+
+    /// Store a message that `provider` received at `arrived` (unix seconds), it's deleted at
+    /// `expires` if the sender asked for that
+    pub fn push(
+        &mut self,
+        provider: &str,
+        msg: EncryptedMessage,
+        arrived: u64,
+        expires: Option<u64>,
+    ) -> Result<(), anyhow::Error> {
+        let digest = msg.digest();
+        let record = Record {
+            arrived,
+            provider: provider.to_owned(),
+            msg: &msg,
+            digest,
+            expires,
+        };
+        let record = encode(&record, self.key.as_ref())?;
+        self.size += match &mut self.backend {
+            Some(backend) => {
+                self.unsynced = true;
+                backend.append(&record)?
+            }
+            None => record.len() as u64,
+        };
+        *self.per_day.entry(arrived / SECONDS_PER_DAY).or_default() += 1;
+        let feed = self.feeds.entry(provider.to_owned()).or_default();
+        if let Some(expires) = expires {
+            let index = feed.messages.len();
+            self.expiring
+                .entry(expires)
+                .or_default()
+                .push((provider.to_owned(), index));
+        }
+        feed.push(msg, digest, arrived, expires);
+
+        if !self.group_commit {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Make sure all messages are on disk and start serving the ones that weren't yet
+    pub fn sync(&mut self) -> Result<(), anyhow::Error> {
+        if self.unsynced {
+            if let Some(backend) = &mut self.backend {
+                backend.sync()?;
+            }
+            self.unsynced = false;
+        }
+        for feed in self.feeds.values_mut() {
+            if feed.synced != feed.messages.len() {
+                feed.synced = feed.messages.len();
+                self.revision += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes whenever the served messages change, i.e. when new ones become available or old
+    /// ones are deleted
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Stop serving messages that arrived before `cutoff` (unix seconds), they are replaced by
+    /// placeholders so the indices clients fetch by stay the same. Returns the number of pruned
+    /// messages. They stay in the store file until it's compacted.
+    pub fn prune(&mut self, cutoff: u64) -> usize {
+        let pruned = self.feeds.values_mut().map(|feed| feed.prune(cutoff)).sum();
+        self.revision += pruned as u64;
+        pruned
+    }
+
+    /// Replace messages whose sender asked for them to be deleted before `now` (unix seconds) by
+    /// placeholders, returns the number of expired messages. Like [`Store::prune`] this only
+    /// affects what's served until the store is compacted.
+    pub fn expire(&mut self, now: u64) -> usize {
+        let still_valid = self.expiring.split_off(&(now + 1));
+        let expired = std::mem::replace(&mut self.expiring, still_valid);
+        let mut count = 0;
+        for (provider, index) in expired.into_values().flatten() {
+            if let Some(feed) = self.feeds.get_mut(&provider) {
+                if index < feed.messages.len() {
+                    feed.delete(index);
+                    count += 1;
+                }
+            }
+        }
+        self.revision += count as u64;
+        count
+    }
+
+    /// Rewrite the store with the messages as they are in memory, so pruned and expired messages
+    /// are only kept as placeholders. Backends make sure a crash leaves either the old or the new
+    /// records.
+    pub fn compact(&mut self) -> Result<(), anyhow::Error> {
+        let records = self.records()?;
+        if let Some(backend) = &mut self.backend {
+            self.size = backend.rewrite(&records)?;
+            self.unsynced = false;
+        }
+        for feed in self.feeds.values_mut() {
+            feed.synced = feed.messages.len();
+            feed.dead = 0;
+        }
+        Ok(())
+    }
+
+    /// Take a snapshot of the messages to compact the store with, see [`Compaction`]
+    pub fn begin_compaction(&self) -> Compaction {
+        let staged = self.backend.as_ref().and_then(|backend| backend.stage());
+        let mut records = Vec::new();
+        if staged.is_some() {
+            for (provider, feed) in &self.feeds {
+                for idx in 0..feed.messages.len() {
+                    let record = feed.record(provider, idx);
+                    records.push(Record {
+                        msg: record.msg.clone(),
+                        arrived: record.arrived,
+                        provider: record.provider,
+                        digest: record.digest,
+                        expires: record.expires,
+                    });
+                }
+            }
+        }
+        Compaction {
+            records,
+            key: self.key.clone(),
+            feeds: self
+                .feeds
+                .iter()
+                .map(|(provider, feed)| (provider.clone(), (feed.messages.len(), feed.dead)))
+                .collect(),
+            staged,
+        }
+    }
+
+    /// Replace the store file by the one `compaction` wrote, with the messages that arrived since
+    /// it was begun appended. Backends that can't stage a rewrite are compacted right here.
+    pub fn finish_compaction(&mut self, compaction: Compaction) -> Result<(), anyhow::Error> {
+        let (staged, backend) = match (compaction.staged, &mut self.backend) {
+            (Some(staged), Some(backend)) => (staged, backend),
+            _ => return self.compact(),
+        };
+        let mut tail = Vec::new();
+        for (provider, feed) in &self.feeds {
+            let start = compaction.feeds.get(provider).map_or(0, |(len, _)| *len);
+            for idx in start..feed.messages.len() {
+                tail.push(encode(&feed.record(provider, idx), self.key.as_ref())?);
+            }
+        }
+        self.size = backend.install(staged, &tail)?;
+        self.unsynced = false;
+        for (provider, feed) in &mut self.feeds {
+            feed.synced = feed.messages.len();
+            // Messages deleted since the snapshot are still in the new file
+            let compacted = compaction.feeds.get(provider).map_or(0, |(_, dead)| *dead);
+            feed.dead = feed.dead.saturating_sub(compacted);
+        }
+        Ok(())
+    }
+
+    /// Copy all messages into a new store at `target`, which has to be empty. Returns the number
+    /// of copied messages.
+    pub fn migrate(&self, target: &Location) -> Result<usize, anyhow::Error> {
+        let mut backend = target.open(&header(self.key.as_ref()), false)?;
+        if backend.load(&mut |_| Ok(()))? > 0 {
+            anyhow::bail!("the target store isn't empty");
+        }
+        let records = self.records()?;
+        backend.rewrite(&records)?;
+        Ok(records.len())
+    }
+
+    /// All messages as encoded records, in the order they have to be loaded in
+    fn records(&self) -> Result<Vec<Vec<u8>>, anyhow::Error> {
+        let mut records = Vec::with_capacity(self.len());
+        for (provider, feed) in &self.feeds {
+            for idx in 0..feed.messages.len() {
+                records.push(encode(&feed.record(provider, idx), self.key.as_ref())?);
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Compacting a big store takes a while, so it's split up to only lock the store briefly:
+/// [`Store::begin_compaction`] takes a snapshot of the messages, [`Compaction::write`] writes them
+/// to a new file without access to the store and [`Store::finish_compaction`] appends the messages
+/// that arrived in the meantime and moves the new file over the old one.
+pub struct Compaction {
+    records: Vec<Record<EncryptedMessage>>,
+    key: Option<Key>,
+    /// Number of messages and deleted bytes of every feed in the snapshot
+    feeds: HashMap<String, (usize, u64)>,
+    /// `None` if the backend can only be rewritten in place (redb, which rewrites in one
+    /// transaction anyway)
+    staged: Option<StagedLog>,
+}
+
+impl Compaction {
+    /// Write the snapshot, this is the slow part and shouldn't hold the store's lock
+    pub fn write(&mut self) -> Result<(), anyhow::Error> {
+        let staged = match &mut self.staged {
+            Some(staged) => staged,
+            None => return Ok(()),
+        };
+        let key = self.key.as_ref();
+        let records = std::mem::take(&mut self.records)
+            .iter()
+            .map(|record| encode(record, key))
+            .collect::<Result<Vec<_>, _>>()?;
+        staged.write(&records)
+    }
+}
+
+/// Check a store file without modifying it: every record has to follow its predecessor's
+/// sequence number, match its checksum and be readable with `key`. Returns the number of records.
+pub fn verify(location: &Location, key: Option<&Key>) -> Result<u64, anyhow::Error> {
+    let path = match location {
+        Location::Log(path) => path,
+        // redb checks its own pages, so only the records themselves are left to check
+        Location::Redb(path) => {
+            let mut backend = Redb::open(Database::open(path)?, &header(key), false)?;
+            let mut records = 0;
+            backend.load(&mut |record| {
+                records += 1;
+                decode(record, key).map(|_| ())
+            })?;
+            return Ok(records);
+        }
+    };
+    let file = File::open(path)?;
+    let log = read_log(&file, &header(key), |record| {
+        decode(record, key).map(|_| ())
+    })?;
+    match log.damage {
+        None => Ok(log.chain.seq),
+        Some(Damage::Torn) => Err(anyhow::anyhow!(
+            "the last record is incomplete, it will be dropped when the server starts ({} intact \
+             records before it)",
+            log.chain.seq
+        )),
+        Some(Damage::Corrupt(reason)) => Err(anyhow::Error::msg(reason)),
+    }
+}
+
+/// Where a store is persisted, given as `--store [log:|redb:]<path>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    /// Our own append-only log file, the default. Simple and fast, but the whole file is rewritten
+    /// when compacting.
+    Log(PathBuf),
+    /// A redb database, transactional and with checksummed pages. Appending is somewhat slower.
+    Redb(PathBuf),
+}
+
+/// Exclusive lock on a store, the server and the maintenance commands hold it for as long as they
+/// use the store so they can't change it under each other. It's taken on a file next to the store,
+/// the log is replaced when compacting and a lock on it would go with the old file.
+pub struct StoreLock {
+    _file: File,
+}
+
+impl Location {
+    /// Take the store's lock, fails if another process holds it
+    pub fn lock(&self) -> Result<StoreLock, anyhow::Error> {
+        let (Location::Log(path) | Location::Redb(path)) = self;
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&lock_path)?;
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.kind() == fs2::lock_contended_error().kind() {
+                anyhow::bail!(
+                    "{} is used by a running server or maintenance command",
+                    path.display()
+                );
+            }
+            return Err(e.into());
+        }
+        Ok(StoreLock { _file: file })
+    }
+
+    fn open(&self, header: &[u8], group_commit: bool) -> Result<Box<dyn Backend>, anyhow::Error> {
+        Ok(match self {
+            Location::Log(path) => Box::new(LogFile::open(path, header)?),
+            Location::Redb(path) => {
+                Box::new(Redb::open(Database::create(path)?, header, group_commit)?)
+            }
+        })
+    }
+}
+
+impl FromStr for Location {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let location = match s.split_once(':') {
+            Some(("log", path)) => Location::Log(path.into()),
+            Some(("redb", path)) => Location::Redb(path.into()),
+            _ => Location::Log(s.into()),
+        };
+        match location {
+            Location::Log(path) | Location::Redb(path) if path.as_os_str().is_empty() => {
+                Err("the store path can't be empty".into())
+            }
+            location => Ok(location),
+        }
+    }
+}
+
+/// Persists the encoded records of a [`Store`], which keeps everything in memory itself
+trait Backend: Send {
+    /// Pass every record to `on_record` in order, returns their size in bytes. Called once when
+    /// opening the store.
+    fn load(
+        &mut self,
+        on_record: &mut dyn FnMut(&[u8]) -> Result<(), anyhow::Error>,
+    ) -> Result<u64, anyhow::Error>;
+
+    /// Add a record, it only has to be durable after the next [`Backend::sync`]. Returns the
+    /// bytes it takes up.
+    fn append(&mut self, record: &[u8]) -> Result<u64, anyhow::Error>;
+
+    fn sync(&mut self) -> Result<(), anyhow::Error>;
+
+    /// Atomically replace all records, returns their size in bytes
+    fn rewrite(&mut self, records: &[Vec<u8>]) -> Result<u64, anyhow::Error>;
+
+    /// A new file the records can be written to without the backend, see [`Compaction`]. `None`
+    /// if the backend can only be rewritten in place.
+    fn stage(&self) -> Option<StagedLog> {
+        None
+    }
+
+    /// Atomically replace all records by the `staged` ones followed by `tail`, returns their size
+    /// in bytes. Only called with what [`Backend::stage`] returned.
+    fn install(&mut self, _staged: StagedLog, _tail: &[Vec<u8>]) -> Result<u64, anyhow::Error> {
+        unreachable!("the backend doesn't stage rewrites")
+    }
+}
+
+/// The append-only log described at [`Store`]
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    header: Vec<u8>,
+    /// Position of the next record in the log
+    chain: Chain,
+    /// Length of the file up to the last completely written record
+    end: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path, header: &[u8]) -> Result<LogFile, anyhow::Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(header)?;
+            file.sync_all()?;
+        }
+        Ok(LogFile {
+            path: path.to_owned(),
+            end: file.metadata()?.len(),
+            file,
+            header: header.to_vec(),
+            chain: Chain::default(),
+        })
+    }
+}
+
+impl Backend for LogFile {
+    fn load(
+        &mut self,
+        on_record: &mut dyn FnMut(&[u8]) -> Result<(), anyhow::Error>,
+    ) -> Result<u64, anyhow::Error> {
+        let log = read_log(&self.file, &self.header, on_record)?;
+        match log.damage {
+            Some(Damage::Corrupt(reason)) => {
+                return Err(anyhow::anyhow!(
+                    "the store is damaged ({}), check it with --verify-store",
+                    reason
+                ))
+            }
+            Some(Damage::Torn) => {
+                warn!("Dropping the incomplete last record of the store");
+                self.file.set_len(log.end)?;
+                self.file.sync_all()?;
+            }
+            None => {}
+        }
+        self.chain = log.chain;
+        self.end = log.end;
+        Ok(log.end - self.header.len() as u64)
+    }
+
+    /// The log only moves on to the next position once the whole frame is written, a frame that
+    /// failed half way is cut off again so the next one follows the last good record
+    fn append(&mut self, record: &[u8]) -> Result<u64, anyhow::Error> {
+        let mut next = self.chain.clone();
+        let frame = next.frame(record);
+        if let Err(e) = self.file.write_all(&frame) {
+            self.file.set_len(self.end)?;
+            return Err(e.into());
+        }
+        self.chain = next;
+        self.end += frame.len() as u64;
+        Ok(frame.len() as u64)
+    }
+
+    fn sync(&mut self) -> Result<(), anyhow::Error> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    fn rewrite(&mut self, records: &[Vec<u8>]) -> Result<u64, anyhow::Error> {
+        let mut staged = self.stage().expect("logs stage rewrites");
+        staged.write(records)?;
+        self.install(staged, &[])
+    }
+
+    fn stage(&self) -> Option<StagedLog> {
+        Some(StagedLog {
+            path: self.path.with_extension("compacting"),
+            header: self.header.clone(),
+            chain: Chain::default(),
+            size: 0,
+        })
+    }
+
+    /// The new file is written next to the old one and then moved over it, so a crash leaves
+    /// either the old or the new file
+    fn install(&mut self, staged: StagedLog, tail: &[Vec<u8>]) -> Result<u64, anyhow::Error> {
+        let StagedLog {
+            path,
+            mut chain,
+            mut size,
+            ..
+        } = staged;
+        let mut file = OpenOptions::new().append(true).open(&path)?;
+        for record in tail {
+            let frame = chain.frame(record);
+            file.write_all(&frame)?;
+            size += frame.len() as u64;
+        }
+        file.sync_all()?;
+        std::fs::rename(&path, &self.path)?;
+        sync_dir(&self.path)?;
+
+        self.file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        self.chain = chain;
+        self.end = self.file.metadata()?.len();
+        Ok(size)
+    }
+}
+
+/// A compacted log written next to the store's, [`LogFile::install`] moves it over the store
+struct StagedLog {
+    path: PathBuf,
+    header: Vec<u8>,
+    /// Position after the written records
+    chain: Chain,
+    size: u64,
+}
+
+impl StagedLog {
+    fn write(&mut self, records: &[Vec<u8>]) -> Result<(), anyhow::Error> {
+        let mut compacted = std::io::BufWriter::new(File::create(&self.path)?);
+        compacted.write_all(&self.header)?;
+        for record in records {
+            let frame = self.chain.frame(record);
+            compacted.write_all(&frame)?;
+            self.size += frame.len() as u64;
+        }
+        compacted
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        Ok(())
+    }
+}
+
+/// Records in a redb table keyed by sequence number, every append is a transaction. With group
+/// commit they are committed without durability and a durable empty transaction syncs them.
+struct Redb {
+    db: Database,
+    next: u64,
+    group_commit: bool,
+    unsynced: bool,
+}
+
+impl Redb {
+    /// Check the header (or write it to a new database) like the log does
+    fn open(db: Database, header: &[u8], group_commit: bool) -> Result<Redb, anyhow::Error> {
+        let txn = db.begin_write()?;
+        {
+            let mut meta = txn.open_table(META)?;
+            let existing = meta.get("header")?.map(|value| value.value().to_vec());
+            match existing {
+                Some(existing) if existing != header => anyhow::bail!(
+                    "not a store, it was created by an older version or with a different store \
+                     key (or without one)"
+                ),
+                Some(_) => {}
+                None => {
+                    meta.insert("header", header)?;
+                }
+            }
+            txn.open_table(RECORDS)?;
+        }
+        txn.commit()?;
+        Ok(Redb {
+            db,
+            next: 0,
+            group_commit,
+            unsynced: false,
+        })
+    }
+}
+
+impl Backend for Redb {
+    fn load(
+        &mut self,
+        on_record: &mut dyn FnMut(&[u8]) -> Result<(), anyhow::Error>,
+    ) -> Result<u64, anyhow::Error> {
+        let txn = self.db.begin_read()?;
+        let table = txn.open_table(RECORDS)?;
+        let mut size = 0;
+        for entry in table.iter()? {
+            let (seq, record) = entry?;
+            if seq.value() != self.next {
+                anyhow::bail!("record {} is missing", self.next);
+            }
+            on_record(record.value())?;
+            size += record.value().len() as u64;
+            self.next += 1;
+        }
+        Ok(size)
+    }
+
+    fn append(&mut self, record: &[u8]) -> Result<u64, anyhow::Error> {
+        let mut txn = self.db.begin_write()?;
+        if self.group_commit {
+            txn.set_durability(Durability::None);
+            self.unsynced = true;
+        }
+        txn.open_table(RECORDS)?.insert(self.next, record)?;
+        txn.commit()?;
+        self.next += 1;
+        Ok(record.len() as u64)
+    }
+
+    fn sync(&mut self) -> Result<(), anyhow::Error> {
+        if std::mem::take(&mut self.unsynced) {
+            self.db.begin_write()?.commit()?;
+        }
+        Ok(())
+    }
+
+    fn rewrite(&mut self, records: &[Vec<u8>]) -> Result<u64, anyhow::Error> {
+        let txn = self.db.begin_write()?;
+        let mut size = 0;
+        {
+            txn.delete_table(RECORDS)?;
+            let mut table = txn.open_table(RECORDS)?;
+            for (seq, record) in records.iter().enumerate() {
+                table.insert(seq as u64, record.as_slice())?;
+                size += record.len() as u64;
+            }
+        }
+        txn.commit()?;
+        self.next = records.len() as u64;
+        self.unsynced = false;
+        Ok(size)
+    }
+}
+
+/// Messages received by one service provider
+#[derive(Default)]
+struct Feed {
+    messages: Vec<EncryptedMessage>,
+    /// Arrival time of every message
+    arrivals: Vec<u64>,
+    /// Digest of every message as it was received, placeholders of expired messages keep the
+    /// digest of the original for receipts and the Merkle tree, also in the store file
+    digests: Vec<[u8; 32]>,
+    /// Expiry time of every message, if its sender asked for one
+    expires: Vec<Option<u64>>,
+    /// Messages before this index were pruned already
+    pruned: usize,
+    /// Messages before this index are on disk and can be served
+    synced: usize,
+    /// Size of the ciphertexts of the messages that weren't deleted
+    bytes: u64,
+    /// Size of the ciphertexts deleted since the store was last compacted
+    dead: u64,
+}
+
+impl Feed {
+    fn push(
+        &mut self,
+        msg: EncryptedMessage,
+        digest: [u8; 32],
+        arrived: u64,
+        expires: Option<u64>,
+    ) {
+        self.bytes += msg.len() as u64;
+        self.digests.push(digest);
+        self.messages.push(msg);
+        self.arrivals.push(arrived);
+        self.expires.push(expires);
+    }
+
+    fn prune(&mut self, cutoff: u64) -> usize {
+        let start = self.pruned;
+        // Arrival times only ever increase, so everything before the first new enough message can
+        // go
+        while self.pruned < self.arrivals.len() && self.arrivals[self.pruned] < cutoff {
+            self.delete(self.pruned);
+            self.pruned += 1;
+        }
+        self.pruned - start
+    }
+
+    /// Replace the message at `index` by a placeholder, the digest of the original stays
+    fn delete(&mut self, index: usize) {
+        let msg = std::mem::replace(&mut self.messages[index], EncryptedMessage::placeholder());
+        self.bytes -= msg.len() as u64;
+        self.dead += msg.len() as u64;
+    }
+
+    /// What's stored for the message at `index`
+    fn record(&self, provider: &str, index: usize) -> Record<&EncryptedMessage> {
+        Record {
+            arrived: self.arrivals[index],
+            provider: provider.to_owned(),
+            msg: &self.messages[index],
+            digest: self.digests[index],
+            expires: self.expires[index],
+        }
+    }
+}
+
+/// What's stored per message, `M` is either an owned or a borrowed message (which serialize the
+/// same way)
+#[derive(Serialize, Deserialize)]
+struct Record<M> {
+    arrived: u64,
+    /// Address of the service provider that received the message
+    provider: String,
+    msg: M,
+    /// Digest of the message as it was received, it stays when the message is replaced by a
+    /// placeholder so receipts and tree heads don't change after compacting
+    digest: [u8; 32],
+    /// Time the sender wants the message deleted at
+    expires: Option<u64>,
+}
+
+fn header(key: Option<&Key>) -> Vec<u8> {
+    let mut header = MAGIC.to_vec();
+    match key {
+        Some(key) => {
+            header.push(ENCRYPTED);
+            header.extend_from_slice(&key.id().as_bytes()[..KEY_ID_LEN]);
+        }
+        None => header.push(0),
+    }
+    header
+}
+
+/// Serialize (and encrypt if there is a key) a record
+fn encode<M: Serialize>(record: &Record<M>, key: Option<&Key>) -> Result<Vec<u8>, anyhow::Error> {
+    Ok(match key {
+        Some(key) => bincode::serialize(&EncryptedMessage::seal(record, key))?,
+        None => bincode::serialize(record)?,
+    })
+}
+
+fn decode(record: &[u8], key: Option<&Key>) -> Result<Record<EncryptedMessage>, anyhow::Error> {
+    match key {
+        Some(key) => bincode::deserialize::<EncryptedMessage>(record)?
+            .open(key)
+            .map_err(|_| anyhow::Error::msg("couldn't decrypt store record")),
+        None => Ok(bincode::deserialize(record)?),
+    }
+}
+
+/// Sequence number and checksum of the next record in the log
+#[derive(Default, Clone)]
+struct Chain {
+    seq: u64,
+    /// Checksum of the previous record, all zero for the first one
+    prev: [u8; 32],
+}
+
+impl Chain {
+    fn checksum(&self, record: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.prev);
+        hasher.update(&self.seq.to_le_bytes());
+        hasher.update(record);
+        hasher.finalize().into()
+    }
+
+    /// Frame a record for appending to the log and advance to the next position
+    fn frame(&mut self, record: &[u8]) -> Vec<u8> {
+        let checksum = self.checksum(record);
+        let mut frame = Vec::with_capacity(FRAME_PREFIX_LEN + record.len());
+        frame.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&self.seq.to_le_bytes());
+        frame.extend_from_slice(&checksum);
+        frame.extend_from_slice(record);
+        self.seq += 1;
+        self.prev = checksum;
+        frame
+    }
+}
+
+/// What's wrong with a log
+enum Damage {
+    /// The last record is incomplete, which is expected after a crash while appending
+    Torn,
+    /// A record before the last one is broken, this needs to be looked at by the operator
+    Corrupt(String),
+}
+
+/// Result of reading a log up to the end or the first broken record
+struct Log {
+    /// Offset after the last intact record
+    end: u64,
+    /// Position after the last intact record
+    chain: Chain,
+    damage: Option<Damage>,
+}
+
+/// Check the header and pass every intact record to `on_record`, in order. Errors of
+/// `on_record` abort reading.
+fn read_log(
+    file: &File,
+    header: &[u8],
+    mut on_record: impl FnMut(&[u8]) -> Result<(), anyhow::Error>,
+) -> Result<Log, anyhow::Error> {
+    let file_len = file.metadata()?.len();
+    // The file may have been written to already, e.g. the header of a new store
+    let mut file = file;
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    let mut existing_header = vec![0u8; header.len()];
+    if reader.read_exact(&mut existing_header).is_err() || existing_header != header {
+        return Err(anyhow::Error::msg(
+            "not a store, it was created by an older version or with a different store key (or \
+             without one)",
+        ));
+    }
+
+    let mut chain = Chain::default();
+    let mut end = header.len() as u64;
+    let damage = loop {
+        if end == file_len {
+            break None;
+        }
+        let mut prefix = [0u8; FRAME_PREFIX_LEN];
+        if reader.read_exact(&mut prefix).is_err() {
+            break Some(Damage::Torn);
+        }
+        let len = u32::from_le_bytes(prefix[..4].try_into().unwrap());
+        let seq = u64::from_le_bytes(prefix[4..12].try_into().unwrap());
+        let frame_end = end + (FRAME_PREFIX_LEN as u64) + len as u64;
+        if frame_end > file_len {
+            break Some(Damage::Torn);
+        }
+        let mut record = vec![0u8; len as usize];
+        reader.read_exact(&mut record)?;
+
+        // A broken last record is most likely an interrupted append, anything else means the
+        // file was damaged (or tampered with) after being written
+        let checksum = chain.checksum(&record);
+        let problem = if seq != chain.seq {
+            Some(format!("record {} has sequence number {}", chain.seq, seq))
+        } else if prefix[12..] != checksum {
+            Some(format!("record {} doesn't match its checksum", chain.seq))
+        } else {
+            None
+        };
+        match problem {
+            Some(_) if frame_end == file_len => break Some(Damage::Torn),
+            Some(problem) => break Some(Damage::Corrupt(problem)),
+            None => {}
+        }
+
+        on_record(&record)?;
+        chain.prev = checksum;
+        chain.seq += 1;
+        end = frame_end;
+    };
+
+    Ok(Log { end, chain, damage })
+}
+
+/// Make a rename durable by syncing the directory containing the file
+fn sync_dir(path: &Path) -> Result<(), anyhow::Error> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    File::open(dir.unwrap_or_else(|| Path::new(".")))?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nym_chat::Message;
+
+    const PROVIDER: &str = "provider";
+
+    /// A log in the temp directory that's removed again when dropped
+    struct TempLog(PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> TempLog {
+            let path = std::env::temp_dir().join(format!(
+                "nym-chat-store-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            TempLog(path)
+        }
+
+        fn location(&self) -> Location {
+            Location::Log(self.0.clone())
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn message(text: &str) -> EncryptedMessage {
+        Message::new("alice".into(), text.into()).encrypt(&Key::generate())
+    }
+
+    /// Store three messages, returns their digests and the file length after each of them
+    fn three_messages(log: &TempLog) -> (Vec<[u8; 32]>, Vec<u64>) {
+        let mut store = Store::open(&log.location(), None, false).unwrap();
+        (0..3)
+            .map(|i| {
+                let msg = message(&i.to_string());
+                let digest = msg.digest();
+                store.push(PROVIDER, msg, 10, None).unwrap();
+                (digest, std::fs::metadata(&log.0).unwrap().len())
+            })
+            .unzip()
+    }
+
+    fn flip_byte(log: &TempLog, offset: u64) {
+        let mut contents = std::fs::read(&log.0).unwrap();
+        contents[offset as usize] ^= 1;
+        std::fs::write(&log.0, contents).unwrap();
+    }
+
+    #[test]
+    fn truncated_last_records_are_dropped() {
+        let log = TempLog::new("truncated");
+        let (digests, ends) = three_messages(&log);
+        let file = OpenOptions::new().write(true).open(&log.0).unwrap();
+        file.set_len(ends[2] - 5).unwrap();
+        assert!(verify(&log.location(), None).is_err());
+
+        let mut store = Store::open(&log.location(), None, false).unwrap();
+        assert_eq!(store.digests(PROVIDER), &digests[..2]);
+        assert_eq!(std::fs::metadata(&log.0).unwrap().len(), ends[1]);
+        // The log continues after the last intact record
+        let msg = message("after the crash");
+        let digest = msg.digest();
+        store.push(PROVIDER, msg, 10, None).unwrap();
+        drop(store);
+        assert_eq!(verify(&log.location(), None).unwrap(), 3);
+        let store = Store::open(&log.location(), None, false).unwrap();
+        assert_eq!(store.digests(PROVIDER), &[digests[0], digests[1], digest]);
+    }
+
+    #[test]
+    fn half_written_frame_prefixes_are_dropped() {
+        let log = TempLog::new("prefix");
+        let (digests, _) = three_messages(&log);
+        let mut file = OpenOptions::new().append(true).open(&log.0).unwrap();
+        file.write_all(&[7; FRAME_PREFIX_LEN - 1]).unwrap();
+
+        let store = Store::open(&log.location(), None, false).unwrap();
+        assert_eq!(store.digests(PROVIDER), &digests[..]);
+    }
+
+    #[test]
+    fn corrupt_last_records_are_dropped() {
+        let log = TempLog::new("corrupt-tail");
+        let (digests, ends) = three_messages(&log);
+        flip_byte(&log, ends[2] - 1);
+
+        let store = Store::open(&log.location(), None, false).unwrap();
+        assert_eq!(store.digests(PROVIDER), &digests[..2]);
+    }
+
+    #[test]
+    fn corrupt_records_before_the_last_are_refused() {
+        let log = TempLog::new("corrupt");
+        let (_, ends) = three_messages(&log);
+        flip_byte(&log, ends[1] - 1);
+
+        assert!(verify(&log.location(), None).is_err());
+        assert!(Store::open(&log.location(), None, false).is_err());
+        // Nothing was dropped, the operator has to look at it
+        assert_eq!(std::fs::metadata(&log.0).unwrap().len(), ends[2]);
+    }
+
+    #[test]
+    fn failed_appends_leave_the_log_where_it_was() {
+        let log = TempLog::new("failed-append");
+        let record = |text: &str| {
+            let msg = message(text);
+            let record = Record {
+                arrived: 10,
+                provider: PROVIDER.to_owned(),
+                digest: msg.digest(),
+                msg,
+                expires: None,
+            };
+            encode(&record, None).unwrap()
+        };
+        let mut backend = LogFile::open(&log.0, &header(None)).unwrap();
+        backend.load(&mut |_| Ok(())).unwrap();
+        backend.append(&record("first")).unwrap();
+        let end = backend.end;
+
+        // Writing to a read-only handle fails like a full disk would
+        let writable = std::mem::replace(&mut backend.file, File::open(&log.0).unwrap());
+        assert!(backend.append(&record("lost")).is_err());
+        assert_eq!(backend.chain.seq, 1);
+        assert_eq!(backend.end, end);
+
+        backend.file = writable;
+        backend.append(&record("second")).unwrap();
+        drop(backend);
+        assert_eq!(verify(&log.location(), None).unwrap(), 2);
+    }
+
+    #[test]
+    fn expired_messages_keep_their_digest_after_compacting() {
+        let log = TempLog::new("digest");
+        let msg = message("hi");
+        let digest = msg.digest();
+        let mut store = Store::open(&log.location(), None, false).unwrap();
+        store.push(PROVIDER, msg, 10, Some(20)).unwrap();
+        assert_eq!(store.expire(30), 1);
+        store.compact().unwrap();
+        drop(store);
+
+        let store = Store::open(&log.location(), None, false).unwrap();
+        assert_eq!(store.digests(PROVIDER), &[digest]);
+        assert_eq!(store.receipt_data(PROVIDER, 0), vec![(digest, 10)]);
+    }
+}
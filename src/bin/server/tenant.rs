@@ -0,0 +1,306 @@
+use crate::config::Config;
+use crate::store::Store;
+use crate::time::Clock;
+use crate::watchdog::{Watchdog, PING_INTERVAL, STALL_TIMEOUT};
+use futures::sink::SinkExt;
+use futures::stream::StreamExt;
+use nym_chat::Envelope;
+use nym_websocket::responses::ServerResponse;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::select;
+use tokio::sync::watch;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error, info, warn};
+
+/// Service provider addresses of all tenants by name, known once their Nym clients told us
+pub type Addresses = Arc<Mutex<HashMap<String, String>>>;
+
+/// One community served by the server: a Nym client (and thus service provider address) of its
+/// own and its own fetch endpoint at `/<name>/fetch/<n>`. Configured as `[name=]ws://...`, unnamed
+/// tenants are named after their position.
+pub struct Tenant {
+    pub name: Option<String>,
+    pub websocket: String,
+}
+
+impl FromStr for Tenant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, _)) if name.is_empty() || name.contains('/') => {
+                Err("tenant names can't be empty or contain /".into())
+            }
+            Some((name, websocket)) => Ok(Tenant {
+                name: Some(name.into()),
+                websocket: websocket.into(),
+            }),
+            None => Ok(Tenant {
+                name: None,
+                websocket: s.into(),
+            }),
+        }
+    }
+}
+
+/// Length of the window the rate limit applies to
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Longest maximum age a message can ask for, messages asking for more are kept without expiry like
+/// those that don't ask. Keeps the (untrusted) age from overflowing the expiry time.
+const MAX_AGE_SECS: u64 = 100 * 365 * 24 * 60 * 60;
+
+/// Wait before reconnecting to a Nym client that went away, doubled after every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Everything needed to store the messages of a tenant, kept across reconnects
+pub struct Ingest {
+    store: Arc<Mutex<Store>>,
+    config: watch::Receiver<Config>,
+    clock: Clock,
+    /// Start of the current rate limit window and the messages accepted in it per tenant
+    window_start: Instant,
+    window_counts: HashMap<String, u32>,
+}
+
+/// Receive messages for the tenant `name` from its Nym client and save them in the store, tagged
+/// with the tenant's service provider address. If the connection to the Nym client breaks or
+/// stalls it's re-established, the HTTP API keeps serving the stored messages in the meantime.
+pub async fn receive(
+    name: String,
+    websocket: String,
+    store: Arc<Mutex<Store>>,
+    addresses: Addresses,
+    config: watch::Receiver<Config>,
+    clock: Clock,
+    watchdog: Watchdog,
+) {
+    let mut ingest = Ingest::new(store, config, clock);
+    let mut delay = RECONNECT_DELAY;
+    loop {
+        match connect(&name, &websocket).await {
+            Ok(ws) => {
+                delay = RECONNECT_DELAY;
+                watchdog.connected(&name);
+                let reason = serve(&name, &addresses, &watchdog, ws, &mut ingest).await;
+                watchdog.disconnected(&name, &reason);
+            }
+            Err(e) => watchdog.disconnected(&name, &format!("couldn't connect: {}", e)),
+        }
+        info!("Reconnecting to the nym client of {} in {:?}", name, delay);
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+type WebSocket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Open a connection to the nym native client and query our own identity
+async fn connect(name: &str, websocket: &str) -> Result<WebSocket, anyhow::Error> {
+    debug!("Connecting to websocket of {} at {}", name, websocket);
+    let (mut ws, _) = connect_async(websocket).await?;
+
+    debug!("Requesting own identity from nym client");
+    ws.send(build_identity_request()).await?;
+    Ok(ws)
+}
+
+/// Handle everything the Nym client sends until the connection closes or stalls and return why it
+/// ended. Nothing the client (or anyone sending us messages) sends can make this fail,
+/// undecodable data is logged and dropped.
+async fn serve(
+    name: &str,
+    addresses: &Addresses,
+    watchdog: &Watchdog,
+    mut ws: WebSocket,
+    ingest: &mut Ingest,
+) -> String {
+    // The Nym client is pinged regularly, if not even pongs come back it's stalled
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    let mut last_traffic = Instant::now();
+
+    // If we receive a message that is a valid encrypted message we save it in the message database
+    // for clients to query
+    let mut address = None;
+    loop {
+        let msg = select! {
+            msg = ws.next() => msg,
+            _ = ping.tick() => {
+                if last_traffic.elapsed() >= STALL_TIMEOUT {
+                    return format!("no traffic for {}s", last_traffic.elapsed().as_secs());
+                }
+                if let Err(e) = ws.send(Message::Ping(Vec::new())).await {
+                    return format!("ping failed: {}", e);
+                }
+                continue;
+            }
+        };
+        let msg = match msg {
+            Some(Ok(msg)) => msg,
+            Some(Err(e)) => return format!("connection failed: {}", e),
+            None => return "connection closed".into(),
+        };
+        last_traffic = Instant::now();
+        watchdog.traffic(name);
+
+        let msg = match parse_nym_message(msg) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => continue,
+            Err(NymMessageError::Closed) => return "closed by the nym client".into(),
+            Err(NymMessageError::Undecodable(e)) => {
+                warn!("Could not decode nym client response");
+                debug!("Nym client response decoding error: {}", e);
+                continue;
+            }
+        };
+
+        let msg_bytes = match msg {
+            ServerResponse::Received(msg_bytes) => {
+                debug!("Received client request {:?}", msg_bytes);
+                msg_bytes
+            }
+            ServerResponse::SelfAddress(addr) => {
+                // The address on a line of its own, so operators can copy it into invites and
+                // client options (or point clients at /provider-address)
+                info!(
+                    "Tenant {} listening on service provider address\n{}",
+                    name, addr
+                );
+                let addr = addr.to_string();
+                addresses
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_owned(), addr.clone());
+                address = Some(addr);
+                continue;
+            }
+            ServerResponse::Error(err) => {
+                error!("Received error from nym client: {}", err);
+                continue;
+            }
+        };
+
+        match &address {
+            Some(address) => ingest.accept(name, address, &msg_bytes.message),
+            None => warn!("Dropping message received before our own address was known"),
+        }
+    }
+}
+
+impl Ingest {
+    pub fn new(store: Arc<Mutex<Store>>, config: watch::Receiver<Config>, clock: Clock) -> Ingest {
+        Ingest {
+            store,
+            config,
+            clock,
+            window_start: Instant::now(),
+            window_counts: HashMap::new(),
+        }
+    }
+
+    /// Store a message received by the tenant `name` unless it's over its rate limit or storage
+    /// quota
+    pub fn accept(&mut self, name: &str, address: &str, bytes: &[u8]) {
+        if self.window_start.elapsed() >= RATE_LIMIT_WINDOW {
+            self.window_start = Instant::now();
+            self.window_counts.clear();
+        }
+        let window_count = self.window_counts.entry(name.to_owned()).or_default();
+        if matches!(self.config.borrow().rate_limit(name), Some(limit) if *window_count >= limit) {
+            debug!("Rate limit of {} reached, dropping message", name);
+            return;
+        }
+        *window_count += 1;
+
+        let max_stored_bytes = self.config.borrow().max_stored_bytes(name);
+        if let Some(max_stored_bytes) = max_stored_bytes {
+            if self.store.lock().unwrap().stored_bytes(address) >= max_stored_bytes {
+                debug!("Storage quota of {} used up, dropping message", name);
+                return;
+            }
+        }
+
+        let limit = self.config.borrow().max_message_bytes();
+        match Envelope::decode(bytes, limit) {
+            Ok(envelope) => {
+                let arrived = self.clock.now();
+                let expires = envelope
+                    .max_age_secs
+                    .and_then(|max_age| expiry(arrived, max_age));
+                let result =
+                    self.store
+                        .lock()
+                        .unwrap()
+                        .push(address, envelope.msg, arrived, expires);
+                match result {
+                    Ok(()) => debug!(tenant = name, bytes = bytes.len(), "Stored message"),
+                    Err(e) => error!("Couldn't persist message: {}", e),
+                }
+            }
+            Err(e) => {
+                warn!("Could not decode client request");
+                debug!("Client request decoding error: {}", e);
+            }
+        }
+    }
+}
+
+/// When a message that arrived at `arrived` and asked to be kept `max_age` seconds expires, `None`
+/// if it asked for more than [`MAX_AGE_SECS`]
+fn expiry(arrived: u64, max_age: u64) -> Option<u64> {
+    if max_age > MAX_AGE_SECS {
+        return None;
+    }
+    arrived.checked_add(max_age)
+}
+
+fn build_identity_request() -> tokio_tungstenite::tungstenite::Message {
+    let nym_message = nym_websocket::requests::ClientRequest::SelfAddress;
+    Message::Binary(nym_message.serialize())
+}
+
+enum NymMessageError {
+    /// The Nym client closed the connection
+    Closed,
+    Undecodable(String),
+}
+
+/// Decode a frame of the Nym client, `None` for frames without content (pings and pongs, which
+/// tungstenite answers itself)
+fn parse_nym_message(
+    msg: tokio_tungstenite::tungstenite::Message,
+) -> Result<Option<ServerResponse>, NymMessageError> {
+    match msg {
+        Message::Binary(bytes) => ServerResponse::deserialize(&bytes)
+            .map(Some)
+            .map_err(|e| NymMessageError::Undecodable(format!("{:?}", e))),
+        Message::Ping(_) | Message::Pong(_) => Ok(None),
+        Message::Close(_) => Err(NymMessageError::Closed),
+        msg => Err(NymMessageError::Undecodable(format!(
+            "unexpected message {:?}",
+            msg
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absurd_max_ages_mean_no_expiry() {
+        let arrived = 1_700_000_000;
+        assert_eq!(expiry(arrived, 0), Some(arrived));
+        assert_eq!(expiry(arrived, 60), Some(arrived + 60));
+        assert_eq!(expiry(arrived, MAX_AGE_SECS), Some(arrived + MAX_AGE_SECS));
+        assert_eq!(expiry(arrived, MAX_AGE_SECS + 1), None);
+        assert_eq!(expiry(arrived, u64::MAX), None);
+        assert_eq!(expiry(u64::MAX, 1), None);
+    }
+}
@@ -0,0 +1,40 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing_subscriber::fmt::time::FormatTime;
+
+/// Source of the arrival times the server stores and logs. Exact times would let anyone who seizes
+/// the server correlate messages with traffic they observed entering the mixnet, so they can be
+/// rounded down to buckets of a configurable size.
+#[derive(Clone, Copy)]
+pub struct Clock {
+    /// Bucket size in seconds
+    bucket: u64,
+}
+
+impl Clock {
+    /// A clock rounding down to multiples of `bucket` seconds, `None` or `0` keeps exact seconds
+    pub fn new(bucket: Option<u64>) -> Clock {
+        Clock {
+            bucket: bucket.unwrap_or(1).max(1),
+        }
+    }
+
+    /// Current unix time rounded down to the bucket
+    pub fn now(&self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time before 1970")
+            .as_secs();
+        now - now % self.bucket
+    }
+}
+
+/// Log lines use the same coarse times as the store, otherwise the logs would undo the bucketing
+impl FormatTime for Clock {
+    fn format_time(&self, w: &mut dyn fmt::Write) -> fmt::Result {
+        match chrono::DateTime::from_timestamp(self.now() as i64, 0) {
+            Some(time) => write!(w, "{}", time.format("%Y-%m-%dT%H:%M:%SZ")),
+            None => write!(w, "{}", self.now()),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+/// How often the Nym clients are pinged, their answers count as traffic
+pub const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A Nym client that didn't send anything (not even a pong) for this long is considered stalled
+/// and the connection is re-established
+pub const STALL_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Keeps track of the connections to the tenants' Nym clients for `/readyz` and the stats page
+/// and runs the operator's alert command when a connection goes down or comes back.
+#[derive(Clone)]
+pub struct Watchdog {
+    tenants: Arc<Mutex<HashMap<String, TenantHealth>>>,
+    alert_command: Option<Arc<String>>,
+}
+
+#[derive(Default)]
+struct TenantHealth {
+    connected: bool,
+    last_traffic: Option<Instant>,
+    /// Connections lost or attempts failed since the server started
+    failures: u64,
+    /// We alerted about the connection being down and haven't told that it's back yet
+    alerted: bool,
+}
+
+impl Watchdog {
+    /// All tenants start out disconnected, so the server only becomes ready once every one of
+    /// them connected
+    pub fn new<'a>(
+        tenants: impl IntoIterator<Item = &'a str>,
+        alert_command: Option<String>,
+    ) -> Self {
+        Watchdog {
+            tenants: Arc::new(Mutex::new(
+                tenants
+                    .into_iter()
+                    .map(|name| (name.to_owned(), TenantHealth::default()))
+                    .collect(),
+            )),
+            alert_command: alert_command.map(Arc::new),
+        }
+    }
+
+    pub fn connected(&self, tenant: &str) {
+        let mut tenants = self.tenants.lock().unwrap();
+        let health = tenants.entry(tenant.to_owned()).or_default();
+        health.connected = true;
+        health.last_traffic = Some(Instant::now());
+        if health.alerted {
+            health.alerted = false;
+            self.alert(tenant, "up", "connected");
+        }
+    }
+
+    /// The connection was lost or couldn't be established, alerts once per outage
+    pub fn disconnected(&self, tenant: &str, reason: &str) {
+        let mut tenants = self.tenants.lock().unwrap();
+        let health = tenants.entry(tenant.to_owned()).or_default();
+        health.connected = false;
+        health.failures += 1;
+        if !health.alerted {
+            health.alerted = true;
+            self.alert(tenant, "down", reason);
+        }
+    }
+
+    /// Anything received from the Nym client, including pongs
+    pub fn traffic(&self, tenant: &str) {
+        if let Some(health) = self.tenants.lock().unwrap().get_mut(tenant) {
+            health.last_traffic = Some(Instant::now());
+        }
+    }
+
+    /// Whether the Nym clients of all tenants are connected
+    pub fn ready(&self) -> bool {
+        self.tenants
+            .lock()
+            .unwrap()
+            .values()
+            .all(|health| health.connected)
+    }
+
+    /// One line per tenant, used by `/readyz` and the stats page
+    pub fn render(&self) -> String {
+        let tenants = self.tenants.lock().unwrap();
+        let mut names = tenants.keys().collect::<Vec<_>>();
+        names.sort();
+        let mut page = String::new();
+        for name in names {
+            let health = &tenants[name];
+            let state = match health.connected {
+                true => "connected",
+                false => "disconnected",
+            };
+            let last_traffic = match health.last_traffic {
+                Some(last_traffic) => format!("{}s ago", last_traffic.elapsed().as_secs()),
+                None => "never".into(),
+            };
+            let _ = writeln!(
+                page,
+                "{}: {}, last traffic {}, {} failures",
+                name, state, last_traffic, health.failures
+            );
+        }
+        page
+    }
+
+    /// Run the alert command in the background with the details in environment variables
+    fn alert(&self, tenant: &str, event: &str, reason: &str) {
+        warn!("Nym client of {} is {}: {}", tenant, event, reason);
+        let command = match &self.alert_command {
+            Some(command) => command,
+            None => return,
+        };
+        let child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command.as_str())
+            .env("NYM_CHAT_TENANT", tenant)
+            .env("NYM_CHAT_EVENT", event)
+            .env("NYM_CHAT_REASON", reason)
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                tokio::spawn(async move {
+                    match child.wait().await {
+                        Ok(status) if !status.success() => {
+                            error!("Alert command failed with {}", status)
+                        }
+                        Err(e) => error!("Couldn't wait for alert command: {}", e),
+                        Ok(_) => {}
+                    }
+                });
+            }
+            Err(e) => error!("Couldn't run alert command: {}", e),
+        }
+    }
+}
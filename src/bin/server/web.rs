@@ -0,0 +1,239 @@
+//! A page to read a room in the browser without installing anything, served at `/web` when the
+//! server is built with the `web-ui` feature. There's no WASM build of the library yet, so the page
+//! decodes the few payloads it shows itself and decrypts with WebCrypto: the room key is entered
+//! on the page and the page doesn't send it anywhere. The page itself comes from the server though,
+//! so a malicious server could serve one that does, unlike the client it only protects the key
+//! from servers users trust. It polls the fetch endpoint like the client does.
+//!
+//! Like the client, the page checks the signatures of authored messages (with WebCrypto's Ed25519,
+//! where the browser has it), drops forged ones and shows the author's fingerprint. Burn-after-
+//! reading messages are shown once and then removed from the page.
+//!
+//! Browsers can't reach the mixnet, so the page can only send through `/submit`, i.e. to servers
+//! running with `--loopback`. Everywhere else it's read-only. Rooms with a key chain (see the epoch
+//! module of the library) aren't supported, their messages are encrypted with epoch keys.
+
+/// The page, relative URLs make it work for every tenant at `/<tenant>/web`
+pub const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>nym-chat</title>
+<style>
+body { font-family: sans-serif; max-width: 50em; margin: auto; }
+#messages div { padding: 0.2em 0; }
+.sender { font-weight: bold; margin-right: 0.5em; }
+.fingerprint { color: gray; font-family: monospace; margin-right: 0.5em; }
+input { width: 100%; box-sizing: border-box; }
+</style>
+</head>
+<body>
+<h1>nym-chat</h1>
+<form id="join">
+<p>Room key (hex). This page doesn't send it anywhere, but it comes from the server, so only
+enter it on servers you trust and use the nym-chat client otherwise:</p>
+<input id="key" type="password" autocomplete="off">
+</form>
+<div id="messages"></div>
+<form id="send" hidden>
+<input id="name" placeholder="Name">
+<input id="text" placeholder="Message">
+</form>
+<p id="status"></p>
+<script>
+let key = null;
+let roomId = null;
+let lastSeen = 0;
+
+const status = (text) => { document.getElementById("status").textContent = text; };
+
+// Reads the bincode encoding of the payloads the page understands
+class Reader {
+  constructor(bytes) {
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.length);
+    this.pos = 0;
+  }
+  u8() { return this.view.getUint8(this.pos++); }
+  u32() { const v = this.view.getUint32(this.pos, true); this.pos += 4; return v; }
+  u64() { const v = this.view.getBigUint64(this.pos, true); this.pos += 8; return Number(v); }
+  done() { return this.pos >= this.view.byteLength; }
+  bytes() {
+    const len = this.u64();
+    const bytes = new Uint8Array(this.view.buffer, this.view.byteOffset + this.pos, len);
+    this.pos += len;
+    return bytes;
+  }
+  string() { return new TextDecoder("utf-8", { fatal: true }).decode(this.bytes()); }
+  // Messages of older clients end after the text, they have no timestamp
+  message() {
+    const sender = this.string();
+    const msg = this.string();
+    const sent = !this.done() && this.u8() ? this.u64() : null;
+    return { sender, msg, sent };
+  }
+  // Variant indices of the library's Payload, a test of the library keeps them from changing
+  payload() {
+    switch (this.u32()) {
+      case 0: return this.message();   // Message
+      case 8:   // Authored, the signature is checked by verify()
+        return { ...this.message(), author: this.string(), signature: this.bytes() };
+      case 9: return { ...this.message(), burn: true };   // BurnAfterReading
+      case 11: this.u64(); this.u64(); return this.payload();   // Counted
+      default: return null;
+    }
+  }
+}
+
+const encodeU64 = (value) => {
+  const bytes = new Uint8Array(8);
+  new DataView(bytes.buffer).setBigUint64(0, BigInt(value), true);
+  return [...bytes];
+};
+
+const encodeString = (text) => {
+  const bytes = new TextEncoder().encode(text);
+  return [...encodeU64(bytes.length), ...bytes];
+};
+
+// The bincode encoding of a message, which is what authors sign
+const encodeMessage = (message) => [
+  ...encodeString(message.sender),
+  ...encodeString(message.msg),
+  ...(message.sent === null ? [0] : [1, ...encodeU64(message.sent)]),
+];
+
+const fromHex = (hex) => new Uint8Array(hex.match(/../g).map((byte) => parseInt(byte, 16)));
+const toHex = (bytes) => [...bytes].map((byte) => byte.toString(16).padStart(2, "0")).join("");
+
+// Fingerprint of the author of an authored message like the client shows it, null if the
+// signature is invalid (the client drops those messages too) and "unverified" if the browser
+// can't check Ed25519 signatures
+async function verify(message) {
+  if (!/^[0-9a-f]{64}$/.test(message.author) || message.signature.length !== 64) {
+    return null;
+  }
+  const author = fromHex(message.author);
+  const signed = new Uint8Array([
+    ...new TextEncoder().encode("nym-chat author" + roomId),
+    ...encodeMessage(message),
+  ]);
+  try {
+    const publicKey = await crypto.subtle.importKey("raw", author, "Ed25519", false, ["verify"]);
+    if (!await crypto.subtle.verify("Ed25519", publicKey, message.signature, signed)) {
+      return null;
+    }
+  } catch (e) {
+    return e.name === "NotSupportedError" ? "unverified" : null;
+  }
+  const digest = new Uint8Array(await crypto.subtle.digest("SHA-256", author));
+  return toHex(digest.slice(0, 10)).match(/..../g).join(" ");
+}
+
+async function decrypt(encrypted) {
+  try {
+    const iv = new Uint8Array(encrypted.nonce);
+    const data = new Uint8Array(encrypted.data);
+    const plain = await crypto.subtle.decrypt({ name: "AES-GCM", iv }, key, data);
+    const message = new Reader(new Uint8Array(plain)).payload();
+    if (message && message.author !== undefined) {
+      message.fingerprint = await verify(message);
+      if (message.fingerprint === null) return null;
+    }
+    return message;
+  } catch (e) {
+    return null;   // another room's message
+  }
+}
+
+function show(message) {
+  const row = document.createElement("div");
+  const sender = document.createElement("span");
+  sender.className = "sender";
+  sender.textContent = message.sender;
+  row.append(sender);
+  if (message.fingerprint) {
+    // Anyone can claim any name, the fingerprint tells authors apart
+    const fingerprint = document.createElement("span");
+    fingerprint.className = "fingerprint";
+    fingerprint.textContent = message.fingerprint.slice(0, 9);
+    fingerprint.title = message.fingerprint;
+    row.append(fingerprint);
+  }
+  if (message.burn) {
+    // Shown once, closing it removes it from the page like in the client
+    let text = message.msg;
+    message.msg = null;
+    const reveal = document.createElement("button");
+    reveal.textContent = "Show burn-after-reading message";
+    reveal.onclick = () => {
+      const close = document.createElement("button");
+      close.textContent = "Close";
+      close.onclick = () => row.remove();
+      reveal.replaceWith(text, " ", close);
+      text = null;
+    };
+    row.append(reveal);
+  } else {
+    row.append(message.msg);
+  }
+  if (message.sent) {
+    row.title = new Date(message.sent * 1000).toLocaleString();
+  }
+  document.getElementById("messages").append(row);
+}
+
+async function poll() {
+  try {
+    const response = await fetch("fetch/" + lastSeen);
+    const messages = await response.json();
+    lastSeen += messages.length;
+    for (const encrypted of messages) {
+      const message = await decrypt(encrypted);
+      if (message) show(message);
+    }
+    status("");
+  } catch (e) {
+    status("Couldn't reach the server, retrying");
+  }
+  setTimeout(poll, 5000);
+}
+
+document.getElementById("join").onsubmit = async (event) => {
+  event.preventDefault();
+  const hex = document.getElementById("key").value.trim();
+  if (!/^[0-9a-fA-F]{64}$/.test(hex)) {
+    status("The room key has to be 64 hex digits");
+    return;
+  }
+  const raw = fromHex(hex);
+  key = await crypto.subtle.importKey("raw", raw, "AES-GCM", false, ["encrypt", "decrypt"]);
+  // Authors sign the room's id along with the message, see the identity module
+  roomId = toHex(new Uint8Array(await crypto.subtle.digest("SHA-256", raw)).slice(0, 8));
+  document.getElementById("join").hidden = true;
+  document.getElementById("send").hidden = false;
+  poll();
+};
+
+document.getElementById("send").onsubmit = async (event) => {
+  event.preventDefault();
+  const text = document.getElementById("text");
+  const name = document.getElementById("name").value || "web";
+  const message = { sender: name, msg: text.value, sent: Math.floor(Date.now() / 1000) };
+  const payload = [0, 0, 0, 0, ...encodeMessage(message)];
+  const nonce = crypto.getRandomValues(new Uint8Array(12));
+  const plain = new Uint8Array(payload);
+  const sealed = await crypto.subtle.encrypt({ name: "AES-GCM", iv: nonce }, key, plain);
+  const data = new Uint8Array(sealed);
+  // Unframed envelope: the nonce, the ciphertext with its length and no expiry
+  const envelope = new Uint8Array([...nonce, ...encodeU64(data.length), ...data, 0]);
+  const response = await fetch("submit", { method: "POST", body: envelope });
+  if (response.ok) {
+    text.value = "";
+  } else {
+    status("This server only takes messages through the mixnet, use the nym-chat client");
+  }
+};
+</script>
+</body>
+</html>
+"#;
@@ -0,0 +1,225 @@
+//! Broadcast rooms are read-only for everyone but the holders of a signing key. The room key still
+//! encrypts everything, the signing key only decides who may post: messages are signed and
+//! clients that know the room's verifying key drop everything without a valid signature.
+//!
+//! The signature covers the room id too, so a signed message can't be replayed into another room
+//! that uses the same signing key.
+//!
+//! Rooms can also be created from a [`RoomSecret`] that both keys are derived from: members who may
+//! post get the secret, read-only members only get the derived room key and verifying key. Posting
+//! permissions are enforced by the signatures alone, nobody needs to trust the server for them.
+//!
+//! Servers use the same kind of keys to sign receipts, see the receipt module.
+
+use crate::{constant_time, Key, Message, Payload};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer};
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+use zeroize::Zeroize;
+
+/// Secret key allowed to post to a broadcast room
+pub struct SigningKey {
+    keypair: Keypair,
+}
+
+/// Public half of a [`SigningKey`], clients use it to verify messages of a broadcast room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyingKey {
+    key: PublicKey,
+}
+
+/// Message together with the signature of the room's signing key
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SignedMessage {
+    pub msg: Message,
+    signature: Vec<u8>,
+}
+
+/// Any other payload (a poll, a vote, a contact card, ...) together with the signature of the
+/// room's signing key
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SignedPayload {
+    pub payload: Box<Payload>,
+    signature: Vec<u8>,
+}
+
+/// Root secret of a room with separate read and write permissions, see the module documentation
+#[derive(Clone)]
+pub struct RoomSecret {
+    secret: [u8; 32],
+}
+
+impl RoomSecret {
+    pub fn generate() -> RoomSecret {
+        RoomSecret {
+            secret: crate::determinism::with_rng(|rng| rng.gen()),
+        }
+    }
+
+    /// Key encrypting the room's messages, all members get it
+    pub fn read_key(&self) -> Key {
+        Key::from_bytes(&self.derive(b"nym-chat read key")).expect("hashes are 32 bytes")
+    }
+
+    /// Key signing the room's messages, only members who may post can derive it
+    pub fn write_key(&self) -> SigningKey {
+        SigningKey::from_bytes(&self.derive(b"nym-chat write key")).expect("hashes are 32 bytes")
+    }
+
+    /// Hex encoding of the secret, the inverse of `RoomSecret::from_str`
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.secret)
+    }
+
+    /// Differently labeled hashes of the secret, knowing one of them doesn't reveal the others
+    fn derive(&self, label: &[u8]) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(label);
+        hasher.update(self.secret);
+        hasher.finalize().to_vec()
+    }
+}
+
+impl SigningKey {
+    /// Generate a fresh random signing key
+    pub fn generate() -> SigningKey {
+        let bytes = crate::determinism::with_rng(|rng| rng.gen::<[u8; 32]>());
+        SigningKey::from_bytes(&bytes).expect("32 bytes are a valid secret key")
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<SigningKey, anyhow::Error> {
+        let secret = SecretKey::from_bytes(bytes)
+            .map_err(|_| anyhow::Error::msg("wrong signing key length"))?;
+        let public = PublicKey::from(&secret);
+        Ok(SigningKey {
+            keypair: Keypair { secret, public },
+        })
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey {
+            key: self.keypair.public,
+        }
+    }
+
+    /// Hex encoding of the secret key, the inverse of `SigningKey::from_str`
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.keypair.secret.as_bytes())
+    }
+
+    pub(crate) fn sign_data(&self, data: &[u8]) -> Vec<u8> {
+        self.keypair.sign(data).to_bytes().to_vec()
+    }
+
+    pub fn sign(&self, msg: Message, room: &Key) -> SignedMessage {
+        let signature = self.keypair.sign(&signed_data(&msg, room));
+        SignedMessage {
+            msg,
+            signature: signature.to_bytes().to_vec(),
+        }
+    }
+
+    pub fn sign_payload(&self, payload: Payload, room: &Key) -> SignedPayload {
+        let signature = self.sign_data(&signed_payload_data(&payload, room));
+        SignedPayload {
+            payload: Box::new(payload),
+            signature,
+        }
+    }
+}
+
+impl VerifyingKey {
+    pub(crate) fn verify_data(&self, data: &[u8], signature: &[u8]) -> bool {
+        Signature::from_bytes(signature)
+            .and_then(|signature| self.key.verify_strict(data, &signature))
+            .is_ok()
+    }
+
+    /// Short representation for comparing keys out of band, e.g. `3f2a 91c0 ...`
+    pub fn fingerprint(&self) -> String {
+        Sha256::digest(self.key.as_bytes())[..10]
+            .chunks(2)
+            .map(hex::encode)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl SignedMessage {
+    /// The message if it was signed by `key` for `room`
+    pub fn verify(self, key: &VerifyingKey, room: &Key) -> Result<Message, ()> {
+        let signature = Signature::from_bytes(&self.signature).map_err(|_| ())?;
+        key.key
+            .verify_strict(&signed_data(&self.msg, room), &signature)
+            .map_err(|_| ())?;
+        Ok(self.msg)
+    }
+}
+
+impl SignedPayload {
+    /// The payload if it was signed by `key` for `room`
+    pub fn verify(self, key: &VerifyingKey, room: &Key) -> Result<Payload, ()> {
+        match key.verify_data(&signed_payload_data(&self.payload, room), &self.signature) {
+            true => Ok(*self.payload),
+            false => Err(()),
+        }
+    }
+}
+
+fn signed_data(msg: &Message, room: &Key) -> Vec<u8> {
+    let mut data = room.id().into_bytes();
+    data.extend(bincode::serialize(msg).expect("Serialization can't fail"));
+    data
+}
+
+/// Prefixed so a payload's signature can't pass for a message's
+fn signed_payload_data(payload: &Payload, room: &Key) -> Vec<u8> {
+    let mut data = b"nym-chat payload".to_vec();
+    data.extend(room.id().into_bytes());
+    data.extend(bincode::serialize(payload).expect("Serialization can't fail"));
+    data
+}
+
+impl FromStr for SigningKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut secret = [0; 32];
+        constant_time::decode_hex(s, &mut secret)
+            .map_err(|_| anyhow::Error::msg("invalid signing key"))?;
+        let key = SigningKey::from_bytes(&secret);
+        secret[..].zeroize();
+        key
+    }
+}
+
+impl FromStr for RoomSecret {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut secret = [0; 32];
+        constant_time::decode_hex(s, &mut secret)
+            .map_err(|_| anyhow::Error::msg("invalid room secret"))?;
+        Ok(RoomSecret { secret })
+    }
+}
+
+impl FromStr for VerifyingKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key = PublicKey::from_bytes(&hex::decode(s)?)
+            .map_err(|_| anyhow::Error::msg("invalid verifying key"))?;
+        Ok(VerifyingKey { key })
+    }
+}
+
+impl fmt::Display for VerifyingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.key.as_bytes()))
+    }
+}
@@ -0,0 +1,89 @@
+//! Parsing and comparing secrets without timing side channels. Hex decoding usually looks digits up
+//! in a table and stops at the first invalid one, both of which leak something about the secret
+//! through timing. Here every digit is decoded with the same arithmetic and validity is only
+//! checked once at the end, comparisons go through `subtle`.
+//!
+//! Lengths aren't treated as secret, all our keys have the same length anyway.
+
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
+
+/// Decode `hex` into `out`, which has to be exactly half as long. On error `out` is zeroed.
+pub fn decode_hex(hex: &str, out: &mut [u8]) -> Result<(), anyhow::Error> {
+    let hex = hex.as_bytes();
+    let mut valid = Choice::from((hex.len() == 2 * out.len()) as u8);
+    for (idx, byte) in out.iter_mut().enumerate() {
+        let (high, high_valid) = nibble(hex.get(2 * idx).copied().unwrap_or_default());
+        let (low, low_valid) = nibble(hex.get(2 * idx + 1).copied().unwrap_or_default());
+        valid &= high_valid & low_valid;
+        *byte = high << 4 | low;
+    }
+    if bool::from(valid) {
+        Ok(())
+    } else {
+        out.zeroize();
+        Err(anyhow::Error::msg("invalid hex encoding"))
+    }
+}
+
+/// Value of a hex digit and whether it is one, computed without branches or table lookups (the
+/// approach of libsodium's `sodium_hex2bin`)
+fn nibble(c: u8) -> (u8, Choice) {
+    let digit = c ^ b'0';
+    // 0xff if `digit` is 0-9, 0 otherwise
+    let digit_mask = ((digit as u16).wrapping_sub(10) >> 8) as u8;
+    // Upper and lower case letters map to 10-15
+    let letter = (c & !32).wrapping_sub(b'A' - 10);
+    let letter_mask =
+        (((letter as u16).wrapping_sub(10) ^ (letter as u16).wrapping_sub(16)) >> 8) as u8;
+    let value = (digit & digit_mask) | (letter & letter_mask);
+    (value, Choice::from((digit_mask | letter_mask) & 1))
+}
+
+/// Compare two secrets in time depending only on their lengths
+pub fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_secrets_compare_equal() {
+        assert!(eq(b"secret", b"secret"));
+        assert!(eq(b"", b""));
+    }
+
+    #[test]
+    fn unequal_secrets_compare_unequal() {
+        assert!(!eq(b"secret", b"secreT"));
+        assert!(!eq(b"Secret", b"secret"));
+    }
+
+    #[test]
+    fn secrets_of_different_length_compare_unequal() {
+        assert!(!eq(b"secret", b"secret2"));
+        assert!(!eq(b"secret", b"secre"));
+        assert!(!eq(b"", b"s"));
+    }
+
+    #[test]
+    fn hex_decodes_like_the_hex_crate() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        for hex in &[hex::encode(&bytes), hex::encode(&bytes).to_uppercase()] {
+            let mut out = [0u8; 256];
+            decode_hex(hex, &mut out).unwrap();
+            assert_eq!(out[..], bytes[..]);
+        }
+    }
+
+    #[test]
+    fn invalid_hex_is_refused_and_zeroed() {
+        for hex in &["0g", "g0", "0 ", "0", "000", "/0", ":0", "@0", "G0", "`0"] {
+            let mut out = [0xffu8; 1];
+            assert!(decode_hex(hex, &mut out).is_err(), "{:?}", hex);
+            assert_eq!(out, [0]);
+        }
+    }
+}
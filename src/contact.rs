@@ -0,0 +1,13 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Contact details shared with `/card`, clients show them but don't act on them. Nothing ties the
+/// card to its sender, the fingerprint is only worth something if it's compared out of band.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ContactCard {
+    pub name: String,
+    /// Fingerprint of the sender's broadcast verifying key, if they have one
+    pub fingerprint: Option<String>,
+    /// Invite links of rooms the sender can be reached in
+    pub rooms: Vec<String>,
+}
@@ -0,0 +1,86 @@
+//! Noticing when the server (or the network) drops messages. Clients that opt in number the
+//! messages they send: every session picks a random stream id and counts from zero, inside the
+//! encryption so the server can't fake the numbers. Receivers remember the newest number of every
+//! stream per server and warn when numbers are skipped.
+//!
+//! Older clients can't decode numbered messages and skip them, so numbering is opt-in. Streams
+//! start anew with every session, so messages lost at the end of a session go unnoticed.
+
+use crate::{determinism, Payload};
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A payload with its number in the sender's stream
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Counted {
+    pub stream: u64,
+    pub counter: u64,
+    pub payload: Box<Payload>,
+}
+
+impl Counted {
+    /// Name the numbered message claims to be from, if it's a message
+    pub fn sender(&self) -> Option<&str> {
+        match &*self.payload {
+            Payload::Message(msg) | Payload::BurnAfterReading(msg) => Some(&msg.sender),
+            Payload::Signed(signed) => Some(&signed.msg.sender),
+            Payload::Authored(authored) => Some(&authored.msg.sender),
+            _ => None,
+        }
+    }
+}
+
+/// Numbers our messages
+pub struct Counter {
+    stream: u64,
+    next: u64,
+}
+
+impl Counter {
+    pub fn new() -> Counter {
+        Counter {
+            stream: determinism::with_rng(|rng| rng.gen()),
+            next: 0,
+        }
+    }
+
+    /// Wrap `payload` with the next number
+    pub fn count(&mut self, payload: Payload) -> Payload {
+        let counted = Counted {
+            stream: self.stream,
+            counter: self.next,
+            payload: Box::new(payload),
+        };
+        self.next += 1;
+        Payload::Counted(counted)
+    }
+}
+
+impl Default for Counter {
+    fn default() -> Self {
+        Counter::new()
+    }
+}
+
+/// Newest numbers received per server and stream
+#[derive(Default)]
+pub struct Gaps {
+    newest: HashMap<(usize, u64), u64>,
+}
+
+impl Gaps {
+    /// Record a numbered payload fetched from `server`, returns how many of the stream's messages
+    /// before it were skipped. The first message of a stream we see (e.g. after joining) never
+    /// counts as a gap, neither do numbers older than the newest.
+    pub fn check(&mut self, server: usize, counted: &Counted) -> u64 {
+        let newest = self
+            .newest
+            .entry((server, counted.stream))
+            .or_insert(counted.counter);
+        let skipped = counted.counter.saturating_sub(*newest + 1);
+        *newest = (*newest).max(counted.counter);
+        skipped
+    }
+}
@@ -0,0 +1,13 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Payload defined by a third party, e.g. a bot attached to a daemon. The type URI tells clients
+/// what the data is, clients that don't know the type ignore it. This lets bots define their own
+/// payloads without a new variant in [`crate::Payload`].
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Custom {
+    pub sender: String,
+    /// Identifies the format of `data`, e.g. `https://example.org/weather/v1`
+    pub type_uri: String,
+    pub data: Vec<u8>,
+}
@@ -0,0 +1,160 @@
+//! Where the library gets randomness and the current time from: nonces, keys, salts, poll ids,
+//! timestamps of messages and jitter all go through here. Normally that's the operating system.
+//!
+//! Builds with the `deterministic` feature (never a default) can call [`init`], after which
+//! everything comes from an RNG seeded with the given seed and a clock that ticks one second per
+//! reading, so runs are deterministic and encrypted output can be reproduced while debugging.
+//! Unit tests seed only their own thread with [`seeded`] instead.
+//!
+//! Never use it for real rooms: keys and nonces become predictable to anyone who knows the seed,
+//! and two runs with the same seed reuse nonces.
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+#[cfg(any(test, feature = "deterministic"))]
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(feature = "deterministic")]
+use std::sync::{Mutex, OnceLock};
+
+/// What the deterministic clock starts at (unix seconds, 2020-09-13), the same for every seed
+pub const START: u64 = 1_600_000_000;
+
+#[cfg(feature = "deterministic")]
+static SEEDED: OnceLock<Mutex<Seeded>> = OnceLock::new();
+
+#[cfg(test)]
+thread_local! {
+    static SCOPED: std::cell::RefCell<Option<Seeded>> = const { std::cell::RefCell::new(None) };
+}
+
+#[cfg_attr(not(any(test, feature = "deterministic")), allow(dead_code))]
+struct Seeded {
+    rng: rand::rngs::StdRng,
+    now: u64,
+}
+
+#[cfg(any(test, feature = "deterministic"))]
+impl Seeded {
+    fn new(seed: u64) -> Seeded {
+        Seeded {
+            rng: StdRng::seed_from_u64(seed),
+            now: START,
+        }
+    }
+}
+
+/// Take randomness from `seed` and time from the deterministic clock from now on. Only the first
+/// call has an effect, call it before anything random happens.
+#[cfg(feature = "deterministic")]
+pub fn init(seed: u64) {
+    let _ = SEEDED.set(Mutex::new(Seeded::new(seed)));
+}
+
+/// Whether [`init`] was called
+pub fn is_deterministic() -> bool {
+    with_seeded(|seeded| seeded.is_some())
+}
+
+/// Run `f` with randomness from `seed` and the deterministic clock, on this thread only
+#[cfg(test)]
+pub(crate) fn seeded<T>(seed: u64, f: impl FnOnce() -> T) -> T {
+    let previous = SCOPED.with(|scoped| scoped.replace(Some(Seeded::new(seed))));
+    let result = f();
+    SCOPED.with(|scoped| scoped.replace(previous));
+    result
+}
+
+/// Move the deterministic clock of [`seeded`] forward, e.g. into the next epoch
+#[cfg(test)]
+pub(crate) fn advance(secs: u64) {
+    SCOPED.with(|scoped| {
+        if let Some(seeded) = scoped.borrow_mut().as_mut() {
+            seeded.now += secs;
+        }
+    });
+}
+
+fn with_seeded<T>(f: impl FnOnce(Option<&mut Seeded>) -> T) -> T {
+    #[cfg(test)]
+    let f = match SCOPED.with(|scoped| scoped.borrow().is_some()) {
+        true => return SCOPED.with(|scoped| f(scoped.borrow_mut().as_mut())),
+        false => f,
+    };
+    #[cfg(feature = "deterministic")]
+    if let Some(seeded) = SEEDED.get() {
+        return f(Some(&mut seeded.lock().unwrap()));
+    }
+    f(None)
+}
+
+/// Run `f` with the RNG, e.g. `with_rng(|rng| rng.gen::<[u8; 32]>())`
+pub fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    with_seeded(|seeded| match seeded {
+        Some(seeded) => f(&mut seeded.rng),
+        None => f(&mut OsRng),
+    })
+}
+
+/// Current time in unix seconds
+pub fn now() -> u64 {
+    with_seeded(|seeded| match seeded {
+        Some(seeded) => {
+            seeded.now += 1;
+            seeded.now
+        }
+        None => system_now(),
+    })
+}
+
+/// Like [`now`], but without advancing the deterministic clock. For comparing times that were
+/// read with [`now`] against the present, e.g. on every redraw.
+pub fn current() -> u64 {
+    with_seeded(|seeded| match seeded {
+        Some(seeded) => seeded.now,
+        None => system_now(),
+    })
+}
+
+fn system_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, Message};
+
+    fn encrypt_hello() -> Vec<u8> {
+        let key = Key::generate();
+        let msg = Message::new("alice".into(), "hello".into());
+        bincode::serialize(&msg.encrypt(&key)).unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_ciphertext() {
+        assert_eq!(seeded(7, encrypt_hello), seeded(7, encrypt_hello));
+    }
+
+    #[test]
+    fn other_seeds_other_ciphertext() {
+        assert_ne!(seeded(7, encrypt_hello), seeded(8, encrypt_hello));
+        assert_ne!(encrypt_hello(), encrypt_hello());
+    }
+
+    #[test]
+    fn clock_only_ticks_when_read_with_now() {
+        seeded(7, || {
+            assert!(is_deterministic());
+            assert_eq!(current(), START);
+            assert_eq!(current(), START);
+            assert_eq!(now(), START + 1);
+            assert_eq!(current(), START + 1);
+            advance(60);
+            assert_eq!(current(), START + 61);
+        });
+        assert!(!is_deterministic());
+    }
+}
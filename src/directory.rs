@@ -0,0 +1,37 @@
+//! Directory rooms let operators announce mirror servers. A directory room is an ordinary room
+//! whose key is published by the operators (e.g. on their website); instead of chat messages it
+//! contains [`Announcement`]s. Clients started with the directory key read the room once after
+//! joining and add every announced server to their mirror list.
+//!
+//! Servers don't know which rooms they serve, so an announced server can mirror any room. Anyone
+//! with the directory key can announce servers though, so only use directories of operators you
+//! trust with your metadata.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A server that accepts and serves messages of all rooms
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Announcement {
+    /// URL messages are fetched from
+    pub url: String,
+    /// Base58 encoded Nym address of the server's service provider
+    pub service_provider: String,
+}
+
+/// Servers announced in a directory room, announcements are expected in the order they were sent.
+/// A later announcement of the same URL replaces an earlier one, e.g. after the operator moved the
+/// service provider to a new Nym address.
+pub fn servers(announcements: impl IntoIterator<Item = Announcement>) -> Vec<Announcement> {
+    let mut servers = Vec::<Announcement>::new();
+    for announcement in announcements {
+        match servers
+            .iter_mut()
+            .find(|server| server.url == announcement.url)
+        {
+            Some(server) => *server = announcement,
+            None => servers.push(announcement),
+        }
+    }
+    servers
+}
@@ -0,0 +1,516 @@
+//! Rooms whose new members can't read what was said before they joined. Such rooms have a chain
+//! key next to the room key: every epoch (a day unless configured otherwise) the chain key is
+//! replaced by an HKDF of itself and messages are encrypted with a key derived from the chain key
+//! of the epoch they are sent in. The chain only runs forward, so whoever knows the chain key of
+//! one epoch can read that epoch and all later ones, but nothing before.
+//!
+//! Invites carry a chain position: "full history" invites the oldest one the inviting member
+//! knows (the root for the room's creator), "no history" invites the current one. The room key is
+//! still part of every invite, it names the room and covers signatures, and messages of clients
+//! that don't know about chains are encrypted with it, so those stay readable for everyone.
+//!
+//! The epoch of a message is stored in the first four bytes of its nonce, masked with a hash of
+//! the room key so the server can't tell chain rooms apart by it. Recipients derive the matching
+//! key right away instead of trying every epoch.
+//!
+//! Keeping the oldest chain position keeps the whole history readable, also for whoever steals
+//! it. Clients ratchet it forward to the current epoch instead (see
+//! [`RoomKeys::forget_old_epochs`]), then stolen keys read nothing before the current epoch. Only
+//! the message key of the previous epoch is kept a little longer, see [`GRACE_SECS`].
+//!
+//! None of this protects rooms without a chain: their messages are encrypted with the room key,
+//! which every member keeps for as long as they're in the room.
+
+use crate::{constant_time, determinism, EncryptedMessage, Key, Payload, Undecryptable};
+use hkdf::Hkdf;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use zeroize::Zeroize;
+
+/// Epoch length of new chain rooms unless configured otherwise
+pub const DEFAULT_EPOCH_SECS: u64 = 24 * 60 * 60;
+
+/// Shorter epochs are refused, the chain would have to be walked too far to catch up
+const MIN_EPOCH_SECS: u64 = 60;
+
+/// How long into an epoch the message key of the previous one is kept for messages that arrive
+/// late (at most one epoch). Only that epoch's key is kept, it derives nothing else.
+pub const GRACE_SECS: u64 = 60 * 60;
+
+/// Chain positions further behind the current epoch are refused, walking them forward takes an
+/// HKDF per epoch (that's almost two years of the shortest epochs and ages of daily ones)
+const MAX_EPOCHS_BEHIND: u64 = 1 << 20;
+
+/// Every this many epochs the chain key is kept as a checkpoint when the chain is walked past it.
+/// Anyone can put any epoch into a nonce, with checkpoints the key of one costs at most this many
+/// HKDFs instead of a walk from our chain position.
+const CHECKPOINT_EPOCHS: u64 = 1 << 10;
+
+/// Chain key of one epoch, it derives the keys of this epoch and all later ones. It's wiped from
+/// memory when dropped, so forgotten positions don't linger in copies.
+#[derive(Clone)]
+pub struct ChainKey {
+    epoch_secs: u64,
+    epoch: u64,
+    key: [u8; 32],
+}
+
+impl ChainKey {
+    /// Root of a new chain, starting with the current epoch
+    pub fn generate(epoch_secs: u64) -> ChainKey {
+        ChainKey {
+            epoch_secs,
+            epoch: determinism::now() / epoch_secs,
+            key: determinism::with_rng(|rng| rng.gen()),
+        }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// The epoch messages sent now belong to, never earlier than the chain key's own
+    pub fn current_epoch(&self) -> u64 {
+        (determinism::current() / self.epoch_secs).max(self.epoch)
+    }
+
+    /// Chain key of a later epoch, `None` for earlier ones as the chain only runs forward
+    pub fn at(&self, epoch: u64) -> Option<ChainKey> {
+        if epoch < self.epoch {
+            return None;
+        }
+        let key = (self.epoch..epoch).fold(self.key, |mut key, _| {
+            let next = expand(&key, b"nym-chat chain key");
+            key.zeroize();
+            next
+        });
+        Some(ChainKey {
+            epoch_secs: self.epoch_secs,
+            epoch,
+            key,
+        })
+    }
+
+    fn message_key(&self) -> Key {
+        Key::from_bytes(&expand(&self.key, b"nym-chat message key")).expect("32 bytes")
+    }
+}
+
+impl Zeroize for ChainKey {
+    fn zeroize(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+impl Drop for ChainKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+fn expand(key: &[u8; 32], info: &[u8]) -> [u8; 32] {
+    let mut expanded = [0; 32];
+    Hkdf::<Sha256>::new(None, key)
+        .expand(info, &mut expanded)
+        .expect("32 bytes are a valid output length");
+    expanded
+}
+
+/// Everything needed to encrypt and decrypt a room's payloads: the room key and, in rooms with a
+/// key chain, the oldest chain position we know
+pub struct RoomKeys {
+    room: Key,
+    chain: Mutex<Option<ChainKey>>,
+    /// Masks the epoch in nonces
+    mask: [u8; 4],
+    /// Message keys of our own epochs and the ones of messages that decrypted, deriving them
+    /// walks the chain
+    keys: Mutex<HashMap<u64, Key>>,
+    /// Chain keys at multiples of [`CHECKPOINT_EPOCHS`] the chain was walked past
+    checkpoints: Mutex<BTreeMap<u64, ChainKey>>,
+}
+
+impl RoomKeys {
+    pub fn new(room: Key, chain: Option<ChainKey>) -> RoomKeys {
+        let mut hasher = Sha256::new();
+        hasher.update(b"nym-chat epoch mask");
+        hasher.update(&room.key);
+        let mut mask = [0; 4];
+        mask.copy_from_slice(&hasher.finalize()[..4]);
+        RoomKeys {
+            room,
+            chain: Mutex::new(chain),
+            mask,
+            keys: Mutex::new(HashMap::new()),
+            checkpoints: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn room(&self) -> &Key {
+        &self.room
+    }
+
+    /// Oldest chain position we know, for "full history" invites
+    pub fn chain(&self) -> Option<ChainKey> {
+        self.chain.lock().unwrap().clone()
+    }
+
+    /// Chain position of the current epoch, for "no history" invites
+    pub fn current_chain(&self) -> Option<ChainKey> {
+        self.chain()
+            .and_then(|chain| chain.at(chain.current_epoch()))
+    }
+
+    /// Move our chain position forward to the current epoch and forget the keys of all earlier
+    /// ones. The message key of the previous epoch is kept in memory until [`GRACE_SECS`] into the
+    /// current one, for messages that arrive late. Returns the new position to store instead of
+    /// the old one, `None` if it didn't move.
+    pub fn forget_old_epochs(&self) -> Option<ChainKey> {
+        let mut chain = self.chain.lock().unwrap();
+        let old = chain.as_mut()?;
+        let epoch = old.current_epoch();
+        let in_grace = determinism::current().saturating_sub(epoch * old.epoch_secs)
+            < GRACE_SECS.min(old.epoch_secs);
+        let mut keys = self.keys.lock().unwrap();
+        let new = if epoch > old.epoch {
+            if in_grace {
+                let previous = old.at(epoch - 1)?.message_key();
+                keys.entry(epoch - 1).or_insert(previous);
+            }
+            // The old position zeroizes itself when it's replaced, like the message keys
+            let new = old.at(epoch)?;
+            *old = new.clone();
+            Some(new)
+        } else {
+            None
+        };
+        keys.retain(|key_epoch, _| *key_epoch >= epoch || (in_grace && *key_epoch + 1 == epoch));
+        let mut checkpoints = self.checkpoints.lock().unwrap();
+        *checkpoints = checkpoints.split_off(&epoch);
+        new
+    }
+
+    pub fn encrypt(&self, payload: &Payload) -> EncryptedMessage {
+        self.encrypt_padded(payload, &[])
+    }
+
+    /// Encrypt with the key of the current epoch, or the room key in rooms without a chain
+    pub fn encrypt_padded(&self, payload: &Payload, buckets: &[usize]) -> EncryptedMessage {
+        let epoch = match self.chain() {
+            Some(chain) => chain.current_epoch(),
+            None => return payload.encrypt_padded(&self.room, buckets),
+        };
+        let key = self.epoch_key(epoch).expect("the current epoch is known");
+        self.keys.lock().unwrap().insert(epoch, key.clone());
+        let prefix = self.mask_epoch(epoch);
+        EncryptedMessage::seal_tagged(payload, &key, buckets, Some(prefix))
+    }
+
+    /// Decrypt with the key of the message's epoch, falling back to the room key for messages of
+    /// clients without chains. Messages from before our chain position look like messages of
+    /// other rooms.
+    pub fn try_decrypt(&self, msg: EncryptedMessage) -> Result<Payload, Undecryptable> {
+        if self.chain().is_none() {
+            return Payload::try_decrypt(msg, &self.room);
+        }
+        let epoch = self.mask_epoch_bytes(msg.nonce_prefix());
+        match self.epoch_key(epoch) {
+            Some(key) => match Payload::try_decrypt(msg.clone(), &key) {
+                Err(Undecryptable::OtherKey) => Payload::try_decrypt(msg, &self.room),
+                // Only keys of epochs that really have messages are kept, the prefix could be
+                // anything
+                Ok(payload) => {
+                    self.keys.lock().unwrap().insert(epoch, key);
+                    Ok(payload)
+                }
+                result => result,
+            },
+            None => Payload::try_decrypt(msg, &self.room),
+        }
+    }
+
+    /// Message key of `epoch`, if it's neither before our chain position nor in the future (one
+    /// epoch of clock skew is tolerated)
+    fn epoch_key(&self, epoch: u64) -> Option<Key> {
+        let chain = self.chain()?;
+        if epoch > chain.current_epoch() + 1 {
+            return None;
+        }
+        if let Some(key) = self.keys.lock().unwrap().get(&epoch) {
+            return Some(key.clone());
+        }
+        Some(self.walk(chain, epoch)?.message_key())
+    }
+
+    /// Chain key of `epoch`, walked to from the closest checkpoint before it (or our chain
+    /// position), leaving checkpoints on the way
+    fn walk(&self, chain: ChainKey, epoch: u64) -> Option<ChainKey> {
+        if epoch < chain.epoch {
+            return None;
+        }
+        let mut checkpoints = self.checkpoints.lock().unwrap();
+        let mut position = match checkpoints.range(chain.epoch..=epoch).next_back() {
+            Some((_, checkpoint)) => checkpoint.clone(),
+            None => chain,
+        };
+        while position.epoch < epoch {
+            let next = (position.epoch / CHECKPOINT_EPOCHS + 1) * CHECKPOINT_EPOCHS;
+            position = position.at(next.min(epoch))?;
+            if position.epoch % CHECKPOINT_EPOCHS == 0 {
+                checkpoints.insert(position.epoch, position.clone());
+            }
+        }
+        Some(position)
+    }
+
+    /// Epochs are stored in 32 bits, enough for second long epochs until 2106
+    fn mask_epoch(&self, epoch: u64) -> [u8; 4] {
+        let mut prefix = (epoch as u32).to_le_bytes();
+        prefix
+            .iter_mut()
+            .zip(&self.mask)
+            .for_each(|(byte, mask)| *byte ^= mask);
+        prefix
+    }
+
+    fn mask_epoch_bytes(&self, prefix: [u8; 4]) -> u64 {
+        u32::from_le_bytes(self.mask_epoch(u32::from_le_bytes(prefix) as u64)) as u64
+    }
+}
+
+impl fmt::Display for ChainKey {
+    /// `<epoch length in seconds>.<epoch>.<hex encoded key>`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}",
+            self.epoch_secs,
+            self.epoch,
+            hex::encode(self.key)
+        )
+    }
+}
+
+impl FromStr for ChainKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow::Error::msg("invalid chain key");
+        let mut parts = s.splitn(3, '.');
+        let epoch_secs = parts.next().ok_or_else(invalid)?.parse::<u64>()?;
+        let epoch = parts.next().ok_or_else(invalid)?.parse()?;
+        let mut key = [0; 32];
+        constant_time::decode_hex(parts.next().ok_or_else(invalid)?, &mut key)
+            .map_err(|_| invalid())?;
+        if epoch_secs < MIN_EPOCH_SECS {
+            anyhow::bail!("chain key epochs can't be shorter than {}s", MIN_EPOCH_SECS);
+        }
+        // One epoch of clock skew is tolerated like for messages
+        let current = determinism::current() / epoch_secs;
+        if epoch > current + 1 || epoch + MAX_EPOCHS_BEHIND < current {
+            anyhow::bail!("the chain key's epoch is too far from the current one");
+        }
+        Ok(ChainKey {
+            epoch_secs,
+            epoch,
+            key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::determinism::{advance, seeded};
+    use crate::Message;
+
+    fn hello() -> Payload {
+        Payload::Message(Message::new("alice".into(), "hello".into()))
+    }
+
+    fn chain_room() -> RoomKeys {
+        RoomKeys::new(
+            Key::generate(),
+            Some(ChainKey::generate(DEFAULT_EPOCH_SECS)),
+        )
+    }
+
+    /// Seconds until the next epoch starts
+    fn until_next_epoch() -> u64 {
+        DEFAULT_EPOCH_SECS - determinism::current() % DEFAULT_EPOCH_SECS
+    }
+
+    #[test]
+    fn chain_keys_derive_forward_only() {
+        seeded(3, || {
+            let root = ChainKey::generate(DEFAULT_EPOCH_SECS);
+            let start = root.epoch();
+            let later = root.at(start + 5).unwrap();
+            assert_eq!(later.epoch(), start + 5);
+            // Walking in steps ends at the same key as walking at once
+            let stepped = root.at(start + 2).unwrap().at(start + 5).unwrap();
+            assert_eq!(stepped.to_string(), later.to_string());
+            assert_eq!(root.at(start).unwrap().to_string(), root.to_string());
+            assert_ne!(later.key, root.key);
+            assert!(later.at(start + 4).is_none());
+            assert!(root.at(start - 1).is_none());
+
+            let parsed = root.to_string().parse::<ChainKey>().unwrap();
+            assert_eq!(parsed.to_string(), root.to_string());
+        });
+    }
+
+    #[test]
+    fn chain_keys_far_from_now_are_refused() {
+        seeded(4, || {
+            let root = ChainKey::generate(MIN_EPOCH_SECS);
+            let current = root.current_epoch();
+            let parse = |chain: ChainKey| chain.to_string().parse::<ChainKey>();
+            assert!(parse(root.at(current + 1).unwrap()).is_ok());
+            assert!(parse(root.at(current + 2).unwrap()).is_err());
+            let short = format!(
+                "{}.{}.{}",
+                MIN_EPOCH_SECS - 1,
+                current,
+                hex::encode(root.key)
+            );
+            assert!(short.parse::<ChainKey>().is_err());
+        });
+    }
+
+    #[test]
+    fn messages_decrypt_across_epoch_boundaries() {
+        seeded(5, || {
+            let keys = chain_room();
+            let member = RoomKeys::new(keys.room().clone(), keys.chain());
+            let first = keys.chain().unwrap().current_epoch();
+            // Writing the message reads the clock once more
+            advance(until_next_epoch() - 2);
+            let before = keys.encrypt(&hello());
+            assert_eq!(keys.chain().unwrap().current_epoch(), first);
+            advance(1);
+            let after = keys.encrypt(&hello());
+            assert_eq!(keys.chain().unwrap().current_epoch(), first + 1);
+
+            assert!(member.try_decrypt(before.clone()).is_ok());
+            assert!(member.try_decrypt(after.clone()).is_ok());
+            // Invited without history: today on, but nothing before
+            let newcomer = RoomKeys::new(keys.room().clone(), keys.current_chain());
+            assert!(newcomer.try_decrypt(before).is_err());
+            assert!(newcomer.try_decrypt(after).is_ok());
+        });
+    }
+
+    #[test]
+    fn one_epoch_of_clock_skew_is_tolerated() {
+        seeded(6, || {
+            let keys = chain_room();
+            let chain = keys.chain().unwrap();
+            // Like a sender whose clock is ahead by `epochs`
+            let ahead = |epochs| {
+                let epoch = chain.current_epoch() + epochs;
+                let key = chain.at(epoch).unwrap().message_key();
+                let prefix = keys.mask_epoch(epoch);
+                EncryptedMessage::seal_tagged(&hello(), &key, &[], Some(prefix))
+            };
+            assert!(keys.try_decrypt(ahead(1)).is_ok());
+            assert!(keys.try_decrypt(ahead(2)).is_err());
+        });
+    }
+
+    #[test]
+    fn forged_epochs_only_walk_from_checkpoints() {
+        seeded(7, || {
+            let room = Key::generate();
+            let mut root = ChainKey::generate(MIN_EPOCH_SECS);
+            let start = root.epoch() - 3 * CHECKPOINT_EPOCHS;
+            root.epoch = start;
+            let keys = RoomKeys::new(room.clone(), Some(root.clone()));
+            let current = root.current_epoch();
+            // Messages anyone could send, with made up epochs and nothing the keys decrypt
+            let forged = |epoch| {
+                let prefix = keys.mask_epoch(epoch);
+                EncryptedMessage::seal_tagged(&hello(), &Key::generate(), &[], Some(prefix))
+            };
+            for epoch in &[
+                current,
+                start + 1,
+                current - 1,
+                start + CHECKPOINT_EPOCHS + 5,
+            ] {
+                assert!(keys.try_decrypt(forged(*epoch)).is_err());
+            }
+            assert!(keys.keys.lock().unwrap().is_empty());
+            let checkpoints = keys
+                .checkpoints
+                .lock()
+                .unwrap()
+                .keys()
+                .copied()
+                .collect::<Vec<_>>();
+            let multiples = (start / CHECKPOINT_EPOCHS + 1..=current / CHECKPOINT_EPOCHS)
+                .map(|n| n * CHECKPOINT_EPOCHS)
+                .collect::<Vec<_>>();
+            assert_eq!(checkpoints, multiples);
+
+            // Keys derived from a checkpoint are the chain's own
+            let member = RoomKeys::new(room, Some(root));
+            let msg = keys.encrypt(&hello());
+            assert!(member.try_decrypt(msg).is_ok());
+            let old = root_message(&keys, start + 2 * CHECKPOINT_EPOCHS + 1);
+            assert!(keys.try_decrypt(old).is_ok());
+        });
+    }
+
+    /// A message of `epoch` as a member holding the chain key of `keys` sends it
+    fn root_message(keys: &RoomKeys, epoch: u64) -> EncryptedMessage {
+        let key = keys.chain().unwrap().at(epoch).unwrap().message_key();
+        EncryptedMessage::seal_tagged(&hello(), &key, &[], Some(keys.mask_epoch(epoch)))
+    }
+
+    #[test]
+    fn forgotten_epochs_cant_be_derived_from_the_current_state() {
+        seeded(1, || {
+            let keys = chain_room();
+            let old = keys.encrypt(&hello());
+            let first = keys.chain().unwrap().epoch();
+            advance(2 * DEFAULT_EPOCH_SECS);
+
+            let chain = keys.forget_old_epochs().expect("two epochs passed");
+            assert_eq!(chain.epoch(), first + 2);
+            assert_eq!(keys.chain().unwrap().epoch(), first + 2);
+            assert!((first..first + 2).all(|epoch| chain.at(epoch).is_none()));
+            assert!(keys.try_decrypt(old.clone()).is_err());
+
+            let restored = RoomKeys::new(
+                keys.room().clone(),
+                Some(chain.to_string().parse().unwrap()),
+            );
+            assert!(restored.try_decrypt(old).is_err());
+            assert!(restored.try_decrypt(keys.encrypt(&hello())).is_ok());
+        });
+    }
+
+    #[test]
+    fn previous_epoch_stays_readable_for_the_grace_period_only() {
+        seeded(2, || {
+            let keys = chain_room();
+            let late = keys.encrypt(&hello());
+            advance(until_next_epoch() + 10);
+
+            let chain = keys.forget_old_epochs().expect("a new epoch started");
+            assert!(chain.at(chain.epoch() - 1).is_none());
+            assert!(keys.try_decrypt(late.clone()).is_ok());
+            let restored = RoomKeys::new(keys.room().clone(), Some(chain));
+            assert!(restored.try_decrypt(late.clone()).is_err());
+
+            advance(GRACE_SECS);
+            assert!(keys.forget_old_epochs().is_none());
+            assert!(keys.try_decrypt(late).is_err());
+        });
+    }
+}
@@ -0,0 +1,63 @@
+//! How the binaries end when something goes wrong: a readable message on stderr and an exit code
+//! telling scripts and service managers what to look at. Errors are passed up as [`Fatal`] to the
+//! binary's `main`, which prints them once the terminal is restored.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Anything not covered by the codes below
+    Failure = 1,
+    /// Invalid options, config files, keys or links, retrying won't help
+    Config = 2,
+    /// The Nym client at `--websocket` can't be reached
+    NymUnreachable = 3,
+    /// The chat server (or the directory room on it) can't be reached
+    ServerUnreachable = 4,
+}
+
+/// An error the program ends with
+#[derive(Debug)]
+pub struct Fatal {
+    pub code: ExitCode,
+    pub error: anyhow::Error,
+}
+
+impl Fatal {
+    /// Ends with `message`, which is shown as is (e.g. already translated)
+    pub fn new(code: ExitCode, message: impl fmt::Display) -> Fatal {
+        Fatal {
+            code,
+            error: anyhow::Error::msg(message.to_string()),
+        }
+    }
+
+    /// Print the error and its causes and exit with its code
+    pub fn exit(self) -> ! {
+        eprintln!("{:#}", self.error);
+        std::process::exit(self.code as i32)
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for Fatal {
+    fn from(error: E) -> Fatal {
+        Fatal {
+            code: ExitCode::Failure,
+            error: error.into(),
+        }
+    }
+}
+
+/// Attach an exit code and context to errors, like anyhow's `Context`
+pub trait OrExit<T> {
+    fn or_exit(self, code: ExitCode, context: impl fmt::Display) -> Result<T, Fatal>;
+}
+
+impl<T, E: Into<anyhow::Error>> OrExit<T> for Result<T, E> {
+    fn or_exit(self, code: ExitCode, context: impl fmt::Display) -> Result<T, Fatal> {
+        self.map_err(|error| Fatal {
+            code,
+            error: error.into().context(context.to_string()),
+        })
+    }
+}
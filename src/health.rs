@@ -0,0 +1,53 @@
+//! Counters of what goes wrong between the client and the servers: errors the Nym client reports,
+//! reconnects to it and sends and fetches that failed. Transports and the client loop count into a
+//! shared [`Health`], `/stats` shows the totals and `--statsd` exports them, so bots running for
+//! weeks can be monitored like any other service.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    /// The Nym client answered a request with an error
+    NymError,
+    /// The connection to the Nym client was lost and opened again
+    Reconnect,
+    /// Handing a message to a server's service provider failed
+    SendFailure,
+    /// Fetching new messages from a server failed
+    FetchFailure,
+}
+
+impl Problem {
+    pub const ALL: [Problem; 4] = [
+        Problem::NymError,
+        Problem::Reconnect,
+        Problem::SendFailure,
+        Problem::FetchFailure,
+    ];
+
+    /// Name of the metric, e.g. for statsd
+    pub fn name(self) -> &'static str {
+        match self {
+            Problem::NymError => "nym_errors",
+            Problem::Reconnect => "reconnects",
+            Problem::SendFailure => "send_failures",
+            Problem::FetchFailure => "fetch_failures",
+        }
+    }
+}
+
+/// Problems counted since the client started, can be shared between tasks
+#[derive(Debug, Default)]
+pub struct Health {
+    counts: [AtomicUsize; 4],
+}
+
+impl Health {
+    pub fn record(&self, problem: Problem) {
+        self.counts[problem as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, problem: Problem) -> usize {
+        self.counts[problem as usize].load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,748 @@
+use crate::epoch::ChainKey;
+use crate::notify::Rules;
+use crate::outbox::Outbox;
+use crate::receipt::{Receipt, TreeHead};
+use crate::report::Reports;
+use crate::schedule::Schedule;
+use crate::{EncryptedMessage, Key, Message};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// Number of message digests remembered to detect messages fetched from several servers
+const MAX_SEEN: usize = 10_000;
+
+/// Local append-only store of all messages received in a room, so the client doesn't have to keep
+/// the whole history in memory and can resume where it stopped after a restart.
+///
+/// Messages are stored as length prefixed records, each encrypted to the room key like drafts and
+/// settings, so nothing in the data directory can be read without the room key. Records written
+/// by older clients are plaintext bincode, they are still read. Rooms can opt out of storing
+/// messages on disk at all, see [`Retention`], and incognito sessions keep everything in memory
+/// (see [`History::in_memory`]).
+pub struct History {
+    storage: Storage,
+    position_path: PathBuf,
+    draft_path: PathBuf,
+    notify_path: PathBuf,
+    retention_path: PathBuf,
+    schedule_path: PathBuf,
+    auto_reply_path: PathBuf,
+    outbox_path: PathBuf,
+    archived_path: PathBuf,
+    priority_path: PathBuf,
+    reports_path: PathBuf,
+    receipts_path: PathBuf,
+    tree_head_path: PathBuf,
+    chain_path: PathBuf,
+    chain_floor_path: PathBuf,
+    /// Digests of the newest messages we received, see [`History::mark_seen`]
+    seen: HashSet<[u8; 32]>,
+    seen_order: VecDeque<[u8; 32]>,
+    /// Missing if nothing is stored on disk
+    seen_file: Option<File>,
+    /// Contents of the files above by their path, if nothing is stored on disk
+    memory: Option<RefCell<HashMap<PathBuf, Vec<u8>>>>,
+    /// Used to encrypt drafts and settings, they might contain things the user hasn't decided to
+    /// share yet
+    key: Key,
+}
+
+enum Storage {
+    File {
+        file: File,
+        /// Offset of every record in the file, so random access doesn't require scanning
+        offsets: Vec<u64>,
+        end: u64,
+    },
+    /// The room's messages aren't persisted, they are gone once the client exits
+    Memory(Vec<Message>),
+}
+
+/// Per-room settings for what is kept locally. They are enforced when the history is opened, so
+/// changes take effect the next time the client starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Retention {
+    /// Write messages to disk, if disabled an existing history is deleted
+    pub persist: bool,
+    /// Delete messages sent more than this many days ago
+    pub keep_days: Option<u64>,
+    /// Include the messages in archives created with `/export-archive`
+    pub export: bool,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Retention {
+            persist: true,
+            keep_days: None,
+            export: true,
+        }
+    }
+}
+
+/// How eagerly a room is fetched, set with `/priority`. Busy rooms can be fetched more often than
+/// `--fetch-interval` asks for, rooms that are only read now and then every few minutes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+/// Fetch interval of low priority rooms, unless `--fetch-interval` is even longer
+const LOW_PRIORITY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+impl Priority {
+    /// Time between two fetches given the configured `interval`: high priority rooms are fetched
+    /// four times as often
+    pub fn interval(self, interval: Duration) -> Duration {
+        match self {
+            Priority::High => (interval / 4).max(Duration::from_millis(1)),
+            Priority::Normal => interval,
+            Priority::Low => interval.max(LOW_PRIORITY_INTERVAL),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "high" => Ok(Priority::High),
+            "normal" => Ok(Priority::Normal),
+            "low" => Ok(Priority::Low),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        })
+    }
+}
+
+impl History {
+    /// Open (or create) the history of `room` inside `data_dir`
+    pub fn open(data_dir: &Path, room: &Key) -> Result<History, anyhow::Error> {
+        std::fs::create_dir_all(data_dir)?;
+        let retention_path = data_dir.join(format!("{}.retention", room.id()));
+        let retention = std::fs::read(&retention_path)
+            .ok()
+            .and_then(|sealed| open_sealed::<Retention>(&sealed, room))
+            .unwrap_or_default();
+        let history_path = data_dir.join(format!("{}.history", room.id()));
+        let storage = if retention.persist {
+            open_records(&history_path, retention.keep_days, room)?
+        } else {
+            if history_path.exists() {
+                std::fs::remove_file(&history_path)?;
+            }
+            Storage::Memory(Vec::new())
+        };
+
+        let (seen_order, seen_file) = open_seen(&data_dir.join(format!("{}.seen", room.id())))?;
+        let mut history = History::new(data_dir, room, storage, None);
+        history.seen = seen_order.iter().copied().collect();
+        history.seen_order = seen_order;
+        history.seen_file = Some(seen_file);
+        Ok(history)
+    }
+
+    /// History of an incognito session: it never touches the disk and everything in it is wiped
+    /// from memory when it's dropped
+    pub fn in_memory(room: &Key) -> History {
+        let memory = Some(RefCell::new(HashMap::new()));
+        History::new(Path::new(""), room, Storage::Memory(Vec::new()), memory)
+    }
+
+    fn new(
+        data_dir: &Path,
+        room: &Key,
+        storage: Storage,
+        memory: Option<RefCell<HashMap<PathBuf, Vec<u8>>>>,
+    ) -> History {
+        History {
+            storage,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+            seen_file: None,
+            memory,
+            position_path: data_dir.join(format!("{}.position", room.id())),
+            draft_path: data_dir.join(format!("{}.draft", room.id())),
+            notify_path: data_dir.join(format!("{}.notify", room.id())),
+            retention_path: data_dir.join(format!("{}.retention", room.id())),
+            schedule_path: data_dir.join(format!("{}.schedule", room.id())),
+            auto_reply_path: data_dir.join(format!("{}.auto-reply", room.id())),
+            outbox_path: data_dir.join(format!("{}.outbox", room.id())),
+            archived_path: data_dir.join(format!("{}.archived", room.id())),
+            priority_path: data_dir.join(format!("{}.priority", room.id())),
+            reports_path: data_dir.join(format!("{}.reports", room.id())),
+            receipts_path: data_dir.join(format!("{}.receipts", room.id())),
+            tree_head_path: data_dir.join(format!("{}.tree-head", room.id())),
+            chain_path: data_dir.join(format!("{}.chain", room.id())),
+            chain_floor_path: data_dir.join(format!("{}.chain-floor", room.id())),
+            key: room.clone(),
+        }
+    }
+
+    /// Number of stored messages
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::File { offsets, .. } => offsets.len(),
+            Storage::Memory(messages) => messages.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append a message and return its index
+    pub fn append(&mut self, msg: &Message) -> Result<usize, anyhow::Error> {
+        let (file, offsets, end) = match &mut self.storage {
+            Storage::File { file, offsets, end } => (file, offsets, end),
+            Storage::Memory(messages) => {
+                messages.push(msg.clone());
+                return Ok(messages.len() - 1);
+            }
+        };
+        let record = bincode::serialize(&EncryptedMessage::seal(msg, &self.key))?;
+        let mut buf = Vec::with_capacity(record.len() + 4);
+        buf.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&record);
+        file.write_all(&buf)?;
+
+        offsets.push(*end);
+        *end += buf.len() as u64;
+        Ok(offsets.len() - 1)
+    }
+
+    /// Load the messages with indices in `range`, out of bound indices are ignored
+    pub fn load(&mut self, range: Range<usize>) -> Result<Vec<Message>, anyhow::Error> {
+        let range = range.start.min(self.len())..range.end.min(self.len());
+        if range.is_empty() {
+            return Ok(vec![]);
+        }
+
+        match &self.storage {
+            Storage::File { file, offsets, .. } => {
+                read_records(file, offsets[range.start], range.len(), &self.key)
+            }
+            Storage::Memory(messages) => Ok(messages[range].to_vec()),
+        }
+    }
+
+    /// Server side index of the first message we haven't fetched yet
+    pub fn read_position(&self) -> usize {
+        self.read_position_file(&self.position_path)
+    }
+
+    pub fn set_read_position(&self, position: usize) -> Result<(), anyhow::Error> {
+        self.write_file(&self.position_path, position.to_string().into_bytes())
+    }
+
+    /// Like [`History::read_position`] but for a mirror server, identified by its URL. `None` for
+    /// mirrors we never fetched from.
+    pub fn mirror_position(&self, url: &str) -> Option<usize> {
+        let path = self.mirror_position_path(url);
+        self.read_file(&path)?;
+        Some(self.read_position_file(&path))
+    }
+
+    pub fn set_mirror_position(&self, url: &str, position: usize) -> Result<(), anyhow::Error> {
+        self.write_file(
+            &self.mirror_position_path(url),
+            position.to_string().into_bytes(),
+        )
+    }
+
+    /// Where fetching continues for each of the servers: the read position for the first one, the
+    /// primary server, and the mirror positions for the others
+    pub fn fetch_positions<'a>(
+        &self,
+        urls: impl IntoIterator<Item = &'a str>,
+    ) -> Vec<Option<usize>> {
+        urls.into_iter()
+            .enumerate()
+            .map(|(idx, url)| match idx {
+                0 => Some(self.read_position()),
+                _ => self.mirror_position(url),
+            })
+            .collect()
+    }
+
+    fn read_position_file(&self, path: &Path) -> usize {
+        self.read_file(path)
+            .and_then(|pos| String::from_utf8(pos).ok())
+            .and_then(|pos| pos.trim().parse().ok())
+            .unwrap_or(0)
+    }
+
+    fn mirror_position_path(&self, url: &str) -> PathBuf {
+        let url_hash = hex::encode(&Sha256::digest(url.as_bytes())[..8]);
+        self.position_path
+            .with_extension(format!("position-{}", url_hash))
+    }
+
+    /// Remember the digest of a received message, returns false if we saw it before (e.g. because
+    /// it was fetched from another server already). Only the newest digests are kept, that's
+    /// enough since servers serve messages in the order they received them.
+    pub fn mark_seen(&mut self, digest: [u8; 32]) -> Result<bool, anyhow::Error> {
+        if !self.seen.insert(digest) {
+            return Ok(false);
+        }
+        if let Some(seen_file) = &mut self.seen_file {
+            seen_file.write_all(&digest)?;
+        }
+        self.seen_order.push_back(digest);
+        if self.seen_order.len() > MAX_SEEN {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Digests remembered by [`History::mark_seen`], oldest first
+    pub fn seen(&self) -> impl Iterator<Item = &[u8; 32]> {
+        self.seen_order.iter()
+    }
+
+    /// Unsent input from the last session, if any
+    pub fn draft(&self) -> Option<String> {
+        self.read_sealed(&self.draft_path)
+    }
+
+    /// Store unsent input, an empty draft removes the stored one
+    pub fn set_draft(&self, draft: &str) -> Result<(), anyhow::Error> {
+        if draft.is_empty() {
+            return self.remove_file(&self.draft_path);
+        }
+        self.write_sealed(&self.draft_path, &draft.to_owned())
+    }
+
+    /// Notification settings of the room, the defaults if they were never changed
+    pub fn notify_rules(&self) -> Rules {
+        self.read_sealed(&self.notify_path).unwrap_or_default()
+    }
+
+    pub fn set_notify_rules(&self, rules: &Rules) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.notify_path, rules)
+    }
+
+    /// Keep the server's receipt for one of our messages, one JSON object per line
+    pub fn save_receipt(&self, receipt: &Receipt) -> Result<(), anyhow::Error> {
+        let mut line = serde_json::to_vec(receipt)?;
+        line.push(b'\n');
+        if let Some(memory) = &self.memory {
+            let mut memory = memory.borrow_mut();
+            memory
+                .entry(self.receipts_path.clone())
+                .or_default()
+                .extend(line);
+            return Ok(());
+        }
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.receipts_path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    /// Where receipts are kept, `None` if only in memory
+    pub fn receipts_path(&self) -> Option<&Path> {
+        match self.memory {
+            Some(_) => None,
+            None => Some(&self.receipts_path),
+        }
+    }
+
+    /// Retention settings of the room, the defaults (keep everything) if they were never changed
+    pub fn retention(&self) -> Retention {
+        self.read_sealed(&self.retention_path).unwrap_or_default()
+    }
+
+    pub fn set_retention(&self, retention: &Retention) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.retention_path, retention)
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.read_sealed(&self.priority_path).unwrap_or_default()
+    }
+
+    pub fn set_priority(&self, priority: Priority) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.priority_path, &priority)
+    }
+
+    /// Messages waiting to be sent with `/schedule`
+    pub fn schedule(&self) -> Schedule {
+        self.read_sealed(&self.schedule_path).unwrap_or_default()
+    }
+
+    pub fn set_schedule(&self, schedule: &Schedule) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.schedule_path, schedule)
+    }
+
+    /// Whether the room opted into auto-replies while the user is away, see the away module
+    pub fn auto_reply(&self) -> bool {
+        self.read_sealed(&self.auto_reply_path).unwrap_or_default()
+    }
+
+    pub fn set_auto_reply(&self, enabled: bool) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.auto_reply_path, &enabled)
+    }
+
+    /// Whether the room was archived with `/archive`: its history stays readable, but nothing is
+    /// fetched or sent anymore
+    pub fn archived(&self) -> bool {
+        self.read_sealed(&self.archived_path).unwrap_or_default()
+    }
+
+    pub fn set_archived(&self, archived: bool) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.archived_path, &archived)
+    }
+
+    /// Reports received as the room's moderator, see the report module
+    pub fn reports(&self) -> Reports {
+        self.read_sealed(&self.reports_path).unwrap_or_default()
+    }
+
+    pub fn set_reports(&self, reports: &Reports) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.reports_path, reports)
+    }
+
+    /// Delete everything stored about `room` in `data_dir` when leaving it: messages, read
+    /// positions, drafts and settings. Returns the number of deleted files. The history must not
+    /// be used afterwards, writing to it would create the files again.
+    pub fn wipe(data_dir: &Path, room: &Key) -> Result<usize, anyhow::Error> {
+        let prefix = format!("{}.", room.id());
+        let mut deleted = 0;
+        for entry in std::fs::read_dir(data_dir)? {
+            let path = entry?.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            if name.starts_with(&prefix) {
+                std::fs::remove_file(&path)?;
+                deleted += 1;
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Messages that didn't reach every server yet, see the outbox module
+    pub fn outbox(&self) -> Outbox {
+        self.read_sealed(&self.outbox_path).unwrap_or_default()
+    }
+
+    pub fn set_outbox(&self, outbox: &Outbox) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.outbox_path, outbox)
+    }
+
+    /// Latest verified tree head of the server's log, newer ones have to be consistent with it
+    pub fn tree_head(&self) -> Option<TreeHead> {
+        self.read_sealed(&self.tree_head_path)
+    }
+
+    pub fn set_tree_head(&self, head: &TreeHead) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.tree_head_path, head)
+    }
+
+    /// Oldest chain position we know in a room with a key chain, so reconnecting with just the
+    /// room key keeps it readable
+    pub fn chain(&self) -> Option<ChainKey> {
+        self.read_sealed::<String>(&self.chain_path)?.parse().ok()
+    }
+
+    pub fn set_chain(&self, chain: &ChainKey) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.chain_path, &chain.to_string())
+    }
+
+    /// Epoch our chain position was moved forward to unless --keep-old-epochs is given (see
+    /// `RoomKeys::forget_old_epochs`), older positions like the one of an invite are moved up to
+    /// it so the forgotten epochs stay forgotten
+    pub fn chain_floor(&self) -> Option<u64> {
+        self.read_sealed(&self.chain_floor_path)
+    }
+
+    pub fn set_chain_floor(&self, epoch: u64) -> Result<(), anyhow::Error> {
+        self.write_sealed(&self.chain_floor_path, &epoch)
+    }
+
+    /// Like [`History::chain`], without opening the history
+    pub fn stored_chain(data_dir: &Path, room: &Key) -> Option<ChainKey> {
+        let path = data_dir.join(format!("{}.chain", room.id()));
+        open_sealed::<String>(&std::fs::read(path).ok()?, room)?
+            .parse()
+            .ok()
+    }
+
+    /// Like [`History::read_position`], without opening the history. `None` for rooms that were
+    /// never fetched.
+    pub fn stored_read_position(data_dir: &Path, room: &Key) -> Option<usize> {
+        let path = data_dir.join(format!("{}.position", room.id()));
+        std::fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    /// Settings are stored encrypted to the room key like drafts
+    fn read_sealed<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        open_sealed(&self.read_file(path)?, &self.key)
+    }
+
+    fn write_sealed<T: Serialize>(&self, path: &Path, value: &T) -> Result<(), anyhow::Error> {
+        let sealed = EncryptedMessage::seal(value, &self.key);
+        self.write_file(path, bincode::serialize(&sealed)?)
+    }
+
+    fn read_file(&self, path: &Path) -> Option<Vec<u8>> {
+        match &self.memory {
+            Some(memory) => memory.borrow().get(path).cloned(),
+            None => std::fs::read(path).ok(),
+        }
+    }
+
+    fn write_file(&self, path: &Path, contents: Vec<u8>) -> Result<(), anyhow::Error> {
+        match &self.memory {
+            Some(memory) => {
+                if let Some(mut old) = memory.borrow_mut().insert(path.to_owned(), contents) {
+                    old.zeroize();
+                }
+            }
+            None => std::fs::write(path, contents)?,
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), anyhow::Error> {
+        match &self.memory {
+            Some(memory) => {
+                if let Some(mut old) = memory.borrow_mut().remove(path) {
+                    old.zeroize();
+                }
+            }
+            None if path.exists() => std::fs::remove_file(path)?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+impl Drop for History {
+    /// Messages and settings that were only kept in memory are wiped, they were never meant to
+    /// outlive the session
+    fn drop(&mut self) {
+        if let Storage::Memory(messages) = &mut self.storage {
+            for msg in messages.iter_mut() {
+                msg.sender.zeroize();
+                msg.msg.zeroize();
+            }
+        }
+        if let Some(memory) = &self.memory {
+            memory.borrow_mut().values_mut().for_each(Zeroize::zeroize);
+        }
+    }
+}
+
+/// Open the history file and build its index. With `keep_days` set, messages sent before then are
+/// deleted (together with undated messages of older clients received before them).
+fn open_records(path: &Path, keep_days: Option<u64>, room: &Key) -> Result<Storage, anyhow::Error> {
+    let file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+
+    // Build the index, a truncated last record (e.g. after a crash) is ignored and overwritten
+    let mut offsets = Vec::new();
+    let mut reader = BufReader::new(&file);
+    let mut end = 0;
+    let file_len = file.metadata()?.len();
+    loop {
+        let mut len = [0u8; 4];
+        if reader.read_exact(&mut len).is_err() {
+            break;
+        }
+        let record_end = end + 4 + u32::from_le_bytes(len) as u64;
+        if record_end > file_len {
+            break;
+        }
+        reader.seek(SeekFrom::Start(record_end))?;
+        offsets.push(end);
+        end = record_end;
+    }
+    file.set_len(end)?;
+
+    if let (Some(days), false) = (keep_days, offsets.is_empty()) {
+        let now = crate::determinism::now();
+        let cutoff = now.saturating_sub(days * 24 * 60 * 60);
+        let expired = read_records(&file, 0, offsets.len(), room)?
+            .iter()
+            .rposition(|msg| msg.sent.map_or(false, |sent| sent < cutoff))
+            .map_or(0, |last| last + 1);
+        if expired > 0 {
+            // Rewrite the file with the remaining records, the index is rebuilt by opening it again
+            let mut rest = Vec::new();
+            (&file).seek(SeekFrom::Start(
+                offsets.get(expired).copied().unwrap_or(end),
+            ))?;
+            (&file).read_to_end(&mut rest)?;
+            let tmp_path = path.with_extension("history.tmp");
+            std::fs::write(&tmp_path, rest)?;
+            std::fs::rename(&tmp_path, path)?;
+            return open_records(path, None, room);
+        }
+    }
+
+    Ok(Storage::File { file, offsets, end })
+}
+
+/// Read `count` records starting at `offset`
+fn read_records(
+    file: &File,
+    offset: u64,
+    count: usize,
+    room: &Key,
+) -> Result<Vec<Message>, anyhow::Error> {
+    let mut file = file;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+    (0..count)
+        .map(|_| -> Result<Message, anyhow::Error> {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            let mut record = vec![0u8; u32::from_le_bytes(len) as usize];
+            reader.read_exact(&mut record)?;
+            match open_sealed(&record, room) {
+                Some(msg) => Ok(msg),
+                None => Ok(Message::from_bincode(&record)?),
+            }
+        })
+        .collect()
+}
+
+fn open_sealed<T: DeserializeOwned>(sealed: &[u8], key: &Key) -> Option<T> {
+    bincode::deserialize::<EncryptedMessage>(sealed)
+        .ok()?
+        .open(key)
+        .ok()
+}
+
+/// Load the newest digests of seen messages, the file is rewritten when it got too long
+fn open_seen(path: &Path) -> Result<(VecDeque<[u8; 32]>, File), anyhow::Error> {
+    let existing = match std::fs::read(path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    // A truncated last digest is ignored
+    let mut seen = existing
+        .chunks_exact(32)
+        .map(|chunk| {
+            let mut digest = [0u8; 32];
+            digest.copy_from_slice(chunk);
+            digest
+        })
+        .collect::<VecDeque<_>>();
+    let rewrite = seen.len() > MAX_SEEN || existing.len() % 32 != 0;
+    seen.drain(..seen.len().saturating_sub(MAX_SEEN));
+
+    if rewrite {
+        let buf = seen.iter().flatten().copied().collect::<Vec<_>>();
+        std::fs::write(path, buf)?;
+    }
+    let file = OpenOptions::new().append(true).create(true).open(path)?;
+    Ok((seen, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_have_no_position_until_fetched_from() {
+        let history = History::in_memory(&Key::generate());
+        let url = "http://mirror.example";
+        assert_eq!(history.mirror_position(url), None);
+        history.set_mirror_position(url, 0).unwrap();
+        assert_eq!(history.mirror_position(url), Some(0));
+        history.set_mirror_position(url, 12).unwrap();
+        assert_eq!(history.mirror_position(url), Some(12));
+        assert_eq!(history.mirror_position("http://other.example"), None);
+    }
+
+    #[test]
+    fn fetching_continues_at_the_stored_positions() {
+        let history = History::in_memory(&Key::generate());
+        let urls = [
+            "http://primary.example",
+            "http://mirror.example",
+            "http://new.example",
+        ];
+        assert_eq!(history.fetch_positions(urls), vec![Some(0), None, None]);
+        history.set_read_position(7).unwrap();
+        history.set_mirror_position(urls[1], 3).unwrap();
+        assert_eq!(history.fetch_positions(urls), vec![Some(7), Some(3), None]);
+    }
+
+    #[test]
+    fn messages_are_stored_encrypted() {
+        let data_dir =
+            std::env::temp_dir().join(format!("nym-chat-history-{}", std::process::id()));
+        let room = Key::generate();
+        let msg = Message::new("alice".into(), "meet at the usual place".into());
+        let mut history = History::open(&data_dir, &room).unwrap();
+        history.append(&msg).unwrap();
+        drop(history);
+
+        let stored = std::fs::read(data_dir.join(format!("{}.history", room.id()))).unwrap();
+        let found = stored
+            .windows(msg.msg.len())
+            .any(|window| window == msg.msg.as_bytes());
+        let loaded = History::open(&data_dir, &room).unwrap().load(0..1).unwrap();
+        std::fs::remove_dir_all(&data_dir).unwrap();
+        assert!(!found);
+        assert_eq!(loaded[0].msg, msg.msg);
+    }
+
+    #[test]
+    fn plaintext_records_of_older_clients_are_read() {
+        let data_dir = std::env::temp_dir().join(format!("nym-chat-legacy-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let room = Key::generate();
+        let msg = Message::new("alice".into(), "hello".into());
+        let record = bincode::serialize(&msg).unwrap();
+        let mut stored = (record.len() as u32).to_le_bytes().to_vec();
+        stored.extend_from_slice(&record);
+        std::fs::write(data_dir.join(format!("{}.history", room.id())), stored).unwrap();
+
+        let mut history = History::open(&data_dir, &room).unwrap();
+        history
+            .append(&Message::new("bob".into(), "hi".into()))
+            .unwrap();
+        let loaded = history.load(0..2).unwrap();
+        drop(history);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+        assert_eq!(loaded[0].msg, "hello");
+        assert_eq!(loaded[1].msg, "hi");
+    }
+}
@@ -0,0 +1,72 @@
+//! Participants are told apart by their identity key (see the profile module), not by the name they
+//! claim. Clients with an identity sign their messages with it, receivers show the claimed name
+//! together with the start of the key's fingerprint, and petnames from the address book replace
+//! the claimed name entirely. Names are never more than a part of the encrypted message.
+
+use crate::broadcast::{SigningKey, VerifyingKey};
+use crate::{Key, Message};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Message together with its author's verifying key and signature
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuthoredMessage {
+    pub msg: Message,
+    /// Hex encoded verifying key, keys don't implement serde
+    author: String,
+    signature: Vec<u8>,
+}
+
+impl AuthoredMessage {
+    pub fn sign(msg: Message, identity: &SigningKey, room: &Key) -> AuthoredMessage {
+        let signature = identity.sign_data(&signed_data(&msg, room));
+        AuthoredMessage {
+            msg,
+            author: identity.verifying_key().to_string(),
+            signature,
+        }
+    }
+
+    /// The message and its author's key if the signature is valid for `room`
+    pub fn verify(self, room: &Key) -> Result<(Message, VerifyingKey), ()> {
+        let author = VerifyingKey::from_str(&self.author).map_err(|_| ())?;
+        if !author.verify_data(&signed_data(&self.msg, room), &self.signature) {
+            return Err(());
+        }
+        Ok((self.msg, author))
+    }
+}
+
+/// Covers the room like broadcast signatures, prefixed so the two can't be mistaken for each other
+fn signed_data(msg: &Message, room: &Key) -> Vec<u8> {
+    let mut data = b"nym-chat author".to_vec();
+    data.extend(room.id().into_bytes());
+    data.extend(bincode::serialize(msg).expect("Serialization can't fail"));
+    data
+}
+
+/// Petnames profiles stored before there was an address book, the client moves them there
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Petnames {
+    by_fingerprint: BTreeMap<String, String>,
+}
+
+impl Petnames {
+    pub fn is_empty(&self) -> bool {
+        self.by_fingerprint.is_empty()
+    }
+
+    /// Fingerprints and their petnames, ordered by fingerprint
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.by_fingerprint
+            .iter()
+            .map(|(fingerprint, name)| (fingerprint.as_str(), name.as_str()))
+    }
+}
+
+/// The first two groups of a fingerprint, enough to notice someone using another's name
+pub fn short_fingerprint(fingerprint: &str) -> &str {
+    fingerprint.get(..9).unwrap_or(fingerprint)
+}
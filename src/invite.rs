@@ -0,0 +1,365 @@
+use crate::broadcast::{RoomSecret, VerifyingKey};
+use crate::epoch::ChainKey;
+use crate::report::ReportKey;
+use crate::Key;
+use nym_addressing::clients::Recipient;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use url::Url;
+
+/// URI scheme used for invite links, e.g.
+/// `nymchat://join?server=http://127.0.0.1:3030&provider=<nym-address>&key=<hex>&room=lobby`
+pub const INVITE_SCHEME: &str = "nymchat";
+
+/// Query parameters of invite links, each may only be given once
+const PARAMETERS: &[&str] = &[
+    "server",
+    "provider",
+    "key",
+    "hint",
+    "room",
+    "max_age",
+    "broadcast",
+    "secret",
+    "report",
+    "chain",
+    "from",
+];
+
+/// What the link is used for, encoded as the host part of the URI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InviteKind {
+    /// Join a room, the key is the room key
+    Join,
+    /// Link another device, the key is a one-time key the device sync bundle is encrypted to
+    Link,
+}
+
+/// Everything a new participant needs to join a room, bundled into a single link that can be
+/// shared out of band. The room key is optional so that links can be shared over channels that
+/// shouldn't see the key, in that case a hint can be attached to remind the recipient which key
+/// to use.
+#[derive(Clone)]
+pub struct Invite {
+    pub kind: InviteKind,
+    /// HTTP API of the server storing the room's messages
+    pub server: String,
+    /// Nym address of the server
+    pub service_provider: Recipient,
+    /// Pre shared key of the room, if it should be part of the link
+    pub key: Option<Key>,
+    /// Hint for the recipient about the key if it isn't embedded in the link
+    pub hint: Option<String>,
+    /// Human readable name of the room
+    pub room_name: Option<String>,
+    /// Seconds after which servers should delete the room's messages, if the room opted into that
+    pub max_age: Option<u64>,
+    /// Verifying key of a broadcast room, only messages signed by its signing key are shown
+    pub broadcast: Option<VerifyingKey>,
+    /// Root secret of a room with read and write permissions, only in links for members who may
+    /// post. The key and verifying key are derived from it and left out of the link.
+    pub secret: Option<RoomSecret>,
+    /// Key of the room's moderator, members seal their abuse reports to it
+    pub report: Option<ReportKey>,
+    /// Chain position in rooms whose members only read history from when they joined, see the
+    /// epoch module
+    pub chain: Option<ChainKey>,
+    /// Server side index from which device links look for their device sync bundle, so the new
+    /// device doesn't read the whole room
+    pub from: Option<usize>,
+}
+
+impl Invite {
+    /// The same invite for a read-only member, without the root secret
+    pub fn read_only(&self) -> Invite {
+        Invite {
+            kind: self.kind,
+            server: self.server.clone(),
+            service_provider: self.service_provider,
+            key: self.key.clone(),
+            hint: self.hint.clone(),
+            room_name: self.room_name.clone(),
+            max_age: self.max_age,
+            broadcast: self.broadcast,
+            secret: None,
+            report: self.report,
+            chain: self.chain.clone(),
+            from: self.from,
+        }
+    }
+}
+
+impl fmt::Display for Invite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let host = match self.kind {
+            InviteKind::Join => "join",
+            InviteKind::Link => "link",
+        };
+        let mut url = Url::parse(&format!("{}://{}", INVITE_SCHEME, host)).expect("valid base url");
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("server", &self.server);
+            query.append_pair("provider", &self.service_provider.to_string());
+            match (&self.secret, &self.key) {
+                (Some(secret), _) => {
+                    query.append_pair("secret", &secret.to_hex());
+                }
+                (None, Some(key)) => {
+                    query.append_pair("key", &key.to_hex());
+                }
+                (None, None) => {}
+            }
+            if let Some(hint) = &self.hint {
+                query.append_pair("hint", hint);
+            }
+            if let Some(room_name) = &self.room_name {
+                query.append_pair("room", room_name);
+            }
+            if let Some(max_age) = self.max_age {
+                query.append_pair("max_age", &max_age.to_string());
+            }
+            if let Some(broadcast) = self.broadcast.filter(|_| self.secret.is_none()) {
+                query.append_pair("broadcast", &broadcast.to_string());
+            }
+            if let Some(report) = &self.report {
+                query.append_pair("report", &report.to_string());
+            }
+            if let Some(chain) = &self.chain {
+                query.append_pair("chain", &chain.to_string());
+            }
+            if let Some(from) = self.from {
+                query.append_pair("from", &from.to_string());
+            }
+        }
+        write!(f, "{}", url)
+    }
+}
+
+impl FromStr for Invite {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = Url::parse(s)?;
+        if url.scheme() != INVITE_SCHEME {
+            return Err(anyhow::anyhow!(
+                "invite link has to start with {}://",
+                INVITE_SCHEME
+            ));
+        }
+
+        let kind = match url.host_str() {
+            Some("join") => InviteKind::Join,
+            Some("link") => InviteKind::Link,
+            _ => return Err(anyhow::Error::msg("unknown invite link type")),
+        };
+
+        let mut server = None;
+        let mut service_provider = None;
+        let mut key = None;
+        let mut hint = None;
+        let mut room_name = None;
+        let mut max_age = None;
+        let mut broadcast = None;
+        let mut secret = None;
+        let mut report = None;
+        let mut chain = None;
+        let mut from = None;
+        let mut seen = HashSet::new();
+        for (name, value) in url.query_pairs() {
+            // Otherwise the link could show one key to whoever reads it and join with another
+            if PARAMETERS.contains(&name.as_ref()) && !seen.insert(name.clone()) {
+                return Err(anyhow::anyhow!("invite link has {} more than once", name));
+            }
+            match name.as_ref() {
+                "server" => server = Some(value.into_owned()),
+                "provider" => {
+                    service_provider = Some(
+                        Recipient::try_from_base58_string(value.as_ref())
+                            .map_err(|e| anyhow::anyhow!("invalid provider address: {:?}", e))?,
+                    )
+                }
+                "key" => key = Some(Key::from_str(&value)?),
+                "hint" => hint = Some(value.into_owned()),
+                "room" => room_name = Some(value.into_owned()),
+                "max_age" => max_age = Some(value.parse()?),
+                "broadcast" => broadcast = Some(VerifyingKey::from_str(&value)?),
+                "secret" => secret = Some(RoomSecret::from_str(&value)?),
+                "report" => report = Some(ReportKey::from_str(&value)?),
+                "chain" => chain = Some(ChainKey::from_str(&value)?),
+                "from" => from = Some(value.parse()?),
+                // Ignore unknown parameters so newer links still work with older clients
+                _ => {}
+            }
+        }
+
+        // Older clients don't know about secrets and fail to join without a key, which is better
+        // than joining a room they can't read
+        if let Some(secret) = &secret {
+            key = Some(secret.read_key());
+            broadcast = Some(secret.write_key().verifying_key());
+        }
+        Ok(Invite {
+            kind,
+            server: server
+                .ok_or_else(|| anyhow::Error::msg("invite link is missing the server"))?,
+            service_provider: service_provider
+                .ok_or_else(|| anyhow::Error::msg("invite link is missing the provider"))?,
+            key,
+            hint,
+            room_name,
+            max_age,
+            broadcast,
+            secret,
+            report,
+            chain,
+            from,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadcast::SigningKey;
+    use crate::report::ModeratorKey;
+
+    /// A well-formed Nym address: the ed25519 base point as identity and gateway
+    const PROVIDER: &str = "6x5SYnLroiN7WYq8NQYU9KHcH4YjpBbwpUfVu3EB7ieH.\
+        c8fpTXm3XTRgE5maYQ24Li4L65wMYvAFomzXknxVEx7@6x5SYnLroiN7WYq8NQYU9KHcH4YjpBbwpUfVu3EB7ieH";
+
+    fn invite() -> Invite {
+        Invite {
+            kind: InviteKind::Join,
+            server: "http://127.0.0.1:3030".into(),
+            service_provider: Recipient::try_from_base58_string(PROVIDER).unwrap(),
+            key: None,
+            hint: None,
+            room_name: None,
+            max_age: None,
+            broadcast: None,
+            secret: None,
+            report: None,
+            chain: None,
+            from: None,
+        }
+    }
+
+    /// Link with the server and provider of [`invite`] and the given query parameters after them
+    fn link(parameters: &str) -> String {
+        format!("{}&{}", invite(), parameters)
+    }
+
+    #[test]
+    fn every_parameter_round_trips() {
+        let key = Key::generate();
+        let broadcast = SigningKey::generate().verifying_key();
+        let report = ModeratorKey::generate().report_key();
+        let chain = ChainKey::generate(3600);
+        let sent = Invite {
+            kind: InviteKind::Link,
+            key: Some(key.clone()),
+            hint: Some("the usual one & more".into()),
+            room_name: Some("lobby".into()),
+            max_age: Some(86400),
+            broadcast: Some(broadcast),
+            report: Some(report),
+            chain: Some(chain.clone()),
+            from: Some(42),
+            ..invite()
+        };
+
+        let received = Invite::from_str(&sent.to_string()).unwrap();
+        assert_eq!(received.kind, InviteKind::Link);
+        assert_eq!(received.server, sent.server);
+        assert_eq!(
+            received.service_provider.to_string(),
+            sent.service_provider.to_string()
+        );
+        assert!(received.key == Some(key));
+        assert_eq!(received.hint.as_deref(), Some("the usual one & more"));
+        assert_eq!(received.room_name.as_deref(), Some("lobby"));
+        assert_eq!(received.max_age, Some(86400));
+        assert_eq!(received.broadcast, Some(broadcast));
+        assert!(received.secret.is_none());
+        assert_eq!(received.report, Some(report));
+        assert_eq!(
+            received.chain.map(|chain| chain.to_string()),
+            Some(chain.to_string())
+        );
+        assert_eq!(received.from, Some(42));
+    }
+
+    #[test]
+    fn secret_overrides_key_and_broadcast() {
+        let secret = RoomSecret::generate();
+        let sent = Invite {
+            key: Some(Key::generate()),
+            broadcast: Some(SigningKey::generate().verifying_key()),
+            secret: Some(secret.clone()),
+            ..invite()
+        };
+        // The secret stands in for both, they aren't in the link
+        let link = sent.to_string();
+        assert!(!link.contains("key="));
+        assert!(!link.contains("broadcast="));
+
+        let other = format!(
+            "&key={}&broadcast={}",
+            Key::generate().to_hex(),
+            SigningKey::generate().verifying_key()
+        );
+        for link in &[link.clone(), link + &other] {
+            let received = Invite::from_str(link).unwrap();
+            assert_eq!(received.secret.unwrap().to_hex(), secret.to_hex());
+            assert!(received.key == Some(secret.read_key()));
+            assert_eq!(received.broadcast, Some(secret.write_key().verifying_key()));
+        }
+    }
+
+    #[test]
+    fn unknown_parameters_are_ignored() {
+        let key = Key::generate();
+        let received =
+            Invite::from_str(&link(&format!("key={}&future=1&future=2", key.to_hex()))).unwrap();
+        assert!(received.key == Some(key));
+    }
+
+    #[test]
+    fn duplicate_parameters_are_refused() {
+        let (first, second) = (Key::generate(), Key::generate());
+        let twice = link(&format!("key={}&key={}", first.to_hex(), second.to_hex()));
+        assert!(Invite::from_str(&twice).is_err());
+        assert!(Invite::from_str(&link("room=a&room=b")).is_err());
+        let server = format!("{}&server=http://example.org", invite());
+        assert!(Invite::from_str(&server).is_err());
+    }
+
+    #[test]
+    fn malformed_links_are_refused() {
+        for parameters in &[
+            "key=zz",
+            "key=00",
+            "broadcast=zz",
+            "secret=zz",
+            "report=zz",
+            "chain=zz",
+            "max_age=soon",
+            "from=-1",
+        ] {
+            assert!(
+                Invite::from_str(&link(parameters)).is_err(),
+                "{}",
+                parameters
+            );
+        }
+        let missing_provider = format!("{}://join?server=http://127.0.0.1:3030", INVITE_SCHEME);
+        assert!(Invite::from_str(&missing_provider).is_err());
+        let missing_server = format!("{}://join?provider={}", INVITE_SCHEME, PROVIDER);
+        assert!(Invite::from_str(&missing_server).is_err());
+        let wrong_kind = invite().to_string().replacen("join", "leave", 1);
+        assert!(Invite::from_str(&wrong_kind).is_err());
+        let wrong_scheme = invite().to_string().replacen(INVITE_SCHEME, "https", 1);
+        assert!(Invite::from_str(&wrong_scheme).is_err());
+    }
+}
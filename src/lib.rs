@@ -1,43 +1,371 @@
 use aes_gcm::AeadInPlace;
 use aes_gcm::NewAead;
 use aes_gcm::{Aes256Gcm, Key as AesKey, Nonce};
+use bincode::Options;
 use rand::Rng;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::str::FromStr;
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroize;
+
+pub mod address_book;
+pub mod archive;
+pub mod away;
+pub mod backup;
+pub mod broadcast;
+pub mod constant_time;
+pub mod contact;
+pub mod counter;
+pub mod custom;
+pub mod determinism;
+pub mod directory;
+pub mod epoch;
+pub mod exit;
+pub mod health;
+pub mod history;
+pub mod identity;
+pub mod invite;
+pub mod link;
+pub mod merkle;
+pub mod notify;
+pub mod outbox;
+pub mod padding;
+pub mod poll;
+pub mod presence;
+pub mod profile;
+pub mod receipt;
+pub mod report;
+pub mod schedule;
+pub mod schema;
+pub mod snapshot;
+pub mod snippet;
+pub mod surb;
+pub mod transcript;
+pub mod transport;
+pub mod usage;
 
 type KeyLen = generic_array::typenum::U32;
 type NonceLen = generic_array::typenum::U12;
 
-/// Clear text message from a sender, ideally the sender's identity would be ensured through
-/// cryptographic means, for now it's only a string attached to the message.
-#[derive(Debug, Serialize, Deserialize)]
+/// Approximate number of bytes that fit into a single mixnet packet once the Nym client added its
+/// fragmentation header and ack data. Bigger messages are split into several fragments which all
+/// have to make it through the mixnet, so they are slower and more likely to get lost.
+pub const FRAGMENT_CAPACITY: usize = 1600;
+
+/// Servers drop envelopes bigger than this unless their operator configured another limit, chat
+/// messages are a tiny fraction of it
+pub const DEFAULT_MAX_MESSAGE_BYTES: u64 = 64 << 10;
+
+/// Average delay each of the three mix layers adds to a packet, the Nym client's default
+const MIX_DELAY: Duration = Duration::from_millis(50);
+
+/// Average gap between two packets the Nym client sends, so the fragments of big messages queue
+/// up behind each other
+const SENDING_DELAY: Duration = Duration::from_millis(20);
+
+/// Bytes added by AES-GCM: nonce and authentication tag
+const ENCRYPTION_OVERHEAD: usize = 12 + TAG_LEN;
+
+/// Length of the AES-GCM authentication tag at the end of every ciphertext
+const TAG_LEN: usize = 16;
+
+/// Start of every framed envelope. Unframed envelopes of older clients start with a random nonce
+/// instead, see [`Envelope::decode`].
+const ENVELOPE_MAGIC: &[u8; 4] = b"nymc";
+
+/// Format of the envelope inside a frame, newer formats get a new tag so servers can tell them
+/// apart from the ones they know
+pub const ENVELOPE_FORMAT: u8 = 1;
+
+/// Magic, format tag and length prefix
+const ENVELOPE_HEADER_LEN: usize = ENVELOPE_MAGIC.len() + 1 + 4;
+
+/// Salt of keys derived from passphrases, the same for every room since everyone has to derive
+/// the same key. It only keeps guesses from being shared with other applications' tables.
+const PASSPHRASE_SALT: &[u8] = b"nym-chat room key from passphrase v1";
+
+/// Clear text message from a sender. The sender is only a name anyone can claim, clients with an
+/// identity send messages as [`Payload::Authored`] instead, signed with their identity key (see
+/// [`Message::decrypt_authored`] and the identity module).
+///
+/// Fields may only be added at the end: older clients ignore trailing data, so they can still
+/// read messages of newer ones. That only goes back to clients that know [`Payload`], the first
+/// clients encrypted bare messages without a timestamp and can't read anything newer clients
+/// send. Their messages are still read, see [`Payload::try_decrypt`].
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Message {
     pub sender: String,
     pub msg: String,
+    /// When the message was sent (unix seconds) according to the sender's clock, missing in
+    /// messages of older clients
+    #[serde(default)]
+    pub sent: Option<u64>,
+}
+
+/// Messages of clients that didn't send timestamps yet
+#[derive(Deserialize)]
+struct LegacyMessage {
+    sender: String,
+    msg: String,
+}
+
+/// Payloads of clients that didn't send timestamps yet, only messages differ
+#[derive(Deserialize)]
+enum LegacyPayload {
+    Message(LegacyMessage),
+}
+
+/// Everything that can be sent to a room. After decryption clients match on the variant, to the
+/// server all of them look the same.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub enum Payload {
+    Message(Message),
+    Presence(presence::Presence),
+    /// Only sent to directory rooms
+    Announcement(directory::Announcement),
+    /// Message of a broadcast room, older clients can't decode it and skip it
+    Signed(broadcast::SignedMessage),
+    Poll(poll::Poll),
+    Vote(poll::Vote),
+    Contact(contact::ContactCard),
+    Custom(custom::Custom),
+    /// Message signed by its author's identity key, older clients can't decode it and skip it
+    Authored(identity::AuthoredMessage),
+    /// Message that clients show once and then forget, it's never stored or exported
+    BurnAfterReading(Message),
+    /// Abuse report only the room's moderator can open, older clients can't decode it and skip it
+    Report(report::SealedReport),
+    /// Payload numbered by its sender to detect dropped messages, older clients can't decode it
+    /// and skip it
+    Counted(counter::Counted),
+    /// Payload other than a message of a broadcast room, older clients can't decode it and skip it
+    SignedPayload(broadcast::SignedPayload),
 }
 
 /// Message encrypted to a key defining a chat room. Every message encrypted by the same key will
 /// appear to all participants who joined the room with that pre shared key.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct EncryptedMessage {
+    #[schemars(with = "[u8; 12]")]
     nonce: Nonce<NonceLen>,
     data: Vec<u8>,
 }
 
-/// Pre shared key defining a chat room
+/// What clients send to the server: the encrypted message followed by instructions for the server.
+/// On the wire it's framed by a format tag and its length (see [`Envelope::to_bytes`]), servers
+/// still accept unframed envelopes and bare messages from older clients.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Envelope {
+    pub msg: EncryptedMessage,
+    /// Set by clients in rooms that opted into expiring messages: the server deletes the message
+    /// this many seconds after receiving it
+    pub max_age_secs: Option<u64>,
+}
+
+/// Pre shared key defining a chat room. It's wiped from memory when dropped and compared in
+/// constant time.
+#[derive(Clone)]
 pub struct Key {
     key: AesKey<KeyLen>,
 }
 
+/// Rough cost of sending a message through the mixnet. The latency only covers the way to the
+/// server with the Nym client's default delays, fetching adds up to one fetch interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    /// Encrypted and framed size
+    pub bytes: usize,
+    /// Sphinx packets the Nym client splits it into, all of them have to arrive
+    pub packets: usize,
+    pub latency: Duration,
+}
+
 impl Message {
     pub fn new(sender: String, msg: String) -> Message {
-        Message { sender, msg }
+        let sent = Some(determinism::now());
+        Message { sender, msg, sent }
+    }
+
+    /// Deserialize a bincode serialized message, including ones stored by older clients
+    pub fn from_bincode(serialized: &[u8]) -> Result<Message, bincode::Error> {
+        bincode::deserialize(serialized).or_else(|_| {
+            let legacy = bincode::deserialize::<LegacyMessage>(serialized)?;
+            Ok(legacy.into())
+        })
     }
 
     pub fn encrypt(&self, key: &Key) -> EncryptedMessage {
+        Payload::Message(self.clone()).encrypt(key)
+    }
+
+    /// Size of the message once encrypted and serialized, i.e. what is actually sent through the
+    /// mixnet
+    pub fn encrypted_len(&self) -> usize {
+        // The payload's variant tag and the message itself
+        let plaintext = 4 + bincode::serialized_size(self).expect("Serialization can't fail");
+        // Plus the length prefix of the ciphertext, the envelope's frame and its unset max age
+        ENCRYPTION_OVERHEAD + 8 + plaintext as usize + ENVELOPE_HEADER_LEN + 1
+    }
+
+    /// Number of mixnet fragments the message will be split into
+    pub fn fragments(&self) -> usize {
+        (self.encrypted_len() + FRAGMENT_CAPACITY - 1) / FRAGMENT_CAPACITY
+    }
+
+    /// What sending the message costs, see [`Estimate`]
+    pub fn estimate(&self) -> Estimate {
+        let packets = self.fragments();
+        Estimate {
+            bytes: self.encrypted_len(),
+            packets,
+            latency: MIX_DELAY * 3 + SENDING_DELAY * packets as u32,
+        }
+    }
+
+    /// Decrypts a chat message, other payloads are treated like messages for other rooms. Signed
+    /// messages (see the identity module) only decrypt if their signature is valid.
+    pub fn decrypt(msg: EncryptedMessage, key: &Key) -> Result<Message, ()> {
+        Message::decrypt_authored(msg, key).map(|(msg, _)| msg)
+    }
+
+    /// Like [`Message::decrypt`], together with the key that signed the message if it was signed.
+    /// Anyone in the room can claim any sender name, the key's fingerprint tells people apart.
+    pub fn decrypt_authored(
+        msg: EncryptedMessage,
+        key: &Key,
+    ) -> Result<(Message, Option<broadcast::VerifyingKey>), ()> {
+        match Payload::decrypt(msg, key)? {
+            Payload::Message(msg) => Ok((msg, None)),
+            Payload::Authored(authored) => {
+                let (msg, author) = authored.verify(key)?;
+                Ok((msg, Some(author)))
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl Payload {
+    pub fn encrypt(&self, key: &Key) -> EncryptedMessage {
+        EncryptedMessage::seal(self, key)
+    }
+
+    /// Like [`Payload::encrypt`], with the ciphertext padded to one of `buckets`, see the padding
+    /// module
+    pub fn encrypt_padded(&self, key: &Key, buckets: &[usize]) -> EncryptedMessage {
+        EncryptedMessage::seal_padded(self, key, buckets)
+    }
+
+    pub fn decrypt(msg: EncryptedMessage, key: &Key) -> Result<Payload, ()> {
+        Payload::try_decrypt(msg, key).map_err(|_| ())
+    }
+
+    /// Like [`Payload::decrypt`], but tells why a message couldn't be decrypted
+    pub fn try_decrypt(msg: EncryptedMessage, key: &Key) -> Result<Payload, Undecryptable> {
+        let serialized = msg.decrypt(key).map_err(|()| Undecryptable::OtherKey)?;
+        // The first clients encrypted bare messages. They never padded, so only what decodes as
+        // one without any bytes left over is taken for one, a short name could otherwise pass as
+        // the variant tag of a payload.
+        let bare = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .deserialize::<LegacyMessage>(&serialized);
+        if let Ok(legacy) = bare {
+            return Ok(Payload::Message(legacy.into()));
+        }
+        bincode::deserialize(&serialized)
+            .or_else(|_| match bincode::deserialize(&serialized)? {
+                LegacyPayload::Message(msg) => Ok(Payload::Message(msg.into())),
+            })
+            .map_err(|_: bincode::Error| Undecryptable::UnknownPayload)
+    }
+}
+
+/// Why a fetched message couldn't be decrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Undecryptable {
+    /// Encrypted to another key. Servers serve all their rooms together, so that's usually
+    /// another room's message, but it's also what a wrong room key looks like.
+    OtherKey,
+    /// Encrypted to our key, but by a newer (or broken) client sending payloads we don't know
+    UnknownPayload,
+}
+
+/// Messages a fetch skipped because the server stopped serving them (see `retention_secs`), part
+/// of fetch responses for clients that asked for gaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Gap {
+    /// Index of the oldest message the server still serves, the response starts there
+    pub first_available: usize,
+    /// Number of messages between the requested position and `first_available`
+    pub pruned: usize,
+}
+
+impl EncryptedMessage {
+    /// Stand-in for a message the server deleted, it keeps the indices of later messages stable.
+    /// It can't be decrypted, so clients skip it like messages of other rooms.
+    pub fn placeholder() -> EncryptedMessage {
+        EncryptedMessage {
+            nonce: Default::default(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Hash of the ciphertext, the same message fetched from different servers has the same digest
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.nonce);
+        hasher.update(&self.data);
+        hasher.finalize().into()
+    }
+
+    /// Size of the ciphertext in bytes, placeholders have none
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Serialize and encrypt any payload to a room key. All payloads look the same on the wire,
+    /// so the server can't tell chat messages from other kinds of payloads.
+    pub fn seal<T: Serialize>(payload: &T, key: &Key) -> EncryptedMessage {
+        EncryptedMessage::seal_padded(payload, key, &[])
+    }
+
+    /// Like [`EncryptedMessage::seal`], but zeros are appended to the serialized payload until the
+    /// ciphertext fills one of `buckets`. Payloads are decoded allowing trailing bytes, so the
+    /// padding is ignored when opening.
+    pub fn seal_padded<T: Serialize>(
+        payload: &T,
+        key: &Key,
+        buckets: &[usize],
+    ) -> EncryptedMessage {
+        EncryptedMessage::seal_tagged(payload, key, buckets, None)
+    }
+
+    /// Like [`EncryptedMessage::seal_padded`], with the first four nonce bytes set to `prefix`
+    /// instead of random ones, see the epoch module
+    pub(crate) fn seal_tagged<T: Serialize>(
+        payload: &T,
+        key: &Key,
+        buckets: &[usize],
+        prefix: Option<[u8; 4]>,
+    ) -> EncryptedMessage {
         let cipher = Aes256Gcm::new(&key.key);
-        let nonce = Nonce::<NonceLen>::from_slice(&rand::rngs::OsRng.gen::<[u8; 12]>()).clone();
-        let mut serialized = bincode::serialize(&self).expect("Serialization can't fail");
+        let mut nonce = determinism::with_rng(|rng| rng.gen::<[u8; 12]>());
+        if let Some(prefix) = prefix {
+            nonce[..4].copy_from_slice(&prefix);
+        }
+        let nonce = Nonce::<NonceLen>::from_slice(&nonce).clone();
+        let mut serialized = bincode::serialize(payload).expect("Serialization can't fail");
+        let ciphertext_len = serialized.len() + TAG_LEN;
+        let padded = padding::padded_len(ciphertext_len, buckets);
+        serialized.resize(padded - TAG_LEN, 0);
         cipher
             .encrypt_in_place(&nonce, b"", &mut serialized)
             .expect("encryption failure");
@@ -48,27 +376,393 @@ impl Message {
         }
     }
 
-    pub fn decrypt(msg: EncryptedMessage, key: &Key) -> Result<Message, ()> {
-        let mut serialized = msg.data;
+    /// Decrypt and deserialize a payload, fails if the key is wrong or the payload malformed
+    pub fn open<T: DeserializeOwned>(self, key: &Key) -> Result<T, ()> {
+        bincode::deserialize(&self.decrypt(key)?).map_err(|_| ())
+    }
+
+    /// First four bytes of the nonce, see [`EncryptedMessage::seal_tagged`]
+    pub(crate) fn nonce_prefix(&self) -> [u8; 4] {
+        let mut prefix = [0; 4];
+        prefix.copy_from_slice(&self.nonce[..4]);
+        prefix
+    }
+
+    fn decrypt(self, key: &Key) -> Result<Vec<u8>, ()> {
+        let mut serialized = self.data;
         let cipher = Aes256Gcm::new(&key.key);
         cipher
-            .decrypt_in_place(&msg.nonce, b"", &mut serialized)
+            .decrypt_in_place(&self.nonce, b"", &mut serialized)
             .map_err(|_| ())?;
+        Ok(serialized)
+    }
+}
+
+impl Envelope {
+    /// Frame the bincode encoded envelope: magic, format tag and the length of the envelope as a
+    /// little-endian u32
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let envelope = bincode::serialize(self).expect("Serialization can't fail");
+        let mut bytes = Vec::with_capacity(ENVELOPE_HEADER_LEN + envelope.len());
+        bytes.extend_from_slice(ENVELOPE_MAGIC);
+        bytes.push(ENVELOPE_FORMAT);
+        bytes.extend_from_slice(&(envelope.len() as u32).to_le_bytes());
+        bytes.extend(envelope);
+        bytes
+    }
 
-        bincode::deserialize(&serialized).map_err(|_| ())
+    /// Decode what a client sent, at most `limit` bytes are accepted. Framed envelopes are only
+    /// decoded if their format is known and the length matches, anything else is tried as an
+    /// unframed envelope or a bare message of an older client.
+    pub fn decode(bytes: &[u8], limit: u64) -> Result<Envelope, anyhow::Error> {
+        if bytes.len() as u64 > limit {
+            anyhow::bail!("{} bytes exceed the limit of {} bytes", bytes.len(), limit);
+        }
+        // Trailing data is fine, newer clients may add fields at the end
+        let options = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(limit);
+
+        let legacy = |bytes: &[u8]| -> Result<Envelope, bincode::Error> {
+            options.deserialize::<Envelope>(bytes).or_else(|_| {
+                Ok(Envelope {
+                    msg: options.deserialize(bytes)?,
+                    max_age_secs: None,
+                })
+            })
+        };
+
+        if !bytes.starts_with(ENVELOPE_MAGIC) {
+            return Ok(legacy(bytes)?);
+        }
+        Envelope::decode_frame(bytes)
+            .and_then(|envelope| Ok(options.deserialize(envelope)?))
+            // The nonce of an older client's envelope may start like a frame by chance
+            .or_else(|e| legacy(bytes).map_err(|_| e))
+    }
+
+    /// The envelope inside a frame of a known format
+    fn decode_frame(bytes: &[u8]) -> Result<&[u8], anyhow::Error> {
+        if bytes.len() < ENVELOPE_HEADER_LEN {
+            anyhow::bail!("truncated envelope frame");
+        }
+        let format = bytes[ENVELOPE_MAGIC.len()];
+        if format != ENVELOPE_FORMAT {
+            anyhow::bail!("unknown envelope format {}", format);
+        }
+        let mut len = [0; 4];
+        len.copy_from_slice(&bytes[ENVELOPE_MAGIC.len() + 1..ENVELOPE_HEADER_LEN]);
+        let envelope = &bytes[ENVELOPE_HEADER_LEN..];
+        if u32::from_le_bytes(len) as usize != envelope.len() {
+            anyhow::bail!(
+                "envelope length {} doesn't match the frame's {}",
+                envelope.len(),
+                u32::from_le_bytes(len)
+            );
+        }
+        Ok(envelope)
     }
 }
 
-impl FromStr for Key {
-    type Err = anyhow::Error;
+impl From<LegacyMessage> for Message {
+    fn from(legacy: LegacyMessage) -> Self {
+        Message {
+            sender: legacy.sender,
+            msg: legacy.msg,
+            sent: None,
+        }
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = hex::decode(s)?;
+impl Key {
+    /// Generate a fresh random key
+    pub fn generate() -> Key {
+        let mut bytes = determinism::with_rng(|rng| rng.gen::<[u8; 32]>());
+        let key = Key {
+            key: *AesKey::<KeyLen>::from_slice(&bytes),
+        };
+        bytes[..].zeroize();
+        key
+    }
+
+    /// Short identifier of the room derived from the key, safe to use e.g. in file names
+    pub fn id(&self) -> String {
+        hex::encode(&Sha256::digest(&self.key)[..8])
+    }
+
+    /// Hex encoding of the key, the inverse of `Key::from_str`
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.key)
+    }
+
+    /// BIP39 mnemonic (24 words) encoding the key, meant for writing it down as a backup
+    pub fn to_mnemonic(&self) -> String {
+        bip39::Mnemonic::from_entropy(&self.key)
+            .expect("32 bytes are valid entropy")
+            .to_string()
+    }
+
+    /// Recover a key from its BIP39 mnemonic, see `Key::to_mnemonic`
+    pub fn from_mnemonic(phrase: &str) -> Result<Key, anyhow::Error> {
+        let mut bytes = bip39::Mnemonic::parse(phrase)?.to_entropy();
+        let key = Key::from_bytes(&bytes);
+        bytes.zeroize();
+        key
+    }
+
+    /// Derive the key of a room from a passphrase, so it can be shared like a password. Anyone can
+    /// fetch the room's messages and try passphrases against them offline, Argon2id only makes
+    /// each guess expensive: the passphrase has to be long and random (e.g. six diceware words).
+    ///
+    /// Surrounding whitespace is ignored and the passphrase is normalized to NFC first, so it
+    /// derives the same key however it was typed or pasted.
+    pub fn from_passphrase(passphrase: &str) -> Key {
+        let params = argon2::Params::new(64 * 1024, 3, 1, Some(32)).expect("valid parameters");
+        let argon2 =
+            argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+        let mut normalized = passphrase.trim().nfc().collect::<String>();
+        let mut bytes = [0; 32];
+        argon2
+            .hash_password_into(normalized.as_bytes(), PASSPHRASE_SALT, &mut bytes)
+            .expect("the output length fits the parameters");
+        let key = Key::from_bytes(&bytes).expect("32 bytes are a key");
+        normalized.zeroize();
+        bytes[..].zeroize();
+        key
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Key, anyhow::Error> {
         if bytes.len() != 32 {
             return Err(anyhow::Error::msg("wrong key length"));
         }
         Ok(Key {
-            key: *aes_gcm::Key::<KeyLen>::from_slice(&bytes),
+            key: *aes_gcm::Key::<KeyLen>::from_slice(bytes),
         })
     }
 }
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    /// Parses either a hex encoded key or a mnemonic phrase. Hex keys are decoded in constant
+    /// time straight into the key, see the constant_time module.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().contains(char::is_whitespace) {
+            return Key::from_mnemonic(s);
+        }
+        let mut bytes = [0; 32];
+        let key = constant_time::decode_hex(s, &mut bytes)
+            .map_err(|_| anyhow::Error::msg("invalid room key"))
+            .and_then(|()| Key::from_bytes(&bytes));
+        bytes[..].zeroize();
+        key
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        constant_time::eq(&self.key, &other.key)
+    }
+}
+
+impl Eq for Key {}
+
+impl Drop for Key {
+    fn drop(&mut self) {
+        self.key.as_mut_slice().zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadcast::SigningKey;
+    use crate::identity::AuthoredMessage;
+
+    /// What the first clients encrypted, before there were payloads
+    #[derive(Serialize)]
+    struct BareMessage {
+        sender: String,
+        msg: String,
+    }
+
+    /// Variant index and the rest of a bincode serialized payload
+    fn variant(payload: &Payload) -> (u32, Vec<u8>) {
+        let serialized = bincode::serialize(payload).unwrap();
+        let variant = bincode::deserialize(&serialized[..4]).unwrap();
+        (variant, serialized[4..].to_vec())
+    }
+
+    /// The web page (src/bin/server/web.rs) decodes these payloads itself, by their variant index
+    /// and bincode layout
+    #[test]
+    fn payloads_the_web_page_decodes_keep_their_layout() {
+        let room = Key::generate();
+        let msg = Message::new("alice".into(), "hi".into());
+        let serialized = bincode::serialize(&msg).unwrap();
+
+        assert_eq!(
+            variant(&Payload::Message(msg.clone())),
+            (0, serialized.clone())
+        );
+        assert_eq!(
+            variant(&Payload::BurnAfterReading(msg.clone())),
+            (9, serialized.clone())
+        );
+
+        let identity = SigningKey::generate();
+        let authored = AuthoredMessage::sign(msg.clone(), &identity, &room);
+        let (index, rest) = variant(&Payload::Authored(authored));
+        assert_eq!(index, 8);
+        // The message, then the author's hex encoded key and the signature
+        assert_eq!(rest[..serialized.len()], serialized[..]);
+        let rest = &rest[serialized.len()..];
+        assert_eq!(rest[..8], 64u64.to_le_bytes());
+        assert_eq!(rest[8 + 64..8 + 64 + 8], 64u64.to_le_bytes());
+        assert_eq!(rest.len(), 8 + 64 + 8 + 64);
+
+        let counted = Payload::Counted(counter::Counted {
+            stream: 1,
+            counter: 2,
+            payload: Box::new(Payload::Message(msg.clone())),
+        });
+        let (index, rest) = variant(&counted);
+        assert_eq!(index, 11);
+        assert_eq!(rest[..8], 1u64.to_le_bytes());
+        assert_eq!(rest[8..16], 2u64.to_le_bytes());
+        assert_eq!(
+            rest[16..],
+            bincode::serialize(&Payload::Message(msg)).unwrap()[..]
+        );
+    }
+
+    /// Rooms joined with a passphrase have to keep their key across client versions, a changed
+    /// derivation would silently move everyone to another room
+    #[test]
+    fn passphrase_keys_are_stable() {
+        let key = Key::from_passphrase("correct horse battery staple");
+        assert_eq!(
+            key.to_hex(),
+            "6387ba5ee5b2ea9b28cc03545f5b132c82b103251c99022056869cf9172f817d"
+        );
+    }
+
+    #[test]
+    fn passphrases_are_normalized() {
+        // "é" precomposed and as "e" with a combining accent
+        let key = Key::from_passphrase("caf\u{e9} au lait");
+        assert!(key == Key::from_passphrase("cafe\u{301} au lait"));
+        assert!(key == Key::from_passphrase("  caf\u{e9} au lait\n"));
+        assert!(key != Key::from_passphrase("cafe au lait"));
+    }
+
+    #[test]
+    fn bare_messages_of_the_first_clients_decrypt() {
+        let room = Key::generate();
+        // Short names are the ones whose length could be taken for a payload's variant tag
+        for sender in &["", "al", "alice", "a much longer name than any variant"] {
+            let bare = BareMessage {
+                sender: sender.to_string(),
+                msg: "hi".into(),
+            };
+            let encrypted = EncryptedMessage::seal(&bare, &room);
+
+            let msg = Message::decrypt(encrypted, &room).unwrap();
+            assert_eq!(msg.sender, *sender);
+            assert_eq!(msg.msg, "hi");
+            assert_eq!(msg.sent, None);
+        }
+    }
+
+    #[test]
+    fn payloads_are_not_taken_for_bare_messages() {
+        let room = Key::generate();
+        let msg = Message::new("alice".into(), "hi".into());
+        let payloads = vec![
+            Payload::Message(msg.clone()),
+            Payload::BurnAfterReading(msg),
+            Payload::Presence(presence::Presence {
+                sender: String::new(),
+                status: presence::PresenceStatus::Joined,
+            }),
+        ];
+        for payload in payloads {
+            let expected = std::mem::discriminant(&payload);
+            for buckets in &[&[][..], &[256][..]] {
+                let encrypted = payload.encrypt_padded(&room, buckets);
+                let decrypted = Payload::try_decrypt(encrypted, &room).unwrap();
+                assert_eq!(std::mem::discriminant(&decrypted), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn authored_messages_yield_their_author() {
+        let room = Key::generate();
+        let identity = SigningKey::generate();
+        let msg = Message::new("alice".into(), "hi".into());
+        let authored = AuthoredMessage::sign(msg, &identity, &room);
+
+        let (msg, author) =
+            Message::decrypt_authored(Payload::Authored(authored).encrypt(&room), &room).unwrap();
+        assert_eq!(msg.msg, "hi");
+        assert_eq!(author, Some(identity.verifying_key()));
+    }
+
+    #[test]
+    fn plain_messages_have_no_author() {
+        let room = Key::generate();
+        let msg = Message::new("alice".into(), "hi".into());
+
+        let (_, author) = Message::decrypt_authored(msg.encrypt(&room), &room).unwrap();
+        assert_eq!(author, None);
+    }
+
+    #[test]
+    fn tampered_messages_are_rejected() {
+        let room = Key::generate();
+        let identity = SigningKey::generate();
+        let msg = Message::new("alice".into(), "hi".into());
+        let mut authored = AuthoredMessage::sign(msg, &identity, &room);
+        authored.msg.msg = "send me your keys".into();
+
+        let encrypted = Payload::Authored(authored).encrypt(&room);
+        assert!(Message::decrypt_authored(encrypted, &room).is_err());
+    }
+
+    #[test]
+    fn signed_payloads_only_verify_with_the_rooms_key() {
+        let room = Key::generate();
+        let signing_key = SigningKey::generate();
+        let vote = || {
+            Payload::Vote(poll::Vote {
+                poll: 1,
+                voter: "alice".into(),
+                option: 0,
+            })
+        };
+
+        let signed = signing_key.sign_payload(vote(), &room);
+        let payload = signed.verify(&signing_key.verifying_key(), &room).unwrap();
+        assert!(matches!(payload, Payload::Vote(vote) if vote.voter == "alice"));
+        let signed = signing_key.sign_payload(vote(), &room);
+        let other_key = SigningKey::generate().verifying_key();
+        assert!(signed.verify(&other_key, &room).is_err());
+        let signed = signing_key.sign_payload(vote(), &Key::generate());
+        assert!(signed.verify(&signing_key.verifying_key(), &room).is_err());
+    }
+
+    #[test]
+    fn signatures_for_other_rooms_are_rejected() {
+        let room = Key::generate();
+        let other_room = Key::generate();
+        let identity = SigningKey::generate();
+        let msg = Message::new("alice".into(), "hi".into());
+        // Replayed from another room the author is in
+        let authored = AuthoredMessage::sign(msg, &identity, &other_room);
+
+        let encrypted = Payload::Authored(authored).encrypt(&room);
+        assert!(Message::decrypt_authored(encrypted, &room).is_err());
+    }
+}
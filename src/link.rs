@@ -0,0 +1,37 @@
+use crate::{EncryptedMessage, Key};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Everything a second device needs to present the same persona as the first one. The bundle is
+/// encrypted to a one-time key and sent through the mixnet to the server like any other message,
+/// the one-time key is then handed to the new device out of band as a `nymchat://link` URI.
+#[derive(Serialize, Deserialize)]
+pub struct DeviceSync {
+    /// Name attached to outgoing messages
+    pub name: String,
+    /// Hex encoded room key
+    pub room: String,
+    /// Human readable name of the room
+    pub room_name: Option<String>,
+    /// Index of the first message the first device hasn't fetched yet
+    pub read_position: usize,
+    /// Chain position of the room if it has a key chain, see the epoch module. Bundles of older
+    /// clients end before it and can't be opened, link devices running the same version.
+    pub chain: Option<String>,
+    /// Hex encoded identity key of the first device's profile, `None` if it runs without one
+    pub identity: Option<String>,
+    /// Invite links of all rooms of the profile, with secrets and chain positions
+    pub rooms: Vec<String>,
+    /// Read positions of the profile's rooms by room id, rooms that were never fetched are missing
+    pub read_positions: BTreeMap<String, usize>,
+}
+
+impl DeviceSync {
+    pub fn encrypt(&self, one_time_key: &Key) -> EncryptedMessage {
+        EncryptedMessage::seal(self, one_time_key)
+    }
+
+    pub fn decrypt(msg: EncryptedMessage, one_time_key: &Key) -> Result<DeviceSync, ()> {
+        msg.open(one_time_key)
+    }
+}
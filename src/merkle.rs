@@ -0,0 +1,377 @@
+//! Merkle tree over a server's log as in Certificate Transparency (RFC 6962): the server signs the
+//! root of the tree over all message digests (a [`crate::receipt::TreeHead`]) and proves that a
+//! newer tree only appends to an older one. A client that remembers the last tree head it saw
+//! notices when the server removes, reorders or replaces messages. Inclusion proofs show that a
+//! single message is part of a tree.
+
+use sha2::{Digest, Sha256};
+use std::iter::FromIterator;
+
+pub type Hash = [u8; 32];
+
+/// Hash of a leaf, the leaves are the digests of the messages in the order they were received
+pub fn leaf_hash(digest: &[u8; 32]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0u8]);
+    hasher.update(digest);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([1u8]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Largest power of two smaller than `n`, where the tree over `n` leaves is split
+fn split(n: usize) -> usize {
+    let mut k = 1;
+    while k * 2 < n {
+        k *= 2;
+    }
+    k
+}
+
+/// Root of the tree over the given leaf hashes
+pub fn root(leaves: &[Hash]) -> Hash {
+    leaves.iter().copied().collect::<Tree>().root(leaves.len())
+}
+
+/// Proof that the tree over the first `old_size` leaves is a prefix of the tree over all `leaves`
+pub fn consistency_proof(leaves: &[Hash], old_size: usize) -> Vec<Hash> {
+    leaves
+        .iter()
+        .copied()
+        .collect::<Tree>()
+        .consistency_proof(old_size, leaves.len())
+}
+
+/// Proof that leaf `index` is part of the tree over all `leaves`
+pub fn inclusion_proof(leaves: &[Hash], index: usize) -> Vec<Hash> {
+    leaves
+        .iter()
+        .copied()
+        .collect::<Tree>()
+        .inclusion_proof(index, leaves.len())
+}
+
+/// Tree that grows one leaf at a time and keeps the roots of all its complete subtrees, so roots
+/// and proofs take a logarithmic number of hashes instead of rehashing every leaf
+#[derive(Debug, Clone, Default)]
+pub struct Tree {
+    /// `levels[h][i]` is the root of the complete subtree over leaves `i * 2^h..(i + 1) * 2^h`
+    levels: Vec<Vec<Hash>>,
+}
+
+impl Tree {
+    /// Number of leaves
+    pub fn len(&self) -> usize {
+        self.levels.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append a leaf hash, completing the subtrees it's the last leaf of
+    pub fn push(&mut self, leaf: Hash) {
+        let mut hash = leaf;
+        for level in 0.. {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let nodes = &mut self.levels[level];
+            nodes.push(hash);
+            if nodes.len() % 2 == 1 {
+                break;
+            }
+            hash = node_hash(&nodes[nodes.len() - 2], &nodes[nodes.len() - 1]);
+        }
+    }
+
+    /// Root of the tree over the first `size` leaves, which have to be there
+    pub fn root(&self, size: usize) -> Hash {
+        assert!(size <= self.len(), "the tree has fewer leaves");
+        match size {
+            0 => Sha256::digest(&[]).into(),
+            _ => self.subtree_root(0, size),
+        }
+    }
+
+    /// Proof that the tree over the first `old_size` leaves is a prefix of the one over the first
+    /// `new_size` leaves, which have to be there
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Vec<Hash> {
+        assert!(new_size <= self.len(), "the tree has fewer leaves");
+        let mut proof = Vec::new();
+        if 0 < old_size && old_size < new_size {
+            self.subproof(old_size, 0, new_size, true, &mut proof);
+        }
+        proof
+    }
+
+    /// Proof that leaf `index` is part of the tree over the first `size` leaves, which have to be
+    /// there
+    pub fn inclusion_proof(&self, index: usize, size: usize) -> Vec<Hash> {
+        assert!(
+            index < size && size <= self.len(),
+            "the tree has fewer leaves"
+        );
+        let mut proof = Vec::new();
+        self.path(index, 0, size, &mut proof);
+        proof
+    }
+
+    /// Root of the subtree over the leaves `start..end`. The trees split at the largest power of
+    /// two, so the subtrees of a tree starting at 0 start at a multiple of that power and their
+    /// left halves are complete subtrees.
+    fn subtree_root(&self, start: usize, end: usize) -> Hash {
+        let n = end - start;
+        if n.is_power_of_two() {
+            let level = n.trailing_zeros() as usize;
+            return self.levels[level][start >> level];
+        }
+        let k = split(n);
+        node_hash(
+            &self.subtree_root(start, start + k),
+            &self.subtree_root(start + k, end),
+        )
+    }
+
+    fn path(&self, index: usize, start: usize, end: usize, proof: &mut Vec<Hash>) {
+        let n = end - start;
+        if n == 1 {
+            return;
+        }
+        let k = split(n);
+        if index < k {
+            self.path(index, start, start + k, proof);
+            proof.push(self.subtree_root(start + k, end));
+        } else {
+            self.path(index - k, start + k, end, proof);
+            proof.push(self.subtree_root(start, start + k));
+        }
+    }
+
+    fn subproof(&self, m: usize, start: usize, end: usize, complete: bool, proof: &mut Vec<Hash>) {
+        let n = end - start;
+        if m == n {
+            if !complete {
+                proof.push(self.subtree_root(start, end));
+            }
+            return;
+        }
+        let k = split(n);
+        if m <= k {
+            self.subproof(m, start, start + k, complete, proof);
+            proof.push(self.subtree_root(start + k, end));
+        } else {
+            self.subproof(m - k, start + k, end, false, proof);
+            proof.push(self.subtree_root(start, start + k));
+        }
+    }
+}
+
+impl FromIterator<Hash> for Tree {
+    fn from_iter<I: IntoIterator<Item = Hash>>(leaves: I) -> Tree {
+        let mut tree = Tree::default();
+        leaves.into_iter().for_each(|leaf| tree.push(leaf));
+        tree
+    }
+}
+
+/// Check that `leaf` is leaf `index` of the tree with `size` leaves and `root`, following RFC 9162
+/// section 2.1.3.2
+pub fn verify_inclusion(index: u64, size: u64, leaf: &Hash, root: &Hash, proof: &[Hash]) -> bool {
+    if index >= size {
+        return false;
+    }
+    let mut fn_ = index;
+    let mut sn = size - 1;
+    let mut r = *leaf;
+    for p in proof {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            r = node_hash(p, &r);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            r = node_hash(&r, p);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    sn == 0 && r == *root
+}
+
+/// Check a consistency proof between the tree heads `(old_size, old_root)` and `(new_size,
+/// new_root)`, following RFC 9162 section 2.1.4.2
+pub fn verify_consistency(
+    old_size: u64,
+    new_size: u64,
+    old_root: &Hash,
+    new_root: &Hash,
+    proof: &[Hash],
+) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+    if old_size == new_size {
+        return proof.is_empty() && old_root == new_root;
+    }
+    if old_size == 0 {
+        // Every tree extends the empty one
+        return proof.is_empty();
+    }
+
+    let mut path = proof.to_vec();
+    if old_size.is_power_of_two() {
+        path.insert(0, *old_root);
+    }
+    let (first, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    let mut fn_ = old_size - 1;
+    let mut sn = new_size - 1;
+    while fn_ & 1 == 1 {
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    let mut fr = *first;
+    let mut sr = *first;
+    for c in rest {
+        if sn == 0 {
+            return false;
+        }
+        if fn_ & 1 == 1 || fn_ == sn {
+            fr = node_hash(c, &fr);
+            sr = node_hash(c, &sr);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            sr = node_hash(&sr, c);
+        }
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+    fr == *old_root && sr == *new_root && sn == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MTH of RFC 6962 section 2.1, straight from the definition
+    fn reference_root(leaves: &[Hash]) -> Hash {
+        match leaves.len() {
+            0 => Sha256::digest(&[]).into(),
+            1 => leaves[0],
+            n => {
+                let k = split(n);
+                node_hash(&reference_root(&leaves[..k]), &reference_root(&leaves[k..]))
+            }
+        }
+    }
+
+    fn leaves(n: usize) -> Vec<Hash> {
+        (0..n)
+            .map(|i| leaf_hash(&Sha256::digest(&i.to_le_bytes()).into()))
+            .collect()
+    }
+
+    #[test]
+    fn roots_match_the_definition() {
+        let all = leaves(33);
+        let tree = all.iter().copied().collect::<Tree>();
+        for size in 0..=all.len() {
+            assert_eq!(
+                tree.root(size),
+                reference_root(&all[..size]),
+                "size {}",
+                size
+            );
+        }
+        assert_eq!(root(&all[..1]), all[0]);
+    }
+
+    #[test]
+    fn inclusion_proofs_verify() {
+        let all = leaves(18);
+        let tree = all.iter().copied().collect::<Tree>();
+        // Odd sizes leave the last leaf without a sibling on some levels, the leaf after the tree
+        // stands in for a wrong one
+        for size in 1..all.len() {
+            let root = tree.root(size);
+            for index in 0..size {
+                let proof = tree.inclusion_proof(index, size);
+                let (i, n) = (index as u64, size as u64);
+                assert!(verify_inclusion(i, n, &all[index], &root, &proof));
+                assert!(!verify_inclusion(i, n, &all[size], &root, &proof));
+                if let Some((_, rest)) = proof.split_first() {
+                    let tampered = [&[all[size]][..], rest].concat();
+                    assert!(!verify_inclusion(i, n, &all[index], &root, &tampered));
+                }
+                if size > 1 {
+                    let other = (index + 1) % size;
+                    assert!(!verify_inclusion(
+                        other as u64,
+                        n,
+                        &all[index],
+                        &root,
+                        &proof
+                    ));
+                }
+            }
+        }
+        assert_eq!(inclusion_proof(&all[..5], 4), tree.inclusion_proof(4, 5));
+        assert!(!verify_inclusion(1, 1, &all[1], &tree.root(1), &[]));
+    }
+
+    #[test]
+    fn consistency_proofs_verify() {
+        let all = leaves(17);
+        let tree = all.iter().copied().collect::<Tree>();
+        for new_size in 0..=all.len() {
+            let new_root = tree.root(new_size);
+            for old_size in 0..=new_size {
+                let old_root = tree.root(old_size);
+                let proof = tree.consistency_proof(old_size, new_size);
+                let (m, n) = (old_size as u64, new_size as u64);
+                assert!(verify_consistency(m, n, &old_root, &new_root, &proof));
+                if 0 < old_size && old_size < new_size {
+                    let other = tree.root(old_size - 1);
+                    assert!(!verify_consistency(m, n, &other, &new_root, &proof));
+                    assert!(!verify_consistency(m, n, &old_root, &old_root, &proof));
+                    assert!(!verify_consistency(m, n, &old_root, &new_root, &proof[1..]));
+                }
+            }
+        }
+        assert!(!verify_consistency(5, 4, &tree.root(5), &tree.root(4), &[]));
+    }
+
+    #[test]
+    fn trees_only_extend_what_was_there() {
+        let mut replaced = leaves(7);
+        let original = replaced.iter().copied().collect::<Tree>();
+        replaced[2] = leaf_hash(&[0xff; 32]);
+        let tree = replaced.iter().copied().collect::<Tree>();
+        let proof = tree.consistency_proof(3, 7);
+        assert!(!verify_consistency(
+            3,
+            7,
+            &original.root(3),
+            &tree.root(7),
+            &proof
+        ));
+        assert_eq!(consistency_proof(&replaced, 3), proof);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::Message;
+use chrono::{Local, Timelike};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Which messages of a room get the user's attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Level {
+    All,
+    /// Only messages mentioning our name or one of the watched keywords
+    Mentions,
+    Muted,
+}
+
+/// Time span (in local time) without notifications, may wrap around midnight like 22:00-07:00
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// Minutes since midnight
+    pub start: u16,
+    pub end: u16,
+}
+
+/// Notification settings of a room. They are evaluated here instead of in the front-ends, so the
+/// TUI, the screen reader mode and daemon subscribers all agree on what deserves a notification.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    pub level: Level,
+    /// Words that trigger a notification like a mention, matched case-insensitively
+    pub keywords: Vec<String>,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// Why a message deserves a notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    Message,
+    Mention,
+    Keyword(String),
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            level: Level::Mentions,
+            keywords: Vec::new(),
+            quiet_hours: None,
+        }
+    }
+}
+
+impl Rules {
+    /// Decide whether `msg` deserves a notification, `minute` is the current local time in minutes
+    /// since midnight. Our own messages never do.
+    pub fn evaluate(&self, msg: &Message, own_name: &str, minute: u16) -> Option<Reason> {
+        if msg.sender == own_name || self.level == Level::Muted {
+            return None;
+        }
+        if self
+            .quiet_hours
+            .map_or(false, |quiet| quiet.contains(minute))
+        {
+            return None;
+        }
+
+        if mentions(&msg.msg, own_name) {
+            return Some(Reason::Mention);
+        }
+        let text = msg.msg.to_lowercase();
+        if let Some(keyword) = self
+            .keywords
+            .iter()
+            .find(|keyword| contains_word(&text, &keyword.to_lowercase()))
+        {
+            return Some(Reason::Keyword(keyword.clone()));
+        }
+        match self.level {
+            Level::All => Some(Reason::Message),
+            _ => None,
+        }
+    }
+
+    /// [`Rules::evaluate`] at the current local time
+    pub fn evaluate_now(&self, msg: &Message, own_name: &str) -> Option<Reason> {
+        let now = Local::now();
+        self.evaluate(msg, own_name, (now.hour() * 60 + now.minute()) as u16)
+    }
+
+    /// Add a keyword to the watch list, returns false if it was already watched
+    pub fn watch(&mut self, keyword: &str) -> bool {
+        let watched = self
+            .keywords
+            .iter()
+            .any(|existing| existing.to_lowercase() == keyword.to_lowercase());
+        if !watched {
+            self.keywords.push(keyword.to_owned());
+        }
+        !watched
+    }
+
+    /// Remove a keyword from the watch list, returns false if it wasn't watched
+    pub fn unwatch(&mut self, keyword: &str) -> bool {
+        let len = self.keywords.len();
+        self.keywords
+            .retain(|existing| existing.to_lowercase() != keyword.to_lowercase());
+        self.keywords.len() != len
+    }
+}
+
+impl QuietHours {
+    pub fn contains(&self, minute: u16) -> bool {
+        if self.start <= self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+/// Whether `text` mentions `name` as a word of its own, ignoring case
+pub fn mentions(text: &str, name: &str) -> bool {
+    contains_word(&text.to_lowercase(), &name.to_lowercase())
+}
+
+/// Whether `word` occurs in `text` with no letters or digits directly around it, so "al" doesn't
+/// match "also" but does match "@al,"
+fn contains_word(text: &str, word: &str) -> bool {
+    if word.is_empty() {
+        return false;
+    }
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.map_or(false, char::is_alphanumeric) && !after.map_or(false, char::is_alphanumeric)
+    })
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Level::All),
+            "mentions" => Ok(Level::Mentions),
+            "muted" => Ok(Level::Muted),
+            _ => Err(format!("expected all, mentions or muted, got {}", s)),
+        }
+    }
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::All => "all",
+            Level::Mentions => "mentions",
+            Level::Muted => "muted",
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = String;
+
+    /// Parses `HH:MM-HH:MM`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_time = |time: &str| -> Option<u16> {
+            let (hour, minute) = time.trim().split_once(':')?;
+            let (hour, minute) = (hour.parse::<u16>().ok()?, minute.parse::<u16>().ok()?);
+            (hour < 24 && minute < 60).then(|| hour * 60 + minute)
+        };
+        let invalid = || format!("expected HH:MM-HH:MM, got {}", s);
+        let (start, end) = s.split_once('-').ok_or_else(invalid)?;
+        Ok(QuietHours {
+            start: parse_time(start).ok_or_else(invalid)?,
+            end: parse_time(end).ok_or_else(invalid)?,
+        })
+    }
+}
+
+impl fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
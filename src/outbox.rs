@@ -0,0 +1,52 @@
+//! Messages we're sending, kept until every server of the room took them. The outbox is stored
+//! encrypted to the room key like the schedule (see
+//! [`History::set_outbox`](crate::history::History::set_outbox)), so messages typed while the Nym
+//! client was unreachable or while the client crashed mid-send go out after the next start. A
+//! message whose send finished right before a crash is sent again, others then see it twice.
+
+use crate::Envelope;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Queued {
+    /// What the user typed, only to list the queue
+    pub text: String,
+    pub envelope: Envelope,
+    /// URLs of the servers that didn't take the message yet
+    pub servers: Vec<String>,
+}
+
+/// Queued messages in the order they were written
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Outbox {
+    queued: Vec<Queued>,
+}
+
+impl Outbox {
+    pub fn push(&mut self, queued: Queued) {
+        self.queued.push(queued);
+    }
+
+    /// Don't send the message at `index` (as listed by `iter`), returns it if there was one
+    pub fn cancel(&mut self, index: usize) -> Option<Queued> {
+        (index < self.queued.len()).then(|| self.queued.remove(index))
+    }
+
+    /// All queued messages, to try sending them again. Put back the ones that still have servers
+    /// left with `push`.
+    pub fn take(&mut self) -> Vec<Queued> {
+        std::mem::take(&mut self.queued)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Queued> {
+        self.queued.iter()
+    }
+}
@@ -0,0 +1,206 @@
+//! Padding of sent payloads. The size of a ciphertext tells the server (and anyone watching where
+//! it leaves the mixnet) roughly how long a message is, padding rounds it up to one of a few
+//! bucket sizes so messages of similar length can't be told apart.
+//!
+//! Which buckets work best depends on the room: with the [`Policy::Adaptive`] policy the buckets
+//! are quantiles of the ciphertext sizes observed in the room, so our messages take sizes that
+//! are common there anyway and the overhead stays small. More buckets cost less bandwidth but
+//! tell more about the length, fewer buckets the other way round.
+//!
+//! The padding is appended to the serialized payload inside the encryption. Payloads are decoded
+//! allowing trailing bytes, so clients that don't pad read padded payloads just fine.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+/// Buckets of the fixed policy, ciphertexts beyond the largest are padded to a multiple of it
+pub const FIXED_BUCKETS: &[usize] = &[128, 256, 512, 1024, 2048, 4096];
+
+/// Number of the room's newest ciphertext sizes adaptive buckets are computed from
+const WINDOW: usize = 1000;
+
+/// Below this many observed sizes adaptive padding uses the fixed buckets
+const MIN_SAMPLES: usize = 50;
+
+/// Buckets of the adaptive policy unless configured otherwise
+const DEFAULT_ADAPTIVE_BUCKETS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Ciphertexts are as long as the payload, like before padding existed
+    Off,
+    /// Pad to [`FIXED_BUCKETS`]
+    Fixed,
+    /// Pad to this many quantiles of the room's traffic
+    Adaptive { buckets: usize },
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::Off
+    }
+}
+
+/// Ciphertext sizes of the room's newest messages, as the server sees them
+#[derive(Debug, Default)]
+pub struct Traffic {
+    sizes: VecDeque<usize>,
+}
+
+impl Traffic {
+    pub fn observe(&mut self, ciphertext_len: usize) {
+        if self.sizes.len() == WINDOW {
+            self.sizes.pop_front();
+        }
+        self.sizes.push_back(ciphertext_len);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// `count` evenly spaced quantiles of the observed sizes, the largest is the biggest size seen
+    fn quantiles(&self, count: usize) -> Vec<usize> {
+        let mut sizes = self.sizes.iter().copied().collect::<Vec<_>>();
+        sizes.sort_unstable();
+        let mut quantiles = (1..=count)
+            .map(|idx| sizes[(idx * sizes.len() / count).max(1) - 1])
+            .collect::<Vec<_>>();
+        quantiles.dedup();
+        quantiles
+    }
+
+    /// Average share of padding if the observed messages had been padded to `buckets`
+    pub fn overhead(&self, buckets: &[usize]) -> f64 {
+        let (padded, unpadded) = self.sizes.iter().fold((0, 0), |(padded, unpadded), len| {
+            (padded + padded_len(*len, buckets), unpadded + len)
+        });
+        match padded {
+            0 => 0.0,
+            padded => (padded - unpadded) as f64 / padded as f64,
+        }
+    }
+}
+
+impl Policy {
+    /// Bucket sizes to pad to given the room's traffic, empty if nothing is padded
+    pub fn buckets(&self, traffic: &Traffic) -> Vec<usize> {
+        match self {
+            Policy::Off => Vec::new(),
+            Policy::Fixed => FIXED_BUCKETS.to_vec(),
+            Policy::Adaptive { .. } if traffic.len() < MIN_SAMPLES => FIXED_BUCKETS.to_vec(),
+            Policy::Adaptive { buckets } => traffic.quantiles(*buckets),
+        }
+    }
+}
+
+/// Length a ciphertext of `len` bytes is padded to: the smallest bucket that fits, a multiple of
+/// the largest bucket beyond that, or `len` itself without buckets
+pub fn padded_len(len: usize, buckets: &[usize]) -> usize {
+    let largest = match buckets.last() {
+        Some(largest) => *largest,
+        None => return len,
+    };
+    buckets
+        .iter()
+        .copied()
+        .find(|bucket| *bucket >= len)
+        .unwrap_or_else(|| (len + largest - 1) / largest * largest)
+}
+
+impl FromStr for Policy {
+    type Err = anyhow::Error;
+
+    /// `off`, `fixed`, `adaptive` or `adaptive:<number of buckets>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "off" => Ok(Policy::Off),
+            None if s == "fixed" => Ok(Policy::Fixed),
+            None if s == "adaptive" => Ok(Policy::Adaptive {
+                buckets: DEFAULT_ADAPTIVE_BUCKETS,
+            }),
+            Some(("adaptive", buckets)) => match buckets.parse() {
+                Ok(buckets) if buckets > 0 => Ok(Policy::Adaptive { buckets }),
+                _ => anyhow::bail!("the number of buckets has to be a positive number"),
+            },
+            _ => anyhow::bail!("expected off, fixed, adaptive or adaptive:<buckets>"),
+        }
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Policy::Off => f.write_str("off"),
+            Policy::Fixed => f.write_str("fixed"),
+            Policy::Adaptive { buckets } => write!(f, "adaptive:{}", buckets),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, Message, Payload};
+
+    #[test]
+    fn smallest_fitting_bucket_is_chosen() {
+        assert_eq!(padded_len(1, FIXED_BUCKETS), 128);
+        assert_eq!(padded_len(128, FIXED_BUCKETS), 128);
+        assert_eq!(padded_len(129, FIXED_BUCKETS), 256);
+        assert_eq!(padded_len(4096, FIXED_BUCKETS), 4096);
+        // Beyond the largest bucket, multiples of it
+        assert_eq!(padded_len(4097, FIXED_BUCKETS), 8192);
+        assert_eq!(padded_len(12_288, FIXED_BUCKETS), 12_288);
+        assert_eq!(padded_len(77, &[]), 77);
+    }
+
+    #[test]
+    fn adaptive_buckets_follow_the_traffic() {
+        let policy = Policy::Adaptive { buckets: 4 };
+        let mut traffic = Traffic::default();
+        (0..MIN_SAMPLES - 1).for_each(|i| traffic.observe(100 + i));
+        assert_eq!(policy.buckets(&traffic), FIXED_BUCKETS);
+
+        let mut traffic = Traffic::default();
+        (1..=100).for_each(|size| traffic.observe(size * 10));
+        assert_eq!(policy.buckets(&traffic), [250, 500, 750, 1000]);
+        assert_eq!(Policy::Off.buckets(&traffic), Vec::<usize>::new());
+        assert_eq!(Policy::Fixed.buckets(&traffic), FIXED_BUCKETS);
+    }
+
+    #[test]
+    fn padded_payloads_round_trip() {
+        let key = Key::generate();
+        for text in &["", "hi", &"long ".repeat(1000)] {
+            let msg = Message::new("alice".into(), text.to_string());
+            let padded = Payload::Message(msg.clone()).encrypt_padded(&key, FIXED_BUCKETS);
+            let unpadded = Payload::Message(msg.clone()).encrypt_padded(&key, &[]);
+            assert_eq!(padded.len(), padded_len(unpadded.len(), FIXED_BUCKETS));
+            match Payload::decrypt(padded, &key) {
+                Ok(Payload::Message(decrypted)) => assert_eq!(decrypted.msg, msg.msg),
+                _ => panic!("padded message didn't decrypt"),
+            }
+        }
+    }
+
+    #[test]
+    fn policies_parse_and_display() {
+        for policy in &["off", "fixed", "adaptive:4", "adaptive:1"] {
+            assert_eq!(policy.parse::<Policy>().unwrap().to_string(), *policy);
+        }
+        assert_eq!(
+            "adaptive".parse::<Policy>().unwrap(),
+            Policy::Adaptive {
+                buckets: DEFAULT_ADAPTIVE_BUCKETS
+            }
+        );
+        assert!("adaptive:0".parse::<Policy>().is_err());
+        assert!("random".parse::<Policy>().is_err());
+    }
+}
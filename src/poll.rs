@@ -0,0 +1,89 @@
+//! Polls are the first structured payload on top of plain messages: a [`Poll`] asks a question,
+//! every participant answers with a [`Vote`] referencing the poll's id and clients count the votes
+//! themselves. The server only ever sees encrypted payloads, so it can't tell polls, votes and
+//! messages apart.
+
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Poll {
+    /// Random id votes refer to, shown to users as 8 hex digits
+    pub id: u32,
+    pub sender: String,
+    pub question: String,
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Vote {
+    pub poll: u32,
+    pub voter: String,
+    /// Index into the poll's options
+    pub option: usize,
+}
+
+/// Client-side aggregation of received polls and votes. Names aren't authenticated, so just like
+/// the roster the results are only as trustworthy as the participants of the room.
+#[derive(Default)]
+pub struct Polls {
+    polls: Vec<Poll>,
+    /// Latest vote of every participant per poll, voting again changes the vote
+    votes: HashMap<u32, HashMap<String, usize>>,
+}
+
+impl Poll {
+    pub fn new(sender: String, question: String, options: Vec<String>) -> Poll {
+        Poll {
+            id: crate::determinism::with_rng(|rng| rng.gen()),
+            sender,
+            question,
+            options,
+        }
+    }
+}
+
+impl Polls {
+    pub fn add_poll(&mut self, poll: Poll) {
+        if self.get(poll.id).is_none() {
+            self.polls.push(poll);
+        }
+    }
+
+    /// Votes may arrive before their poll (e.g. from a faster mirror), they are kept and counted
+    /// once the poll shows up
+    pub fn add_vote(&mut self, vote: Vote) {
+        self.votes
+            .entry(vote.poll)
+            .or_default()
+            .insert(vote.voter, vote.option);
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Poll> {
+        self.polls.iter().find(|poll| poll.id == id)
+    }
+
+    /// All polls in the order they were received
+    pub fn polls(&self) -> &[Poll] {
+        &self.polls
+    }
+
+    /// Number of votes for every option of the poll, votes for options that don't exist are ignored
+    pub fn results(&self, id: u32) -> Option<Vec<usize>> {
+        let poll = self.get(id)?;
+        let mut counts = vec![0; poll.options.len()];
+        for option in self
+            .votes
+            .get(&id)
+            .into_iter()
+            .flat_map(|votes| votes.values())
+        {
+            if let Some(count) = counts.get_mut(*option) {
+                *count += 1;
+            }
+        }
+        Some(counts)
+    }
+}
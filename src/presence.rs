@@ -0,0 +1,64 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a participant stays in the roster after their last beacon
+pub const PRESENCE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Beacons are sent at a random interval in this range (in seconds) so that their timing doesn't
+/// identify the sender across rooms or sessions.
+pub const BEACON_INTERVAL_SECS: (u64, u64) = (30, 90);
+
+/// Encrypted presence beacon, sent to the room like a normal message. No account is involved, the
+/// roster is just whatever names were announced recently.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Presence {
+    pub sender: String,
+    pub status: PresenceStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum PresenceStatus {
+    /// First beacon after starting the client
+    Joined,
+    /// The user interacted with the client recently
+    Active,
+    /// The client is running but the user hasn't been interacting with it
+    Away,
+}
+
+/// Client-side aggregation of received presence beacons
+#[derive(Default)]
+pub struct Roster {
+    members: HashMap<String, (PresenceStatus, Instant)>,
+}
+
+impl Roster {
+    pub fn update(&mut self, presence: Presence) {
+        self.members
+            .insert(presence.sender, (presence.status, Instant::now()));
+    }
+
+    /// Members that sent a beacon within `PRESENCE_TIMEOUT`, sorted by name
+    pub fn members(&self) -> Vec<(&str, PresenceStatus)> {
+        let mut members = self
+            .members
+            .iter()
+            .filter(|(_, (_, last_seen))| last_seen.elapsed() < PRESENCE_TIMEOUT)
+            .map(|(name, (status, _))| (name.as_str(), *status))
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(name, _)| *name);
+        members
+    }
+}
+
+impl PresenceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PresenceStatus::Joined => "joined",
+            PresenceStatus::Active => "active",
+            PresenceStatus::Away => "away",
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! The user's profile: name, identity key and the rooms they joined, so after the first run
+//! starting the client without arguments is enough. It's stored encrypted with a key derived from
+//! a passphrase, an empty passphrase only protects against accidental reads.
+//!
+//! The key is derived with Argon2id, files start with [`ARGON2_MARKER`] and the salt. Profiles
+//! written before that only start with the salt and are opened with PBKDF2, saving them again
+//! moves them to Argon2id.
+
+use crate::broadcast::SigningKey;
+use crate::epoch::ChainKey;
+use crate::identity::Petnames;
+use crate::invite::Invite;
+use crate::{EncryptedMessage, Key};
+use hmac::Hmac;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use zeroize::Zeroize;
+
+/// Start of profiles whose key is derived with Argon2id
+const ARGON2_MARKER: &[u8] = b"nym-chat profile argon2id";
+
+/// PBKDF2 rounds of profiles written before Argon2id, they are still read
+const LEGACY_KDF_ROUNDS: u32 = 100_000;
+
+const SALT_LEN: usize = 16;
+
+pub struct Profile {
+    pub name: String,
+    /// Long-term key of the user, contact cards carry its fingerprint
+    identity: SigningKey,
+    /// Invite links of the rooms the user joined or created, in the order they were added
+    pub rooms: Vec<String>,
+    /// Names given to other participants' identity keys before they moved to the address book
+    pub petnames: Petnames,
+    /// Service provider addresses servers told us at `/provider-address`, by their URL, so
+    /// starting with just a server's URL doesn't ask it again
+    pub providers: BTreeMap<String, String>,
+}
+
+/// What is encrypted to the profile key, keys as hex since they don't implement serde
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    name: String,
+    identity: String,
+    rooms: Vec<String>,
+    petnames: Petnames,
+    providers: BTreeMap<String, String>,
+}
+
+/// Profiles written before provider addresses were cached
+#[derive(Deserialize)]
+struct PetnameStored {
+    name: String,
+    identity: String,
+    rooms: Vec<String>,
+    petnames: Petnames,
+}
+
+/// Profiles written before petnames existed
+#[derive(Deserialize)]
+struct LegacyStored {
+    name: String,
+    identity: String,
+    rooms: Vec<String>,
+}
+
+impl Profile {
+    /// New profile with a fresh identity key
+    pub fn new(name: String) -> Profile {
+        Profile {
+            name,
+            identity: SigningKey::generate(),
+            rooms: Vec::new(),
+            petnames: Petnames::default(),
+            providers: BTreeMap::new(),
+        }
+    }
+
+    pub fn identity(&self) -> &SigningKey {
+        &self.identity
+    }
+
+    /// Present `identity` from now on, e.g. the one of the device this one was linked to
+    pub fn set_identity(&mut self, identity: SigningKey) {
+        self.identity = identity;
+    }
+
+    /// Remember a room by its invite link, returns false if it's known already
+    pub fn add_room(&mut self, invite: String) -> bool {
+        let known = self.rooms.contains(&invite);
+        if !known {
+            self.rooms.push(invite);
+        }
+        !known
+    }
+
+    /// Forget the room with the id `room_id`, returns false if it wasn't known
+    pub fn remove_room(&mut self, room_id: &str) -> bool {
+        let before = self.rooms.len();
+        self.rooms.retain(|invite| {
+            let key = Invite::from_str(invite).ok().and_then(|invite| invite.key);
+            key.map_or(true, |key| key.id() != room_id)
+        });
+        self.rooms.len() < before
+    }
+
+    /// Put `chain` into the invites of the room with the id `room_id` that carry an older chain
+    /// position, so they don't keep what the client forgot (see `RoomKeys::forget_old_epochs`).
+    /// Returns false if no invite changed.
+    pub fn forward_chain(&mut self, room_id: &str, chain: &ChainKey) -> bool {
+        let mut changed = false;
+        for stored in &mut self.rooms {
+            let mut invite = match Invite::from_str(stored) {
+                Ok(invite) => invite,
+                Err(_) => continue,
+            };
+            let ours = invite.key.as_ref().map_or(false, |key| key.id() == room_id);
+            let older = invite
+                .chain
+                .as_ref()
+                .map_or(false, |old| old.epoch() < chain.epoch());
+            if ours && older {
+                invite.chain = Some(chain.clone());
+                *stored = invite.to_string();
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Load the profile at `path`, `None` if there is none yet. A wrong passphrase is an error.
+    pub fn load(path: &Path, passphrase: &str) -> Result<Option<Profile>, anyhow::Error> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let (bytes, derive_key): (_, fn(&str, &[u8]) -> Key) =
+            match bytes.strip_prefix(ARGON2_MARKER) {
+                Some(bytes) => (bytes, derive_key),
+                None => (&bytes[..], derive_legacy_key),
+            };
+        if bytes.len() < SALT_LEN {
+            anyhow::bail!("the profile is truncated");
+        }
+        let (salt, sealed) = bytes.split_at(SALT_LEN);
+        let key = derive_key(passphrase, salt);
+        let open = || bincode::deserialize::<EncryptedMessage>(sealed).map_err(|_| ());
+        let stored: Stored = bincode::deserialize::<EncryptedMessage>(sealed)?
+            .open(&key)
+            .or_else(|_| {
+                open()?.open(&key).map(|older: PetnameStored| Stored {
+                    name: older.name,
+                    identity: older.identity,
+                    rooms: older.rooms,
+                    petnames: older.petnames,
+                    providers: BTreeMap::new(),
+                })
+            })
+            .or_else(|_| {
+                open()?.open(&key).map(|legacy: LegacyStored| Stored {
+                    name: legacy.name,
+                    identity: legacy.identity,
+                    rooms: legacy.rooms,
+                    petnames: Petnames::default(),
+                    providers: BTreeMap::new(),
+                })
+            })
+            .map_err(|_| anyhow::Error::msg("wrong passphrase or damaged profile"))?;
+        Ok(Some(Profile {
+            name: stored.name,
+            identity: SigningKey::from_str(&stored.identity)?,
+            rooms: stored.rooms,
+            petnames: stored.petnames,
+            providers: stored.providers,
+        }))
+    }
+
+    /// Encrypt the profile with a fresh salt and replace the file at `path`
+    pub fn save(&self, path: &Path, passphrase: &str) -> Result<(), anyhow::Error> {
+        let salt = crate::determinism::with_rng(|rng| rng.gen::<[u8; SALT_LEN]>());
+        let stored = Stored {
+            name: self.name.clone(),
+            identity: self.identity.to_hex(),
+            rooms: self.rooms.clone(),
+            petnames: self.petnames.clone(),
+            providers: self.providers.clone(),
+        };
+        let sealed = EncryptedMessage::seal(&stored, &derive_key(passphrase, &salt));
+        let mut bytes = ARGON2_MARKER.to_vec();
+        bytes.extend_from_slice(&salt);
+        bytes.extend(bincode::serialize(&sealed)?);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Written next to the old profile first, so a crash can't leave a half written one
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, bytes)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Same parameters as [`Key::from_passphrase`], it's only done once per start
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key {
+    let params = argon2::Params::new(64 * 1024, 3, 1, Some(32)).expect("valid parameters");
+    let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut bytes = [0u8; 32];
+    argon2
+        .hash_password_into(passphrase.as_bytes(), salt, &mut bytes)
+        .expect("the output length fits the parameters");
+    let key = Key::from_bytes(&bytes).expect("32 bytes are a valid key");
+    bytes.zeroize();
+    key
+}
+
+fn derive_legacy_key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut bytes = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, LEGACY_KDF_ROUNDS, &mut bytes);
+    let key = Key::from_bytes(&bytes).expect("32 bytes are a valid key");
+    bytes.zeroize();
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_profiles_load() {
+        let path = std::env::temp_dir().join(format!("nym-chat-profile-{}", std::process::id()));
+        let mut profile = Profile::new("alice".into());
+        profile.add_room("invite".into());
+        profile.save(&path, "secret").unwrap();
+        assert!(std::fs::read(&path).unwrap().starts_with(ARGON2_MARKER));
+
+        let loaded = Profile::load(&path, "secret").unwrap().unwrap();
+        let wrong = Profile::load(&path, "wrong");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.name, "alice");
+        assert_eq!(loaded.rooms, vec!["invite".to_owned()]);
+        assert_eq!(loaded.identity().to_hex(), profile.identity().to_hex());
+        assert!(wrong.is_err());
+    }
+
+    #[test]
+    fn pbkdf2_profiles_still_load() {
+        let path = std::env::temp_dir().join(format!("nym-chat-pbkdf2-{}", std::process::id()));
+        let profile = Profile::new("bob".into());
+        let stored = Stored {
+            name: profile.name.clone(),
+            identity: profile.identity().to_hex(),
+            rooms: Vec::new(),
+            petnames: Petnames::default(),
+            providers: BTreeMap::new(),
+        };
+        let salt = [7u8; SALT_LEN];
+        let sealed = EncryptedMessage::seal(&stored, &derive_legacy_key("secret", &salt));
+        let mut bytes = salt.to_vec();
+        bytes.extend(bincode::serialize(&sealed).unwrap());
+        std::fs::write(&path, bytes).unwrap();
+
+        let loaded = Profile::load(&path, "secret").unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.name, "bob");
+        assert_eq!(loaded.identity().to_hex(), profile.identity().to_hex());
+    }
+}
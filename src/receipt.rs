@@ -0,0 +1,105 @@
+//! Receipts are signed by the server for every message it stored: they bind the digest of the
+//! ciphertext to its position in the server's log and its arrival time. Senders that keep the
+//! receipts of their messages can prove that the server accepted them, where and when, even if
+//! the server later claims otherwise. The arrival time is only as exact as the server stores it
+//! (see its `--timestamp-bucket`), anyone a receipt is shown to learns roughly when its sender
+//! was online.
+//!
+//! One server signs the receipts and tree heads of all its tenants with the same key, so the
+//! service provider address of the tenant is part of everything it signs. A receipt of one
+//! tenant's log doesn't prove anything about another's.
+
+use crate::broadcast::{SigningKey, VerifyingKey};
+use crate::merkle::Hash;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Receipt {
+    /// See [`crate::EncryptedMessage::digest`]
+    pub digest: [u8; 32],
+    /// Index of the message in the server's log, the one clients fetch by
+    pub index: u64,
+    /// Arrival time (unix seconds), possibly rounded down by the server
+    pub arrived: u64,
+    signature: Vec<u8>,
+}
+
+impl Receipt {
+    /// Receipt for a message in the log of the service provider `provider`
+    pub fn sign(
+        key: &SigningKey,
+        provider: &str,
+        digest: [u8; 32],
+        index: u64,
+        arrived: u64,
+    ) -> Receipt {
+        Receipt {
+            digest,
+            index,
+            arrived,
+            signature: key.sign_data(&signed_data(provider, &digest, index, arrived)),
+        }
+    }
+
+    /// Whether the receipt was signed by the server with the verifying key `key` for the log of
+    /// `provider`
+    pub fn verify(&self, key: &VerifyingKey, provider: &str) -> bool {
+        key.verify_data(
+            &signed_data(provider, &self.digest, self.index, self.arrived),
+            &self.signature,
+        )
+    }
+}
+
+/// The signature can't be mistaken for one of a broadcast message or a tree head thanks to the
+/// prefix
+fn signed_data(provider: &str, digest: &[u8; 32], index: u64, arrived: u64) -> Vec<u8> {
+    let mut data = b"nym-chat receipt".to_vec();
+    push_provider(&mut data, provider);
+    data.extend_from_slice(digest);
+    data.extend_from_slice(&index.to_le_bytes());
+    data.extend_from_slice(&arrived.to_le_bytes());
+    data
+}
+
+/// Signed root of the Merkle tree over all messages in the server's log (see the merkle module),
+/// clients keep the last one they saw and ask the server to prove that newer trees extend it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TreeHead {
+    /// Number of messages in the tree
+    pub size: u64,
+    pub root: Hash,
+    signature: Vec<u8>,
+}
+
+impl TreeHead {
+    pub fn sign(key: &SigningKey, provider: &str, size: u64, root: Hash) -> TreeHead {
+        TreeHead {
+            size,
+            root,
+            signature: key.sign_data(&tree_head_data(provider, size, &root)),
+        }
+    }
+
+    pub fn verify(&self, key: &VerifyingKey, provider: &str) -> bool {
+        key.verify_data(
+            &tree_head_data(provider, self.size, &self.root),
+            &self.signature,
+        )
+    }
+}
+
+fn tree_head_data(provider: &str, size: u64, root: &Hash) -> Vec<u8> {
+    let mut data = b"nym-chat tree head".to_vec();
+    push_provider(&mut data, provider);
+    data.extend_from_slice(&size.to_le_bytes());
+    data.extend_from_slice(root);
+    data
+}
+
+/// The address is prefixed with its length, so it can't run into the fields after it
+fn push_provider(data: &mut Vec<u8>, provider: &str) {
+    data.extend_from_slice(&(provider.len() as u64).to_le_bytes());
+    data.extend_from_slice(provider.as_bytes());
+}
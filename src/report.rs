@@ -0,0 +1,183 @@
+//! Abuse reports. A room can name a moderator by their [`ReportKey`] (invites carry it), members
+//! then report messages to the moderator with `/report`. Reports travel through the room like any
+//! other payload, but inside the room encryption they are sealed to the report key: everyone in
+//! the room can see that a report was sent, only the moderator can read who reported what and why.
+//!
+//! Sealing uses an ephemeral X25519 key per report, the AES key is derived from the shared secret.
+//! Reports are claims of the reporter, the quoted message isn't signed, so moderators should check
+//! it against their own history.
+
+use crate::{constant_time, EncryptedMessage, Key, Message};
+use rand::Rng;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroize;
+
+/// Secret key of a room's moderator, it opens the reports sent to its [`ReportKey`]
+#[derive(Clone)]
+pub struct ModeratorKey {
+    secret: StaticSecret,
+}
+
+/// Public half of a [`ModeratorKey`], members seal their reports to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportKey {
+    key: PublicKey,
+}
+
+/// A member's complaint about a message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// Name of the member reporting
+    pub reporter: String,
+    /// The message as the reporter saw it
+    pub reported: Message,
+    pub reason: String,
+}
+
+/// A [`Report`] only the moderator can open
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SealedReport {
+    /// Public half of the ephemeral key the report was sealed with
+    ephemeral: [u8; 32],
+    report: EncryptedMessage,
+}
+
+impl ModeratorKey {
+    pub fn generate() -> ModeratorKey {
+        ModeratorKey {
+            secret: StaticSecret::from(crate::determinism::with_rng(|rng| rng.gen::<[u8; 32]>())),
+        }
+    }
+
+    pub fn report_key(&self) -> ReportKey {
+        ReportKey {
+            key: PublicKey::from(&self.secret),
+        }
+    }
+
+    /// Hex encoding of the secret key, the inverse of `ModeratorKey::from_str`
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.secret.to_bytes())
+    }
+}
+
+impl Report {
+    pub fn seal(&self, key: &ReportKey) -> SealedReport {
+        let ephemeral =
+            StaticSecret::from(crate::determinism::with_rng(|rng| rng.gen::<[u8; 32]>()));
+        let public = PublicKey::from(&ephemeral);
+        let aes_key = derive_key(&ephemeral.diffie_hellman(&key.key), &public, &key.key);
+        SealedReport {
+            ephemeral: public.to_bytes(),
+            report: EncryptedMessage::seal(self, &aes_key),
+        }
+    }
+}
+
+impl SealedReport {
+    /// The report, if it was sealed to the report key of `key`
+    pub fn open(self, key: &ModeratorKey) -> Result<Report, ()> {
+        let ephemeral = PublicKey::from(self.ephemeral);
+        let report_key = PublicKey::from(&key.secret);
+        let aes_key = derive_key(
+            &key.secret.diffie_hellman(&ephemeral),
+            &ephemeral,
+            &report_key,
+        );
+        self.report.open(&aes_key)
+    }
+}
+
+/// Both public keys are hashed in, so a report can't be moved to another ephemeral key
+fn derive_key(
+    shared: &x25519_dalek::SharedSecret,
+    ephemeral: &PublicKey,
+    report_key: &PublicKey,
+) -> Key {
+    let mut hasher = Sha256::new();
+    hasher.update(b"nym-chat report key");
+    hasher.update(shared.as_bytes());
+    hasher.update(ephemeral.as_bytes());
+    hasher.update(report_key.as_bytes());
+    Key::from_bytes(&hasher.finalize()).expect("hashes are 32 bytes")
+}
+
+/// Reports a moderator received, grouped by the reported message so that one message reported by
+/// many members shows up once
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Reports {
+    /// Reporters and their reasons per reported message, keyed by sender and text
+    by_message: BTreeMap<(String, String), Vec<(String, String)>>,
+}
+
+impl Reports {
+    /// Count a report, reporting the same message twice only counts once per reporter
+    pub fn add(&mut self, report: Report) {
+        let Report {
+            reporter,
+            reported,
+            reason,
+        } = report;
+        let reports = self
+            .by_message
+            .entry((reported.sender, reported.msg))
+            .or_default();
+        if !reports.iter().any(|(existing, _)| *existing == reporter) {
+            reports.push((reporter, reason));
+        }
+    }
+
+    /// Reported messages with their reporters and reasons, the most reported first
+    pub fn summary(&self) -> Vec<(&str, &str, &[(String, String)])> {
+        let mut summary = self
+            .by_message
+            .iter()
+            .map(|((sender, msg), reports)| (sender.as_str(), msg.as_str(), reports.as_slice()))
+            .collect::<Vec<_>>();
+        summary.sort_by_key(|(_, _, reports)| std::cmp::Reverse(reports.len()));
+        summary
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_message.is_empty()
+    }
+}
+
+impl FromStr for ModeratorKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut secret = [0; 32];
+        constant_time::decode_hex(s, &mut secret)
+            .map_err(|_| anyhow::Error::msg("invalid moderator key"))?;
+        let key = ModeratorKey {
+            secret: StaticSecret::from(secret),
+        };
+        secret[..].zeroize();
+        Ok(key)
+    }
+}
+
+impl FromStr for ReportKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut key = [0; 32];
+        hex::decode_to_slice(s, &mut key).map_err(|_| anyhow::Error::msg("invalid report key"))?;
+        Ok(ReportKey {
+            key: PublicKey::from(key),
+        })
+    }
+}
+
+impl fmt::Display for ReportKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.key.as_bytes()))
+    }
+}
@@ -0,0 +1,71 @@
+//! Messages queued with `/schedule` to be sent later. The queue is stored encrypted to the room key
+//! like drafts (see [`History::set_schedule`](crate::history::History::set_schedule)) and only
+//! goes out while the client is running, messages that became due while it wasn't are sent right
+//! after the next start.
+
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scheduled {
+    /// Unix seconds after which the message is sent
+    pub at: u64,
+    pub msg: String,
+}
+
+/// Pending scheduled messages in the order they are due
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Schedule {
+    pending: Vec<Scheduled>,
+}
+
+impl Schedule {
+    pub fn add(&mut self, scheduled: Scheduled) {
+        let position = self
+            .pending
+            .partition_point(|other| other.at <= scheduled.at);
+        self.pending.insert(position, scheduled);
+    }
+
+    /// Cancel the message at `index` (as listed by `iter`), returns it if there was one
+    pub fn cancel(&mut self, index: usize) -> Option<Scheduled> {
+        (index < self.pending.len()).then(|| self.pending.remove(index))
+    }
+
+    /// The next message if it's due at unix time `now`
+    pub fn due(&self, now: u64) -> Option<&Scheduled> {
+        self.pending.first().filter(|scheduled| scheduled.at <= now)
+    }
+
+    /// Remove the message returned by `due` once it's sent
+    pub fn sent(&mut self) {
+        if !self.pending.is_empty() {
+            self.pending.remove(0);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Pending messages in the order they are due
+    pub fn iter(&self) -> impl Iterator<Item = &Scheduled> {
+        self.pending.iter()
+    }
+}
+
+/// Unix time of the next occurrence of the local time `HH:MM` after `now`, i.e. today or tomorrow
+pub fn next_time(time: &str, now: DateTime<Local>) -> Result<u64, String> {
+    let time = NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| format!("expected HH:MM, got {}", time))?;
+    let mut day = now.naive_local().date();
+    loop {
+        // Times skipped by a daylight saving change don't exist on that day, try the next one
+        if let Some(at) = Local.from_local_datetime(&day.and_time(time)).earliest() {
+            if at > now {
+                return Ok(at.timestamp() as u64);
+            }
+        }
+        day = day + Duration::days(1);
+    }
+}
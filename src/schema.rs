@@ -0,0 +1,30 @@
+//! Machine-readable description of the wire types for third-party implementations. The JSON Schema
+//! is derived from the Rust definitions when compiling, so it always matches the running version;
+//! servers serve it at `/schema`.
+//!
+//! Payloads are serialized with bincode inside the encryption, the schema describes their
+//! structure (field order, enum variants in the order of their bincode tags). The HTTP API uses
+//! JSON: fetch responses are `{"messages": [EncryptedMessage], "receipts": [Receipt], "gap": Gap}`
+//! (or just the list of messages for clients that didn't ask for receipts).
+
+use crate::receipt::{Receipt, TreeHead};
+use crate::{EncryptedMessage, Envelope, Gap, Payload};
+use schemars::gen::SchemaSettings;
+use serde_json::json;
+
+/// Schema document with a definition of every wire type, e.g. `definitions.Envelope`
+pub fn wire_types() -> serde_json::Value {
+    let mut gen = SchemaSettings::draft07().into_generator();
+    gen.subschema_for::<Envelope>();
+    gen.subschema_for::<EncryptedMessage>();
+    gen.subschema_for::<Payload>();
+    gen.subschema_for::<Receipt>();
+    gen.subschema_for::<TreeHead>();
+    gen.subschema_for::<Gap>();
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "nym-chat wire types",
+        "version": env!("CARGO_PKG_VERSION"),
+        "definitions": gen.take_definitions(),
+    })
+}
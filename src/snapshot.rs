@@ -0,0 +1,74 @@
+//! Snapshots let new participants download a server's whole history at once. The server
+//! periodically bundles all messages it received into a gzip compressed bincode list, clients
+//! that start from scratch download it and continue with regular fetches starting at the number
+//! of messages it contained.
+//!
+//! Ciphertexts hardly compress, most of the savings come from not encoding every byte as a JSON
+//! number like fetch responses do.
+
+use crate::EncryptedMessage;
+use bincode::Options;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+/// Snapshots bigger than this once decompressed are refused, so a server can't make clients run
+/// out of memory with a small download that decompresses to gigabytes
+pub const MAX_SNAPSHOT_BYTES: u64 = 256 << 20;
+
+/// Compress a list of messages serialized with bincode into a snapshot
+pub fn compress(serialized: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(serialized)
+        .expect("writing to a Vec can't fail");
+    encoder.finish().expect("writing to a Vec can't fail")
+}
+
+/// Decompress and deserialize a snapshot of at most [`MAX_SNAPSHOT_BYTES`]
+pub fn decode(snapshot: &[u8]) -> Result<Vec<EncryptedMessage>, anyhow::Error> {
+    decode_limited(snapshot, MAX_SNAPSHOT_BYTES)
+}
+
+fn decode_limited(snapshot: &[u8], limit: u64) -> Result<Vec<EncryptedMessage>, anyhow::Error> {
+    let mut serialized = Vec::new();
+    // One byte more than allowed tells a snapshot of exactly `limit` bytes from a bigger one
+    GzDecoder::new(snapshot)
+        .take(limit + 1)
+        .read_to_end(&mut serialized)?;
+    if serialized.len() as u64 > limit {
+        anyhow::bail!("the snapshot exceeds the limit of {} bytes", limit);
+    }
+    // The same encoding as `bincode::serialize`, but without allocating more than the limit for
+    // lengths that claim otherwise
+    let options = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit);
+    Ok(options.deserialize(&serialized)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, Message};
+
+    #[test]
+    fn snapshots_round_trip() {
+        let key = Key::generate();
+        let messages = vec![Message::new("alice".into(), "hi".into()).encrypt(&key)];
+        let snapshot = compress(&bincode::serialize(&messages).unwrap());
+
+        let decoded = decode(&snapshot).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].digest(), messages[0].digest());
+    }
+
+    #[test]
+    fn oversized_snapshots_are_refused() {
+        // Zeros compress to almost nothing, like a gzip bomb
+        let snapshot = compress(&[0; 4096]);
+        assert!(decode_limited(&snapshot, 1024).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! Named snippets of prewritten text for recurring messages, e.g. the daily standup of a team
+//! room. They are read from the TOML file given with `--snippets`, one `name = "text"` per line,
+//! and `/snip <name> [<value> | <value>...]` sends the text with its placeholders filled in:
+//! `{1}`, `{2}`, ... are the values given after the name, `{name}` is our name and `{date}` and
+//! `{time}` are the local date and time when it's sent. `{{` and `}}` stand for literal braces.
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Snippets {
+    snippets: BTreeMap<String, String>,
+}
+
+/// Why a snippet couldn't be expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpandError {
+    /// There is no snippet of that name
+    Unknown,
+    /// The snippet has a `{<number>}` placeholder but fewer values were given
+    MissingValue(usize),
+    /// A placeholder that is neither a number nor one of the named ones, or an unclosed brace
+    InvalidPlaceholder(String),
+}
+
+impl Snippets {
+    pub fn load(path: &Path) -> Result<Snippets, anyhow::Error> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty()
+    }
+
+    /// Names of all snippets in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.snippets.keys().map(String::as_str)
+    }
+
+    /// The text of snippet `name` with its placeholders replaced, `values` fill in `{1}`, `{2}`...
+    pub fn expand(
+        &self,
+        name: &str,
+        values: &[&str],
+        sender: &str,
+        now: DateTime<Local>,
+    ) -> Result<String, ExpandError> {
+        let template = self.snippets.get(name).ok_or(ExpandError::Unknown)?;
+        let mut text = String::with_capacity(template.len());
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find(|c| c == '{' || c == '}') {
+            text.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                text.push_str(&rest[..1]);
+                rest = &rest[2..];
+                continue;
+            }
+            let end = match (rest.starts_with('{'), rest.find('}')) {
+                (true, Some(end)) => end,
+                _ => return Err(ExpandError::InvalidPlaceholder(rest.to_owned())),
+            };
+            let placeholder = &rest[1..end];
+            match placeholder {
+                "name" => text.push_str(sender),
+                "date" => text.push_str(&now.format("%Y-%m-%d").to_string()),
+                "time" => text.push_str(&now.format("%H:%M").to_string()),
+                number => match number.parse::<usize>() {
+                    Ok(index) if index > 0 => {
+                        let value = values
+                            .get(index - 1)
+                            .ok_or(ExpandError::MissingValue(index))?;
+                        text.push_str(value);
+                    }
+                    _ => return Err(ExpandError::InvalidPlaceholder(placeholder.to_owned())),
+                },
+            }
+            rest = &rest[end + 1..];
+        }
+        text.push_str(rest);
+        Ok(text)
+    }
+}
@@ -0,0 +1,87 @@
+//! Bookkeeping of the reply SURBs (single use reply blocks) we gave a server. A SURB lets the
+//! server answer us through the mixnet without learning our address, but each one works only once
+//! and only until the mixnet's keys change. Features that need answers (fetching through the
+//! mixnet, acknowledgements) shouldn't attach a fresh SURB to every request, they keep a pool at
+//! the server topped up: [`SurbPool::wanted`] says how many to hand over with
+//! [`Transport::send_with_surb`](crate::transport::Transport::send_with_surb), SURBs the server
+//! used are reported back with [`SurbPool::consumed`].
+//!
+//! The Nym client creates the SURBs itself when a message asks for one, so the pool only counts
+//! them and remembers when they were handed over.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long a SURB is assumed to stay usable, the mixnet's keys rotate less often than this
+pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(12 * 60 * 60);
+
+#[derive(Debug, Clone)]
+pub struct SurbPool {
+    /// SURBs the server should have at any time
+    target: usize,
+    lifetime: Duration,
+    /// Handed over SURBs that weren't used yet, oldest first
+    provisioned: VecDeque<Instant>,
+    consumed: usize,
+    expired: usize,
+}
+
+impl SurbPool {
+    pub fn new(target: usize, lifetime: Duration) -> SurbPool {
+        SurbPool {
+            target,
+            lifetime,
+            provisioned: VecDeque::new(),
+            consumed: 0,
+            expired: 0,
+        }
+    }
+
+    /// How many SURBs to hand over now. SURBs are replaced a while before they expire (after
+    /// three quarters of their lifetime), so the server never runs out while waiting for new ones.
+    pub fn wanted(&mut self, now: Instant) -> usize {
+        let refresh_after = self.lifetime * 3 / 4;
+        while let Some(handed_over) = self.provisioned.front() {
+            if now.duration_since(*handed_over) < refresh_after {
+                break;
+            }
+            self.provisioned.pop_front();
+            self.expired += 1;
+        }
+        self.target.saturating_sub(self.provisioned.len())
+    }
+
+    /// A SURB was handed over at `now`
+    pub fn provisioned(&mut self, now: Instant) {
+        self.provisioned.push_back(now);
+    }
+
+    /// The server answered using one of our SURBs. It uses the oldest ones first, we can't tell
+    /// which one it was anyway.
+    pub fn consumed(&mut self) {
+        if self.provisioned.pop_front().is_some() {
+            self.consumed += 1;
+        }
+    }
+
+    /// SURBs the server should still have
+    pub fn available(&self) -> usize {
+        self.provisioned.len()
+    }
+
+    /// SURBs used by the server so far
+    pub fn consumed_count(&self) -> usize {
+        self.consumed
+    }
+
+    /// SURBs replaced before the server used them
+    pub fn expired_count(&self) -> usize {
+        self.expired
+    }
+}
+
+impl Default for SurbPool {
+    fn default() -> SurbPool {
+        SurbPool::new(10, DEFAULT_LIFETIME)
+    }
+}
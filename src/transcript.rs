@@ -0,0 +1,128 @@
+//! Tamper-evident transcript of everything a client sent, received and did, for deployments that
+//! have to keep records. Every entry is a JSON line containing the hash of the line before it and
+//! a signature over its contents, so removing, reordering or changing entries breaks the chain and
+//! only the holder of the signing key can append new ones. [`verify`] checks a whole transcript.
+
+use crate::broadcast::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// One line of the transcript
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// Position in the transcript, starting at 0
+    pub seq: u64,
+    /// Unix seconds
+    pub time: u64,
+    pub event: Event,
+    /// Hex encoded SHA-256 of the previous line, zeros for the first entry
+    pub prev: String,
+    /// Hex encoded signature over everything above
+    signature: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Sent {
+        sender: String,
+        msg: String,
+        /// Hex encoded digest of the ciphertext, it matches the server's receipts
+        digest: String,
+    },
+    Received {
+        sender: String,
+        msg: String,
+        digest: String,
+    },
+    /// Anything else worth recording, like starting the client or running a command
+    Control { description: String },
+}
+
+/// Appends signed entries to a transcript file
+pub struct Transcript {
+    file: File,
+    key: SigningKey,
+    seq: u64,
+    prev: [u8; 32],
+}
+
+impl Transcript {
+    /// Continue the transcript at `path` (or start a new one), existing entries have to verify
+    /// with `key` so we never extend a broken chain
+    pub fn open(path: &Path, key: SigningKey) -> Result<Transcript, anyhow::Error> {
+        let (seq, prev) = match path.exists() {
+            true => read_chain(path, &key.verifying_key())?,
+            false => (0, [0; 32]),
+        };
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        Ok(Transcript {
+            file,
+            key,
+            seq,
+            prev,
+        })
+    }
+
+    pub fn record(&mut self, event: Event) -> Result<(), anyhow::Error> {
+        let time = crate::determinism::now();
+        let mut entry = Entry {
+            seq: self.seq,
+            time,
+            event,
+            prev: hex::encode(self.prev),
+            signature: String::new(),
+        };
+        entry.signature = hex::encode(self.key.sign_data(&signed_data(&entry)?));
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.flush()?;
+        self.seq += 1;
+        self.prev = Sha256::digest(line.trim_end().as_bytes()).into();
+        Ok(())
+    }
+}
+
+/// Check every entry of the transcript at `path` against `key`, returns the number of entries or
+/// an error naming the first broken one
+pub fn verify(path: &Path, key: &VerifyingKey) -> Result<u64, anyhow::Error> {
+    read_chain(path, key).map(|(seq, _)| seq)
+}
+
+/// Number of entries and hash of the last line
+fn read_chain(path: &Path, key: &VerifyingKey) -> Result<(u64, [u8; 32]), anyhow::Error> {
+    let mut seq = 0;
+    let mut prev = [0; 32];
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let entry: Entry = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("entry {} is malformed: {}", seq, e))?;
+        if entry.seq != seq || entry.prev != hex::encode(prev) {
+            anyhow::bail!("the chain is broken at entry {}", seq);
+        }
+        let signature = hex::decode(&entry.signature)?;
+        if !key.verify_data(&signed_data(&entry)?, &signature) {
+            anyhow::bail!("entry {} has an invalid signature", seq);
+        }
+        seq += 1;
+        prev = Sha256::digest(line.as_bytes()).into();
+    }
+    Ok((seq, prev))
+}
+
+/// The signature covers the entry without its signature, prefixed so it can't be mistaken for
+/// other signatures of the same key
+fn signed_data(entry: &Entry) -> Result<Vec<u8>, anyhow::Error> {
+    let mut data = b"nym-chat transcript".to_vec();
+    data.extend(serde_json::to_vec(&(
+        entry.seq,
+        entry.time,
+        &entry.event,
+        &entry.prev,
+    ))?);
+    Ok(data)
+}
@@ -0,0 +1,440 @@
+//! How clients reach servers: envelopes are handed to a server's service provider and new
+//! ciphertexts are fetched from its HTTP API. The [`Transport`] trait hides how that happens, so
+//! the client loop doesn't care whether it talks to a Nym native client ([`Websocket`]) or,
+//! during development and in tests, directly to a server started with `--loopback`
+//! ([`Loopback`]). Other transports like an embedded Nym client only have to implement it.
+//! Failed sends and fetches of any transport are counted by wrapping it in [`Counted`], the
+//! websocket transport additionally counts the Nym client's errors and reconnects itself.
+
+use crate::health::{Health, Problem};
+use crate::receipt::Receipt;
+use crate::{EncryptedMessage, Envelope, Gap};
+use futures::future::BoxFuture;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use nym_addressing::clients::Recipient;
+use nym_websocket::responses::ServerResponse;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::Message;
+
+/// A server the room is read from and sent to
+#[derive(Clone)]
+pub struct Server {
+    pub url: String,
+    pub service_provider: Recipient,
+}
+
+impl FromStr for Server {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (url, service_provider) = s
+            .rsplit_once('=')
+            .ok_or("expected <url>=<service provider address>")?;
+        let service_provider = Recipient::try_from_base58_string(service_provider)
+            .map_err(|_| "invalid service provider address")?;
+        Ok(Server {
+            url: url.to_owned(),
+            service_provider,
+        })
+    }
+}
+
+/// How the HTTP requests to servers look, read from the TOML file given with `--http-config`.
+/// Servers behind a CDN or web application firewall may only let requests through that look like
+/// a browser's or carry some header, and polling traffic blends in better if it looks like
+/// everything else on the network.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Sent instead of reqwest's default (which is no user agent at all)
+    pub user_agent: Option<String>,
+    /// Additional headers sent with every request
+    pub headers: BTreeMap<String, String>,
+    /// Requests taking longer fail and are retried like any other failed fetch
+    pub timeout_secs: Option<u64>,
+    pub connect_timeout_secs: Option<u64>,
+    /// `"1.1"` to never use HTTP/2, `"2"` to use it without negotiating, by default it's used if
+    /// the server offers it. `"3"` talks HTTP/3 to servers started with --http3, given by their
+    /// `https://` URL, only with the `http3` feature.
+    pub http_version: Option<HttpVersion>,
+    /// Idle connections kept open per server
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Seconds after which idle connections are closed
+    pub pool_idle_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum HttpVersion {
+    #[serde(rename = "1.1")]
+    Http1,
+    #[serde(rename = "2")]
+    Http2,
+    #[cfg(feature = "http3")]
+    #[serde(rename = "3")]
+    Http3,
+}
+
+impl HttpConfig {
+    pub fn load(path: &Path) -> Result<HttpConfig, anyhow::Error> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// An HTTP client making requests the way this config asks for
+    pub fn client(&self) -> Result<reqwest::Client, anyhow::Error> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        let mut builder = reqwest::Client::builder().default_headers(headers);
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(timeout) = self.timeout_secs {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = self.connect_timeout_secs {
+            builder = builder.connect_timeout(Duration::from_secs(timeout));
+        }
+        builder = match self.http_version {
+            Some(HttpVersion::Http1) => builder.http1_only(),
+            Some(HttpVersion::Http2) => builder.http2_prior_knowledge(),
+            // reqwest only speaks HTTP/3 with rustls
+            #[cfg(feature = "http3")]
+            Some(HttpVersion::Http3) => builder.use_rustls_tls().http3_prior_knowledge(),
+            None => builder,
+        };
+        if let Some(max_idle) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(timeout) = self.pool_idle_timeout_secs {
+            builder = builder.pool_idle_timeout(Duration::from_secs(timeout));
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// New messages of a server
+#[derive(Debug, Default)]
+pub struct Batch {
+    pub messages: Vec<EncryptedMessage>,
+    /// Receipts of the messages, if asked for and the server signs them
+    pub receipts: Vec<Receipt>,
+    /// Set if the server skipped messages past its retention period, `messages` then start at
+    /// `gap.first_available`
+    pub gap: Option<Gap>,
+}
+
+pub trait Transport: Send + Sync {
+    /// Hand an envelope to `server`
+    fn send<'a>(
+        &'a self,
+        server: &'a Server,
+        envelope: &'a Envelope,
+    ) -> BoxFuture<'a, Result<(), anyhow::Error>>;
+
+    /// Hand an envelope to `server` together with a reply SURB, so the server can answer through
+    /// the mixnet (see the surb module). Only transports going through a Nym client can do that.
+    fn send_with_surb<'a>(
+        &'a self,
+        _server: &'a Server,
+        _envelope: &'a Envelope,
+    ) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async { Err(anyhow::anyhow!("this transport can't attach reply SURBs")) })
+    }
+
+    /// Messages stored by the server at `url` after the first `last_seen` ones (or the oldest
+    /// ones it still serves), padded to a size class if `pad` is set and with their receipts if
+    /// asked for
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        last_seen: usize,
+        pad: bool,
+        receipts: bool,
+    ) -> BoxFuture<'a, Result<Batch, anyhow::Error>>;
+
+    /// Disconnect gracefully, nothing to do for transports without a connection
+    fn close(&self) -> BoxFuture<'_, Result<(), anyhow::Error>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+type WebSocketStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Sends through a Nym native client's websocket, the way to use nym-chat privately. If sending
+/// fails (e.g. because the Nym client was restarted) the connection is opened again once.
+pub struct Websocket {
+    url: String,
+    ws: Mutex<SplitSink<WebSocketStream, Message>>,
+    health: Arc<Health>,
+    http: reqwest::Client,
+}
+
+impl Websocket {
+    /// Connect to the Nym client's websocket at `url`, fetches are made with `http`
+    pub async fn connect(
+        url: &str,
+        http: reqwest::Client,
+        health: Arc<Health>,
+    ) -> Result<Websocket, anyhow::Error> {
+        let ws = open(url, health.clone()).await?;
+        Ok(Websocket {
+            url: url.to_owned(),
+            ws: Mutex::new(ws),
+            health,
+            http,
+        })
+    }
+
+    /// Hand `envelope` to the Nym client, reconnecting once if that fails
+    async fn send_request(
+        &self,
+        server: &Server,
+        envelope: &Envelope,
+        with_reply_surb: bool,
+    ) -> Result<(), anyhow::Error> {
+        let nym_packet = nym_websocket::requests::ClientRequest::Send {
+            recipient: server.service_provider,
+            message: envelope.to_bytes(),
+            with_reply_surb,
+        };
+        let request = Message::Binary(nym_packet.serialize());
+        let mut ws = self.ws.lock().await;
+        if ws.send(request.clone()).await.is_ok() {
+            return Ok(());
+        }
+        *ws = open(&self.url, self.health.clone()).await?;
+        self.health.record(Problem::Reconnect);
+        ws.send(request).await?;
+        Ok(())
+    }
+}
+
+/// Connect to the Nym client, the errors it sends back are counted until the connection closes
+async fn open(
+    url: &str,
+    health: Arc<Health>,
+) -> Result<SplitSink<WebSocketStream, Message>, anyhow::Error> {
+    let (ws, _) = tokio_tungstenite::connect_async(url).await?;
+    let (sink, mut stream) = ws.split();
+    tokio::spawn(async move {
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Message::Binary(bytes) = frame {
+                if let Ok(ServerResponse::Error(_)) = ServerResponse::deserialize(&bytes) {
+                    health.record(Problem::NymError);
+                }
+            }
+        }
+    });
+    Ok(sink)
+}
+
+impl Transport for Websocket {
+    fn send<'a>(
+        &'a self,
+        server: &'a Server,
+        envelope: &'a Envelope,
+    ) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(self.send_request(server, envelope, false))
+    }
+
+    fn send_with_surb<'a>(
+        &'a self,
+        server: &'a Server,
+        envelope: &'a Envelope,
+    ) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(self.send_request(server, envelope, true))
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        last_seen: usize,
+        pad: bool,
+        receipts: bool,
+    ) -> BoxFuture<'a, Result<Batch, anyhow::Error>> {
+        Box::pin(fetch(&self.http, url, last_seen, pad, receipts))
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            self.ws.lock().await.close().await?;
+            Ok(())
+        })
+    }
+}
+
+/// POSTs envelopes to the server's `/submit` endpoint, only servers started with `--loopback`
+/// accept them. Nothing about this is private, it's for local development and tests that
+/// shouldn't depend on a Nym client.
+#[derive(Default)]
+pub struct Loopback {
+    http: reqwest::Client,
+}
+
+impl Loopback {
+    /// Send and fetch with `http`
+    pub fn new(http: reqwest::Client) -> Loopback {
+        Loopback { http }
+    }
+}
+
+impl Transport for Loopback {
+    fn send<'a>(
+        &'a self,
+        server: &'a Server,
+        envelope: &'a Envelope,
+    ) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            self.http
+                .post(format!("{}/submit", server.url))
+                .body(envelope.to_bytes())
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        last_seen: usize,
+        pad: bool,
+        receipts: bool,
+    ) -> BoxFuture<'a, Result<Batch, anyhow::Error>> {
+        Box::pin(fetch(&self.http, url, last_seen, pad, receipts))
+    }
+}
+
+/// Counts the failed sends and fetches of another transport in a [`Health`]
+pub struct Counted {
+    pub inner: Arc<dyn Transport>,
+    pub health: Arc<Health>,
+}
+
+impl Transport for Counted {
+    fn send<'a>(
+        &'a self,
+        server: &'a Server,
+        envelope: &'a Envelope,
+    ) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let result = self.inner.send(server, envelope).await;
+            if result.is_err() {
+                self.health.record(Problem::SendFailure);
+            }
+            result
+        })
+    }
+
+    fn send_with_surb<'a>(
+        &'a self,
+        server: &'a Server,
+        envelope: &'a Envelope,
+    ) -> BoxFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let result = self.inner.send_with_surb(server, envelope).await;
+            if result.is_err() {
+                self.health.record(Problem::SendFailure);
+            }
+            result
+        })
+    }
+
+    fn fetch<'a>(
+        &'a self,
+        url: &'a str,
+        last_seen: usize,
+        pad: bool,
+        receipts: bool,
+    ) -> BoxFuture<'a, Result<Batch, anyhow::Error>> {
+        Box::pin(async move {
+            let result = self.inner.fetch(url, last_seen, pad, receipts).await;
+            if result.is_err() {
+                self.health.record(Problem::FetchFailure);
+            }
+            result
+        })
+    }
+
+    fn close(&self) -> BoxFuture<'_, Result<(), anyhow::Error>> {
+        self.inner.close()
+    }
+}
+
+/// Fetch response of servers asked for receipts, older servers ignore the request and send only
+/// the messages
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FetchResponse {
+    WithReceipts {
+        messages: Vec<EncryptedMessage>,
+        receipts: Vec<Receipt>,
+        #[serde(default)]
+        gap: Option<Gap>,
+    },
+    Messages(Vec<EncryptedMessage>),
+}
+
+/// Fetch all messages after the first `last_seen` ones from the server's HTTP API, together with
+/// their receipts if asked for. Padded responses only contain additional whitespace, so they are
+/// parsed the same way (and servers that don't know about padding or receipts simply ignore the
+/// parameters). Servers that know about gaps skip messages past their retention period. All
+/// transports so far fetch like this.
+pub async fn fetch(
+    http: &reqwest::Client,
+    base_url: &str,
+    last_seen: usize,
+    pad: bool,
+    receipts: bool,
+) -> Result<Batch, anyhow::Error> {
+    let url = format!(
+        "{}/fetch/{}?pad={}&receipts={}&gaps=true",
+        base_url, last_seen, pad, receipts
+    );
+    let response = http.get(url).send().await?.json().await?;
+    Ok(match response {
+        FetchResponse::WithReceipts {
+            messages,
+            receipts,
+            gap,
+        } => Batch {
+            messages,
+            receipts,
+            gap,
+        },
+        FetchResponse::Messages(messages) => Batch {
+            messages,
+            ..Batch::default()
+        },
+    })
+}
+
+/// Ask the server at `base_url` for its service provider address, so users only need its URL.
+/// Whoever controls the HTTP connection controls the answer, so it's only as trustworthy as that
+/// connection; explicitly given addresses are preferable on plain HTTP.
+pub async fn provider_address(
+    http: &reqwest::Client,
+    base_url: &str,
+) -> Result<Recipient, anyhow::Error> {
+    let response = http
+        .get(format!("{}/provider-address", base_url))
+        .send()
+        .await?
+        .error_for_status()?;
+    Recipient::try_from_base58_string(response.text().await?.trim())
+        .map_err(|e| anyhow::anyhow!("invalid provider address: {:?}", e))
+}
@@ -0,0 +1,121 @@
+//! Usage beacons clients can opt into (`--usage-collector`), so developers learn which features
+//! are used. A beacon only has counts: how often each [`Feature`] was used since the last one,
+//! rounded down to a power of two so unusual numbers don't single anyone out, and the client
+//! version. No names, rooms, keys or times are in it. It's sent through the mixnet as a
+//! [`Custom`] payload without a sender to a collector room, at most once every [`INTERVAL`].
+
+use crate::custom::Custom;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Type URI of beacons, collectors pick them out of the room by it
+pub const TYPE_URI: &str = "https://github.com/sgeisler/nym-chat/usage/v1";
+
+/// Time between two beacons of a client
+pub const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// A message was sent to the room
+    Message,
+    /// A command was entered, the command itself isn't counted
+    Command,
+    Invite,
+    Poll,
+    Schedule,
+    BurnAfterReading,
+    /// An archive was exported or imported
+    Archive,
+    /// A plugin handled a command
+    Plugin,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 8] = [
+        Feature::Message,
+        Feature::Command,
+        Feature::Invite,
+        Feature::Poll,
+        Feature::Schedule,
+        Feature::BurnAfterReading,
+        Feature::Archive,
+        Feature::Plugin,
+    ];
+
+    /// Name of the feature in beacons
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Message => "message",
+            Feature::Command => "command",
+            Feature::Invite => "invite",
+            Feature::Poll => "poll",
+            Feature::Schedule => "schedule",
+            Feature::BurnAfterReading => "burn_after_reading",
+            Feature::Archive => "archive",
+            Feature::Plugin => "plugin",
+        }
+    }
+}
+
+/// What a beacon says
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Beacon {
+    pub version: String,
+    /// Coarse count of every feature used, unused ones are left out
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl Beacon {
+    /// Read a beacon out of a payload of the collector room, `None` for anything else
+    pub fn from_custom(custom: &Custom) -> Option<Beacon> {
+        match custom.type_uri == TYPE_URI {
+            true => serde_json::from_slice(&custom.data).ok(),
+            false => None,
+        }
+    }
+}
+
+/// Features used since the last beacon
+#[derive(Debug, Default)]
+pub struct Usage {
+    counts: [u64; Feature::ALL.len()],
+}
+
+impl Usage {
+    pub fn record(&mut self, feature: Feature) {
+        self.counts[feature as usize] += 1;
+    }
+
+    /// The beacon for what was used since the last one and start counting anew, `None` if
+    /// nothing was used
+    pub fn beacon(&mut self) -> Option<Custom> {
+        let counts = Feature::ALL
+            .iter()
+            .zip(&self.counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(feature, count)| (feature.name().to_owned(), coarse(*count)))
+            .collect::<BTreeMap<_, _>>();
+        self.counts = Default::default();
+        if counts.is_empty() {
+            return None;
+        }
+        let beacon = Beacon {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            counts,
+        };
+        Some(Custom {
+            sender: String::new(),
+            type_uri: TYPE_URI.to_owned(),
+            data: serde_json::to_vec(&beacon).expect("serialization can't fail"),
+        })
+    }
+}
+
+/// Round a count down to a power of two
+fn coarse(count: u64) -> u64 {
+    match count {
+        0 => 0,
+        count => 1 << (63 - count.leading_zeros()),
+    }
+}